@@ -0,0 +1,99 @@
+//! Counts the allocations made to serve a request on a persistent connection.
+//!
+//! Run with `cargo bench --bench allocations`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use fobserver::{
+    args::Args,
+    http::{self, HTTPRequest, HTTPResponse},
+    router::Router,
+    Server,
+};
+
+const ROUNDS: u64 = 10_000;
+
+/// The system allocator, counting the allocations made by every thread.
+struct Counting;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    let mut response = HTTPResponse::ok();
+    response.body = Some("Hello, world!".into());
+
+    Ok(response)
+}
+
+/// Sends a request on `stream` and reads its whole response.
+fn fetch(stream: &mut TcpStream, response: &mut [u8]) -> anyhow::Result<()> {
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n")?;
+
+    // The response is chunked, it ends with the empty chunk
+    let mut len = 0;
+    while !response[..len].ends_with(b"0\r\n\r\n") {
+        len += stream.read(&mut response[len..])?;
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut router = Router::new();
+    router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+
+    let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    server
+        .set_workers(1)
+        .set_max_requests_per_connection(usize::MAX)
+        .set_access_log(false);
+    let server = server.start_background()?;
+
+    let mut stream = TcpStream::connect(server.addr()?)?;
+    let mut response = vec![0; 64 * 1024];
+
+    // Warm up
+    fetch(&mut stream, &mut response)?;
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..ROUNDS {
+        fetch(&mut stream, &mut response)?;
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    println!(
+        "{} requests on one connection: {} allocations, {:.1} per request",
+        ROUNDS,
+        allocations,
+        allocations as f64 / ROUNDS as f64
+    );
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Compares serving a large file with `sendfile(2)` and by copying it through
+//! userspace.
+//!
+//! Run with `cargo bench --bench file_serving`.
+
+use std::{
+    fs,
+    io::Read,
+    net::TcpStream,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use fobserver::{
+    args::Args,
+    http::{self, HTTPRequest, HTTPResponse},
+    router::Router,
+    Server,
+};
+
+const ZERO_COPY_ADDR: &str = "127.0.0.1:30481";
+const COPY_ADDR: &str = "127.0.0.1:30482";
+const FILE_SIZE: usize = 64 * 1024 * 1024;
+const ROUNDS: u32 = 20;
+
+fn path() -> PathBuf {
+    std::env::temp_dir().join("fobserver-bench-file-serving.bin")
+}
+
+fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    HTTPResponse::from_file(path())
+}
+
+fn fetch(addr: &str) -> anyhow::Result<usize> {
+    let mut stream = TcpStream::connect(addr)?;
+    std::io::Write::write_all(&mut stream, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+
+    // Discard the body as it arrives, only the server side is measured
+    let mut buffer = vec![0; 256 * 1024];
+    let mut received = 0;
+    loop {
+        match stream.read(&mut buffer)? {
+            0 => return Ok(received),
+            read => received += read,
+        }
+    }
+}
+
+fn measure(addr: &str) -> anyhow::Result<Duration> {
+    // Warm up, with the file in the page cache
+    fetch(addr)?;
+
+    let start = Instant::now();
+    let mut bytes = 0;
+    for _ in 0..ROUNDS {
+        bytes += fetch(addr)?;
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{}: {} responses of {} MiB in {:.2?}: {:.1} MiB/s",
+        addr,
+        ROUNDS,
+        FILE_SIZE / (1024 * 1024),
+        elapsed,
+        bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64()
+    );
+
+    Ok(elapsed)
+}
+
+fn main() -> anyhow::Result<()> {
+    fs::write(path(), vec![b'x'; FILE_SIZE])?;
+
+    for (addr, zero_copy) in [(ZERO_COPY_ADDR, true), (COPY_ADDR, false)] {
+        let mut router = Router::new();
+        router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+
+        let mut server = Server::new(addr, router, Args::new())?;
+        server.set_zero_copy(zero_copy);
+        thread::spawn(move || server.start());
+    }
+    thread::sleep(Duration::from_millis(100));
+
+    let zero_copy = measure(ZERO_COPY_ADDR)?;
+    let copied = measure(COPY_ADDR)?;
+
+    println!(
+        "sendfile: {:.2?} per response, copying: {:.2?} per response ({:.2}x)",
+        zero_copy / ROUNDS,
+        copied / ROUNDS,
+        copied.as_secs_f64() / zero_copy.as_secs_f64()
+    );
+
+    fs::remove_file(path())?;
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Measures how fast a large response is written to a client.
+//!
+//! Run with `cargo bench --bench throughput`.
+
+use std::{
+    io::Read,
+    net::TcpStream,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use fobserver::{
+    args::Args,
+    http::{self, HTTPRequest, HTTPResponse},
+    router::Router,
+    Server,
+};
+
+const ADDR: &str = "127.0.0.1:30480";
+const BODY_SIZE: usize = 10 * 1024 * 1024;
+const ROUNDS: u32 = 20;
+
+fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    let mut response = HTTPResponse::ok();
+    response.body = Some(vec![b'x'; BODY_SIZE].into());
+
+    Ok(response)
+}
+
+fn fetch() -> anyhow::Result<usize> {
+    let mut stream = TcpStream::connect(ADDR)?;
+    std::io::Write::write_all(&mut stream, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+
+    let mut received = Vec::with_capacity(BODY_SIZE + 64 * 1024);
+    stream.read_to_end(&mut received)?;
+
+    Ok(received.len())
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut router = Router::new();
+    router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+
+    let mut server = Server::new(ADDR, router, Args::new())?;
+    thread::spawn(move || server.start());
+    thread::sleep(Duration::from_millis(100));
+
+    // Warm up
+    fetch()?;
+
+    let start = Instant::now();
+    let mut bytes = 0;
+    for _ in 0..ROUNDS {
+        bytes += fetch()?;
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{} responses of {} MiB in {:.2?}: {:.1} MiB/s, {:.2?} per response",
+        ROUNDS,
+        BODY_SIZE / (1024 * 1024),
+        elapsed,
+        bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64(),
+        elapsed / ROUNDS
+    );
+
+    Ok(())
+}
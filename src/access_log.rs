@@ -0,0 +1,99 @@
+use std::{
+    fmt,
+    net::IpAddr,
+    time::{Duration, SystemTime},
+};
+
+use crate::http::{date, Method, StatusCode, Version};
+
+/// The `log` target access log lines are emitted under, at info level.
+pub const TARGET: &str = "fobserver::access";
+
+/// A type alias for a function turning an access log entry into the logged line.
+///
+/// # Parameters
+/// - `entry`: The entry to format.
+///
+/// # Returns
+/// The line to log, without a trailing newline.
+pub type AccessLogFormatter = fn(&AccessLogEntry) -> String;
+
+/// A request handled by the server, as recorded in the access log.
+///
+/// Responses generated by the server itself are recorded as well; the request fields
+/// are `None` when the request couldn't be read (e.g. `400 Bad Request` or
+/// `408 Request Timeout`).
+///
+/// The default format resembles the Common Log Format:
+///
+/// ```
+/// use std::{
+///     net::{IpAddr, Ipv4Addr},
+///     time::{Duration, UNIX_EPOCH},
+/// };
+/// use fobserver::{
+///     access_log::AccessLogEntry,
+///     http::{Method, StatusCode, Version},
+/// };
+///
+/// let entry = AccessLogEntry {
+///     time: UNIX_EPOCH + Duration::from_secs(784887151),
+///     addr: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+///     method: Some(Method::GET),
+///     path: Some("/index.html".to_string()),
+///     version: Some(Version::V11),
+///     status_code: StatusCode::CODE200,
+///     size: 2326,
+///     duration: Duration::from_micros(1500),
+/// };
+///
+/// assert_eq!(
+///     entry.to_string(),
+///     "127.0.0.1 [Tue, 15 Nov 1994 08:12:31 GMT] \"GET /index.html HTTP/1.1\" 200 2326 1.5ms"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessLogEntry {
+    /// When the request was received.
+    pub time: SystemTime,
+    /// The address of the client, `None` for Unix socket connections.
+    pub addr: Option<IpAddr>,
+    /// The method of the request.
+    pub method: Option<Method>,
+    /// The path of the request, including the query string.
+    pub path: Option<String>,
+    /// The HTTP version of the request.
+    pub version: Option<Version>,
+    /// The status code of the response.
+    pub status_code: StatusCode,
+    /// The number of body bytes sent.
+    pub size: u64,
+    /// How long the response took to produce, handlers and middlewares included.
+    pub duration: Duration,
+}
+
+impl fmt::Display for AccessLogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.addr {
+            Some(addr) => write!(f, "{} ", addr)?,
+            None => write!(f, "- ")?,
+        }
+
+        write!(f, "[{}] ", date::format(self.time))?;
+
+        match (&self.method, &self.path, &self.version) {
+            (Some(method), Some(path), Some(version)) => {
+                write!(f, "\"{} {} {}\"", method, path, version)?
+            }
+            _ => write!(f, "\"-\"")?,
+        }
+
+        write!(
+            f,
+            " {} {} {:?}",
+            self.status_code.code(),
+            self.size,
+            self.duration
+        )
+    }
+}
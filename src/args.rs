@@ -1,13 +1,149 @@
 use std::{
-    any::Any,
-    collections::HashMap,
-    sync::{Arc, RwLock},
+    any::{self, Any},
+    collections::{BTreeMap, HashMap},
+    error, fmt,
+    sync::{Arc, PoisonError, RwLock, TryLockError},
 };
 
+/// How the type of values added with [`Args::add_arg`] is described.
+const UNKNOWN_TYPE: &str = "dyn Any";
+
 /// A struct to manage a collection of arguments, allowing for dynamic typing and thread-safe access.
-#[derive(Debug)]
+///
+/// Its `Debug` output lists the name of every argument with the type of its value,
+/// as far as it is known, see [`Args::type_name`].
 pub struct Args {
-    args: HashMap<String, Arc<RwLock<dyn Any + Send + Sync>>>,
+    args: HashMap<String, Arg>,
+    poison_policy: PoisonPolicy,
+}
+
+/// What accessing an argument does once a thread panicked while holding its lock,
+/// see [`Args::set_poison_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoisonPolicy {
+    /// Use the value as the panicking thread left it, and clear the poisoning.
+    #[default]
+    Recover,
+    /// Fail every access with an error.
+    Fail,
+}
+
+/// The error returned by [`Args::try_with`] and [`Args::try_with_mut`] when the
+/// argument is locked elsewhere, e.g. by the caller itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WouldBlock {
+    /// The name of the argument.
+    pub name: String,
+}
+
+impl fmt::Display for WouldBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "arg \"{}\" is locked", self.name)
+    }
+}
+
+impl error::Error for WouldBlock {}
+
+/// The error returned when an argument is accessed as another type than the one it
+/// holds.
+///
+/// # Example
+///
+/// ```
+/// use std::any::type_name;
+/// use fobserver::args::{Args, TypeMismatch};
+///
+/// struct PgPool;
+/// struct MySqlPool;
+///
+/// let mut args = Args::new();
+/// args.insert("db", PgPool);
+///
+/// let err = args.with("db", |_: &MySqlPool| ()).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     format!(
+///         r#"arg "db" holds {} but handler requested {}"#,
+///         type_name::<PgPool>(),
+///         type_name::<MySqlPool>()
+///     )
+/// );
+///
+/// let mismatch = err.downcast_ref::<TypeMismatch>().unwrap();
+/// assert_eq!(mismatch.name, "db");
+/// assert_eq!(mismatch.found, Some(type_name::<PgPool>()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    /// The name of the argument.
+    pub name: String,
+    /// The name of the type of the argument, unknown if it was added with
+    /// [`Args::add_arg`].
+    pub found: Option<&'static str>,
+    /// The name of the type it was accessed as.
+    pub requested: &'static str,
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.found {
+            Some(found) => write!(
+                f,
+                "arg \"{}\" holds {} but handler requested {}",
+                self.name, found, self.requested
+            ),
+            None => write!(
+                f,
+                "arg \"{}\" holds a value of unknown type but handler requested {}",
+                self.name, self.requested
+            ),
+        }
+    }
+}
+
+impl error::Error for TypeMismatch {}
+
+/// An argument and what is known about it.
+struct Arg {
+    value: ArgValue,
+    /// The name of the type of the value, unknown for values added already erased.
+    type_name: Option<&'static str>,
+}
+
+/// The value of an argument, as returned by [`Args::arg`] and [`Args::remove`].
+#[derive(Clone)]
+pub enum ArgValue {
+    /// A value handlers may mutate, behind a lock.
+    Locked(Arc<RwLock<dyn Any + Send + Sync>>),
+    /// A value that never changes, read without locking.
+    Immutable(Arc<dyn Any + Send + Sync>),
+}
+
+impl fmt::Debug for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgValue::Locked(_) => f.write_str("Locked(..)"),
+            ArgValue::Immutable(_) => f.write_str("Immutable(..)"),
+        }
+    }
+}
+
+impl fmt::Debug for Args {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let types: BTreeMap<_, _> = self
+            .args
+            .iter()
+            .map(|(name, arg)| (name, arg.type_name.unwrap_or(UNKNOWN_TYPE)))
+            .collect();
+
+        f.debug_struct("Args").field("args", &types).finish()
+    }
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Args {
@@ -15,13 +151,92 @@ impl Args {
     pub fn new() -> Self {
         Args {
             args: HashMap::new(),
+            poison_policy: PoisonPolicy::default(),
         }
     }
 
+    /// Sets what accessing a mutable argument does once a thread panicked while
+    /// holding its lock, e.g. a handler panicking within [`Args::with_mut`].
+    ///
+    /// By default the lock is recovered: the value is used as the panicking thread
+    /// left it, a warning is logged and later accesses go on as usual. That keeps
+    /// one failed request from breaking every following one, but the value may be
+    /// half updated. [`PoisonPolicy::Fail`] makes every access fail instead.
+    ///
+    /// # Parameters
+    /// - `policy`: What to do with poisoned locks.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::{Args, PoisonPolicy},
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     router::Router,
+    ///     testing::TestServer,
+    /// };
+    ///
+    /// fn count(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let args = args.read().unwrap();
+    ///     let count = args.with_mut("count", |count: &mut u64| {
+    ///         *count += 1;
+    ///         *count
+    ///     })?;
+    ///
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some(count.to_string().into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// fn panics(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     args.read().unwrap().with_mut("count", |_: &mut u64| panic!("Bad input"))
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/count", http::Version::V11, count);
+    /// router.add_route(http::Method::GET, "/panic", http::Version::V11, panics);
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("count", 0_u64);
+    /// let server = TestServer::new(router, args);
+    ///
+    /// // The panic poisons the lock of the count, later requests recover it
+    /// assert_eq!(server.get("/panic")?.status_code, StatusCode::CODE500);
+    /// for count in ["1", "2"] {
+    ///     let response = server.get("/count")?;
+    ///     assert_eq!(response.status_code, StatusCode::CODE200);
+    ///     assert_eq!(response.body.unwrap().as_bytes(), Some(count.as_bytes()));
+    /// }
+    ///
+    /// // Failing fast instead
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/count", http::Version::V11, count);
+    /// router.add_route(http::Method::GET, "/panic", http::Version::V11, panics);
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("count", 0_u64).set_poison_policy(PoisonPolicy::Fail);
+    /// let server = TestServer::new(router, args);
+    ///
+    /// assert_eq!(server.get("/panic")?.status_code, StatusCode::CODE500);
+    /// assert_eq!(server.get("/count")?.status_code, StatusCode::CODE500);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_poison_policy(&mut self, policy: PoisonPolicy) -> &mut Self {
+        self.poison_policy = policy;
+
+        self
+    }
+
     /// Adds an argument to the `Args` collection.
     ///
     /// This method allows you to insert a new argument, identified by a string `name`,
-    /// along with its value wrapped in `Arc<RwLock<dyn Any + Send + Sync>>`.
+    /// along with its value wrapped in `Arc<RwLock<dyn Any + Send + Sync>>`. The type of
+    /// the value is lost by then, prefer [`Args::insert`] so that it is known.
     ///
     /// # Parameters
     /// - `name`: A string slice that represents the name of the argument.
@@ -29,24 +244,693 @@ impl Args {
     ///
     /// # Returns
     /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// If `name` is taken by an [immutable](Args::insert_immutable) argument, it is
+    /// kept and the error is logged.
     pub fn add_arg(&mut self, name: &str, arg: Arc<RwLock<dyn Any + Send + Sync>>) -> &mut Self {
-        self.args.insert(name.to_string(), arg);
+        self.add(name, arg, None)
+    }
+
+    /// Adds a mutable argument whose value has the type named `type_name`, if known,
+    /// replacing the mutable one named `name` if there was one. An immutable one is
+    /// kept.
+    fn add(
+        &mut self,
+        name: &str,
+        value: Arc<RwLock<dyn Any + Send + Sync>>,
+        type_name: Option<&'static str>,
+    ) -> &mut Self {
+        match self.args.get(name) {
+            Some(Arg {
+                value: ArgValue::Immutable(_),
+                ..
+            }) => {
+                log::error!("arg \"{}\" is immutable, it can't be replaced", name);
+                return self;
+            }
+            // Replacing a value with one of another type is almost always a mistake
+            Some(Arg {
+                type_name: Some(previous),
+                ..
+            }) if cfg!(debug_assertions)
+                && type_name.is_some_and(|type_name| type_name != *previous) =>
+            {
+                log::warn!(
+                    "arg \"{}\" holding {} replaced with {}",
+                    name,
+                    previous,
+                    type_name.unwrap_or_default()
+                );
+            }
+            _ => {}
+        }
+
+        let value = ArgValue::Locked(value);
+        self.args.insert(name.to_string(), Arg { value, type_name });
 
         self
     }
 
+    /// Adds `value` to the `Args` collection as an immutable argument, read by
+    /// handlers without any locking, see [`Args::get_ref`].
+    ///
+    /// Suits the state that never changes once the server starts, e.g. its
+    /// configuration or precomputed lookup tables. [`Args::with`] reads immutable
+    /// arguments too, [`Args::with_mut`] and [`Args::arg`] don't.
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument.
+    /// - `value`: The value of the argument.
+    ///
+    /// # Returns
+    /// A `Result` containing a mutable reference to `self` to allow for method
+    /// chaining, or an error if there already is an argument named `name`, mutable or
+    /// not.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     testing::TestServer,
+    /// };
+    ///
+    /// struct Config {
+    ///     greeting: String,
+    /// }
+    ///
+    /// // Reads both kinds of arguments while handling one request
+    /// fn greet(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let args = args.read().unwrap();
+    ///     let config = args.get_ref::<Config>("config")?;
+    ///     let visits = args.with_mut("visits", |visits: &mut u64| {
+    ///         *visits += 1;
+    ///         *visits
+    ///     })?;
+    ///
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some(format!("{} #{}", config.greeting, visits).into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, greet);
+    ///
+    /// let mut args = Args::new();
+    /// args.insert_immutable(
+    ///     "config",
+    ///     Config {
+    ///         greeting: "Hello!".to_string(),
+    ///     },
+    /// )?
+    /// .insert("visits", 0_u64);
+    ///
+    /// // Names are shared by both kinds
+    /// assert!(args.insert_immutable("visits", 1_u64).is_err());
+    /// assert!(args.insert_immutable("config", 1_u64).is_err());
+    ///
+    /// let server = TestServer::new(router, args);
+    /// for visit in 1..=2 {
+    ///     let body = server.get("/")?.body.unwrap();
+    ///     assert_eq!(body.as_bytes(), Some(format!("Hello! #{}", visit).as_bytes()));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn insert_immutable<T: Send + Sync + 'static>(
+        &mut self,
+        name: &str,
+        value: T,
+    ) -> anyhow::Result<&mut Self> {
+        if self.args.contains_key(name) {
+            anyhow::bail!("arg \"{}\" already exists", name);
+        }
+
+        let arg = Arg {
+            value: ArgValue::Immutable(Arc::new(value)),
+            type_name: Some(any::type_name::<T>()),
+        };
+        self.args.insert(name.to_string(), arg);
+
+        Ok(self)
+    }
+
+    /// Returns the immutable argument named `name`, without locking.
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument.
+    ///
+    /// # Returns
+    /// A `Result` containing the argument, or an error if there is no argument named
+    /// `name`, if it isn't a `T`, or if it is a mutable one.
+    pub fn get_ref<T: Send + Sync + 'static>(&self, name: &str) -> anyhow::Result<Arc<T>> {
+        let entry = self.get(name)?;
+
+        match &entry.value {
+            ArgValue::Immutable(value) => {
+                value.clone().downcast().map_err(|_| entry.not_a::<T>(name))
+            }
+            ArgValue::Locked(_) => anyhow::bail!("arg \"{}\" is mutable, see Args::with", name),
+        }
+    }
+
+    /// Adds `value` to the `Args` collection, wrapping it in the lock handlers access
+    /// it through, see [`Args::with`] and [`Args::with_mut`].
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument.
+    /// - `value`: The value of the argument.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// If `name` is taken by an [immutable](Args::insert_immutable) argument, it is
+    /// kept and the error is logged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::args::Args;
+    ///
+    /// let visits = Arc::new(RwLock::new(0_u64));
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("greeting", "Hello!".to_string())
+    ///     .insert_shared("visits", visits.clone());
+    ///
+    /// assert_eq!(args.with("greeting", String::clone)?, "Hello!");
+    ///
+    /// // Both sides see the same value
+    /// args.with_mut("visits", |visits: &mut u64| *visits += 1)?;
+    /// *visits.write().unwrap() += 1;
+    /// assert_eq!(args.with("visits", |visits: &u64| *visits)?, 2);
+    ///
+    /// // The lock is part of the storage, the value isn't wrapped twice
+    /// assert!(args.with("visits", |_: &Arc<RwLock<u64>>| ()).is_err());
+    ///
+    /// // Immutable arguments aren't replaced
+    /// args.insert_immutable("name", "app".to_string())?;
+    /// args.insert("name", "other".to_string());
+    /// assert_eq!(*args.get_ref::<String>("name")?, "app");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn insert<T: Send + Sync + 'static>(&mut self, name: &str, value: T) -> &mut Self {
+        self.insert_shared(name, Arc::new(RwLock::new(value)))
+    }
+
+    /// Adds a value the caller keeps a handle on to the `Args` collection, see
+    /// [`Args::insert`]. The caller and the handlers share the same lock.
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument.
+    /// - `value`: The locked value of the argument.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// If `name` is taken by an [immutable](Args::insert_immutable) argument, it is
+    /// kept and the error is logged.
+    pub fn insert_shared<T: Send + Sync + 'static>(
+        &mut self,
+        name: &str,
+        value: Arc<RwLock<T>>,
+    ) -> &mut Self {
+        self.add(name, value, Some(any::type_name::<T>()))
+    }
+
     /// Retrieves an argument from the `Args` collection by its name.
     ///
-    /// This method returns an `Option<Arc<RwLock<dyn Any + Send + Sync>>>`.
-    /// If the argument with the specified `name` exists, it returns `Some(arg)`.
-    /// Otherwise, it returns `None`.
+    /// This method returns an `Option<ArgValue>`. If the argument with the specified
+    /// `name` exists, it returns `Some(arg)`, locked or
+    /// [immutable](Args::insert_immutable) as it was added. Otherwise, it returns
+    /// `None`, exactly when [`Args::contains`] returns `false`.
     ///
     /// # Parameters
     /// - `name`: A string slice that represents the name of the argument to retrieve.
     ///
     /// # Returns
     /// An `Option` containing the argument if it exists, or `None` if it does not.
-    pub fn arg(&self, name: &str) -> Option<Arc<RwLock<dyn Any + Send + Sync>>> {
-        self.args.get(name).cloned()
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::args::{ArgValue, Args};
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("visits", 0_u64);
+    /// args.insert_immutable("name", "app".to_string())?;
+    ///
+    /// let Some(ArgValue::Locked(visits)) = args.arg("visits") else {
+    ///     panic!("visits is mutable");
+    /// };
+    /// *visits.write().unwrap().downcast_mut::<u64>().unwrap() += 1;
+    /// assert_eq!(args.with("visits", |visits: &u64| *visits)?, 1);
+    ///
+    /// let Some(ArgValue::Immutable(name)) = args.arg("name") else {
+    ///     panic!("name is immutable");
+    /// };
+    /// assert_eq!(name.downcast_ref::<String>().unwrap(), "app");
+    ///
+    /// assert!(args.contains("name") && args.arg("name").is_some());
+    /// assert!(!args.contains("missing") && args.arg("missing").is_none());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn arg(&self, name: &str) -> Option<ArgValue> {
+        Some(self.args.get(name)?.value.clone())
+    }
+
+    /// Returns the names of the arguments, in no particular order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::args::Args;
+    ///
+    /// struct Counter(u64);
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("greeting", "Hello!".to_string())
+    ///     .insert_shared("visits", Arc::new(RwLock::new(0_u64)))
+    ///     .insert("counter", Counter(0))
+    ///     .add_arg("erased", Arc::new(RwLock::new(1.5)));
+    ///
+    /// let mut keys: Vec<_> = args.keys().collect();
+    /// keys.sort();
+    /// assert_eq!(keys, ["counter", "erased", "greeting", "visits"]);
+    ///
+    /// assert_eq!(args.type_name("greeting"), Some("alloc::string::String"));
+    /// assert_eq!(args.type_name("visits"), Some("u64"));
+    /// assert!(args.type_name("counter").unwrap().ends_with("::Counter"));
+    /// assert_eq!(args.type_name("erased"), None);
+    /// assert_eq!(args.type_name("missing"), None);
+    ///
+    /// // Sorted by name
+    /// let dump = format!("{:?}", args);
+    /// assert!(dump.starts_with(r#"Args { args: {"counter": ""#));
+    /// assert!(dump.ends_with(
+    ///     r#"::Counter", "erased": "dyn Any", "greeting": "alloc::string::String", "visits": "u64"} }"#
+    /// ));
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.args.keys().map(String::as_str)
+    }
+
+    /// Returns the name of the type of the argument named `name`, as given by
+    /// [`std::any::type_name`], or `None` if there is no such argument or if it was
+    /// added with [`Args::add_arg`], which erases it.
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument.
+    pub fn type_name(&self, name: &str) -> Option<&'static str> {
+        self.args.get(name)?.type_name
+    }
+
+    /// Returns `true` if there is an argument named `name`.
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument.
+    pub fn contains(&self, name: &str) -> bool {
+        self.args.contains_key(name)
+    }
+
+    /// Removes the argument named `name` from the `Args` collection.
+    ///
+    /// Handlers holding a clone of the argument, e.g. taken with [`Args::arg`], keep
+    /// it until they drop it; later lookups don't find it. Through the
+    /// `Arc<RwLock<Args>>` of a running server, see
+    /// [`Server::with_shared`](crate::Server::with_shared), removal waits for the
+    /// handlers reading the collection to release their lock.
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument to remove.
+    ///
+    /// # Returns
+    /// An `Option` containing the removed argument, locked or
+    /// [immutable](Args::insert_immutable), or `None` if there was none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{SocketAddr, TcpStream},
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    /// };
+    /// use fobserver::{
+    ///     args::{ArgValue, Args},
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn greet(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let args = args.read().unwrap();
+    ///     if !args.contains("greeting") {
+    ///         return Ok(HTTPResponse::not_found());
+    ///     }
+    ///
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some(args.with("greeting", String::clone)?.into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// fn get(addr: SocketAddr) -> anyhow::Result<String> {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, greet);
+    /// let router = Arc::new(RwLock::new(router));
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("greeting", "Hello!".to_string());
+    /// let args = Arc::new(RwLock::new(args));
+    ///
+    /// let server = Server::with_shared("127.0.0.1:0", router, args.clone())?.start_background()?;
+    /// let addr = server.addr()?;
+    /// assert!(get(addr)?.contains("Hello!"));
+    ///
+    /// let Some(ArgValue::Locked(held)) = args.read().unwrap().arg("greeting") else {
+    ///     panic!("greeting is mutable");
+    /// };
+    ///
+    /// // Requests racing with the removal see the argument or don't, never half of it
+    /// let clients: Vec<_> = (0..4)
+    ///     .map(|_| {
+    ///         thread::spawn(move || -> anyhow::Result<()> {
+    ///             for _ in 0..10 {
+    ///                 let response = get(addr)?;
+    ///                 assert!(
+    ///                     response.starts_with("HTTP/1.1 404 Not Found") || response.contains("Hello!")
+    ///                 );
+    ///             }
+    ///
+    ///             Ok(())
+    ///         })
+    ///     })
+    ///     .collect();
+    ///
+    /// assert!(args.write().unwrap().remove("greeting").is_some());
+    /// assert!(args.write().unwrap().remove("greeting").is_none());
+    ///
+    /// for client in clients {
+    ///     client.join().unwrap()?;
+    /// }
+    /// assert!(get(addr)?.starts_with("HTTP/1.1 404 Not Found"));
+    ///
+    /// // The clone taken before is still usable
+    /// assert_eq!(held.read().unwrap().downcast_ref::<String>().unwrap(), "Hello!");
+    ///
+    /// server.shutdown();
+    /// server.join()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn remove(&mut self, name: &str) -> Option<ArgValue> {
+        Some(self.args.remove(name)?.value)
+    }
+
+    /// Returns `true` if the argument named `name` is
+    /// [immutable](Args::insert_immutable).
+    pub(crate) fn is_immutable(&self, name: &str) -> bool {
+        self.args
+            .get(name)
+            .is_some_and(|arg| matches!(arg.value, ArgValue::Immutable(_)))
+    }
+
+    /// Returns the number of arguments in the `Args` collection, including the ones a
+    /// running server registers in it, like its statistics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::args::{ArgValue, Args};
+    ///
+    /// let mut args = Args::new();
+    /// assert!(args.is_empty());
+    ///
+    /// args.insert("answer", 42).insert("answer", 43);
+    /// assert!(args.contains("answer"));
+    /// assert_eq!(args.len(), 1);
+    ///
+    /// args.remove("answer");
+    /// assert!(!args.contains("answer"));
+    ///
+    /// // Immutable arguments are returned too
+    /// args.insert_immutable("name", "app".to_string())?;
+    /// let Some(ArgValue::Immutable(name)) = args.remove("name") else {
+    ///     panic!("name is immutable");
+    /// };
+    /// assert_eq!(name.downcast_ref::<String>().unwrap(), "app");
+    /// assert!(!args.contains("name") && args.arg("name").is_none());
+    /// assert!(args.is_empty());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    /// Returns `true` if the `Args` collection holds no argument.
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// Calls `f` with a shared reference to the argument named `name`, read-locking
+    /// it for the duration of the call unless it is immutable.
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument.
+    /// - `f`: The function to call with the argument.
+    ///
+    /// # Returns
+    /// A `Result` containing what `f` returned, or an error if there is no argument
+    /// named `name`, if it isn't a `T`, or if its lock is poisoned and the
+    /// [policy](Args::set_poison_policy) is to fail.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::args::Args;
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("greeting", "Hello!".to_string());
+    ///
+    /// let len = args.with("greeting", |greeting: &String| greeting.len())?;
+    /// assert_eq!(len, 6);
+    ///
+    /// let err = args.with("missing", |_: &String| ()).unwrap_err();
+    /// assert_eq!(err.to_string(), r#"No arg named "missing""#);
+    ///
+    /// let err = args.with("greeting", |_: &u32| ()).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     r#"arg "greeting" holds alloc::string::String but handler requested u32"#
+    /// );
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with<T: 'static, R>(&self, name: &str, f: impl FnOnce(&T) -> R) -> anyhow::Result<R> {
+        self.read(name, f, true)
+    }
+
+    /// Like [`Args::with`], but fails with a [`WouldBlock`] error instead of waiting
+    /// if the argument is write-locked, e.g. by the caller itself, which would wait
+    /// forever.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::args::{Args, WouldBlock};
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("count", 0_u64);
+    ///
+    /// let inner = args.with_mut("count", |_: &mut u64| {
+    ///     args.try_with("count", |count: &u64| *count)
+    /// })?;
+    /// let err = inner.unwrap_err();
+    /// assert_eq!(
+    ///     err.downcast_ref::<WouldBlock>(),
+    ///     Some(&WouldBlock {
+    ///         name: "count".to_string()
+    ///     })
+    /// );
+    ///
+    /// assert_eq!(args.try_with("count", |count: &u64| *count)?, 0);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn try_with<T: 'static, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&T) -> R,
+    ) -> anyhow::Result<R> {
+        self.read(name, f, false)
+    }
+
+    /// Calls `f` with a shared reference to the argument named `name`, waiting for its
+    /// lock if `block` is set.
+    fn read<T: 'static, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&T) -> R,
+        block: bool,
+    ) -> anyhow::Result<R> {
+        let entry = self.get(name)?;
+        let value = match &entry.value {
+            ArgValue::Locked(value) => value,
+            ArgValue::Immutable(value) => {
+                let arg = value
+                    .downcast_ref::<T>()
+                    .ok_or_else(|| entry.not_a::<T>(name))?;
+
+                return Ok(f(arg));
+            }
+        };
+
+        let locked = match block {
+            true => value.read().map_err(TryLockError::from),
+            false => value.try_read(),
+        };
+        let arg = self.recover(name, value, locked)?;
+        let arg = arg
+            .downcast_ref::<T>()
+            .ok_or_else(|| entry.not_a::<T>(name))?;
+
+        Ok(f(arg))
+    }
+
+    /// Calls `f` with a mutable reference to the argument named `name`, write-locking
+    /// it for the duration of the call.
+    ///
+    /// # Parameters
+    /// - `name`: A string slice that represents the name of the argument.
+    /// - `f`: The function to call with the argument.
+    ///
+    /// # Returns
+    /// A `Result` containing what `f` returned, or an error if there is no argument
+    /// named `name`, if it isn't a `T`, if it is immutable, or if its lock is poisoned
+    /// and the [policy](Args::set_poison_policy) is to fail.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::args::Args;
+    ///
+    /// struct Counter {
+    ///     value: usize,
+    /// }
+    ///
+    /// let mut args = Args::new();
+    /// args.insert("counter", Counter { value: 0 });
+    ///
+    /// for _ in 0..3 {
+    ///     args.with_mut("counter", |counter: &mut Counter| counter.value += 1)?;
+    /// }
+    /// assert_eq!(args.with("counter", |counter: &Counter| counter.value)?, 3);
+    ///
+    /// let err = args.with_mut("counter", |_: &mut String| ()).unwrap_err();
+    /// assert!(err.to_string().ends_with("::Counter but handler requested alloc::string::String"));
+    ///
+    /// assert!(args.with_mut("count", |_: &mut Counter| ()).is_err());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_mut<T: 'static, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> anyhow::Result<R> {
+        self.write(name, f, true)
+    }
+
+    /// Like [`Args::with_mut`], but fails with a [`WouldBlock`] error instead of
+    /// waiting if the argument is locked, e.g. by the caller itself, which would wait
+    /// forever.
+    pub fn try_with_mut<T: 'static, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> anyhow::Result<R> {
+        self.write(name, f, false)
+    }
+
+    /// Calls `f` with a mutable reference to the argument named `name`, waiting for
+    /// its lock if `block` is set.
+    fn write<T: 'static, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut T) -> R,
+        block: bool,
+    ) -> anyhow::Result<R> {
+        let entry = self.get(name)?;
+        let ArgValue::Locked(value) = &entry.value else {
+            anyhow::bail!("arg \"{}\" is immutable", name);
+        };
+
+        let locked = match block {
+            true => value.write().map_err(TryLockError::from),
+            false => value.try_write(),
+        };
+        let mut arg = self.recover(name, value, locked)?;
+        let arg = arg
+            .downcast_mut::<T>()
+            .ok_or_else(|| entry.not_a::<T>(name))?;
+
+        Ok(f(arg))
+    }
+
+    /// Turns the outcome of locking the argument named `name` into its guard, or into
+    /// an error if it is locked elsewhere, or poisoned and the policy says to fail.
+    fn recover<G>(
+        &self,
+        name: &str,
+        lock: &RwLock<dyn Any + Send + Sync>,
+        locked: Result<G, TryLockError<G>>,
+    ) -> anyhow::Result<G> {
+        match locked {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::WouldBlock) => Err(WouldBlock {
+                name: name.to_string(),
+            }
+            .into()),
+            Err(TryLockError::Poisoned(err)) => match self.poison_policy {
+                PoisonPolicy::Recover => {
+                    log::warn!("Recovering arg \"{}\", poisoned by a panic", name);
+                    lock.clear_poison();
+
+                    Ok(PoisonError::into_inner(err))
+                }
+                PoisonPolicy::Fail => Err(anyhow::anyhow!("arg \"{}\" is poisoned: {}", name, err)),
+            },
+        }
+    }
+
+    /// Returns the argument named `name`, or an error naming it if there is none.
+    fn get(&self, name: &str) -> anyhow::Result<&Arg> {
+        self.args
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("No arg named \"{}\"", name))
+    }
+}
+
+impl Arg {
+    /// Returns the error telling that the argument, named `name`, isn't a `T`.
+    fn not_a<T>(&self, name: &str) -> anyhow::Error {
+        TypeMismatch {
+            name: name.to_string(),
+            found: self.type_name,
+            requested: any::type_name::<T>(),
+        }
+        .into()
     }
 }
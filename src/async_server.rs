@@ -0,0 +1,675 @@
+use std::{
+    io::{self, Write},
+    net::IpAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use socket2::SockRef;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    sync::mpsc,
+    task,
+};
+
+use crate::{
+    args::Args,
+    buffers::Buffer,
+    http::{HTTPRequest, StatusCode},
+    is_disconnect, is_timeout,
+    limit::LimitPolicy,
+    listener::Listener,
+    parser::RequestParser,
+    read_error_status,
+    reaper::Reaper,
+    router::Router,
+    sendfile::SendFile,
+    span::Span,
+    ConnectionDecision, ConnectionError, ConnectionErrorKind, ConnectionGuard, Error, QueuePolicy,
+    Server, ServerConfig, ShutdownHandle, UpgradeFunction, ACQUIRE_POLL_INTERVAL, RETRY_AFTER,
+};
+
+/// How many pieces of a response can wait to be written to the socket.
+const RESPONSE_CHANNEL_CAPACITY: usize = 16;
+
+impl Server {
+    /// Starts the server on the current tokio runtime, listening for incoming requests.
+    ///
+    /// Each connection is served by its own task. Handlers stay synchronous and run on
+    /// the blocking thread pool of the runtime, so [`Server::set_workers`] and
+    /// [`Server::set_queue_depth`] don't apply. Runs until a shutdown is requested through
+    /// a [`ShutdownHandle`], or until one of the listeners fails.
+    ///
+    /// Only TCP listeners without TLS are supported.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating success or failure.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    ///     time::{Duration, Instant},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    /// use tokio::{
+    ///     io::{AsyncReadExt, AsyncWriteExt},
+    ///     net::TcpStream,
+    /// };
+    ///
+    /// fn slow(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     // A blocking call, e.g. a database query
+    ///     thread::sleep(Duration::from_millis(100));
+    ///
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, slow);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_access_log(false);
+    ///
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    ///
+    /// tokio::runtime::Runtime::new()?.block_on(async move {
+    ///     let server = tokio::spawn(async move { server.start_async().await });
+    ///
+    ///     // Far more requests than a pool of worker threads would serve at once
+    ///     let start = Instant::now();
+    ///     let clients = (0..300)
+    ///         .map(|_| {
+    ///             tokio::spawn(async move {
+    ///                 let mut stream = TcpStream::connect(addr).await?;
+    ///                 stream
+    ///                     .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+    ///                     .await?;
+    ///
+    ///                 let mut response = String::new();
+    ///                 stream.read_to_string(&mut response).await?;
+    ///
+    ///                 Ok::<_, anyhow::Error>(response)
+    ///             })
+    ///         })
+    ///         .collect::<Vec<_>>();
+    ///
+    ///     for client in clients {
+    ///         assert!(client.await??.starts_with("HTTP/1.1 200 OK"));
+    ///     }
+    ///
+    ///     // Served concurrently, instead of 30 seconds one after the other
+    ///     assert!(start.elapsed() < Duration::from_secs(5));
+    ///
+    ///     tokio::task::spawn_blocking(move || handle.shutdown()).await?;
+    ///     server.await??;
+    ///
+    ///     Ok::<(), anyhow::Error>(())
+    /// })?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub async fn start_async(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "tls")]
+        if self.config.tls.is_some() {
+            return Err(Error::Config(anyhow::anyhow!(
+                "TLS is not supported by the async server"
+            )));
+        }
+
+        let listeners = self
+            .listeners
+            .iter()
+            .map(|listener| match listener {
+                Listener::Tcp(listener) => {
+                    let listener = listener.try_clone()?;
+                    listener.set_nonblocking(true)?;
+
+                    Ok(TcpListener::from_std(listener)?)
+                }
+                #[cfg(unix)]
+                Listener::Unix { .. } => Err(anyhow::anyhow!(
+                    "Unix sockets are not supported by the async server"
+                )),
+            })
+            .collect::<anyhow::Result<Vec<TcpListener>>>()?;
+
+        self.register_stats()?;
+
+        for listener in &self.listeners {
+            log::info!("Server started on {}", listener);
+        }
+
+        let reaper = Reaper::spawn(self.config.clone())?;
+
+        // One acceptor per listener, all spawning tasks on the same runtime
+        let acceptors = listeners
+            .into_iter()
+            .map(|listener| {
+                let acceptor = Acceptor {
+                    router: self.router.clone(),
+                    args: self.args.clone(),
+                    config: self.config.clone(),
+                    handle: self.shutdown_handle(),
+                };
+
+                tokio::spawn(acceptor.run(listener))
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::new();
+
+        for acceptor in acceptors {
+            results.push(
+                acceptor
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("An acceptor panicked").into())),
+            );
+        }
+
+        let connections = self.config.stats.connections.clone();
+        let timeout = self.config.drain_timeout;
+        let drained = task::spawn_blocking(move || connections.drain(timeout)).await;
+
+        if let Some(reaper) = &reaper {
+            reaper.stop();
+        }
+        drained.map_err(anyhow::Error::from)?;
+
+        // The sockets are shared with the clones, leave them as they were found
+        for listener in &self.listeners {
+            if let Listener::Tcp(listener) = listener {
+                listener.set_nonblocking(false)?;
+            }
+        }
+
+        log::info!("Server stopped");
+
+        results.into_iter().collect()
+    }
+}
+
+/// Accepts the connections of a single listener.
+struct Acceptor {
+    router: Arc<RwLock<Router>>,
+    args: Arc<RwLock<Args>>,
+    config: Arc<ServerConfig>,
+    handle: ShutdownHandle,
+}
+
+impl Acceptor {
+    /// Accepts connections on `listener` until a shutdown is requested.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating success or failure.
+    async fn run(self, listener: TcpListener) -> Result<(), Error> {
+        let result = self.accept_loop(&listener).await;
+
+        // A failing listener takes the others down with it
+        if result.is_err() {
+            let handle = self.handle.clone();
+            task::spawn_blocking(move || handle.shutdown());
+        }
+
+        result
+    }
+
+    /// Accepts connections on `listener` and spawns a task for each of them.
+    async fn accept_loop(&self, listener: &TcpListener) -> Result<(), Error> {
+        while !self.handle.is_shutdown() {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tokio::time::sleep(self.config.accept_failed(err)?).await;
+
+                    continue;
+                }
+            };
+
+            // The connection may be the one waking us up for shutdown
+            if self.handle.is_shutdown() {
+                break;
+            }
+
+            self.config.stats.accepted();
+
+            // Unwanted and misbehaving clients are turned away before taking any room
+            match self.config.decide(Some(addr)) {
+                ConnectionDecision::Accept => {}
+                ConnectionDecision::Reject => continue,
+                ConnectionDecision::RejectWith(status_code) => {
+                    let config = self.config.clone();
+                    tokio::spawn(reject(stream, config, status_code, None));
+
+                    continue;
+                }
+            }
+
+            if let Some(filter) = self.config.filter(Some(addr.ip())) {
+                if filter.forbidden_response {
+                    let config = self.config.clone();
+                    tokio::spawn(reject(stream, config, StatusCode::CODE403, None));
+                }
+
+                continue;
+            }
+
+            let permit = match self.config.admit(Some(addr.ip())) {
+                Ok(permit) => permit,
+                Err(retry_after) => {
+                    log::warn!(
+                        "Too many connections from {}, refusing connection",
+                        addr.ip()
+                    );
+
+                    if self.config.limit_policy == LimitPolicy::Reject {
+                        let config = self.config.clone();
+                        tokio::spawn(reject(
+                            stream,
+                            config,
+                            StatusCode::CODE429,
+                            Some(retry_after),
+                        ));
+                    }
+
+                    continue;
+                }
+            };
+
+            let max = self.config.max_connections;
+            let connections = &self.config.stats.connections;
+
+            let mut guard = match connections.acquire(max, Duration::ZERO) {
+                Some(guard) => guard,
+                None if self.config.queue_policy == QueuePolicy::Reject => {
+                    log::warn!("Too many open connections, rejecting connection");
+                    let config = self.config.clone();
+                    tokio::spawn(reject(
+                        stream,
+                        config,
+                        StatusCode::CODE503,
+                        Some(RETRY_AFTER),
+                    ));
+
+                    continue;
+                }
+                // Waiting on the counter would block the runtime, poll it instead
+                None => loop {
+                    tokio::time::sleep(ACQUIRE_POLL_INTERVAL).await;
+
+                    if let Some(guard) = connections.acquire(max, Duration::ZERO) {
+                        break guard;
+                    }
+
+                    if self.handle.is_shutdown() {
+                        return Ok(());
+                    }
+                },
+            };
+            guard.permit = permit;
+            guard.track(SockRef::from(&stream));
+
+            let router = self.router.clone();
+            let args = self.args.clone();
+            let config = self.config.clone();
+
+            // The guard is dropped with the task, even if it panics
+            let addr = Some(addr.ip());
+            let connection = async move {
+                let result =
+                    handle_connection(stream, addr, &guard, router, args, config.clone()).await;
+
+                if let Err(err) = result {
+                    config.connection_failed(ConnectionError::new(err, addr));
+                }
+            };
+            tokio::spawn(Span::connection(addr).instrument(connection));
+        }
+
+        Ok(())
+    }
+}
+
+/// Handles a single connection: reads each request, dispatches it and writes the
+/// response, until the connection is not persistent anymore.
+///
+/// # Returns
+///
+/// Returns a `Result` indicating success or failure.
+async fn handle_connection(
+    stream: TcpStream,
+    addr: Option<IpAddr>,
+    guard: &ConnectionGuard,
+    router: Arc<RwLock<Router>>,
+    args: Arc<RwLock<Args>>,
+    config: Arc<ServerConfig>,
+) -> anyhow::Result<()> {
+    config.configure_socket(SockRef::from(&stream))?;
+
+    let (mut reader, mut writer) = stream.into_split();
+
+    let mut buffer = Buffer::with_capacity(config.read_buffer_size);
+    let mut served = 0;
+
+    loop {
+        // Between two requests the client only has the idle timeout to send more
+        let idle = match served {
+            0 => config.read_timeout,
+            _ => config.keep_alive_idle_timeout,
+        };
+
+        // A persistent connection waiting for more can be closed on shutdown
+        let waiting = served > 0 && buffer.is_empty();
+        if waiting && !guard.set_idle(true) {
+            return Ok(());
+        }
+
+        let mut request = match read_request(&mut reader, &mut buffer, idle, &config).await {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                // The connection itself failed, nobody is left to answer
+                if let Some(status_code) = read_error_status(&err) {
+                    let mut response = Vec::new();
+                    config.refuse(&mut response, status_code, None, addr)?;
+                    write(&mut writer, &response, &config)
+                        .await
+                        .context(ConnectionErrorKind::Write)?;
+                    close(&mut reader, &mut writer, guard, &config).await;
+                }
+
+                return Err(err.context(ConnectionErrorKind::Read));
+            }
+        };
+        request.addr = addr;
+        served += 1;
+
+        if waiting {
+            guard.set_idle(false);
+        }
+
+        // Counted until the response is written, even if the handler panics
+        let inflight = config.stats.inflight();
+
+        if let Some(hints) = Server::early_hints(&request, &router) {
+            match write(&mut writer, &hints, &config).await {
+                Err(err) if is_disconnect(&err) => {
+                    config.client_disconnected();
+
+                    return Ok(());
+                }
+                result => result.context(ConnectionErrorKind::Write)?,
+            }
+            config.stats.written(hints.len() as u64);
+        }
+
+        let timeout = config.handler_timeout(&request, &router);
+
+        // Handlers block, they run on their own thread and stream the response back
+        let (sender, mut receiver) = mpsc::channel(RESPONSE_CHANNEL_CAPACITY);
+        let responder = {
+            let (router, args, config) = (router.clone(), args.clone(), config.clone());
+            let span = Span::request(&request);
+
+            task::spawn_blocking(move || -> anyhow::Result<(bool, Option<UpgradeFunction>)> {
+                // Handlers run on this thread, their events land in the request span
+                let _request = span.enter();
+
+                let exchange = Server::respond(request, &router, args, &config, served)?;
+                let keep_alive = exchange.keep_alive;
+                let (status_code, duration) = (exchange.response.status_code, exchange.duration);
+
+                let upgrade = exchange.send(ChannelWriter { sender }, &config)?;
+                span.record_response(status_code, duration);
+
+                Ok((keep_alive, upgrade))
+            })
+        };
+
+        // The response starts once the handler returned, unless it runs out of time
+        let first = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, receiver.recv()).await {
+                Ok(first) => first,
+                Err(_) => {
+                    log::warn!("Handler timed out, closing connection");
+
+                    // The handler keeps running, but its response goes nowhere
+                    let mut response = Vec::new();
+                    config.refuse(&mut response, StatusCode::CODE504, None, addr)?;
+                    write(&mut writer, &response, &config)
+                        .await
+                        .context(ConnectionErrorKind::Write)?;
+
+                    return Ok(());
+                }
+            },
+            None => receiver.recv().await,
+        };
+
+        match forward(first, receiver, &mut writer, &config).await {
+            Err(err) if is_timeout(&err) => {
+                log::warn!("Client stopped reading the response, closing connection");
+
+                return Ok(());
+            }
+            // Dropping the receiver makes the handler thread fail to send the rest of
+            // the body, which stops its producer
+            Err(err) if is_disconnect(&err) => {
+                config.client_disconnected();
+
+                return Ok(());
+            }
+            result => result.context(ConnectionErrorKind::Write)?,
+        }
+
+        let (keep_alive, upgrade) = responder.await??;
+        drop(inflight);
+
+        if let Some(upgrade) = upgrade {
+            if !buffer.is_empty() {
+                log::warn!(
+                    "Dropping {} bytes received before the upgrade",
+                    buffer.len()
+                );
+            }
+
+            // Callbacks block like handlers, they get their own thread too
+            let stream = reader.reunite(writer)?.into_std()?;
+            stream.set_nonblocking(false)?;
+            task::spawn_blocking(move || upgrade(stream)).await?;
+
+            return Ok(());
+        }
+
+        if !keep_alive {
+            close(&mut reader, &mut writer, guard, &config).await;
+
+            return Ok(());
+        }
+    }
+}
+
+/// Reads the next HTTP request from the connection.
+///
+/// # Arguments
+///
+/// * `reader` - The reading half of the connection.
+/// * `buffer` - The bytes received on the connection but not consumed yet. Bytes
+///   following the request are left in it for the next call.
+/// * `idle` - How long to wait for the request to start.
+/// * `config` - The settings of the server.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the parsed `HTTPRequest`, or `None` if the client
+/// closed the connection or went idle before starting one.
+async fn read_request(
+    reader: &mut OwnedReadHalf,
+    buffer: &mut Vec<u8>,
+    idle: Option<Duration>,
+    config: &ServerConfig,
+) -> anyhow::Result<Option<HTTPRequest>> {
+    let mut chunk = Buffer::zeroed(config.read_buffer_size);
+    let mut parser = RequestParser::new(buffer, config)?;
+
+    while let Some(timeout) = parser.read_timeout(buffer, idle, config)? {
+        let read = reader.read(&mut chunk);
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, read)
+                .await
+                .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+            None => read.await,
+        };
+
+        let len = match result {
+            Ok(len) => len,
+            // The client went idle without starting a request
+            Err(err) if buffer.is_empty() && is_timeout(&err) => {
+                config.stats.idle_timeout();
+
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if len == 0 {
+            break;
+        }
+
+        parser.feed(buffer, &chunk[..len], config)?;
+    }
+
+    parser.finish(buffer)
+}
+
+/// Writes the pieces of a response to the connection as they are produced, starting
+/// with `first`.
+async fn forward(
+    first: Option<Vec<u8>>,
+    mut receiver: mpsc::Receiver<Vec<u8>>,
+    writer: &mut OwnedWriteHalf,
+    config: &ServerConfig,
+) -> io::Result<()> {
+    let mut next = first;
+
+    while let Some(data) = next {
+        write(writer, &data, config).await?;
+        next = receiver.recv().await;
+    }
+
+    Ok(())
+}
+
+/// Ends a connection once its last response is written, without losing it to a
+/// reset: shuts down writes, then discards what the client still sends, see
+/// [`Server::set_lingering_close`].
+async fn close(
+    reader: &mut OwnedReadHalf,
+    writer: &mut OwnedWriteHalf,
+    guard: &ConnectionGuard,
+    config: &ServerConfig,
+) {
+    // The client may stop waiting as soon as the last byte is out
+    if let Err(err) = writer.shutdown().await {
+        log::debug!("Failed to close connection: {}", err);
+
+        return;
+    }
+
+    let Some(linger) = config.linger else {
+        return;
+    };
+
+    // Nothing is left to answer, shutdown doesn't wait for the client
+    if !guard.set_idle(true) {
+        return;
+    }
+
+    let mut discarded = 0;
+    let mut scratch = [0; 4096];
+
+    let discard = async {
+        while discarded < linger.max_bytes {
+            match reader.read(&mut scratch).await {
+                Ok(0) | Err(_) => break,
+                Ok(len) => discarded += len,
+            }
+        }
+    };
+    let _ = tokio::time::timeout(linger.timeout, discard).await;
+
+    if discarded > 0 {
+        log::debug!(
+            "Discarded {} bytes sent before the connection closed",
+            discarded
+        );
+    }
+}
+
+/// Writes `data` to the connection within the write timeout.
+async fn write(writer: &mut OwnedWriteHalf, data: &[u8], config: &ServerConfig) -> io::Result<()> {
+    match config.write_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, writer.write_all(data))
+            .await
+            .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+        None => writer.write_all(data).await,
+    }
+}
+
+/// Answers a connection the server won't serve with `status_code`, before closing it.
+async fn reject(
+    stream: TcpStream,
+    config: Arc<ServerConfig>,
+    status_code: StatusCode,
+    retry_after: Option<Duration>,
+) {
+    let addr = stream.peer_addr().ok().map(|addr| addr.ip());
+    let (_, mut writer) = stream.into_split();
+
+    let mut response = Vec::new();
+    let result = match config.refuse(&mut response, status_code, retry_after, addr) {
+        Ok(()) => write(&mut writer, &response, &config)
+            .await
+            .map_err(Into::into),
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = result {
+        log::debug!("Failed to reject connection: {}", err);
+    }
+}
+
+/// Hands the bytes written to it to the task owning the connection.
+struct ChannelWriter {
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Fails once the connection is gone and nobody receives anymore
+        self.sender
+            .blocking_send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SendFile for ChannelWriter {}
@@ -0,0 +1,139 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    ops::{Deref, DerefMut},
+};
+
+/// How many buffers each thread keeps for reuse.
+const MAX_POOLED: usize = 8;
+
+/// The largest buffer kept for reuse, larger ones are freed so that a single huge
+/// request doesn't hold on to its memory forever.
+const MAX_RETAINED_CAPACITY: usize = 256 * 1024;
+
+thread_local! {
+    /// The buffers released by the thread, ready to be reused.
+    static POOL: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A buffer borrowed from the pool of the current thread, given back to it when
+/// dropped.
+///
+/// Workers handle one request after another, so the buffers they read requests and
+/// write responses with are allocated once rather than for every request.
+pub(crate) struct Buffer(Vec<u8>);
+
+impl Buffer {
+    /// Borrows an empty buffer able to hold at least `capacity` bytes.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        let pooled = POOL.try_with(|pool| pool.borrow_mut().pop()).ok().flatten();
+
+        let mut buffer = pooled.unwrap_or_default();
+        buffer.reserve(capacity);
+
+        Buffer(buffer)
+    }
+
+    /// Borrows a buffer of `len` zeroed bytes.
+    pub(crate) fn zeroed(len: usize) -> Self {
+        let mut buffer = Buffer::with_capacity(len);
+        buffer.resize(len, 0);
+
+        buffer
+    }
+}
+
+impl Deref for Buffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        let capacity = self.0.capacity();
+
+        if capacity == 0 || capacity > MAX_RETAINED_CAPACITY {
+            return;
+        }
+
+        let mut buffer = std::mem::take(&mut self.0);
+        buffer.clear();
+
+        // The pool is gone if the thread is exiting, the buffer is freed instead
+        let _ = POOL.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+
+            if pool.len() < MAX_POOLED {
+                pool.push(buffer);
+            }
+        });
+    }
+}
+
+/// A writer coalescing small writes into larger ones, like
+/// [`BufWriter`](std::io::BufWriter) but with a buffer borrowed from the pool.
+///
+/// Unlike `BufWriter`, the buffered data is not written when the writer is dropped:
+/// it must be flushed explicitly.
+pub(crate) struct BufferedWriter<W: Write> {
+    inner: W,
+    buffer: Buffer,
+    capacity: usize,
+}
+
+impl<W: Write> BufferedWriter<W> {
+    /// Wraps `inner`, writing to it once `capacity` bytes are buffered.
+    pub(crate) fn with_capacity(capacity: usize, inner: W) -> Self {
+        BufferedWriter {
+            inner,
+            buffer: Buffer::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Returns the underlying writer. Data still buffered is not written to it.
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes the buffered data to the underlying writer.
+    fn write_buffer(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.inner.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BufferedWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() + data.len() > self.capacity {
+            self.write_buffer()?;
+        }
+
+        // Data too large to be buffered skips the copy
+        if data.len() >= self.capacity {
+            return self.inner.write(data);
+        }
+
+        self.buffer.extend_from_slice(data);
+
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buffer()?;
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,662 @@
+use std::{
+    net::{TcpListener, ToSocketAddrs},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::{
+    access_log::AccessLogFormatter,
+    args::Args,
+    health::HealthConfig,
+    ip::IpFilter,
+    limit::{ConnectionLimiter, LimitPolicy, SharedLimiter},
+    listener::Listener,
+    maintenance::MaintenanceHandle,
+    router::Router,
+    ConnectionErrorHookFunction, ConnectionFilterFunction, Error, ErrorHandlerFunction,
+    LingerConfig, QueuePolicy, Server, ServerConfig, MAX_BUFFER_SIZE, MIN_STACK_SIZE,
+};
+
+#[cfg(feature = "compression")]
+use crate::compression::CompressionConfig;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
+
+/// Configures a [`Server`] before binding it.
+///
+/// Builders are obtained through [`Server::builder`]. Every setting starts from the
+/// same default as a server created with [`Server::new`], and the settings are
+/// checked once the server is built.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::TcpStream,
+///     sync::{Arc, RwLock},
+///     time::Duration,
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     router::Router,
+///     QueuePolicy, Server,
+/// };
+///
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::ok())
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+///
+/// let server = Server::builder()
+///     .router(router)
+///     .workers(2)
+///     .queue_depth(16)
+///     .queue_policy(QueuePolicy::Reject)
+///     .max_connections(64)
+///     .read_timeout(Some(Duration::from_secs(5)))
+///     .header_timeout(Some(Duration::from_secs(2)))
+///     .access_log(false)
+///     .bind("127.0.0.1:0")?
+///     .start_background()?;
+///
+/// let mut stream = TcpStream::connect(server.addr()?)?;
+/// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response)?;
+/// assert!(response.starts_with("HTTP/1.1 200 OK"));
+///
+/// // Dropping the handle stops the server
+/// drop(server);
+///
+/// // Invalid settings are caught when building
+/// assert!(Server::builder().workers(0).bind("127.0.0.1:0").is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct ServerBuilder {
+    router: Router,
+    args: Args,
+    config: ServerConfig,
+    reuse_address: bool,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConfig>,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        ServerBuilder {
+            router: Router::default(),
+            args: Args::default(),
+            config: ServerConfig::default(),
+            reuse_address: true,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl ServerBuilder {
+    /// Sets the router handling the requests. Defaults to an empty router, answering
+    /// every request with `404 Not Found`.
+    pub fn router(mut self, router: Router) -> Self {
+        self.router = router;
+
+        self
+    }
+
+    /// Sets the arguments shared across handlers. Defaults to empty arguments.
+    pub fn args(mut self, args: Args) -> Self {
+        self.args = args;
+
+        self
+    }
+
+    /// Sets how long the server waits for a client to send data, or `None` to wait
+    /// indefinitely. Defaults to 30 seconds.
+    ///
+    /// See [`Server::set_read_timeout`].
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.read_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long the server waits for a client to accept data, or `None` to wait
+    /// indefinitely. Defaults to 30 seconds.
+    ///
+    /// See [`Server::set_write_timeout`].
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.write_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long a client has to send the headers of a request, or `None` for no
+    /// limit. Defaults to 10 seconds.
+    ///
+    /// See [`Server::set_header_timeout`].
+    pub fn header_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.header_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long a client has to send the body of a request, or `None` for no
+    /// limit. Defaults to 60 seconds.
+    ///
+    /// See [`Server::set_body_timeout`].
+    pub fn body_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.body_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long a persistent connection may stay idle between two requests, or
+    /// `None` to wait indefinitely. Defaults to 5 seconds.
+    ///
+    /// See [`Server::set_keep_alive_idle_timeout`].
+    pub fn keep_alive_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.keep_alive_idle_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long handlers may run before the client is answered with
+    /// `504 Gateway Timeout`, or `None` to let them run indefinitely. Disabled by
+    /// default.
+    ///
+    /// See [`Server::set_handler_timeout`].
+    pub fn handler_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.handler_timeout = timeout;
+
+        self
+    }
+
+    /// Sets whether `TCP_NODELAY` is set on accepted connections. Enabled by default.
+    ///
+    /// See [`Server::set_tcp_nodelay`].
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.config.tcp_nodelay = enabled;
+
+        self
+    }
+
+    /// Enables TCP keepalive on accepted connections, probing them after `idle` of
+    /// inactivity, at least one second. Disabled by default.
+    ///
+    /// See [`Server::set_tcp_keepalive`].
+    pub fn tcp_keepalive(mut self, idle: Duration) -> Self {
+        self.config.tcp_keepalive = Some(idle);
+
+        self
+    }
+
+    /// Sets whether `SO_REUSEADDR` is set before binding, so that a restarted server
+    /// doesn't fail with "address in use" while connections of the previous one
+    /// linger. Enabled by default, ignored on Windows where the option would let other
+    /// processes bind the same port.
+    ///
+    /// Only applies to [`ServerBuilder::bind`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     thread,
+    /// };
+    /// use fobserver::Server;
+    ///
+    /// let mut server = Server::builder().reuse_address(true).bind("127.0.0.1:0")?;
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // The server closes the connection first, leaving it in TIME_WAIT
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// stream.read_to_string(&mut String::new())?;
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    ///
+    /// // A restarted server can bind the same port right away
+    /// let server = Server::builder().reuse_address(true).bind(addr)?;
+    /// assert_eq!(server.local_addr()?, addr);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn reuse_address(mut self, enabled: bool) -> Self {
+        self.reuse_address = enabled;
+
+        self
+    }
+
+    /// Sets how many requests a single connection may send, at least one. Defaults to
+    /// 100.
+    ///
+    /// See [`Server::set_max_requests_per_connection`].
+    pub fn max_requests_per_connection(mut self, max: usize) -> Self {
+        self.config.max_requests_per_connection = max;
+
+        self
+    }
+
+    /// Sets the size of the buffer requests are read with, between 1 byte and 16 MiB.
+    /// Defaults to 4 KiB.
+    pub fn read_buffer_size(mut self, size: usize) -> Self {
+        self.config.read_buffer_size = size;
+
+        self
+    }
+
+    /// Sets the largest request the server accepts, at least one byte. Defaults to 16
+    /// MiB.
+    ///
+    /// See [`Server::set_max_request_bytes`].
+    pub fn max_request_bytes(mut self, max: usize) -> Self {
+        self.config.max_request_bytes = max;
+
+        self
+    }
+
+    /// Sets the longest request line the server accepts, at least one byte. Defaults
+    /// to 8 KiB.
+    ///
+    /// See [`Server::set_max_request_line_bytes`].
+    pub fn max_request_line_bytes(mut self, max: usize) -> Self {
+        self.config.max_request_line_bytes = max;
+
+        self
+    }
+
+    /// Shares the maintenance mode of the server with `handle`.
+    ///
+    /// See [`Server::set_maintenance_handle`].
+    pub fn maintenance_handle(mut self, handle: MaintenanceHandle) -> Self {
+        self.config.maintenance = handle;
+
+        self
+    }
+
+    /// Answers liveness and readiness checks on the paths of `config`.
+    ///
+    /// See [`Server::enable_health_endpoints`].
+    pub fn health_endpoints(mut self, config: HealthConfig) -> Self {
+        self.config.health.enable(config);
+
+        self
+    }
+
+    /// Adds a probe the readiness endpoint consults.
+    ///
+    /// See [`Server::add_readiness_probe`].
+    pub fn readiness_probe<F>(mut self, name: &str, probe: F) -> Self
+    where
+        F: Fn(&Arc<RwLock<Args>>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.config.health.add_probe(name, Arc::new(probe));
+
+        self
+    }
+
+    /// Sets the size above which request bodies are spooled to a temporary file.
+    /// Disabled by default.
+    ///
+    /// See [`Server::set_spool_threshold`].
+    pub fn spool_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.config.spool_threshold = threshold;
+
+        self
+    }
+
+    /// Sets the directory spooled request bodies are written to. Defaults to the
+    /// temporary directory of the system.
+    ///
+    /// See [`Server::set_spool_dir`].
+    pub fn spool_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.spool_dir = dir.into();
+
+        self
+    }
+
+    /// Sets the largest body accepted when it is spooled, at least one byte. Defaults
+    /// to 1 GiB.
+    ///
+    /// See [`Server::set_max_spooled_bytes`].
+    pub fn max_spooled_bytes(mut self, max: u64) -> Self {
+        self.config.max_spooled_bytes = max;
+
+        self
+    }
+
+    /// Sets whether file bodies are sent with `sendfile(2)` where supported. Enabled
+    /// by default.
+    ///
+    /// See [`Server::set_zero_copy`].
+    pub fn zero_copy(mut self, enabled: bool) -> Self {
+        self.config.zero_copy = enabled;
+
+        self
+    }
+
+    /// Sets the size of the pieces response bodies are sent in, between 1 byte and 16
+    /// MiB. Defaults to 4 KiB.
+    pub fn write_chunk_size(mut self, size: usize) -> Self {
+        self.config.write_chunk_size = size;
+
+        self
+    }
+
+    /// Sets the number of worker threads handling connections, at least one. Defaults
+    /// to the number of CPUs available.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.config.workers = workers;
+
+        self
+    }
+
+    /// Sets the prefix of the names of the threads the server spawns, without nul
+    /// bytes. Defaults to `fobserver`.
+    pub fn thread_name(mut self, prefix: impl Into<String>) -> Self {
+        self.config.thread_name = prefix.into();
+
+        self
+    }
+
+    /// Sets the stack size of the threads the server spawns, at least 64 KiB.
+    /// Defaults to the default of the standard library.
+    pub fn thread_stack_size(mut self, size: usize) -> Self {
+        self.config.thread_stack_size = Some(size);
+
+        self
+    }
+
+    /// Sets how many accepted connections can wait for a free worker. Defaults to
+    /// 1024.
+    pub fn queue_depth(mut self, depth: usize) -> Self {
+        self.config.queue_depth = depth;
+
+        self
+    }
+
+    /// Sets what happens to new connections the server has no room for. Defaults to
+    /// [`QueuePolicy::Block`].
+    pub fn queue_policy(mut self, policy: QueuePolicy) -> Self {
+        self.config.queue_policy = policy;
+
+        self
+    }
+
+    /// Limits how many connections can be open at once, at least one. Unlimited by
+    /// default.
+    ///
+    /// See [`Server::set_max_connections`].
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.config.max_connections = Some(max);
+
+        self
+    }
+
+    /// Accepts connections only from the clients `filter` allows. No filter by default.
+    ///
+    /// See [`Server::set_ip_filter`].
+    pub fn ip_filter(mut self, filter: IpFilter) -> Self {
+        self.config.ip_filter = Some(filter);
+
+        self
+    }
+
+    /// Decides about every new connection before anything is read from it. No filter
+    /// by default.
+    ///
+    /// See [`Server::set_connection_filter`].
+    pub fn connection_filter(mut self, filter: ConnectionFilterFunction) -> Self {
+        self.config.connection_filter = Some(filter);
+
+        self
+    }
+
+    /// Limits the connections each client can open. No limit by default.
+    ///
+    /// See [`Server::set_connection_limiter`].
+    pub fn connection_limiter<L: ConnectionLimiter + 'static>(mut self, limiter: L) -> Self {
+        self.config.limiter = Some(SharedLimiter(Arc::new(limiter)));
+
+        self
+    }
+
+    /// Sets what happens to the connections refused by the connection limiter.
+    /// Defaults to [`LimitPolicy::Reject`].
+    pub fn limit_policy(mut self, policy: LimitPolicy) -> Self {
+        self.config.limit_policy = policy;
+
+        self
+    }
+
+    /// Sets how long the server waits for open connections to close once it stops
+    /// accepting new ones, see [`Server::set_drain_timeout`]. Defaults to 30 seconds.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.config.drain_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how connections are closed once their last response is sent, see
+    /// [`Server::set_lingering_close`]. Lingers with the default settings by default.
+    pub fn lingering_close(mut self, linger: Option<LingerConfig>) -> Self {
+        self.config.linger = linger;
+
+        self
+    }
+
+    /// Sets how long the server waits before accepting connections again once it ran
+    /// out of file descriptors, see [`Server::set_accept_backoff`]. Defaults to 100
+    /// milliseconds.
+    pub fn accept_backoff(mut self, backoff: Duration) -> Self {
+        self.config.accept_backoff = backoff;
+
+        self
+    }
+
+    /// Sets the function building the response when a handler returns an error.
+    /// Defaults to a plain `500 Internal Server Error`.
+    ///
+    /// See [`Server::set_error_handler`].
+    pub fn error_handler(mut self, handler: ErrorHandlerFunction) -> Self {
+        self.config.error_handler = Some(handler);
+
+        self
+    }
+
+    /// See [`Server::set_connection_error_hook`].
+    pub fn connection_error_hook(mut self, hook: ConnectionErrorHookFunction) -> Self {
+        self.config.connection_error_hook = Some(hook);
+
+        self
+    }
+
+    /// Enables or disables the access log. Enabled by default.
+    ///
+    /// See [`Server::set_access_log`].
+    pub fn access_log(mut self, enabled: bool) -> Self {
+        self.config.access_log = enabled;
+
+        self
+    }
+
+    /// Sets the function formatting the lines of the access log. Defaults to the
+    /// [`Display`](std::fmt::Display) implementation of the entries.
+    pub fn access_log_formatter(mut self, formatter: AccessLogFormatter) -> Self {
+        self.config.access_log_formatter = Some(formatter);
+
+        self
+    }
+
+    /// Enables gzip compression of the responses. Disabled by default.
+    #[cfg(feature = "compression")]
+    pub fn compression(mut self, config: CompressionConfig) -> Self {
+        self.config.compression = Some(config);
+
+        self
+    }
+
+    /// Renders the errors generated by the server as `application/problem+json`
+    /// documents when the client prefers JSON. Disabled by default.
+    ///
+    /// See [`Server::set_problem_details`].
+    #[cfg(feature = "json")]
+    pub fn problem_details(mut self, enabled: bool) -> Self {
+        self.config.problem_details = enabled;
+
+        self
+    }
+
+    /// Sets the upper bounds of the buckets of the latency histogram, see
+    /// [`Server::set_metrics_buckets`].
+    #[cfg(feature = "metrics")]
+    pub fn metrics_buckets(self, buckets: &[f64]) -> Self {
+        self.config.stats.metrics.set_buckets(buckets);
+
+        self
+    }
+
+    /// Serves HTTPS with the given certificate instead of plain HTTP.
+    ///
+    /// See [`Server::new_tls`].
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+
+        self
+    }
+
+    /// Builds the server, binding every address `addr` resolves to.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address(es) to bind the server to.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Server` instance, or an error if a setting is
+    /// invalid or an address can't be bound.
+    pub fn bind<A: ToSocketAddrs>(self, addr: A) -> Result<Server, Error> {
+        self.validate().map_err(Error::Config)?;
+
+        let config = self.server_config().map_err(Error::Config)?;
+        let listeners = Listener::bind_tcp(addr, self.reuse_address).map_err(Error::Bind)?;
+        let mut server = Server::with_listeners(listeners, self.router, self.args);
+        server.config = Arc::new(config);
+
+        Ok(server)
+    }
+
+    /// Builds the server, accepting connections on an already bound listener.
+    ///
+    /// # Arguments
+    ///
+    /// * `listener` - The listener to accept connections on.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Server` instance, or an error if a setting is
+    /// invalid.
+    pub fn build(self, listener: TcpListener) -> Result<Server, Error> {
+        self.validate().map_err(Error::Config)?;
+
+        let config = self.server_config().map_err(Error::Config)?;
+        let mut server = Server::from_listener(listener, self.router, self.args)?;
+        server.config = Arc::new(config);
+
+        Ok(server)
+    }
+
+    /// Checks that every setting is within its bounds.
+    fn validate(&self) -> anyhow::Result<()> {
+        let config = &self.config;
+
+        anyhow::ensure!(config.workers > 0, "At least one worker is required");
+        anyhow::ensure!(
+            !config.thread_name.contains('\0'),
+            "The thread name must not contain nul bytes"
+        );
+        anyhow::ensure!(
+            config
+                .thread_stack_size
+                .is_none_or(|size| size >= MIN_STACK_SIZE),
+            "The thread stack size must be at least 64 KiB"
+        );
+        anyhow::ensure!(
+            config.max_requests_per_connection > 0,
+            "A connection must be allowed at least one request"
+        );
+        anyhow::ensure!(
+            (1..=MAX_BUFFER_SIZE).contains(&config.read_buffer_size),
+            "The read buffer size must be between 1 byte and 16 MiB"
+        );
+        anyhow::ensure!(
+            (1..=MAX_BUFFER_SIZE).contains(&config.write_chunk_size),
+            "The write chunk size must be between 1 byte and 16 MiB"
+        );
+        anyhow::ensure!(
+            config.max_request_bytes > 0,
+            "The maximum request size must be at least one byte"
+        );
+        anyhow::ensure!(
+            config.max_request_line_bytes > 0,
+            "The maximum request line length must be at least one byte"
+        );
+        anyhow::ensure!(
+            config.max_spooled_bytes > 0,
+            "The maximum spooled body size must be at least one byte"
+        );
+        anyhow::ensure!(
+            config.max_connections != Some(0),
+            "At least one connection must be allowed"
+        );
+
+        anyhow::ensure!(
+            config
+                .tcp_keepalive
+                .is_none_or(|idle| idle >= Duration::from_secs(1)),
+            "The keepalive idle time must be at least one second"
+        );
+
+        let timeouts = [
+            config.read_timeout,
+            config.write_timeout,
+            config.header_timeout,
+            config.body_timeout,
+            config.keep_alive_idle_timeout,
+            config.handler_timeout,
+        ];
+        anyhow::ensure!(
+            !timeouts.contains(&Some(Duration::ZERO)),
+            "Timeouts must not be zero, use None to disable them"
+        );
+        anyhow::ensure!(
+            !config.accept_backoff.is_zero(),
+            "The accept backoff must not be zero"
+        );
+
+        Ok(())
+    }
+
+    /// Returns the settings of the server, loading the TLS certificate if any.
+    fn server_config(&self) -> anyhow::Result<ServerConfig> {
+        #[allow(unused_mut)]
+        let mut config = self.config.clone();
+
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            config.tls = Some(tls.server_config()?);
+        }
+
+        Ok(config)
+    }
+}
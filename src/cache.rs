@@ -0,0 +1,385 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::http::{
+    Body, CacheControl, HTTPRequest, HTTPResponse, HeaderMap, Method, StatusCode, Version,
+};
+
+/// How many responses a router caches by default.
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// How many bytes of responses a router caches by default.
+const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// A handle on the responses cached by a [`Router`](crate::router::Router) for the
+/// routes set up with [`Route::cache`](crate::router::Route::cache).
+///
+/// Handles are obtained with [`Router::cache_handle`](crate::router::Router::cache_handle)
+/// and can be cloned freely, e.g. into the [`Args`](crate::args::Args) of the handlers
+/// changing the data cached responses are computed from.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{
+///     atomic::{AtomicUsize, Ordering},
+///     Arc, RwLock,
+/// };
+/// use fobserver::{
+///     args::Args,
+///     cache::CacheHandle,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     router::Router,
+/// };
+///
+/// static RENDERS: AtomicUsize = AtomicUsize::new(0);
+///
+/// fn report(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(format!("render {}", RENDERS.fetch_add(1, Ordering::SeqCst)).into());
+///
+///     Ok(response)
+/// }
+///
+/// // Changes the data of the report, which must be rendered again
+/// fn update(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     args.read()
+///         .unwrap()
+///         .with("cache", |cache: &CacheHandle| cache.purge("/report"))?;
+///
+///     Ok(HTTPResponse::no_content())
+/// }
+///
+/// let mut router = Router::new();
+/// router
+///     .add_route(http::Method::GET, "/report", http::Version::V11, report)
+///     .cache(std::time::Duration::from_secs(30));
+/// router.add_route(http::Method::POST, "/report", http::Version::V11, update);
+///
+/// let mut args = Args::new();
+/// args.insert("cache", router.cache_handle());
+/// let args = Arc::new(RwLock::new(args));
+///
+/// let mut send = |request: &str| -> anyhow::Result<String> {
+///     let response = router.dispatch(request.parse()?, args.clone())?;
+///     let body = response.body.as_ref().and_then(|body| body.as_bytes()).unwrap_or_default();
+///
+///     Ok(String::from_utf8(body.to_vec())?)
+/// };
+///
+/// assert_eq!(send("GET /report HTTP/1.1\r\n\r\n")?, "render 0");
+/// assert_eq!(send("GET /report HTTP/1.1\r\n\r\n")?, "render 0");
+///
+/// send("POST /report HTTP/1.1\r\n\r\n")?;
+/// assert_eq!(send("GET /report HTTP/1.1\r\n\r\n")?, "render 1");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct CacheHandle {
+    store: Arc<Mutex<Store>>,
+}
+
+impl fmt::Debug for CacheHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let store = self.store();
+
+        f.debug_struct("CacheHandle")
+            .field("entries", &store.entries.len())
+            .field("bytes", &store.bytes)
+            .finish()
+    }
+}
+
+impl Default for CacheHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CacheHandle {
+    /// Creates an empty cache holding up to 1024 responses and 16 MiB.
+    pub fn new() -> Self {
+        CacheHandle {
+            store: Arc::new(Mutex::new(Store {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+                bytes: 0,
+                max_entries: DEFAULT_MAX_ENTRIES,
+                max_bytes: DEFAULT_MAX_BYTES,
+            })),
+        }
+    }
+
+    /// Removes the cached responses to the requests for `path`, whatever their
+    /// method, query string or headers.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the route, e.g. `/report`, without query string.
+    pub fn purge(&self, path: &str) {
+        let mut store = self.store();
+
+        let purged: Vec<Key> = store
+            .entries
+            .keys()
+            .filter(|key| key.path.split('?').next() == Some(path))
+            .cloned()
+            .collect();
+
+        for key in purged {
+            store.remove(&key);
+        }
+    }
+
+    /// Removes every cached response.
+    pub fn clear(&self) {
+        let mut store = self.store();
+
+        store.entries.clear();
+        store.order.clear();
+        store.bytes = 0;
+    }
+
+    /// Returns how many responses are cached, expired ones included until they are
+    /// looked up again or evicted.
+    pub fn len(&self) -> usize {
+        self.store().entries.len()
+    }
+
+    /// Returns `true` if no response is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sets how many responses, and how many bytes of them, are cached at most. The
+    /// least recently used responses are evicted to make room for new ones.
+    pub(crate) fn set_limits(&self, max_entries: usize, max_bytes: usize) {
+        let mut store = self.store();
+
+        store.max_entries = max_entries;
+        store.max_bytes = max_bytes;
+        store.evict(0, 0);
+    }
+
+    /// Answers `request` with a cached response if there is a fresh one, otherwise
+    /// with the one of `handler`, cached for `ttl` if it can be. Only `GET` and `HEAD`
+    /// requests are cached, the others always reach `handler`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to answer.
+    /// * `vary` - The request headers whose values select different responses.
+    /// * `ttl` - How long the response of `handler` stays fresh.
+    /// * `handler` - Produces the response when none is cached.
+    pub(crate) fn fetch<F>(
+        &self,
+        request: HTTPRequest,
+        vary: &[String],
+        ttl: Duration,
+        handler: F,
+    ) -> anyhow::Result<HTTPResponse>
+    where
+        F: FnOnce(HTTPRequest) -> anyhow::Result<HTTPResponse>,
+    {
+        // Other methods change the resource, their responses can't be reused
+        if !matches!(request.method, Method::GET | Method::HEAD) {
+            return handler(request);
+        }
+
+        let key = Key {
+            method: request.method,
+            path: request.path.clone(),
+            version: request.version,
+            headers: vary
+                .iter()
+                .map(|name| request.headers.get(name).map(str::to_string))
+                .collect(),
+        };
+
+        if let Some(response) = self.store().get(&key) {
+            return Ok(response);
+        }
+
+        let response = handler(request)?;
+
+        if let Some(body) = storable(&response) {
+            let entry = Entry {
+                status_code: response.status_code,
+                headers: response.headers.clone(),
+                body: body.map(<[u8]>::to_vec),
+                stored: Instant::now(),
+                ttl,
+                used: 0,
+            };
+
+            self.store().insert(key, entry);
+        }
+
+        Ok(response)
+    }
+
+    /// Locks the store, even if a thread panicked while holding it.
+    fn store(&self) -> MutexGuard<'_, Store> {
+        match self.store.lock() {
+            Ok(store) => store,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Returns the body of `response` if it can be cached: a successful, complete
+/// response held in memory, without cookies and not marked `no-store` or `private`.
+fn storable(response: &HTTPResponse) -> Option<Option<&[u8]>> {
+    if !response.status_code.is_success() || response.status_code == StatusCode::CODE206 {
+        return None;
+    }
+
+    if response.headers.contains("Set-Cookie") {
+        return None;
+    }
+
+    if let Some(value) = response.headers.get("Cache-Control") {
+        // A directive that can't be read might be a no-store
+        let cache_control: CacheControl = value.parse().ok()?;
+
+        if cache_control.no_store || cache_control.private {
+            return None;
+        }
+    }
+
+    match &response.body {
+        None => Some(None),
+        Some(Body::Bytes(bytes)) => Some(Some(bytes)),
+        Some(_) => None,
+    }
+}
+
+/// What selects a cached response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    method: Method,
+    /// The path of the request, query string included.
+    path: String,
+    version: Version,
+    /// The values of the headers the route varies on, in order.
+    headers: Vec<Option<String>>,
+}
+
+/// A cached response.
+struct Entry {
+    status_code: StatusCode,
+    headers: HeaderMap,
+    body: Option<Vec<u8>>,
+    stored: Instant,
+    ttl: Duration,
+    /// When the response was last used, as a tick of the store.
+    used: u64,
+}
+
+impl Entry {
+    /// Returns roughly how much memory the response takes.
+    fn size(&self) -> usize {
+        let headers: usize = self
+            .headers
+            .iter()
+            .map(|(name, value)| name.len() + value.len())
+            .sum();
+
+        headers + self.body.as_ref().map_or(0, Vec::len)
+    }
+}
+
+/// The cached responses, with their order of use.
+struct Store {
+    entries: HashMap<Key, Entry>,
+    /// The keys of the entries, least recently used first.
+    order: BTreeMap<u64, Key>,
+    tick: u64,
+    bytes: usize,
+    max_entries: usize,
+    max_bytes: usize,
+}
+
+impl Store {
+    /// Returns the response cached for `key`, if it is still fresh.
+    fn get(&mut self, key: &Key) -> Option<HTTPResponse> {
+        let entry = self.entries.get(key)?;
+        let age = entry.stored.elapsed();
+
+        if age >= entry.ttl {
+            self.remove(key);
+
+            return None;
+        }
+
+        self.tick += 1;
+        let tick = self.tick;
+
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.used);
+        self.order.insert(tick, key.clone());
+        entry.used = tick;
+
+        let mut response = HTTPResponse {
+            version: Version::V11,
+            status_code: entry.status_code,
+            headers: entry.headers.clone(),
+            body: entry.body.clone().map(Body::Bytes),
+        };
+        response.headers.set("Age", &age.as_secs().to_string());
+
+        Some(response)
+    }
+
+    /// Caches `entry` for `key`, evicting the least recently used responses to make
+    /// room for it.
+    fn insert(&mut self, key: Key, mut entry: Entry) {
+        let size = entry.size();
+
+        if size > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+
+        self.remove(&key);
+        self.evict(1, size);
+
+        self.tick += 1;
+        entry.used = self.tick;
+
+        self.order.insert(entry.used, key.clone());
+        self.entries.insert(key, entry);
+        self.bytes += size;
+    }
+
+    /// Evicts the least recently used responses until `entries` more, of `bytes`
+    /// bytes, fit.
+    fn evict(&mut self, entries: usize, bytes: usize) {
+        let over = |store: &Store| {
+            store.entries.len() + entries > store.max_entries
+                || store.bytes + bytes > store.max_bytes
+        };
+
+        while over(self) {
+            let Some((_, key)) = self.order.pop_first() else {
+                break;
+            };
+
+            if let Some(entry) = self.entries.remove(&key) {
+                self.bytes -= entry.size();
+            }
+        }
+    }
+
+    /// Removes the response cached for `key`.
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.used);
+            self.bytes -= entry.size();
+        }
+    }
+}
@@ -0,0 +1,624 @@
+use std::{
+    io::{self, BufRead, BufReader, Cursor, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+use crate::http::{
+    Extensions, HTTPRequest, HTTPResponse, HeaderMap, Method, ParseError, StatusCode, Version,
+};
+
+/// The largest response head accepted from a server.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// The longest line accepted in the framing of a chunked body.
+const MAX_LINE_SIZE: usize = 4096;
+
+/// A minimal HTTP/1.1 client, for handlers calling other services: webhooks,
+/// health checks, APIs.
+///
+/// A new connection is opened for every request, and the whole response body is
+/// read into memory, whether it is sent with a `Content-Length` or chunked.
+/// Redirects are followed up to a limit. HTTPS URLs are supported with the `tls`
+/// feature, trusting the certificate authorities given with
+/// [`Client::root_certificates`].
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     client::Client,
+///     http::{self, HTTPRequest, HTTPResponse, HeaderMap, StatusCode},
+///     router::Router,
+///     Server,
+/// };
+///
+/// fn sized(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.headers.set("Content-Length", "5");
+///     response.body = Some("sized".into());
+///
+///     Ok(response)
+/// }
+///
+/// // Sent chunked, without a Content-Length
+/// fn echo(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     let body = request.body.unwrap_or_default();
+///     response.body = Some(format!("{} {}", request.headers.get("X-Token").unwrap_or("-"), body).into());
+///
+///     Ok(response)
+/// }
+///
+/// fn moved(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::redirect(StatusCode::CODE303, "/sized"))
+/// }
+///
+/// fn again(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::redirect(StatusCode::CODE302, "again"))
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/sized", http::Version::V11, sized);
+/// router.add_route(http::Method::POST, "/echo", http::Version::V11, echo);
+/// router.add_route(http::Method::POST, "/moved", http::Version::V11, moved);
+/// router.add_route(http::Method::GET, "/again", http::Version::V11, again);
+///
+/// let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+/// let base = format!("http://{}", server.addr()?);
+///
+/// let client = Client::new().max_redirects(3);
+///
+/// let response = client.get(&format!("{}/sized", base))?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"sized"[..]));
+///
+/// let mut headers = HeaderMap::new();
+/// headers.set("X-Token", "secret");
+/// let response = client.request(http::Method::POST, &format!("{}/echo", base), &headers, Some("hello"))?;
+/// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"secret hello"[..]));
+///
+/// // A 303 is followed with a GET
+/// let response = client.request(http::Method::POST, &format!("{}/moved", base), &headers, Some("hello"))?;
+/// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"sized"[..]));
+///
+/// // Endless redirects give up
+/// assert!(client.get(&format!("{}/again", base)).is_err());
+///
+/// // Unless they are not followed
+/// let response = Client::new().max_redirects(0).get(&format!("{}/again", base))?;
+/// assert_eq!(response.status_code, StatusCode::CODE302);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    max_redirects: usize,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    /// Creates a client following up to 5 redirects, with a connect timeout of 5
+    /// seconds and a read timeout of 30 seconds.
+    pub fn new() -> Self {
+        Client {
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(30),
+            max_redirects: 5,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Sets how long connecting to a server may take.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long a server may stay silent while it is sent a request or sends its
+    /// response.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how many redirects are followed before giving up. With 0, redirects are
+    /// not followed and returned as they are.
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+
+        self
+    }
+
+    /// Sets the certificate authorities trusted to sign the certificates of HTTPS
+    /// servers. Without them, HTTPS requests fail.
+    ///
+    /// # Arguments
+    ///
+    /// * `pem` - The PEM-encoded certificates of the authorities.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the client, or an error if no certificate can be
+    /// read from `pem`.
+    #[cfg(feature = "tls")]
+    pub fn root_certificates(mut self, pem: &[u8]) -> anyhow::Result<Self> {
+        use rustls::pki_types::{pem::PemObject, CertificateDer};
+
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            roots.add(cert?)?;
+        }
+        anyhow::ensure!(!roots.is_empty(), "No certificate authority found");
+
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        self.tls = Some(Arc::new(config));
+
+        Ok(self)
+    }
+
+    /// Sends a `GET` request to `url`.
+    ///
+    /// See [`Client::request`].
+    pub fn get(&self, url: &str) -> anyhow::Result<HTTPResponse> {
+        self.request(Method::GET, url, &HeaderMap::new(), None)
+    }
+
+    /// Sends a request to `url` and reads its response, following redirects.
+    ///
+    /// `Host`, `Content-Length` and `Connection` are set by the client. A `303 See
+    /// Other`, like a `301` or `302` answering a `POST`, is followed with a `GET`
+    /// without body; `307` and `308` are followed with the same request. The
+    /// `Authorization` header is dropped when a redirect leads to another host.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The method of the request.
+    /// * `url` - The URL to send the request to, e.g. `http://127.0.0.1:8080/users`.
+    /// * `headers` - The headers of the request.
+    /// * `body` - The body of the request, if any.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the response with its whole body, or an error if
+    /// the URL is invalid, the server can't be reached or doesn't answer with a valid
+    /// response in time, or there are too many redirects.
+    pub fn request(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &HeaderMap,
+        body: Option<&str>,
+    ) -> anyhow::Result<HTTPResponse> {
+        let mut url = Url::parse(url)?;
+        let mut method = method;
+        let mut headers = headers.clone();
+        let mut body = body;
+        let mut redirects = 0;
+
+        loop {
+            let response = self.send(method, &url, &headers, body)?;
+
+            let location = match response.status_code {
+                StatusCode::CODE301
+                | StatusCode::CODE302
+                | StatusCode::CODE303
+                | StatusCode::CODE307
+                | StatusCode::CODE308 => response.headers.get("Location"),
+                _ => None,
+            };
+            let Some(location) = location.filter(|_| self.max_redirects > 0) else {
+                return Ok(response);
+            };
+
+            anyhow::ensure!(redirects < self.max_redirects, "Too many redirects");
+            redirects += 1;
+
+            let next = url.join(location)?;
+
+            let as_get = match response.status_code {
+                StatusCode::CODE303 => method != Method::HEAD,
+                StatusCode::CODE301 | StatusCode::CODE302 => method == Method::POST,
+                _ => false,
+            };
+            if as_get {
+                method = Method::GET;
+                body = None;
+            }
+
+            // Credentials are meant for the host they were given for
+            if next.host != url.host {
+                headers.remove("Authorization");
+            }
+
+            url = next;
+        }
+    }
+
+    /// Sends a single request to `url` and reads its response.
+    fn send(
+        &self,
+        method: Method,
+        url: &Url,
+        headers: &HeaderMap,
+        body: Option<&str>,
+    ) -> anyhow::Result<HTTPResponse> {
+        let mut request = HTTPRequest {
+            method,
+            path: url.path.clone(),
+            version: Version::V11,
+            headers: headers.clone(),
+            addr: None,
+            body: body.map(str::to_string),
+            body_source: None,
+            extensions: Extensions::new(),
+            tls: false,
+            #[cfg(feature = "tls")]
+            peer_certificate: None,
+            #[cfg(feature = "trace-context")]
+            trace_context: None,
+        };
+
+        request.headers.set("Host", &url.authority());
+        request.headers.set("Connection", "close");
+        match body {
+            Some(body) => {
+                let len = body.len().to_string();
+                request.headers.set("Content-Length", &len);
+            }
+            None => {
+                request.headers.remove("Content-Length");
+            }
+        }
+
+        let socket = connect((url.host.as_str(), url.port), self.connect_timeout)?;
+        socket.set_read_timeout(Some(self.read_timeout))?;
+        socket.set_write_timeout(Some(self.read_timeout))?;
+
+        let mut stream = self.wrap(socket, url)?;
+        stream.write_all(&request.to_bytes())?;
+        stream.flush()?;
+
+        let (mut response, leftover) = read_head(&mut stream)?;
+
+        let chunked = is_chunked(&response.headers);
+        response.headers.remove("Transfer-Encoding");
+
+        let bodiless = method == Method::HEAD
+            || response.status_code.is_informational()
+            || matches!(
+                response.status_code,
+                StatusCode::CODE204 | StatusCode::CODE304
+            );
+        if bodiless {
+            return Ok(response);
+        }
+
+        let body = Cursor::new(leftover).chain(stream);
+        let mut data = Vec::new();
+
+        if chunked {
+            Chunked::new(BufReader::new(body)).read_to_end(&mut data)?;
+        } else {
+            match response.headers.content_length() {
+                Some(len) => {
+                    body.take(len).read_to_end(&mut data)?;
+                    anyhow::ensure!(
+                        data.len() as u64 == len,
+                        "The server closed the connection within the body"
+                    );
+                }
+                // The server closes the connection once the body is sent
+                None => {
+                    body.take(u64::MAX).read_to_end(&mut data)?;
+                }
+            }
+        }
+
+        response.body = Some(data.into());
+
+        Ok(response)
+    }
+
+    /// Encrypts the connection to `url` if it uses HTTPS.
+    fn wrap(&self, socket: TcpStream, url: &Url) -> anyhow::Result<Box<dyn ReadWrite>> {
+        if !url.https {
+            return Ok(Box::new(socket));
+        }
+
+        #[cfg(feature = "tls")]
+        {
+            let Some(config) = &self.tls else {
+                anyhow::bail!("No certificate authority is trusted for HTTPS");
+            };
+            let name = rustls::pki_types::ServerName::try_from(url.host.clone())?;
+            let connection = rustls::ClientConnection::new(config.clone(), name)?;
+
+            Ok(Box::new(rustls::StreamOwned::new(connection, socket)))
+        }
+
+        #[cfg(not(feature = "tls"))]
+        anyhow::bail!("HTTPS requires the tls feature")
+    }
+}
+
+/// A connection to a server, encrypted or not.
+trait ReadWrite: Read + Write {}
+
+impl<T: Read + Write> ReadWrite for T {}
+
+/// The parts of an `http` or `https` URL a request needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Url {
+    https: bool,
+    /// The host name or IP address, without brackets.
+    host: String,
+    port: u16,
+    /// The path and query, starting with `/`.
+    path: String,
+}
+
+impl Url {
+    /// Parses an absolute `http` or `https` URL.
+    fn parse(url: &str) -> anyhow::Result<Self> {
+        let (https, rest) = match url.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => (false, rest),
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("https") => (true, rest),
+            _ => anyhow::bail!("Unsupported URL: {}", url),
+        };
+
+        let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(end);
+        anyhow::ensure!(
+            !authority.contains('@'),
+            "Credentials are not supported in URLs"
+        );
+
+        let default_port = if https { 443 } else { 80 };
+        let (host, port) = match authority.strip_prefix('[') {
+            // An IPv6 address
+            Some(bracketed) => match bracketed.split_once(']') {
+                Some((host, "")) => (host, None),
+                Some((host, port)) => (host, port.strip_prefix(':')),
+                None => anyhow::bail!("Invalid host in URL: {}", url),
+            },
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        anyhow::ensure!(!host.is_empty(), "Missing host in URL: {}", url);
+
+        let port = match port {
+            Some(port) => port.parse()?,
+            None => default_port,
+        };
+
+        // The fragment stays on the client
+        let path = path.split('#').next().unwrap_or_default();
+        let path = match path.starts_with('/') {
+            true => path.to_string(),
+            false => format!("/{}", path),
+        };
+
+        Ok(Url {
+            https,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// Returns the value of the `Host` header of the requests to the URL.
+    fn authority(&self) -> String {
+        let host = match self.host.contains(':') {
+            true => format!("[{}]", self.host),
+            false => self.host.clone(),
+        };
+
+        match (self.https, self.port) {
+            (false, 80) | (true, 443) => host,
+            (_, port) => format!("{}:{}", host, port),
+        }
+    }
+
+    /// Resolves the `location` of a redirect against the URL.
+    fn join(&self, location: &str) -> anyhow::Result<Url> {
+        if location.contains("://") {
+            return Url::parse(location);
+        }
+
+        let scheme = if self.https { "https" } else { "http" };
+
+        if location.starts_with("//") {
+            return Url::parse(&format!("{}:{}", scheme, location));
+        }
+
+        let path = match location.starts_with('/') {
+            true => location.to_string(),
+            false => {
+                // Relative to the directory of the current path
+                let current = self.path.split('?').next().unwrap_or_default();
+                let dir = &current[..current.rfind('/').map_or(0, |i| i + 1)];
+
+                format!("{}{}", dir, location)
+            }
+        };
+
+        Ok(Url {
+            path: path.split('#').next().unwrap_or_default().to_string(),
+            ..self.clone()
+        })
+    }
+}
+
+/// Opens a connection to the first of the addresses of `addr` that accepts it.
+pub(crate) fn connect<A: ToSocketAddrs>(addr: A, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last = None;
+
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last = Some(err),
+        }
+    }
+
+    Err(last.unwrap_or_else(|| invalid_data("The address resolves to nothing")))
+}
+
+/// Returns `true` if a message with `headers` has a chunked body.
+pub(crate) fn is_chunked(headers: &HeaderMap) -> bool {
+    headers
+        .get_all("Transfer-Encoding")
+        .flat_map(|value| value.split(','))
+        .any(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+}
+
+/// Reads the head of a response from `stream`.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the response without its body, and the bytes read
+/// past the head.
+pub(crate) fn read_head<R: Read>(stream: &mut R) -> io::Result<(HTTPResponse, Vec<u8>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+
+    let end = loop {
+        if let Some(end) = head_end(&buffer) {
+            break end;
+        }
+
+        if buffer.len() > MAX_HEAD_SIZE {
+            return Err(invalid_data("Response head too large"));
+        }
+
+        let read = stream.read(&mut chunk)?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The server closed the connection before responding",
+            ));
+        }
+
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = std::str::from_utf8(&buffer[..end]).map_err(|err| invalid_data(&err.to_string()))?;
+    let response: HTTPResponse = head
+        .parse()
+        .map_err(|err: ParseError| invalid_data(&err.to_string()))?;
+
+    Ok((response, buffer.split_off(end)))
+}
+
+/// Returns where the body starts, if `data` holds a complete head.
+fn head_end(data: &[u8]) -> Option<usize> {
+    let crlf = data
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|i| i + 4);
+    let lf = data
+        .windows(2)
+        .position(|window| window == b"\n\n")
+        .map(|i| i + 2);
+
+    match (crlf, lf) {
+        (Some(crlf), Some(lf)) => Some(crlf.min(lf)),
+        (crlf, lf) => crlf.or(lf),
+    }
+}
+
+/// Builds the error of a malformed response.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Decodes a body sent with the `chunked` transfer encoding.
+pub(crate) struct Chunked<R> {
+    inner: R,
+    /// The bytes left in the current chunk.
+    remaining: u64,
+    done: bool,
+}
+
+impl<R: BufRead> Chunked<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Chunked {
+            inner,
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    /// Reads a line of the chunk framing.
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        (&mut self.inner)
+            .take(MAX_LINE_SIZE as u64)
+            .read_line(&mut line)?;
+
+        Ok(line)
+    }
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            let line = self.read_line()?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+
+            self.remaining =
+                u64::from_str_radix(size, 16).map_err(|_| invalid_data("Invalid chunk size"))?;
+
+            if self.remaining == 0 {
+                // Skip the trailers, up to the empty line ending the body
+                while !self.read_line()?.trim().is_empty() {}
+                self.done = true;
+
+                return Ok(0);
+            }
+        }
+
+        let max = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The server closed the connection within a chunk",
+            ));
+        }
+
+        self.remaining -= read as u64;
+        if self.remaining == 0 {
+            // The line break following the chunk data
+            self.read_line()?;
+        }
+
+        Ok(read)
+    }
+}
@@ -0,0 +1,203 @@
+use std::io::Write;
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::http::{Body, HTTPResponse, StatusCode};
+
+/// Configuration of the gzip compression applied to responses before they are written.
+///
+/// A response is compressed only when the client accepts `gzip`, it does not already
+/// carry a `Content-Encoding`, its body is at least `min_size` bytes long and its
+/// content type is allowed. Only in-memory bodies are compressed: files and event
+/// streams are always sent as they are.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::Read,
+///     sync::{Arc, RwLock},
+/// };
+/// use flate2::read::GzDecoder;
+/// use fobserver::{
+///     args::Args,
+///     compression::CompressionConfig,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     middleware::{cors, Cors},
+///     router::Router,
+/// };
+///
+/// let config = CompressionConfig {
+///     level: 9,
+///     ..CompressionConfig::default()
+/// };
+///
+/// // Small bodies are not worth compressing
+/// let mut response = HTTPResponse::ok();
+/// response.headers.set("Content-Type", "application/json");
+/// response.body = Some("{}".into());
+/// config.compress(Some("gzip"), &mut response)?;
+/// assert_eq!(response.headers.get("Content-Encoding"), None);
+///
+/// // Images are already compressed
+/// let mut response = HTTPResponse::ok();
+/// response.headers.set("Content-Type", "image/jpeg");
+/// response.body = Some(vec![0; 4096].into());
+/// config.compress(Some("gzip"), &mut response)?;
+/// assert_eq!(response.headers.get("Content-Encoding"), None);
+///
+/// // Large JSON bodies are compressed at the configured level
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.headers.set("Content-Type", "application/json");
+///     response.body = Some(format!("[{}0]", "0, ".repeat(1000)).into());
+///
+///     Ok(response)
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+/// router.add_middleware(cors(Cors {
+///     credentials: true,
+///     ..Cors::default()
+/// }));
+///
+/// let request = "GET / HTTP/1.1\r\nOrigin: https://example.com\r\n\r\n".parse()?;
+/// let mut response = router.dispatch(request, Arc::new(RwLock::new(Args::new())))?;
+/// config.compress(Some("deflate, gzip;q=0.8"), &mut response)?;
+///
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.headers.get("Content-Encoding"), Some("gzip"));
+/// assert_eq!(response.headers.get("Vary"), Some("Origin, Accept-Encoding"));
+///
+/// let compressed = response.body.unwrap().as_bytes().unwrap().to_vec();
+/// assert_eq!(compressed[8], 2); // gzip header flag for maximum compression
+///
+/// let mut body = String::new();
+/// GzDecoder::new(&compressed[..]).read_to_string(&mut body)?;
+/// assert_eq!(body, format!("[{}0]", "0, ".repeat(1000)));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// The minimum body size, in bytes, for a response to be compressed.
+    pub min_size: usize,
+    /// The content types that are compressed. A `type/*` entry matches every subtype.
+    pub content_types: Vec<String>,
+    /// The content types that are never compressed, even if matched by `content_types`.
+    pub excluded_content_types: Vec<String>,
+    /// The compression level, from 0 (none) to 9 (best).
+    pub level: u32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            min_size: 1024,
+            content_types: vec![
+                "text/*".to_string(),
+                "application/json".to_string(),
+                "application/javascript".to_string(),
+                "image/svg+xml".to_string(),
+            ],
+            excluded_content_types: Vec::new(),
+            level: 6,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Compresses the body of `response` with gzip if the configuration allows it.
+    ///
+    /// `Vary: Accept-Encoding` is added to every response that would be compressed for
+    /// a client accepting gzip, so caches don't serve the wrong variant.
+    ///
+    /// # Parameters
+    /// - `accept_encoding`: The `Accept-Encoding` header of the request, if any.
+    /// - `response`: The response to compress in place.
+    ///
+    /// # Returns
+    /// An `anyhow::Result<()>` with an error if the body could not be compressed.
+    pub fn compress(
+        &self,
+        accept_encoding: Option<&str>,
+        response: &mut HTTPResponse,
+    ) -> anyhow::Result<()> {
+        let len = match response.body.as_ref().and_then(Body::as_bytes) {
+            Some(bytes) => bytes.len(),
+            None => return Ok(()),
+        };
+
+        if len < self.min_size
+            || response.headers.contains("Content-Encoding")
+            || response.headers.contains("Content-Range")
+            || matches!(
+                response.status_code,
+                StatusCode::CODE204 | StatusCode::CODE206 | StatusCode::CODE304
+            )
+            || !response.headers.content_type().is_some_and(|content_type| {
+                matches_any(&self.content_types, content_type)
+                    && !matches_any(&self.excluded_content_types, content_type)
+            })
+        {
+            return Ok(());
+        }
+
+        response.add_vary("Accept-Encoding");
+
+        if !accept_encoding.is_some_and(accepts_gzip) {
+            return Ok(());
+        }
+
+        let bytes = response.body.as_ref().and_then(Body::as_bytes).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(self.level.min(9)));
+        encoder.write_all(bytes)?;
+        let compressed = encoder.finish()?;
+
+        response.headers.set("Content-Encoding", "gzip");
+        if response.headers.contains("Content-Length") {
+            response
+                .headers
+                .set("Content-Length", &compressed.len().to_string());
+        }
+        response.body = Some(compressed.into());
+
+        Ok(())
+    }
+}
+
+/// Returns `true` if `content_type` matches one of `patterns`.
+fn matches_any(patterns: &[String], content_type: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix("/*") {
+            Some(kind) => content_type
+                .split_once('/')
+                .is_some_and(|(t, _)| t.eq_ignore_ascii_case(kind)),
+            None => pattern.eq_ignore_ascii_case(content_type),
+        })
+}
+
+/// Returns `true` if an `Accept-Encoding` header allows gzip, i.e. lists `gzip` or
+/// `*` with a non-zero quality.
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    let quality = |coding: &str| {
+        accept_encoding.split(',').find_map(|item| {
+            let mut params = item.split(';');
+            let name = params.next()?.trim();
+
+            if !name.eq_ignore_ascii_case(coding) {
+                return None;
+            }
+
+            Some(
+                params
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0),
+            )
+        })
+    };
+
+    quality("gzip").or_else(|| quality("*")).unwrap_or(0.0) > 0.0
+}
@@ -0,0 +1,229 @@
+use std::{any, env};
+#[cfg(feature = "config")]
+use std::{fs, path::Path};
+
+use anyhow::Context;
+
+use crate::args::Args;
+
+impl Args {
+    /// Creates an `Args` collection from the environment variables named
+    /// `{prefix}_*`, each stored as a `String`.
+    ///
+    /// The name of an argument is the name of its variable without the prefix,
+    /// lowercased, with double underscores standing for dots: `APP_DATABASE_URL` is
+    /// `database_url` and `APP_DATABASE__URL` is `database.url`, as nested tables of a
+    /// configuration file are named, see `Args::from_toml`. Variables whose name or value
+    /// isn't valid Unicode are ignored.
+    ///
+    /// # Parameters
+    /// - `prefix`: The prefix of the variables to read, with or without the trailing
+    ///   underscore.
+    ///
+    /// # Returns
+    /// The collection of the variables found, empty if there is none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::args::Args;
+    ///
+    /// std::env::set_var("MYAPP_DATABASE_URL", "postgres://localhost/app");
+    /// std::env::set_var("MYAPP_CACHE__TTL", "60");
+    /// std::env::set_var("MYAPPLICATION_NAME", "not ours");
+    ///
+    /// let args = Args::from_env("MYAPP");
+    /// assert_eq!(args.len(), 2);
+    /// assert_eq!(
+    ///     args.with("database_url", String::clone)?,
+    ///     "postgres://localhost/app"
+    /// );
+    /// assert_eq!(args.with("cache.ttl", String::clone)?, "60");
+    ///
+    /// // A variable naming an immutable argument is refused, the argument is kept
+    /// let mut args = Args::new();
+    /// args.insert_immutable("database_url", "postgres://db.internal/app".to_string())?;
+    /// let err = args.merge_env("MYAPP").unwrap_err();
+    /// assert_eq!(err.to_string(), r#"arg "database_url" is immutable, it can't be replaced"#);
+    /// assert_eq!(*args.get_ref::<String>("database_url")?, "postgres://db.internal/app");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn from_env(prefix: &str) -> Self {
+        let mut args = Args::new();
+
+        for (name, value) in vars(prefix) {
+            args.insert(&name, value);
+        }
+
+        args
+    }
+
+    /// Replaces or adds the arguments named after the environment variables
+    /// `{prefix}_*`, see [`Args::from_env`] for how they are named.
+    ///
+    /// A variable replacing a `bool`, `i64` or `f64` argument, e.g. one read from a
+    /// configuration file with `Args::from_toml`, is parsed into the same type; every
+    /// other variable is stored as a `String`. This lets the environment override the
+    /// file without changing the type handlers read.
+    ///
+    /// # Parameters
+    /// - `prefix`: The prefix of the variables to read, with or without the trailing
+    ///   underscore.
+    ///
+    /// # Returns
+    /// A `Result` containing a mutable reference to `self` to allow for method
+    /// chaining, or an error if a variable can't be parsed into the type of the
+    /// argument it replaces or if it names an [immutable](Args::insert_immutable)
+    /// argument.
+    pub fn merge_env(&mut self, prefix: &str) -> anyhow::Result<&mut Self> {
+        for (name, value) in vars(prefix) {
+            if self.is_immutable(&name) {
+                anyhow::bail!("arg \"{}\" is immutable, it can't be replaced", name);
+            }
+
+            let type_name = self.type_name(&name);
+
+            if type_name == Some(any::type_name::<bool>()) {
+                let value: bool = parse(&name, &value)?;
+                self.insert(&name, value);
+            } else if type_name == Some(any::type_name::<i64>()) {
+                let value: i64 = parse(&name, &value)?;
+                self.insert(&name, value);
+            } else if type_name == Some(any::type_name::<f64>()) {
+                let value: f64 = parse(&name, &value)?;
+                self.insert(&name, value);
+            } else {
+                self.insert(&name, value);
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Creates an `Args` collection from a TOML file.
+    ///
+    /// Strings, integers, floats and booleans are stored as `String`, `i64`, `f64`
+    /// and `bool`, dates as the `String` they were written as. Nested tables are
+    /// flattened, their keys joined with dots: `url` in the `[database]` table is
+    /// `database.url`. Arrays aren't supported.
+    ///
+    /// Use [`Args::merge_env`] to let environment variables override the file: when
+    /// both set an argument, the variable wins.
+    ///
+    /// # Parameters
+    /// - `path`: The path of the file.
+    ///
+    /// # Returns
+    /// A `Result` containing the collection, or an error if the file can't be read,
+    /// isn't valid TOML or holds an array.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::args::Args;
+    ///
+    /// let path = std::env::temp_dir().join(format!("fobserver-{}.toml", std::process::id()));
+    /// std::fs::write(
+    ///     &path,
+    ///     r#"
+    ///     name = "shop"
+    ///     workers = 4
+    ///     debug = false
+    ///
+    ///     [database]
+    ///     url = "postgres://localhost/shop"
+    ///     timeout = 2.5
+    ///     "#,
+    /// )?;
+    ///
+    /// let mut args = Args::from_toml(&path)?;
+    /// std::fs::remove_file(&path)?;
+    ///
+    /// assert_eq!(args.with("name", String::clone)?, "shop");
+    /// assert_eq!(args.with("workers", |workers: &i64| *workers)?, 4);
+    /// assert_eq!(args.with("database.timeout", |timeout: &f64| *timeout)?, 2.5);
+    ///
+    /// // The environment wins over the file, and keeps its types
+    /// std::env::set_var("SHOP_WORKERS", "16");
+    /// std::env::set_var("SHOP_DEBUG", "true");
+    /// std::env::set_var("SHOP_DATABASE__URL", "postgres://db.internal/shop");
+    /// args.merge_env("SHOP")?;
+    ///
+    /// assert_eq!(args.with("workers", |workers: &i64| *workers)?, 16);
+    /// assert!(args.with("debug", |debug: &bool| *debug)?);
+    /// assert_eq!(
+    ///     args.with("database.url", String::clone)?,
+    ///     "postgres://db.internal/shop"
+    /// );
+    ///
+    /// // A variable that doesn't fit the type of the file is an error
+    /// std::env::set_var("SHOP_WORKERS", "many");
+    /// let err = args.merge_env("SHOP").unwrap_err();
+    /// assert_eq!(err.to_string(), r#"Invalid value for arg "workers": many"#);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn from_toml(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let table: toml::Table = content
+            .parse()
+            .with_context(|| format!("Invalid TOML in {}", path.display()))?;
+
+        let mut args = Args::new();
+        flatten(&mut args, "", table)?;
+
+        Ok(args)
+    }
+}
+
+/// Returns the environment variables named `{prefix}_*`, named as arguments.
+fn vars(prefix: &str) -> impl Iterator<Item = (String, String)> {
+    let prefix = format!("{}_", prefix.trim_end_matches('_'));
+
+    env::vars_os().filter_map(move |(name, value)| {
+        let name = name.into_string().ok()?;
+        let value = value.into_string().ok()?;
+        let name = name.strip_prefix(&prefix)?;
+
+        (!name.is_empty()).then(|| (name.to_lowercase().replace("__", "."), value))
+    })
+}
+
+/// Parses the value of a variable overriding the argument named `name`.
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> anyhow::Result<T> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .with_context(|| format!("Invalid value for arg \"{}\": {}", name, value))
+}
+
+/// Adds the values of `table` to `args`, their names prefixed with `prefix`.
+#[cfg(feature = "config")]
+fn flatten(args: &mut Args, prefix: &str, table: toml::Table) -> anyhow::Result<()> {
+    for (key, value) in table {
+        let name = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        match value {
+            toml::Value::String(value) => args.insert(&name, value),
+            toml::Value::Integer(value) => args.insert(&name, value),
+            toml::Value::Float(value) => args.insert(&name, value),
+            toml::Value::Boolean(value) => args.insert(&name, value),
+            toml::Value::Datetime(value) => args.insert(&name, value.to_string()),
+            toml::Value::Table(table) => {
+                flatten(args, &name, table)?;
+                continue;
+            }
+            toml::Value::Array(_) => anyhow::bail!("Unsupported array for arg \"{}\"", name),
+        };
+    }
+
+    Ok(())
+}
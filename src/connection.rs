@@ -0,0 +1,272 @@
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, Shutdown, SocketAddr, TcpStream},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use socket2::SockRef;
+
+use crate::sendfile::SendFile;
+
+/// A connection accepted by the server, read and written the same way whatever the
+/// transport.
+pub(crate) enum Connection {
+    Plain(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<crate::tls::TlsStream>),
+}
+
+impl Connection {
+    /// Sets the read timeout of the underlying socket.
+    pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.set_read_timeout(timeout),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => stream.socket().set_read_timeout(timeout),
+        }
+    }
+
+    /// Sets the write timeout of the underlying socket.
+    pub(crate) fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.set_write_timeout(timeout),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.set_write_timeout(timeout),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => stream.socket().set_write_timeout(timeout),
+        }
+    }
+
+    /// Returns the underlying socket.
+    pub(crate) fn socket(&self) -> SockRef<'_> {
+        match self {
+            Connection::Plain(stream) => SockRef::from(stream),
+            #[cfg(unix)]
+            Connection::Unix(stream) => SockRef::from(stream),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => SockRef::from(stream.socket()),
+        }
+    }
+
+    /// Shuts down the writing half of the connection once the last response is sent,
+    /// telling the client not to wait for more. TLS connections send their
+    /// `close_notify` alert first.
+    pub(crate) fn close_write(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.shutdown(Shutdown::Write),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.shutdown(Shutdown::Write),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => stream.close_write(),
+        }
+    }
+
+    /// Returns the underlying stream if the connection is plain TCP.
+    pub(crate) fn into_tcp(self) -> Option<TcpStream> {
+        match self {
+            Connection::Plain(stream) => Some(stream),
+            #[cfg(unix)]
+            Connection::Unix(_) => None,
+            #[cfg(feature = "tls")]
+            Connection::Tls(_) => None,
+        }
+    }
+
+    /// Returns `true` if the data is encrypted before reaching the socket.
+    pub(crate) fn is_tls(&self) -> bool {
+        match self {
+            Connection::Plain(_) => false,
+            #[cfg(unix)]
+            Connection::Unix(_) => false,
+            #[cfg(feature = "tls")]
+            Connection::Tls(_) => true,
+        }
+    }
+
+    /// Returns the underlying TCP socket, `None` for Unix sockets.
+    pub(crate) fn tcp_socket(&self) -> Option<SockRef<'_>> {
+        match self {
+            Connection::Plain(stream) => Some(SockRef::from(stream)),
+            #[cfg(unix)]
+            Connection::Unix(_) => None,
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => Some(SockRef::from(stream.socket())),
+        }
+    }
+
+    /// Returns the certificate the client authenticated with, if any.
+    #[cfg(feature = "tls")]
+    pub(crate) fn peer_certificate(&self) -> Option<std::sync::Arc<crate::tls::PeerCertificate>> {
+        match self {
+            Connection::Tls(stream) => stream.peer_certificate(),
+            _ => None,
+        }
+    }
+
+    /// Returns the address of the client, `None` for Unix sockets.
+    pub(crate) fn peer_addr(&self) -> io::Result<Option<SocketAddr>> {
+        match self {
+            Connection::Plain(stream) => Ok(Some(stream.peer_addr()?)),
+            #[cfg(unix)]
+            Connection::Unix(_) => Ok(None),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => Ok(Some(stream.socket().peer_addr()?)),
+        }
+    }
+
+    /// Returns the IP address of the client, `None` for Unix sockets.
+    pub(crate) fn peer_ip(&self) -> io::Result<Option<IpAddr>> {
+        Ok(self.peer_addr()?.map(|addr| addr.ip()))
+    }
+}
+
+/// A stream requests are read from and responses written to.
+///
+/// Streams other than sockets, such as the ones handed over to
+/// [`Server::serve_connection`](crate::Server::serve_connection), have no timeouts
+/// and no peer, and can't be watched by the watchdog.
+pub(crate) trait Transport: Read + Write + SendFile {
+    /// Sets the read timeout of the underlying socket, if any.
+    fn set_read_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the underlying socket, if any.
+    fn socket(&self) -> Option<SockRef<'_>> {
+        None
+    }
+
+    /// Returns `true` if the data is encrypted before reaching the socket.
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    /// Returns the IP address of the client, if known.
+    fn peer_ip(&self) -> io::Result<Option<IpAddr>> {
+        Ok(None)
+    }
+
+    /// Tells the client not to wait for more once the last response is sent.
+    fn close_write(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the certificate the client authenticated with, if any.
+    #[cfg(feature = "tls")]
+    fn peer_certificate(&self) -> Option<std::sync::Arc<crate::tls::PeerCertificate>> {
+        None
+    }
+}
+
+impl Transport for Connection {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        Connection::set_read_timeout(self, timeout)
+    }
+
+    fn socket(&self) -> Option<SockRef<'_>> {
+        Some(Connection::socket(self))
+    }
+
+    fn is_tls(&self) -> bool {
+        Connection::is_tls(self)
+    }
+
+    fn peer_ip(&self) -> io::Result<Option<IpAddr>> {
+        Connection::peer_ip(self)
+    }
+
+    fn close_write(&mut self) -> io::Result<()> {
+        Connection::close_write(self)
+    }
+
+    #[cfg(feature = "tls")]
+    fn peer_certificate(&self) -> Option<std::sync::Arc<crate::tls::PeerCertificate>> {
+        Connection::peer_certificate(self)
+    }
+}
+
+/// A stream handed over by the application, see
+/// [`Server::serve_connection`](crate::Server::serve_connection).
+pub(crate) struct Embedded<S>(pub(crate) S);
+
+impl<S: Read> Read for Embedded<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<S: Write> Write for Embedded<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<S: Write> SendFile for Embedded<S> {}
+
+impl<S: Read + Write> Transport for Embedded<S> {}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.flush(),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+impl SendFile for Connection {
+    fn send_file(
+        &mut self,
+        file: &std::fs::File,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<Option<u64>> {
+        match self {
+            #[cfg(unix)]
+            Connection::Plain(stream) => crate::sendfile::send(stream, file, offset, len),
+            #[cfg(not(unix))]
+            Connection::Plain(_) => Ok(None),
+            #[cfg(unix)]
+            Connection::Unix(stream) => crate::sendfile::send(stream, file, offset, len),
+            // The data is encrypted in userspace
+            #[cfg(feature = "tls")]
+            Connection::Tls(_) => Ok(None),
+        }
+    }
+}
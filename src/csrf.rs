@@ -0,0 +1,57 @@
+use crate::{random, session::Session};
+
+/// The key of the CSRF token in the session.
+pub(crate) const SESSION_KEY: &str = "csrf_token";
+
+/// The number of random bytes in a token.
+const TOKEN_LEN: usize = 32;
+
+/// The CSRF token of a request, found in its
+/// [extensions](crate::http::HTTPRequest::extensions) when the
+/// [`csrf`](crate::middleware::csrf) middleware is used, whatever its strategy.
+/// Pages embed it in their forms, or scripts send it back in a header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsrfToken(pub String);
+
+/// Returns the CSRF token of `session`, creating it if the session has none yet.
+///
+/// The token lives as long as the session, until [`Session::renew`] drops it.
+///
+/// # Arguments
+///
+/// * `session` - The session of the request.
+///
+/// # Returns
+///
+/// Returns the token, 64 hexadecimal characters.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::{csrf, session::Session};
+///
+/// let session = Session::default();
+/// let token = csrf::token_for(&session);
+/// assert_eq!(token.len(), 64);
+/// assert_eq!(csrf::token_for(&session), token);
+///
+/// // Logging in rotates it
+/// session.renew();
+/// assert_ne!(csrf::token_for(&session), token);
+/// ```
+pub fn token_for(session: &Session) -> String {
+    match session.get::<String>(SESSION_KEY) {
+        Some(token) => token,
+        None => {
+            let token = new_token();
+            session.insert(SESSION_KEY, &token);
+
+            token
+        }
+    }
+}
+
+/// Returns a new random token, for the double-submit cookie strategy.
+pub(crate) fn new_token() -> String {
+    random::hex(TOKEN_LEN)
+}
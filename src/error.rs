@@ -0,0 +1,172 @@
+use std::{fmt, io, net::IpAddr};
+
+use crate::{http::ParseError, span};
+
+/// The errors returned by a [`Server`](crate::Server) and its builder.
+///
+/// Handlers keep returning `anyhow::Result`, their errors are carried by
+/// [`Error::Handler`].
+///
+/// # Example
+///
+/// ```
+/// use std::{io, net::TcpListener};
+/// use fobserver::{args::Args, http::HTTPRequest, router::Router, Error, Server};
+///
+/// let taken = TcpListener::bind("127.0.0.1:0")?;
+///
+/// match Server::new(taken.local_addr()?, Router::new(), Args::new()) {
+///     Err(Error::Bind(err)) => assert_eq!(err.kind(), io::ErrorKind::AddrInUse),
+///     _ => panic!("The address should be in use"),
+/// }
+///
+/// // Parsing errors convert with `?`
+/// fn parse(raw: &str) -> Result<HTTPRequest, Error> {
+///     Ok(raw.parse()?)
+/// }
+///
+/// assert!(matches!(parse("GET\r\n\r\n"), Err(Error::Parse(_))));
+/// assert!(parse("GET / HTTP/1.1\r\n\r\n").is_ok());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An address couldn't be bound, e.g. because it is in use.
+    Bind(io::Error),
+    /// A listener failed to accept connections.
+    Accept(io::Error),
+    /// A request or a response is malformed.
+    Parse(ParseError),
+    /// A handler or a middleware failed.
+    Handler(anyhow::Error),
+    /// A setting of the server is invalid.
+    Config(anyhow::Error),
+    /// Any other I/O operation failed, e.g. spawning a thread.
+    Io(io::Error),
+    /// Any other failure, e.g. a lock poisoned by a panic.
+    Other(anyhow::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Bind(err) => write!(f, "Failed to bind: {}", err),
+            Error::Accept(err) => write!(f, "Failed to accept connection: {}", err),
+            Error::Parse(err) => write!(f, "Failed to parse: {}", err),
+            Error::Handler(err) => write!(f, "Handler failed: {}", err),
+            Error::Config(err) => write!(f, "Invalid configuration: {}", err),
+            Error::Io(err) => write!(f, "Error: {}", err),
+            Error::Other(err) => write!(f, "Error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bind(err) | Error::Accept(err) | Error::Io(err) => Some(err),
+            Error::Parse(err) => Some(err),
+            Error::Handler(err) | Error::Config(err) | Error::Other(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        Error::Other(err)
+    }
+}
+
+/// An error that ended a connection, reported to the
+/// [connection error hook](crate::Server::set_connection_error_hook).
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ConnectionError {
+    /// What the connection failed at.
+    pub kind: ConnectionErrorKind,
+    /// The IP address of the client, `None` for Unix sockets.
+    pub addr: Option<IpAddr>,
+    /// The error, with the chain of its causes.
+    pub error: anyhow::Error,
+}
+
+/// What a connection failed at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionErrorKind {
+    /// A request couldn't be read: it is malformed, too large or too slow, or the
+    /// client went away while sending it.
+    Read,
+    /// A response couldn't be written, e.g. because the client stopped reading it.
+    /// Clients closing the connection are not reported.
+    Write,
+    /// Anything else, e.g. a socket option couldn't be set.
+    Other,
+}
+
+/// Describes the failure, also used as the context of the errors of its kind.
+impl fmt::Display for ConnectionErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionErrorKind::Read => f.write_str("Failed to read request"),
+            ConnectionErrorKind::Write => f.write_str("Failed to write response"),
+            ConnectionErrorKind::Other => f.write_str("Connection failed"),
+        }
+    }
+}
+
+impl ConnectionError {
+    /// Wraps an error returned while serving the connection from `addr`, classified
+    /// by the kind it was given as context, if any.
+    pub(crate) fn new(error: anyhow::Error, addr: Option<IpAddr>) -> Self {
+        ConnectionError {
+            kind: error
+                .downcast_ref::<ConnectionErrorKind>()
+                .copied()
+                .unwrap_or(ConnectionErrorKind::Other),
+            addr,
+            error,
+        }
+    }
+
+    /// Logs the error, which is what happens when no hook is set.
+    ///
+    /// Clients going away while a response is written is business as usual, those
+    /// errors are logged at debug level, others at warn level.
+    pub fn log(&self) {
+        match self.kind {
+            ConnectionErrorKind::Write => span::event!(debug, "{}", self),
+            _ => span::event!(warn, "{}", self),
+        }
+    }
+}
+
+impl std::error::Error for ConnectionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// Formats the error with its causes, e.g. `Connection from 127.0.0.1 failed: Failed
+/// to read request: Invalid request`.
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.addr {
+            Some(addr) => write!(f, "Connection from {} failed: {:#}", addr, self.error),
+            None => write!(f, "Connection failed: {:#}", self.error),
+        }
+    }
+}
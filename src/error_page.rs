@@ -0,0 +1,203 @@
+use crate::{
+    health::json_string,
+    http::{
+        mime::{self, Format},
+        HTTPRequest, HTTPResponse, StatusCode,
+    },
+};
+
+/// The HTML page of an error, when no template is set.
+const DEFAULT_HTML: &str = "<!DOCTYPE html>\n<html>\n<head><title>{status} {reason}</title></head>\n<body><h1>{status} {reason}</h1></body>\n</html>\n";
+
+/// The JSON document of an error, when no template is set.
+const DEFAULT_JSON: &str = r#"{"status":{status},"reason":{reason},"path":{path}}"#;
+
+/// The templates of the bodies of the errors the server generates itself, rather than
+/// a handler: `404 Not Found` when no route matches, `500 Internal Server Error` when
+/// a handler fails without an [error handler](crate::Server::set_error_handler),
+/// `505 HTTP Version Not Supported`, and the like.
+///
+/// The body is rendered in the format the `Accept` header of the request prefers:
+/// HTML for browsers, JSON for API clients, plain text otherwise and for requests
+/// that couldn't be read. Each format has a template, `None` using the default one: a
+/// minimal page, a `{"status", "reason", "path"}` document, or the reason phrase. In
+/// templates, `{status}` is replaced with the status code, `{reason}` with its reason
+/// phrase and `{path}` with the path of the request, escaped as HTML in HTML
+/// templates and quoted as JSON strings in JSON ones.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::{
+///     args::Args,
+///     error_page::ErrorPages,
+///     http::{HTTPRequest, StatusCode},
+///     router::Router,
+///     testing::TestServer,
+///     Server,
+/// };
+///
+/// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+/// server.set_error_pages(ErrorPages {
+///     html: Some("<h1>{status}</h1><p>Nothing at {path}</p>".to_string()),
+///     ..ErrorPages::default()
+/// });
+/// let server = TestServer::from_server(&server);
+///
+/// let missing = |accept: &str| {
+///     let request: HTTPRequest = format!("GET /<missing> HTTP/1.1\r\nAccept: {}\r\n\r\n", accept).parse()?;
+///     let response = server.request(request)?;
+///     assert_eq!(response.status_code, StatusCode::CODE404);
+///
+///     let content_type = response.headers.get("Content-Type").unwrap_or_default().to_string();
+///     let body = String::from_utf8(response.body.unwrap().as_bytes().unwrap().to_vec())?;
+///     Ok::<_, anyhow::Error>((content_type, body))
+/// };
+///
+/// // Browsers get the page
+/// let (content_type, body) = missing("text/html,application/xhtml+xml,*/*;q=0.8")?;
+/// assert_eq!(content_type, "text/html; charset=utf-8");
+/// assert_eq!(body, "<h1>404</h1><p>Nothing at /&lt;missing&gt;</p>");
+///
+/// // API clients a document
+/// let (content_type, body) = missing("application/json")?;
+/// assert_eq!(content_type, "application/json");
+/// assert_eq!(body, r#"{"status":404,"reason":"Not Found","path":"/<missing>"}"#);
+///
+/// // Anyone else the reason phrase
+/// let (content_type, body) = missing("*/*")?;
+/// assert_eq!(content_type, "text/plain; charset=utf-8");
+/// assert_eq!(body, "Not Found");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorPages {
+    /// The template of HTML bodies.
+    pub html: Option<String>,
+    /// The template of JSON bodies.
+    pub json: Option<String>,
+    /// The template of plain text bodies.
+    pub text: Option<String>,
+}
+
+impl ErrorPages {
+    /// Creates the response to an error with `status_code`, in the format `request`
+    /// prefers.
+    ///
+    /// # Parameters
+    /// - `status_code`: The status code of the error.
+    /// - `request`: The request answered, `None` if it couldn't be read.
+    /// - `problem_details`: Whether JSON bodies are problem documents, when no JSON
+    ///   template is set.
+    #[cfg_attr(not(feature = "json"), allow(unused_variables))]
+    pub(crate) fn response(
+        &self,
+        status_code: StatusCode,
+        request: Option<&HTTPRequest>,
+        problem_details: bool,
+    ) -> HTTPResponse {
+        let format = request
+            .and_then(|request| request.headers.get("Accept"))
+            .map_or(Format::Text, mime::preferred);
+        let path = request.map_or("", |request| request.path.as_str());
+
+        let (content_type, body) = match format {
+            Format::Html => {
+                let template = self.html.as_deref().unwrap_or(DEFAULT_HTML);
+
+                (
+                    "text/html; charset=utf-8",
+                    fill(template, status_code, path, escape_html),
+                )
+            }
+            #[cfg(feature = "json")]
+            Format::Json if problem_details && self.json.is_none() => {
+                return crate::http::Problem::new(status_code)
+                    .instance(path)
+                    .into_response();
+            }
+            Format::Json => {
+                let template = self.json.as_deref().unwrap_or(DEFAULT_JSON);
+
+                (
+                    "application/json",
+                    fill(template, status_code, path, json_string),
+                )
+            }
+            Format::Text => match &self.text {
+                Some(template) => (
+                    "text/plain; charset=utf-8",
+                    fill(template, status_code, path, str::to_string),
+                ),
+                None => return HTTPResponse::plain_text(status_code, status_code.reason()),
+            },
+        };
+
+        let mut response = HTTPResponse {
+            status_code,
+            body: Some(body.into()),
+            ..HTTPResponse::default()
+        };
+        response.headers.set("Content-Type", content_type);
+
+        response
+    }
+}
+
+/// Replaces the placeholders of `template`, in a single pass so that the values
+/// aren't searched for placeholders themselves.
+///
+/// # Parameters
+/// - `template`: The template.
+/// - `status_code`: The status code of the error.
+/// - `path`: The path of the request.
+/// - `escape`: Turns a text into what stands for it in the format of the template.
+fn fill(
+    template: &str,
+    status_code: StatusCode,
+    path: &str,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let mut filled = String::with_capacity(template.len() + path.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = match rest.find('}').map(|end| &rest[1..end]) {
+            Some("status") => status_code.code().to_string(),
+            Some("reason") => escape(status_code.reason()),
+            Some("path") => escape(path),
+            _ => {
+                filled.push('{');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        filled.push_str(&value);
+        rest = &rest[rest.find('}').map_or(rest.len(), |end| end + 1)..];
+    }
+    filled.push_str(rest);
+
+    filled
+}
+
+/// Escapes the characters of `text` that are markup in HTML.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,252 @@
+use std::{
+    fs::{self, File},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::http::{date, mime, Body, HTTPRequest, HTTPResponse, Method, StatusCode};
+
+/// Serves the file at `path` as the response to `request`.
+///
+/// The file is streamed from disk while the response is written rather than loaded in
+/// memory. Byte-range requests are supported: a single satisfiable range is answered
+/// with `206 Partial Content` and the matching `Content-Range`, an unsatisfiable one
+/// with `416 Range Not Satisfiable`. Overlapping and adjacent ranges are merged, and
+/// when several remain they are answered with a `multipart/byteranges` body holding
+/// the satisfiable ones, streamed from the file as well. Requests for more than 16
+/// ranges get the whole file. Full responses advertise `Accept-Ranges: bytes`.
+///
+/// Every response carries the file's modification time in `Last-Modified`. When the
+/// request has an `If-Modified-Since` at or after that time, it is answered with
+/// `304 Not Modified` without opening the file.
+///
+/// # Arguments
+///
+/// * `request` - The request being answered.
+/// * `path` - The path of the file to serve.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `HTTPResponse`, which is a `404 Not Found` if
+/// the file doesn't exist, or an error if the file can't be read.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Read;
+/// use fobserver::{
+///     files,
+///     http::{Body, HTTPRequest, StatusCode},
+/// };
+///
+/// let path = std::env::temp_dir().join("fobserver-serve-file.bin");
+/// std::fs::write(&path, vec![0u8; 1000])?;
+///
+/// let request = |range: &str| -> anyhow::Result<HTTPRequest> {
+///     Ok(format!("GET /file HTTP/1.1\r\n{}\r\n\r\n", range).parse()?)
+/// };
+///
+/// let response = files::serve_file(&request("")?, &path)?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.headers.get("Accept-Ranges"), Some("bytes"));
+///
+/// let response = files::serve_file(&request("Range: bytes=100-199")?, &path)?;
+/// assert_eq!(response.status_code, StatusCode::CODE206);
+/// assert_eq!(response.headers.get("Content-Range"), Some("bytes 100-199/1000"));
+/// assert!(matches!(response.body, Some(Body::File { offset: 100, len: 100, .. })));
+///
+/// let response = files::serve_file(&request("Range: bytes=5000-")?, &path)?;
+/// assert_eq!(response.status_code, StatusCode::CODE416);
+/// assert_eq!(response.headers.get("Content-Range"), Some("bytes */1000"));
+///
+/// let response = files::serve_file(&request("Range: bytes=0-9, 20-29")?, &path)?;
+/// assert_eq!(response.status_code, StatusCode::CODE206);
+/// assert!(response
+///     .headers
+///     .get("Content-Type")
+///     .unwrap()
+///     .starts_with("multipart/byteranges; boundary="));
+///
+/// let length: usize = response.headers.get("Content-Length").unwrap().parse()?;
+/// let mut body = Vec::new();
+/// if let Some(Body::Stream(mut reader)) = response.body {
+///     reader.read_to_end(&mut body)?;
+/// }
+/// assert_eq!(body.len(), length);
+/// assert!(body.ends_with(b"--\r\n"));
+///
+/// // Overlapping ranges are sent once
+/// let response = files::serve_file(&request("Range: bytes=0-49, 20-99, 100-149")?, &path)?;
+/// assert_eq!(response.status_code, StatusCode::CODE206);
+/// assert_eq!(response.headers.get("Content-Range"), Some("bytes 0-149/1000"));
+///
+/// // Too many ranges get the whole file
+/// let ranges = vec!["0-"; 100].join(",");
+/// let response = files::serve_file(&request(&format!("Range: bytes={}", ranges))?, &path)?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert!(matches!(response.body, Some(Body::File { offset: 0, len: 1000, .. })));
+///
+/// // Revalidating with the returned date doesn't send the file again
+/// let response = files::serve_file(&request("")?, &path)?;
+/// let last_modified = response.headers.get("Last-Modified").unwrap();
+///
+/// let response = files::serve_file(&request(&format!("If-Modified-Since: {}", last_modified))?, &path)?;
+/// assert_eq!(response.status_code, StatusCode::CODE304);
+/// assert_eq!(response.headers.get("Last-Modified"), Some(last_modified));
+/// assert!(response.body.is_none());
+///
+/// let response = files::serve_file(
+///     &request("If-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT")?,
+///     &path,
+/// )?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// # std::fs::remove_file(&path)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn serve_file<P: AsRef<Path>>(request: &HTTPRequest, path: P) -> anyhow::Result<HTTPResponse> {
+    let path = path.as_ref();
+
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return Ok(HTTPResponse::not_found()),
+    };
+
+    // HTTP dates have a one second precision, truncate so revalidation doesn't flap
+    let last_modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| UNIX_EPOCH + Duration::from_secs(modified.as_secs()));
+
+    let mut response = HTTPResponse::default();
+
+    if let Some(last_modified) = last_modified {
+        response
+            .headers
+            .set("Last-Modified", &date::format(last_modified));
+
+        if not_modified(request, last_modified) {
+            response.status_code = StatusCode::CODE304;
+
+            return Ok(response);
+        }
+    }
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(HTTPResponse::not_found()),
+    };
+    let size = metadata.len();
+
+    response
+        .headers
+        .set("Content-Type", mime::from_path(path))
+        .set("Accept-Ranges", "bytes");
+
+    let range = match request.method {
+        Method::GET => request.range(),
+        _ => None,
+    };
+
+    let (offset, len) = match range {
+        None => (0, size),
+        Some(range) => {
+            let mut resolved = range
+                .ranges
+                .iter()
+                .filter_map(|range| range.resolve(size))
+                .collect::<Vec<(u64, u64)>>();
+            resolved.sort_unstable();
+
+            let mut ranges: Vec<(u64, u64)> = Vec::with_capacity(resolved.len());
+            for (first, last) in resolved {
+                match ranges.last_mut() {
+                    Some((_, end)) if first <= end.saturating_add(1) => *end = last.max(*end),
+                    _ => ranges.push((first, last)),
+                }
+            }
+
+            match ranges.as_slice() {
+                [] => {
+                    response.status_code = StatusCode::CODE416;
+                    response.headers.remove("Content-Type");
+                    response
+                        .headers
+                        .set("Content-Range", &format!("bytes */{}", size));
+
+                    return Ok(response);
+                }
+                [(first, last)] => {
+                    response.status_code = StatusCode::CODE206;
+                    response.headers.set(
+                        "Content-Range",
+                        &format!("bytes {}-{}/{}", first, last, size),
+                    );
+
+                    (*first, last - first + 1)
+                }
+                ranges => {
+                    return Ok(HTTPResponse::file_byteranges(
+                        file,
+                        mime::from_path(path),
+                        size,
+                        ranges,
+                    ))
+                }
+            }
+        }
+    };
+
+    if request.method != Method::HEAD {
+        response.body = Some(Body::File { file, offset, len });
+    }
+
+    Ok(response)
+}
+
+/// Returns `true` if `request` is a conditional GET or HEAD whose `If-Modified-Since`
+/// is at or after `last_modified`.
+fn not_modified(request: &HTTPRequest, last_modified: SystemTime) -> bool {
+    if request.method != Method::GET && request.method != Method::HEAD {
+        return false;
+    }
+
+    request
+        .headers
+        .get("If-Modified-Since")
+        .and_then(|since| date::parse(since).ok())
+        .is_some_and(|since| last_modified <= since)
+}
+
+/// Serves the file under the `root` directory matching the path of `request`.
+///
+/// The query string is ignored, directories are served through their `index.html`
+/// and paths trying to escape `root` (e.g. with `..`) are answered with
+/// `404 Not Found`. See [`serve_file`] for how the file itself is sent.
+///
+/// # Arguments
+///
+/// * `request` - The request being answered.
+/// * `root` - The directory containing the files to serve.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `HTTPResponse` or an error if the file can't be read.
+pub fn serve_dir<P: AsRef<Path>>(request: &HTTPRequest, root: P) -> anyhow::Result<HTTPResponse> {
+    let path = request.path.split(['?', '#']).next().unwrap_or_default();
+
+    let mut resolved = PathBuf::from(root.as_ref());
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            _ => return Ok(HTTPResponse::not_found()),
+        }
+    }
+
+    if resolved.is_dir() {
+        resolved.push("index.html");
+    }
+
+    serve_file(request, resolved)
+}
@@ -0,0 +1,288 @@
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, PoisonError, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    args::Args,
+    http::{HTTPRequest, HTTPResponse, Method, StatusCode},
+};
+
+/// A readiness probe: checks a dependency of the server, e.g. pings its database,
+/// and returns why it isn't usable if it isn't.
+pub type ProbeFunction = Arc<dyn Fn(&Arc<RwLock<Args>>) -> Result<(), String> + Send + Sync>;
+
+/// Where a [`Server`](crate::Server) answers health checks, see
+/// [`Server::enable_health_endpoints`](crate::Server::enable_health_endpoints).
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// The path answering `200 OK` as long as the server runs. Defaults to
+    /// `/healthz`.
+    pub liveness_path: String,
+    /// The path answering `200 OK` when every readiness probe passes, `503 Service
+    /// Unavailable` otherwise. Defaults to `/readyz`.
+    pub readiness_path: String,
+    /// How long each probe may take before it counts as failed. Defaults to 5 seconds.
+    pub probe_timeout: Duration,
+    /// Whether health checks appear in the access log. Defaults to `false`, since
+    /// orchestrators send them every few seconds.
+    pub access_log: bool,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            liveness_path: "/healthz".to_string(),
+            readiness_path: "/readyz".to_string(),
+            probe_timeout: Duration::from_secs(5),
+            access_log: false,
+        }
+    }
+}
+
+/// The health endpoints of a server and the probes its readiness depends on.
+#[derive(Clone, Default)]
+pub(crate) struct Health {
+    config: Option<HealthConfig>,
+    probes: Vec<Probe>,
+}
+
+/// A readiness probe and its run in progress, if any.
+#[derive(Clone)]
+struct Probe {
+    name: String,
+    function: ProbeFunction,
+    /// The run not finished yet, shared by the checks made meanwhile: a probe that
+    /// hangs isn't run again until it returns, so it holds a single thread.
+    running: Arc<Mutex<Option<Arc<Run>>>>,
+}
+
+/// A run of a probe, and its result once it finishes.
+#[derive(Default)]
+struct Run {
+    result: Mutex<Option<Result<(), String>>>,
+    finished: Condvar,
+}
+
+impl fmt::Debug for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let probes: Vec<_> = self.probes.iter().map(|probe| &probe.name).collect();
+
+        f.debug_struct("Health")
+            .field("config", &self.config)
+            .field("probes", &probes)
+            .finish()
+    }
+}
+
+impl Health {
+    /// Answers health checks on the paths of `config`.
+    pub(crate) fn enable(&mut self, config: HealthConfig) {
+        self.config = Some(config);
+    }
+
+    /// Adds a probe consulted by the readiness endpoint.
+    pub(crate) fn add_probe(&mut self, name: &str, probe: ProbeFunction) {
+        self.probes.push(Probe {
+            name: name.to_string(),
+            function: probe,
+            running: Arc::new(Mutex::new(None)),
+        });
+    }
+
+    /// Returns `true` if `path` is a health endpoint kept out of the access log.
+    pub(crate) fn is_unlogged(&self, path: &str) -> bool {
+        let Some(config) = &self.config else {
+            return false;
+        };
+        let path = path.split('?').next().unwrap_or_default();
+
+        !config.access_log && (path == config.liveness_path || path == config.readiness_path)
+    }
+
+    /// Answers `request` if it is a health check.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to answer.
+    /// * `args` - The arguments passed to the probes.
+    /// * `thread` - Prepares the threads the probes run on.
+    ///
+    /// # Returns
+    ///
+    /// Returns the response, or `None` if the request is routed normally.
+    pub(crate) fn response(
+        &self,
+        request: &HTTPRequest,
+        args: &Arc<RwLock<Args>>,
+        thread: impl Fn() -> thread::Builder,
+    ) -> Option<HTTPResponse> {
+        let config = self.config.as_ref()?;
+
+        if !matches!(request.method, Method::GET | Method::HEAD) {
+            return None;
+        }
+
+        let path = request.path.split('?').next().unwrap_or_default();
+        let failed = if path == config.liveness_path {
+            Vec::new()
+        } else if path == config.readiness_path {
+            self.run_probes(args, config.probe_timeout, thread)
+        } else {
+            return None;
+        };
+
+        let (status_code, body) = match failed.is_empty() {
+            true => (StatusCode::CODE200, r#"{"status":"ok"}"#.to_string()),
+            false => {
+                let failed: Vec<_> = failed
+                    .iter()
+                    .map(|(name, error)| {
+                        format!(
+                            r#"{{"name":{},"error":{}}}"#,
+                            json_string(name),
+                            json_string(error)
+                        )
+                    })
+                    .collect();
+
+                (
+                    StatusCode::CODE503,
+                    format!(
+                        r#"{{"status":"unavailable","failed":[{}]}}"#,
+                        failed.join(",")
+                    ),
+                )
+            }
+        };
+
+        let len = body.len().to_string();
+        let mut response = HTTPResponse {
+            status_code,
+            body: Some(body.into()),
+            ..HTTPResponse::default()
+        };
+        response
+            .headers
+            .set("Content-Type", "application/json")
+            .set("Content-Length", &len)
+            .set("Cache-Control", "no-store");
+
+        Some(response)
+    }
+
+    /// Runs every probe concurrently, each on its own thread, or waits for the run in
+    /// progress of the probes still running since an earlier check.
+    ///
+    /// # Returns
+    ///
+    /// Returns the names of the probes that failed or didn't finish within `timeout`,
+    /// with the reason.
+    fn run_probes(
+        &self,
+        args: &Arc<RwLock<Args>>,
+        timeout: Duration,
+        thread: impl Fn() -> thread::Builder,
+    ) -> Vec<(String, String)> {
+        let deadline = Instant::now() + timeout;
+
+        let runs: Vec<_> = self
+            .probes
+            .iter()
+            .map(|probe| (&probe.name, probe.start(args, &thread)))
+            .collect();
+
+        runs.into_iter()
+            .filter_map(|(name, run)| {
+                let result = run.and_then(|run| run.wait(deadline));
+
+                result.err().map(|error| (name.clone(), error))
+            })
+            .collect()
+    }
+}
+
+impl Probe {
+    /// Runs the probe on a new thread, unless it is still running.
+    ///
+    /// # Returns
+    ///
+    /// Returns the run to wait for, or why the thread couldn't be started.
+    fn start(
+        &self,
+        args: &Arc<RwLock<Args>>,
+        thread: impl Fn() -> thread::Builder,
+    ) -> Result<Arc<Run>, String> {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(run) = &*running {
+            return Ok(run.clone());
+        }
+
+        let run = Arc::new(Run::default());
+        let function = self.function.clone();
+        let args = args.clone();
+        let slot = self.running.clone();
+        let finished = run.clone();
+
+        // A stuck probe is left behind, it doesn't hold the endpoint
+        thread()
+            .spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| function(&args)))
+                    .unwrap_or_else(|_| Err("Panicked".to_string()));
+
+                *slot.lock().unwrap_or_else(PoisonError::into_inner) = None;
+                finished.finish(result);
+            })
+            .map_err(|err| format!("Failed to start: {}", err))?;
+        *running = Some(run.clone());
+
+        Ok(run)
+    }
+}
+
+impl Run {
+    /// Records the result of the probe and wakes up the checks waiting for it.
+    fn finish(&self, result: Result<(), String>) {
+        *self.result.lock().unwrap_or_else(PoisonError::into_inner) = Some(result);
+        self.finished.notify_all();
+    }
+
+    /// Waits for the result of the probe until `deadline`.
+    fn wait(&self, deadline: Instant) -> Result<(), String> {
+        let result = self.result.lock().unwrap_or_else(PoisonError::into_inner);
+        let timeout = deadline.saturating_duration_since(Instant::now());
+
+        let (result, _) = self
+            .finished
+            .wait_timeout_while(result, timeout, |result| result.is_none())
+            .unwrap_or_else(PoisonError::into_inner);
+
+        result
+            .clone()
+            .unwrap_or_else(|| Err("Timed out".to_string()))
+    }
+}
+
+/// Quotes `value` as a JSON string.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
@@ -1,4 +1,74 @@
-use std::{collections::HashMap, net::IpAddr, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Write},
+    net::{IpAddr, TcpStream},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::Receiver,
+};
+
+mod auth;
+mod body;
+mod byteranges;
+mod cache_control;
+pub mod date;
+mod disposition;
+mod extensions;
+mod headers;
+#[cfg(feature = "json")]
+mod json;
+pub(crate) mod mime;
+#[cfg(feature = "json")]
+pub(crate) mod problem;
+mod range;
+mod sse;
+#[cfg(feature = "trace-context")]
+mod trace_context;
+
+pub use body::Body;
+pub use byteranges::ByteRangePart;
+pub use cache_control::CacheControl;
+pub use extensions::Extensions;
+pub use headers::{HeaderMap, Headers};
+#[cfg(feature = "json")]
+pub use problem::{FieldError, Problem};
+pub use range::{ByteRange, Range};
+pub use sse::Event;
+#[cfg(feature = "trace-context")]
+pub use trace_context::TraceContext;
+
+/// The error returned when a request, a response or one of their parts is malformed.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::http::{HTTPRequest, ParseError};
+///
+/// let err: ParseError = "GET\r\n\r\n".parse::<HTTPRequest>().unwrap_err();
+/// assert_eq!(err.to_string(), "Missing path");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    message: String,
+}
+
+impl ParseError {
+    /// Creates an error explained by `message`.
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        ParseError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
 
 /// Represents an HTTP method.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -17,7 +87,7 @@ pub enum Method {
 /// Provides functionality to convert a string into a `Method` enum.
 /// Example: "GET" becomes `Method::GET`.
 impl FromStr for Method {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_ascii_uppercase().as_str() {
@@ -30,30 +100,91 @@ impl FromStr for Method {
             "OPTIONS" => Ok(Method::OPTIONS),
             "TRACE" => Ok(Method::TRACE),
             "PATCH" => Ok(Method::PATCH),
-            _ => Err(anyhow::anyhow!("No matching HTTP method")),
+            _ => Err(ParseError::new("No matching HTTP method")),
         }
     }
 }
 
-/// Provides functionality to convert a `Method` enum into a string.
+/// Formats a `Method` enum as a string.
 /// Example: `Method::GET` becomes "GET".
-impl ToString for Method {
-    fn to_string(&self) -> String {
-        match &self {
-            Method::GET => "GET".to_string(),
-            Method::HEAD => "HEAD".to_string(),
-            Method::POST => "POST".to_string(),
-            Method::PUT => "PUT".to_string(),
-            Method::DELETE => "DELETE".to_string(),
-            Method::CONNECT => "CONNECT".to_string(),
-            Method::OPTIONS => "OPTIONS".to_string(),
-            Method::TRACE => "TRACE".to_string(),
-            Method::PATCH => "PATCH".to_string(),
-        }
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Method::GET => "GET",
+            Method::HEAD => "HEAD",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::DELETE => "DELETE",
+            Method::CONNECT => "CONNECT",
+            Method::OPTIONS => "OPTIONS",
+            Method::TRACE => "TRACE",
+            Method::PATCH => "PATCH",
+        })
     }
 }
 
 /// Represents an HTTP version.
+///
+/// The server speaks HTTP/1.0 and HTTP/1.1. Requests claiming a later version are
+/// answered `505 HTTP Version Not Supported` without reaching the router, and the
+/// connection is closed.
+///
+/// Responses are always sent as HTTP/1.1, the highest version the server speaks,
+/// whatever the version of the request and the one set by the handler, as
+/// recommended by RFC 9110 section 6.2. An HTTP/1.0 client reads them as HTTP/1.0
+/// responses: their bodies are never sent `chunked`, but with a `Content-Length` or
+/// ended by closing the connection.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::TcpStream,
+///     sync::{Arc, RwLock},
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, Version},
+///     router::Router,
+///     Server,
+/// };
+///
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.version = Version::V20;
+///     response.body = Some("hello".into());
+///
+///     Ok(response)
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", Version::V10, handler);
+/// router.add_route(http::Method::GET, "/", Version::V20, handler);
+///
+/// let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+///
+/// let send = |request: &str| -> anyhow::Result<String> {
+///     let mut stream = TcpStream::connect(server.addr()?)?;
+///     stream.write_all(request.as_bytes())?;
+///
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response)?;
+///
+///     Ok(response)
+/// };
+///
+/// let response = send("GET / HTTP/2.0\r\n\r\n")?;
+/// assert!(response.starts_with("HTTP/1.1 505 HTTP Version Not Supported"));
+/// assert!(response.contains("Connection: close"));
+///
+/// let response = send("GET / HTTP/1.0\r\n\r\n")?;
+/// assert!(response.starts_with("HTTP/1.1 200 OK"));
+/// assert!(!response.contains("Transfer-Encoding"));
+/// assert!(response.contains("Content-Length: 5"));
+/// assert!(response.ends_with("\n\nhello"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Version {
     V10,
@@ -65,7 +196,7 @@ pub enum Version {
 /// Provides functionality to convert a string into a `Version` enum.
 /// Example: "HTTP/1.1" becomes `Version::V11`.
 impl FromStr for Version {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_ascii_uppercase().as_str() {
@@ -73,28 +204,29 @@ impl FromStr for Version {
             "HTTP/1.1" => Ok(Version::V11),
             "HTTP/2.0" => Ok(Version::V20),
             "HTTP/3.0" => Ok(Version::V30),
-            _ => Err(anyhow::anyhow!("No matching HTTP version")),
+            _ => Err(ParseError::new("No matching HTTP version")),
         }
     }
 }
 
-/// Provides functionality to convert a `Version` enum into a string.
+/// Formats a `Version` enum as a string.
 /// Example: `Version::V11` becomes "HTTP/1.1".
-impl ToString for Version {
-    fn to_string(&self) -> String {
-        match &self {
-            Version::V10 => "HTTP/1.0".to_string(),
-            Version::V11 => "HTTP/1.1".to_string(),
-            Version::V20 => "HTTP/2.0".to_string(),
-            Version::V30 => "HTTP/3.0".to_string(),
-        }
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Version::V10 => "HTTP/1.0",
+            Version::V11 => "HTTP/1.1",
+            Version::V20 => "HTTP/2.0",
+            Version::V30 => "HTTP/3.0",
+        })
     }
 }
 
 /// Represents common HTTP status codes.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum StatusCode {
     CODE100, // 100 Continue: Client should continue with the request.
+    CODE101, // 101 Switching Protocols: The server switches to the protocol requested in the Upgrade header.
     CODE102, // 102 Processing: Server is processing the request but no final response is available yet.
     CODE103, // 103 Early Hints: Used to preload resources before the final response is sent.
     CODE200, // 200 OK: The request was successful and the server returned the requested data.
@@ -118,6 +250,11 @@ pub enum StatusCode {
     CODE406, // 406 Not Acceptable: The server cannot produce a response that matches the criteria defined by the client's Accept headers.
     CODE408, // 408 Request Timeout: The server timed out waiting for the client to send a request.
     CODE409, // 418 I'm a Teapot: An April Fools' joke response code from the Hyper Text Coffee Pot Control Protocol.
+    CODE413, // 413 Content Too Large: The request is larger than the server is willing to process.
+    CODE414, // 414 URI Too Long: The request target is longer than the server is willing to interpret.
+    CODE416, // 416 Range Not Satisfiable: None of the ranges in the request's Range header overlap the resource.
+    CODE422, // 422 Unprocessable Content: The request is well-formed but its content failed validation.
+    CODE429, // 429 Too Many Requests: The client has sent too many requests in a given amount of time.
     CODE500, // 500 Internal Server Error: The server encountered a situation it doesn't know how to handle.
     CODE501, // 501 Not Implemented: The request method is not supported by the server.
     CODE502, // 502 Bad Gateway: The server received an invalid response from the upstream server.
@@ -127,60 +264,215 @@ pub enum StatusCode {
     CODE511, // 511 Network Authentication Required: The client needs to authenticate to gain network access (often used in captive portals).
 }
 
-/// Provides functionality to convert a `StatusCode` enum into a string.
-/// Example: `StausCode::CODE100` becomes "100 Continue".
-impl ToString for StatusCode {
-    fn to_string(&self) -> String {
+impl StatusCode {
+    /// Returns the numeric code and the reason phrase of the status.
+    fn parts(&self) -> (u16, &'static str) {
         match self {
-            StatusCode::CODE100 => "100 Continue".to_string(),
-            StatusCode::CODE102 => "102 Processing".to_string(),
-            StatusCode::CODE103 => "103 Early Hints".to_string(),
-            StatusCode::CODE200 => "200 OK".to_string(),
-            //StatusCode::CODE201 => "201 Created".to_string(),
-            StatusCode::CODE202 => "202 Accepted".to_string(),
-            StatusCode::CODE204 => "204 No Content".to_string(),
-            StatusCode::CODE205 => "205 Reset Content".to_string(),
-            StatusCode::CODE206 => "206 Partial Content".to_string(),
-            StatusCode::CODE300 => "300 Multiple Choices".to_string(),
-            StatusCode::CODE301 => "301 Moved Permanently".to_string(),
-            StatusCode::CODE302 => "302 Found".to_string(),
-            StatusCode::CODE303 => "303 See Other".to_string(),
-            StatusCode::CODE304 => "304 Not Modified".to_string(),
-            StatusCode::CODE307 => "307 Temporary Redirect".to_string(),
-            StatusCode::CODE308 => "308 Permanent Redirect".to_string(),
-            StatusCode::CODE400 => "400 Bad Request".to_string(),
-            StatusCode::CODE401 => "401 Unauthorized".to_string(),
-            StatusCode::CODE403 => "403 Forbidden".to_string(),
-            StatusCode::CODE404 => "404 Not Found".to_string(),
-            StatusCode::CODE405 => "405 Method Not Allowed".to_string(),
-            StatusCode::CODE406 => "406 Not Acceptable".to_string(),
-            StatusCode::CODE408 => "408 Request Timeout".to_string(),
-            StatusCode::CODE409 => "409 Conflict".to_string(),
-            StatusCode::CODE500 => "500 Internal Server Error".to_string(),
-            StatusCode::CODE501 => "501 Not Implemented".to_string(),
-            StatusCode::CODE502 => "502 Bad Gateway".to_string(),
-            StatusCode::CODE503 => "503 Service Unabailable".to_string(),
-            StatusCode::CODE504 => "504 Gateway Timeout".to_string(),
-            StatusCode::CODE505 => "505 HTTP Version Not Supported".to_string(),
-            StatusCode::CODE511 => "511 Network Authentication Required".to_string(),
+            StatusCode::CODE100 => (100, "Continue"),
+            StatusCode::CODE101 => (101, "Switching Protocols"),
+            StatusCode::CODE102 => (102, "Processing"),
+            StatusCode::CODE103 => (103, "Early Hints"),
+            StatusCode::CODE200 => (200, "OK"),
+            //StatusCode::CODE201 => (201, "Created"),
+            StatusCode::CODE202 => (202, "Accepted"),
+            StatusCode::CODE204 => (204, "No Content"),
+            StatusCode::CODE205 => (205, "Reset Content"),
+            StatusCode::CODE206 => (206, "Partial Content"),
+            StatusCode::CODE300 => (300, "Multiple Choices"),
+            StatusCode::CODE301 => (301, "Moved Permanently"),
+            StatusCode::CODE302 => (302, "Found"),
+            StatusCode::CODE303 => (303, "See Other"),
+            StatusCode::CODE304 => (304, "Not Modified"),
+            StatusCode::CODE307 => (307, "Temporary Redirect"),
+            StatusCode::CODE308 => (308, "Permanent Redirect"),
+            StatusCode::CODE400 => (400, "Bad Request"),
+            StatusCode::CODE401 => (401, "Unauthorized"),
+            StatusCode::CODE403 => (403, "Forbidden"),
+            StatusCode::CODE404 => (404, "Not Found"),
+            StatusCode::CODE405 => (405, "Method Not Allowed"),
+            StatusCode::CODE406 => (406, "Not Acceptable"),
+            StatusCode::CODE408 => (408, "Request Timeout"),
+            StatusCode::CODE409 => (409, "Conflict"),
+            StatusCode::CODE413 => (413, "Content Too Large"),
+            StatusCode::CODE414 => (414, "URI Too Long"),
+            StatusCode::CODE416 => (416, "Range Not Satisfiable"),
+            StatusCode::CODE422 => (422, "Unprocessable Content"),
+            StatusCode::CODE429 => (429, "Too Many Requests"),
+            StatusCode::CODE500 => (500, "Internal Server Error"),
+            StatusCode::CODE501 => (501, "Not Implemented"),
+            StatusCode::CODE502 => (502, "Bad Gateway"),
+            StatusCode::CODE503 => (503, "Service Unavailable"),
+            StatusCode::CODE504 => (504, "Gateway Timeout"),
+            StatusCode::CODE505 => (505, "HTTP Version Not Supported"),
+            StatusCode::CODE511 => (511, "Network Authentication Required"),
+        }
+    }
+
+    /// Returns the numeric value of the status code, e.g. `404`.
+    pub fn code(&self) -> u16 {
+        self.parts().0
+    }
+
+    /// Returns the reason phrase of the status code, e.g. `Not Found`.
+    pub fn reason(&self) -> &'static str {
+        self.parts().1
+    }
+
+    /// Returns `true` for `1xx` status codes.
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.code())
+    }
+
+    /// Returns `true` for `2xx` status codes.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+
+    /// Returns `true` for `3xx` status codes.
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.code())
+    }
+
+    /// Returns `true` for `4xx` status codes.
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.code())
+    }
+
+    /// Returns `true` for `5xx` status codes.
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.code())
+    }
+}
+
+/// Converts a numeric status code into a `StatusCode` enum.
+/// Example: `404` becomes `StatusCode::CODE404`.
+impl TryFrom<u16> for StatusCode {
+    type Error = ParseError;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        match code {
+            100 => Ok(StatusCode::CODE100),
+            101 => Ok(StatusCode::CODE101),
+            102 => Ok(StatusCode::CODE102),
+            103 => Ok(StatusCode::CODE103),
+            200 => Ok(StatusCode::CODE200),
+            202 => Ok(StatusCode::CODE202),
+            204 => Ok(StatusCode::CODE204),
+            205 => Ok(StatusCode::CODE205),
+            206 => Ok(StatusCode::CODE206),
+            300 => Ok(StatusCode::CODE300),
+            301 => Ok(StatusCode::CODE301),
+            302 => Ok(StatusCode::CODE302),
+            303 => Ok(StatusCode::CODE303),
+            304 => Ok(StatusCode::CODE304),
+            307 => Ok(StatusCode::CODE307),
+            308 => Ok(StatusCode::CODE308),
+            400 => Ok(StatusCode::CODE400),
+            401 => Ok(StatusCode::CODE401),
+            403 => Ok(StatusCode::CODE403),
+            404 => Ok(StatusCode::CODE404),
+            405 => Ok(StatusCode::CODE405),
+            406 => Ok(StatusCode::CODE406),
+            408 => Ok(StatusCode::CODE408),
+            409 => Ok(StatusCode::CODE409),
+            413 => Ok(StatusCode::CODE413),
+            414 => Ok(StatusCode::CODE414),
+            416 => Ok(StatusCode::CODE416),
+            422 => Ok(StatusCode::CODE422),
+            429 => Ok(StatusCode::CODE429),
+            500 => Ok(StatusCode::CODE500),
+            501 => Ok(StatusCode::CODE501),
+            502 => Ok(StatusCode::CODE502),
+            503 => Ok(StatusCode::CODE503),
+            504 => Ok(StatusCode::CODE504),
+            505 => Ok(StatusCode::CODE505),
+            511 => Ok(StatusCode::CODE511),
+            _ => Err(ParseError::new(format!(
+                "Unsupported status code: {}",
+                code
+            ))),
         }
     }
 }
 
+/// Formats a `StatusCode` as it appears in the status line.
+/// Example: `StatusCode::CODE404` becomes "404 Not Found".
+///
+/// # Example
+///
+/// ```
+/// use fobserver::http::StatusCode;
+///
+/// let codes = [
+///     StatusCode::CODE100, StatusCode::CODE101, StatusCode::CODE102, StatusCode::CODE103,
+///     StatusCode::CODE200, StatusCode::CODE202, StatusCode::CODE204, StatusCode::CODE205,
+///     StatusCode::CODE206,
+///     StatusCode::CODE300, StatusCode::CODE301, StatusCode::CODE302, StatusCode::CODE303,
+///     StatusCode::CODE304, StatusCode::CODE307, StatusCode::CODE308, StatusCode::CODE400,
+///     StatusCode::CODE401, StatusCode::CODE403, StatusCode::CODE404, StatusCode::CODE405,
+///     StatusCode::CODE406, StatusCode::CODE408, StatusCode::CODE409, StatusCode::CODE413,
+///     StatusCode::CODE414, StatusCode::CODE416, StatusCode::CODE422, StatusCode::CODE429,
+///     StatusCode::CODE500, StatusCode::CODE501, StatusCode::CODE502, StatusCode::CODE503,
+///     StatusCode::CODE504, StatusCode::CODE505, StatusCode::CODE511,
+/// ];
+///
+/// for code in codes {
+///     let classes = [
+///         code.is_informational(),
+///         code.is_success(),
+///         code.is_redirection(),
+///         code.is_client_error(),
+///         code.is_server_error(),
+///     ];
+///
+///     assert_eq!(classes.iter().filter(|class| **class).count(), 1, "{}", code);
+///     assert!(classes[(code.code() / 100 - 1) as usize]);
+///     assert_eq!(code.to_string(), format!("{} {}", code.code(), code.reason()));
+/// }
+///
+/// assert_eq!(StatusCode::CODE404.to_string(), "404 Not Found");
+/// assert_eq!(format!("{:>16}", StatusCode::CODE200), "          200 OK");
+/// ```
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&format!("{} {}", self.code(), self.reason()))
+    }
+}
+
 /// Represents an HTTP request with method, path, version, headers, and optional body.
 #[derive(Debug)]
 pub struct HTTPRequest {
     pub method: Method,
     pub path: String,
     pub version: Version,
-    pub headers: HashMap<String, String>,
-    pub addr: IpAddr,
+    pub headers: HeaderMap,
+    pub addr: Option<IpAddr>,
     pub body: Option<String>,
+    /// Where the body is when it isn't held in `body`.
+    pub body_source: Option<BodySource>,
+    /// The data attached to the request by middlewares, empty when it is received.
+    pub extensions: Extensions,
+    /// Whether the request was received over a TLS connection.
+    pub(crate) tls: bool,
+    #[cfg(feature = "tls")]
+    pub(crate) peer_certificate: Option<std::sync::Arc<crate::tls::PeerCertificate>>,
+    #[cfg(feature = "trace-context")]
+    pub(crate) trace_context: Option<TraceContext>,
+}
+
+/// Where the body of a request is kept when it is too large to be held in memory,
+/// see [`Server::set_spool_threshold`](crate::Server::set_spool_threshold).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BodySource {
+    /// A temporary file and the length of the body it holds. The file is deleted
+    /// once the response is written, handlers keeping the body must move it away.
+    File(PathBuf, u64),
 }
 
 /// Provides functionality to parse a raw HTTP request string into an `HTTPRequest` struct.
 impl FromStr for HTTPRequest {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut lines = s.lines();
@@ -188,36 +480,41 @@ impl FromStr for HTTPRequest {
         // Parse the request line (e.g., "GET /index.html HTTP/1.1")
         let request_line = lines
             .next()
-            .ok_or_else(|| anyhow::anyhow!("Invalid request"))?;
+            .ok_or_else(|| ParseError::new("Invalid request"))?;
         let mut parts = request_line.split_whitespace();
 
         let method: Method = parts
             .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing method"))?
+            .ok_or_else(|| ParseError::new("Missing method"))?
             .parse()?;
         let path: String = parts
             .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing path"))?
+            .ok_or_else(|| ParseError::new("Missing path"))?
             .to_string();
         let version: Version = parts
             .next()
-            .ok_or_else(|| anyhow::anyhow!("Missing HTTP version"))?
+            .ok_or_else(|| ParseError::new("Missing HTTP version"))?
             .parse()?;
 
         // Parse headers
-        let mut headers = HashMap::new();
+        let mut headers = HeaderMap::new();
         for line in lines.by_ref() {
             if line.is_empty() {
                 break; // Empty line marks the end of headers
             }
             let mut header_parts = line.splitn(2, ':');
-            let header_name = header_parts.next().unwrap().trim().to_string();
+            let header_name = header_parts.next().unwrap();
+            // RFC 9112 section 5.1, a name followed by whitespace could be read
+            // differently by another server
+            if header_name.ends_with(char::is_whitespace) {
+                return Err(ParseError::new("Whitespace before the colon of a header"));
+            }
+            let header_name = header_name.trim();
             let header_value = header_parts
                 .next()
-                .ok_or_else(|| anyhow::anyhow!("Malformed header"))?
-                .trim()
-                .to_string();
-            headers.insert(header_name, header_value);
+                .ok_or_else(|| ParseError::new("Malformed header"))?
+                .trim();
+            headers.append(header_name, header_value);
         }
 
         // Parse body if there are remaining lines
@@ -231,29 +528,49 @@ impl FromStr for HTTPRequest {
             method,
             path,
             version,
+            #[cfg(feature = "trace-context")]
+            trace_context: TraceContext::of(&headers),
             headers,
-            addr: IpAddr::from_str("0.0.0.0")?,
+            addr: None,
             body,
+            body_source: None,
+            extensions: Extensions::new(),
+            tls: false,
+            #[cfg(feature = "tls")]
+            peer_certificate: None,
         })
     }
 }
 
 impl HTTPRequest {
+    /// Returns `true` if the request was received over a TLS connection.
+    pub fn is_tls(&self) -> bool {
+        self.tls
+    }
+
+    /// Returns the certificate the client authenticated with during the TLS
+    /// handshake, see [`ClientAuth`](crate::tls::ClientAuth).
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` for plain connections and clients that didn't present one.
+    #[cfg(feature = "tls")]
+    pub fn peer_certificate(&self) -> Option<&crate::tls::PeerCertificate> {
+        self.peer_certificate.as_deref()
+    }
+
     /// Retrieve a specific header from the HTTP request.
     ///
     /// # Arguments
     ///
     /// * `request` - The HTTP request to retrieve from.
-    /// * `header` - The name of the header to get.
+    /// * `header` - The case-insensitive name of the header to get.
     ///
     /// # Returns
     ///
     /// Returns a `Option` containing the `String` value or None.
     pub fn get_header(request: &HTTPRequest, header: &str) -> Option<String> {
-        match request.headers.get(header) {
-            Some(value) => Some(value.to_string()),
-            None => None,
-        }
+        request.headers.get(header).map(|value| value.to_string())
     }
 
     /// Retrieve the cookies from the HTTP request.
@@ -299,13 +616,113 @@ impl HTTPRequest {
 
         match cookies.get(cookie) {
             Some(cookie) => Ok(cookie.to_string()),
-            None => {
-                return Err(anyhow::anyhow!(
-                    "No cookie with this name ({}) exists",
-                    cookie
-                ));
-            }
+            None => Err(anyhow::anyhow!(
+                "No cookie with this name ({}) exists",
+                cookie
+            )),
+        }
+    }
+
+    /// Parses the `Cache-Control` header of the HTTP request.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Option` containing the parsed directives, or None if the header is
+    /// missing or malformed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPRequest;
+    ///
+    /// let request: HTTPRequest = "GET / HTTP/1.1\r\nCache-Control: max-age=0\r\n\r\n".parse()?;
+    /// assert!(request.cache_control().unwrap().requires_revalidation());
+    ///
+    /// for value in ["no-cache", "no-store, max-age=0", "private, must-revalidate, max-age=60"] {
+    ///     let request: HTTPRequest = format!("GET / HTTP/1.1\r\nCache-Control: {}\r\n\r\n", value).parse()?;
+    ///     assert_eq!(request.cache_control().unwrap().to_string(), value);
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn cache_control(&self) -> Option<CacheControl> {
+        HTTPRequest::get_header(self, "Cache-Control")?.parse().ok()
+    }
+
+    /// Parses the `Range` header of the HTTP request.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Option` containing the requested byte ranges, or None if the header is
+    /// missing or malformed (in which case the whole resource should be sent).
+    pub fn range(&self) -> Option<Range> {
+        HTTPRequest::get_header(self, "Range")?.parse().ok()
+    }
+
+    /// Returns `true` if the client wants to keep the connection open after the
+    /// response.
+    ///
+    /// HTTP/1.1 connections are persistent unless the request carries
+    /// `Connection: close`, HTTP/1.0 ones only if it carries `Connection: keep-alive`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPRequest;
+    ///
+    /// for (request, keep_alive) in [
+    ///     ("GET / HTTP/1.1\r\n\r\n", true),
+    ///     ("GET / HTTP/1.1\r\nConnection: Close\r\n\r\n", false),
+    ///     ("GET / HTTP/1.0\r\n\r\n", false),
+    ///     ("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n", true),
+    /// ] {
+    ///     assert_eq!(request.parse::<HTTPRequest>()?.is_keep_alive(), keep_alive);
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn is_keep_alive(&self) -> bool {
+        let has_option = |option: &str| {
+            self.headers
+                .get_all("Connection")
+                .flat_map(|value| value.split(','))
+                .any(|token| token.trim().eq_ignore_ascii_case(option))
+        };
+
+        match self.version {
+            Version::V10 => has_option("keep-alive"),
+            _ => !has_option("close"),
+        }
+    }
+
+    /// Serializes the request as it is sent on the wire: the request line, the
+    /// headers, an empty line and the body.
+    ///
+    /// The headers are written as they are, a `Content-Length` matching the body must
+    /// already be set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPRequest;
+    ///
+    /// let raw = "POST /users HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\n{}";
+    /// let request: HTTPRequest = raw.parse()?;
+    ///
+    /// assert_eq!(request.to_bytes(), raw.as_bytes());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = format!("{} {} {}\r\n", self.method, self.path, self.version).into_bytes();
+
+        for (name, value) in self.headers.iter() {
+            bytes.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
         }
+        bytes.extend_from_slice(b"\r\n");
+
+        if let Some(body) = &self.body {
+            bytes.extend_from_slice(body.as_bytes());
+        }
+
+        bytes
     }
 }
 
@@ -314,22 +731,671 @@ impl HTTPRequest {
 pub struct HTTPResponse {
     pub version: Version,
     pub status_code: StatusCode,
-    pub headers: HashMap<String, String>,
-    pub body: Option<Vec<u8>>,
+    pub headers: HeaderMap,
+    pub body: Option<Body>,
 }
 
-/// Provides functionality to parse a `HTTPRequest` struct into an HTTP response string.
-impl ToString for HTTPResponse {
-    fn to_string(&self) -> String {
-        format!(
-            "{} {}\n{}",
-            self.version.to_string(),
-            self.status_code.to_string(),
-            self.headers
-                .iter()
-                .map(|(k, v)| { format!("{}: {}", k, v) })
-                .collect::<Vec<String>>()
-                .join("\n"),
-        )
+/// Formats an `HTTPResponse` as an HTTP message: status line, headers, an empty line
+/// and the body. Bodies that are not held in memory (files, event streams) are omitted.
+impl fmt::Display for HTTPResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.head())?;
+
+        match self.body.as_ref().and_then(Body::as_bytes) {
+            Some(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Provides functionality to parse a raw HTTP response string into an `HTTPResponse` struct.
+///
+/// Lines can end with either CRLF or LF. Everything after the empty line ending the
+/// headers is the body.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::http::{HTTPResponse, StatusCode, Version};
+///
+/// let mut response = HTTPResponse::not_found();
+/// response.headers.append("Set-Cookie", "a=1").append("Set-Cookie", "b=2");
+///
+/// let parsed: HTTPResponse = response.to_string().parse()?;
+/// assert_eq!(parsed.version, Version::V11);
+/// assert_eq!(parsed.status_code, response.status_code);
+/// assert_eq!(parsed.headers, response.headers);
+/// assert_eq!(
+///     parsed.body.as_ref().and_then(|body| body.as_bytes()),
+///     response.body.as_ref().and_then(|body| body.as_bytes())
+/// );
+/// assert_eq!(parsed.to_string(), response.to_string());
+///
+/// let response = HTTPResponse::no_content();
+/// let parsed: HTTPResponse = response.to_string().parse()?;
+/// assert_eq!(parsed.status_code, StatusCode::CODE204);
+/// assert!(parsed.body.is_none());
+///
+/// let parsed: HTTPResponse = "HTTP/1.1 302 Found\r\nLocation: /\r\n\r\n".parse()?;
+/// assert_eq!(parsed.headers.get("location"), Some("/"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+impl FromStr for HTTPResponse {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (head, body) = match (s.find("\r\n\r\n"), s.find("\n\n")) {
+            (Some(crlf), Some(lf)) if lf < crlf => (&s[..lf], Some(&s[lf + 2..])),
+            (Some(crlf), _) => (&s[..crlf], Some(&s[crlf + 4..])),
+            (None, Some(lf)) => (&s[..lf], Some(&s[lf + 2..])),
+            (None, None) => (s, None),
+        };
+        let mut lines = head.lines();
+
+        // Parse the status line (e.g., "HTTP/1.1 404 Not Found")
+        let status_line = lines
+            .next()
+            .ok_or_else(|| ParseError::new("Invalid response"))?;
+        let mut parts = status_line.splitn(3, ' ');
+
+        let version: Version = parts
+            .next()
+            .ok_or_else(|| ParseError::new("Missing HTTP version"))?
+            .parse()?;
+        let status_code: StatusCode = parts
+            .next()
+            .ok_or_else(|| ParseError::new("Missing status code"))?
+            .parse::<u16>()
+            .map_err(|_| ParseError::new("Invalid status code"))?
+            .try_into()?;
+
+        // Parse headers
+        let mut headers = HeaderMap::new();
+        for line in lines {
+            let mut header_parts = line.splitn(2, ':');
+            let header_name = header_parts.next().unwrap().trim();
+            let header_value = header_parts
+                .next()
+                .ok_or_else(|| ParseError::new("Malformed header"))?
+                .trim();
+            headers.append(header_name, header_value);
+        }
+
+        Ok(HTTPResponse {
+            version,
+            status_code,
+            headers,
+            body: body.filter(|body| !body.is_empty()).map(Body::from),
+        })
+    }
+}
+
+/// Provides an empty `HTTP/1.1 200 OK` response with no headers and no body.
+impl Default for HTTPResponse {
+    fn default() -> Self {
+        HTTPResponse {
+            version: Version::V11,
+            status_code: StatusCode::CODE200,
+            headers: HeaderMap::new(),
+            body: None,
+        }
+    }
+}
+
+impl HTTPResponse {
+    /// Creates an empty `200 OK` response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{HTTPResponse, StatusCode};
+    ///
+    /// let response = HTTPResponse::ok();
+    ///
+    /// assert_eq!(response.status_code, StatusCode::CODE200);
+    /// assert!(response.body.is_none());
+    /// ```
+    pub fn ok() -> Self {
+        HTTPResponse::default()
+    }
+
+    /// Creates a `204 No Content` response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{HTTPResponse, StatusCode};
+    ///
+    /// let response = HTTPResponse::no_content();
+    ///
+    /// assert_eq!(response.status_code, StatusCode::CODE204);
+    /// assert!(response.body.is_none());
+    /// ```
+    pub fn no_content() -> Self {
+        HTTPResponse {
+            status_code: StatusCode::CODE204,
+            ..HTTPResponse::default()
+        }
+    }
+
+    /// Creates a `400 Bad Request` response with a plain-text body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{HTTPResponse, StatusCode};
+    ///
+    /// let response = HTTPResponse::bad_request();
+    ///
+    /// assert_eq!(response.status_code, StatusCode::CODE400);
+    /// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"Bad Request"[..]));
+    /// ```
+    pub fn bad_request() -> Self {
+        HTTPResponse::plain_text(StatusCode::CODE400, "Bad Request")
+    }
+
+    /// Creates a `404 Not Found` response with a plain-text body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{HTTPResponse, StatusCode};
+    ///
+    /// let response = HTTPResponse::not_found();
+    ///
+    /// assert_eq!(response.status_code, StatusCode::CODE404);
+    /// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"Not Found"[..]));
+    /// ```
+    pub fn not_found() -> Self {
+        HTTPResponse::plain_text(StatusCode::CODE404, "Not Found")
+    }
+
+    /// Creates a `500 Internal Server Error` response with a plain-text body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{HTTPResponse, StatusCode};
+    ///
+    /// let response = HTTPResponse::internal_error();
+    ///
+    /// assert_eq!(response.status_code, StatusCode::CODE500);
+    /// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"Internal Server Error"[..]));
+    /// ```
+    pub fn internal_error() -> Self {
+        HTTPResponse::plain_text(StatusCode::CODE500, "Internal Server Error")
+    }
+
+    /// Creates a `200 OK` response with the contents of the file at `path`.
+    ///
+    /// The `Content-Type` is guessed from the file extension. The file is read while
+    /// the response is written, see [`Server::set_zero_copy`](crate::Server::set_zero_copy).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file to send.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `HTTPResponse` or an error if the file can't be read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{Body, HTTPResponse};
+    ///
+    /// let path = std::env::temp_dir().join("fobserver-from-file.txt");
+    /// std::fs::write(&path, "hello")?;
+    ///
+    /// let response = HTTPResponse::from_file(&path)?;
+    ///
+    /// assert_eq!(response.headers.get("Content-Type"), Some("text/plain; charset=utf-8"));
+    /// assert_eq!(response.headers.get("Content-Length"), Some("5"));
+    /// assert!(matches!(response.body, Some(Body::File { offset: 0, len: 5, .. })));
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<HTTPResponse> {
+        let path = path.as_ref();
+        let file = fs::File::open(path)?;
+        let len = file.metadata()?.len();
+
+        let mut headers = HeaderMap::new();
+        headers
+            .set("Content-Type", mime::from_path(path))
+            .set("Content-Length", &len.to_string());
+
+        Ok(HTTPResponse {
+            headers,
+            body: Some(Body::File {
+                file,
+                offset: 0,
+                len,
+            }),
+            ..HTTPResponse::default()
+        })
+    }
+
+    /// Creates a `200 OK` Server-Sent Events response.
+    ///
+    /// Every `Event` received from `receiver` is written to the client as soon as it
+    /// arrives, and the connection stays open until all senders have been dropped.
+    ///
+    /// When the client goes away, the first event that can't be written ends the
+    /// response and drops `receiver`, so sending the next one fails and the producer
+    /// can stop.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{sync::mpsc, thread};
+    /// use fobserver::http::{Event, HTTPResponse};
+    ///
+    /// let (sender, receiver) = mpsc::channel();
+    ///
+    /// thread::spawn(move || {
+    ///     for i in 0..3 {
+    ///         if sender.send(Event::new(&i.to_string())).is_err() {
+    ///             break; // The client went away
+    ///         }
+    ///     }
+    /// });
+    ///
+    /// let response = HTTPResponse::event_stream(receiver);
+    ///
+    /// assert_eq!(response.headers.get("Content-Type"), Some("text/event-stream"));
+    /// ```
+    ///
+    /// A client leaving in the middle of a stream is not an error of the server: it is
+    /// counted in [`ServerStats::client_disconnects`](crate::stats::ServerStats::client_disconnects)
+    /// and logged at debug level only.
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{
+    ///         atomic::{AtomicBool, Ordering},
+    ///         mpsc, Arc, Mutex, RwLock,
+    ///     },
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, Event, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// struct Warnings(Mutex<Vec<String>>);
+    ///
+    /// impl log::Log for Warnings {
+    ///     fn enabled(&self, metadata: &log::Metadata) -> bool {
+    ///         metadata.level() <= log::Level::Warn
+    ///     }
+    ///
+    ///     fn log(&self, record: &log::Record) {
+    ///         if self.enabled(record.metadata()) {
+    ///             self.0.lock().unwrap().push(record.args().to_string());
+    ///         }
+    ///     }
+    ///
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static WARNINGS: Warnings = Warnings(Mutex::new(Vec::new()));
+    /// log::set_logger(&WARNINGS).unwrap();
+    /// log::set_max_level(log::LevelFilter::Debug);
+    ///
+    /// static STOPPED: AtomicBool = AtomicBool::new(false);
+    ///
+    /// fn ticks(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let (sender, receiver) = mpsc::channel();
+    ///
+    ///     thread::spawn(move || {
+    ///         while sender.send(Event::new("tick")).is_ok() {
+    ///             thread::sleep(Duration::from_millis(10));
+    ///         }
+    ///
+    ///         STOPPED.store(true, Ordering::SeqCst);
+    ///     });
+    ///
+    ///     Ok(HTTPResponse::event_stream(receiver))
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/ticks", http::Version::V11, ticks);
+    /// let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+    ///
+    /// // Read the headers, then leave
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"GET /ticks HTTP/1.1\r\n\r\n")?;
+    /// stream.read(&mut [0; 1024])?;
+    /// drop(stream);
+    ///
+    /// for _ in 0..200 {
+    ///     if STOPPED.load(Ordering::SeqCst) && server.stats().client_disconnects == 1 {
+    ///         break;
+    ///     }
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    ///
+    /// assert!(STOPPED.load(Ordering::SeqCst));
+    /// assert_eq!(server.stats().client_disconnects, 1);
+    /// assert!(WARNINGS.0.lock().unwrap().is_empty());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn event_stream(receiver: Receiver<Event>) -> Self {
+        let mut headers = HeaderMap::new();
+        headers
+            .set("Content-Type", "text/event-stream")
+            .set("Cache-Control", "no-cache");
+
+        HTTPResponse {
+            headers,
+            body: Some(Body::EventStream(receiver)),
+            ..HTTPResponse::default()
+        }
+    }
+
+    /// Creates a redirection to `location`.
+    ///
+    /// The location is validated like every other header before the response is
+    /// written, so a value built from untrusted input can't inject extra headers.
+    ///
+    /// # Arguments
+    ///
+    /// * `status_code` - The redirection status, e.g. `StatusCode::CODE303`.
+    /// * `location` - The URL to redirect to.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{SocketAddr, TcpStream},
+    ///     sync::{Arc, RwLock},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn login(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::redirect(StatusCode::CODE302, "/login"))
+    /// }
+    ///
+    /// fn injected(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     // E.g. a `next` parameter copied into the location
+    ///     Ok(HTTPResponse::redirect(StatusCode::CODE302, "/\r\nSet-Cookie: admin=1"))
+    /// }
+    ///
+    /// fn get(addr: SocketAddr, path: &str) -> anyhow::Result<String> {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path)?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/account", http::Version::V11, login);
+    /// router.add_route(http::Method::GET, "/injected", http::Version::V11, injected);
+    /// let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+    /// let addr = server.addr()?;
+    ///
+    /// let response = get(addr, "/account")?;
+    /// assert!(response.starts_with("HTTP/1.1 302 Found"));
+    /// assert!(response.contains("\nLocation: /login\n"));
+    ///
+    /// // The client gets a single response, the 500, without the injected header
+    /// let response = get(addr, "/injected")?;
+    /// assert!(response.starts_with("HTTP/1.1 500 Internal Server Error"));
+    /// assert_eq!(response.matches("HTTP/1.1").count(), 1);
+    /// assert!(!response.contains("Set-Cookie"));
+    /// assert!(!response.contains("Location"));
+    ///
+    /// server.shutdown();
+    /// server.join()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn redirect(status_code: StatusCode, location: &str) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.set("Location", location);
+
+        HTTPResponse {
+            status_code,
+            headers,
+            ..HTTPResponse::default()
+        }
+    }
+
+    /// Attaches a `Cache-Control` header to the response, replacing any existing one.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_control` - The directives to send.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use fobserver::http::{CacheControl, HTTPResponse};
+    ///
+    /// let mut response = HTTPResponse::ok();
+    /// response.cache_control(
+    ///     CacheControl::new()
+    ///         .public()
+    ///         .max_age(Duration::from_secs(31536000))
+    ///         .immutable(),
+    /// );
+    ///
+    /// assert_eq!(
+    ///     response.headers.get("Cache-Control"),
+    ///     Some("public, immutable, max-age=31536000")
+    /// );
+    /// ```
+    pub fn cache_control(&mut self, cache_control: CacheControl) -> &mut Self {
+        self.headers
+            .set("Cache-Control", &cache_control.to_string());
+
+        self
+    }
+
+    /// Adds a request header to the `Vary` header of the response.
+    ///
+    /// Tokens are merged into the existing value, case-insensitively and without
+    /// duplicates. A `Vary: *` set by the handler is kept as it is, since it already
+    /// covers every header.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The name of the request header the response depends on.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPResponse;
+    ///
+    /// let mut response = HTTPResponse::ok();
+    /// response
+    ///     .add_vary("Accept-Encoding")
+    ///     .add_vary("Origin")
+    ///     .add_vary("accept-encoding");
+    /// assert_eq!(response.headers.get("Vary"), Some("Accept-Encoding, Origin"));
+    ///
+    /// response.headers.set("Vary", "*");
+    /// response.add_vary("Origin");
+    /// assert_eq!(response.headers.get("Vary"), Some("*"));
+    /// ```
+    pub fn add_vary(&mut self, header: &str) -> &mut Self {
+        let mut tokens: Vec<&str> = self
+            .headers
+            .get_all("Vary")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .collect();
+
+        if tokens.contains(&"*") {
+            return self;
+        }
+
+        if !tokens
+            .iter()
+            .any(|token| token.eq_ignore_ascii_case(header))
+        {
+            tokens.push(header);
+        }
+
+        let vary = tokens.join(", ");
+        self.headers.set("Vary", &vary);
+
+        self
+    }
+
+    /// Hands the connection to `callback` once the head of the response is sent,
+    /// e.g. to switch to another protocol after `101 Switching Protocols`.
+    ///
+    /// The response is sent without a body and as it is: the status and the
+    /// `Connection` and `Upgrade` headers are up to the handler. The connection then
+    /// belongs to the callback, it is neither kept alive nor closed by the server.
+    ///
+    /// The callback runs on the thread that handled the request, and the connection
+    /// is counted as active until it returns. On shutdown, it is closed once the
+    /// drain timeout passes, see [`Server::set_drain_timeout`](crate::Server::set_drain_timeout).
+    /// Only plain TCP connections can be handed over, others are closed after the
+    /// response.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - Takes over the connection, with no timeout set.
+    ///
+    /// # Returns
+    ///
+    /// The response, carrying the callback in place of its body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{self, BufRead, BufReader, Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn echo(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok().with_upgrade(|stream| {
+    ///         // Sends back everything received, until the client leaves
+    ///         let _ = io::copy(&mut &stream, &mut &stream);
+    ///     });
+    ///     response.status_code = StatusCode::CODE101;
+    ///     response.headers.set("Connection", "Upgrade").set("Upgrade", "echo");
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/echo", http::Version::V11, echo);
+    ///
+    /// let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+    ///
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"GET /echo HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: echo\r\n\r\n")?;
+    ///
+    /// let mut reader = BufReader::new(stream.try_clone()?);
+    /// let mut line = String::new();
+    /// reader.read_line(&mut line)?;
+    /// assert!(line.starts_with("HTTP/1.1 101 Switching Protocols"));
+    ///
+    /// // Skip the headers, the new protocol starts right after them
+    /// while !line.trim().is_empty() {
+    ///     line.clear();
+    ///     reader.read_line(&mut line)?;
+    /// }
+    ///
+    /// stream.write_all(b"ping")?;
+    /// let mut echoed = [0; 4];
+    /// reader.read_exact(&mut echoed)?;
+    /// assert_eq!(&echoed, b"ping");
+    ///
+    /// // The upgraded connection stays counted until the callback returns
+    /// let stats = server.stats();
+    /// assert_eq!((stats.active_connections, stats.inflight_requests), (1, 0));
+    ///
+    /// drop((stream, reader));
+    /// while server.stats().active_connections > 0 {
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_upgrade<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(TcpStream) + Send + 'static,
+    {
+        self.body = Some(Body::Upgrade(Box::new(callback)));
+
+        self
+    }
+
+    /// Creates a response with the given status code and a `text/plain` body.
+    pub(crate) fn plain_text(status_code: StatusCode, text: &str) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.set("Content-Type", "text/plain; charset=utf-8");
+
+        HTTPResponse {
+            status_code,
+            headers,
+            body: Some(text.into()),
+            ..HTTPResponse::default()
+        }
+    }
+
+    /// Serializes the status line and headers into a string.
+    ///
+    /// The returned string ends with the empty line separating the headers from the body,
+    /// see [`HTTPResponse::write_head`].
+    pub(crate) fn head(&self) -> String {
+        let mut head = Vec::new();
+        // Writing to a vector can't fail
+        let _ = self.write_head(&mut head);
+
+        String::from_utf8_lossy(&head).into_owned()
+    }
+
+    /// Writes the status line and headers to `out`, without building them in a buffer
+    /// of their own.
+    ///
+    /// The empty line separating the headers from the body is written last, so the
+    /// body can follow right away.
+    pub(crate) fn write_head<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{} {}", self.version, self.status_code)?;
+
+        for (name, value) in self.headers.iter() {
+            writeln!(out, "{}: {}", name, value)?;
+        }
+
+        out.write_all(b"\n")
     }
 }
@@ -0,0 +1,156 @@
+use super::{HTTPRequest, HTTPResponse, StatusCode};
+
+impl HTTPRequest {
+    /// Retrieves the credentials of an `Authorization: Basic` header (RFC 7617).
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Option` containing the `(user, password)` pair, or None if the header
+    /// is missing, uses another scheme or is malformed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPRequest;
+    ///
+    /// let request: HTTPRequest =
+    ///     "GET / HTTP/1.1\r\nAuthorization: Basic Zm9iOnNlY3JldA==\r\n\r\n".parse()?;
+    ///
+    /// assert_eq!(
+    ///     request.basic_auth(),
+    ///     Some(("fob".to_string(), "secret".to_string()))
+    /// );
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn basic_auth(&self) -> Option<(String, String)> {
+        let credentials = self.authorization("Basic")?;
+        let decoded = String::from_utf8(crate::base64::decode(&credentials)?).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+
+        Some((user.to_string(), password.to_string()))
+    }
+
+    /// Retrieves the token of an `Authorization: Bearer` header (RFC 6750).
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Option` containing the token, or None if the header is missing or
+    /// uses another scheme.
+    pub fn bearer_token(&self) -> Option<String> {
+        self.authorization("Bearer")
+    }
+
+    /// Retrieves the credentials of the `Authorization` header if it uses `scheme`.
+    fn authorization(&self, scheme: &str) -> Option<String> {
+        let header = HTTPRequest::get_header(self, "Authorization")?;
+        let (name, credentials) = header.trim().split_once(' ')?;
+
+        match name.eq_ignore_ascii_case(scheme) {
+            true => Some(credentials.trim().to_string()),
+            false => None,
+        }
+    }
+}
+
+impl HTTPResponse {
+    /// Creates a `401 Unauthorized` response challenging the client for HTTP Basic
+    /// credentials (RFC 7617).
+    ///
+    /// # Arguments
+    ///
+    /// * `realm` - The protection space; quotes, backslashes and control characters are removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{HTTPResponse, StatusCode};
+    ///
+    /// let response = HTTPResponse::unauthorized_basic("Admin \"area\"\r\n");
+    ///
+    /// assert_eq!(response.status_code, StatusCode::CODE401);
+    /// assert_eq!(
+    ///     response.headers.get("WWW-Authenticate"),
+    ///     Some(r#"Basic realm="Admin area", charset="UTF-8""#)
+    /// );
+    /// ```
+    pub fn unauthorized_basic(realm: &str) -> Self {
+        HTTPResponse::unauthorized(format!(
+            "Basic realm=\"{}\", charset=\"UTF-8\"",
+            sanitize(realm)
+        ))
+    }
+
+    /// Creates a `401 Unauthorized` response challenging the client for a bearer token
+    /// (RFC 6750).
+    ///
+    /// # Arguments
+    ///
+    /// * `realm` - The protection space; quotes, backslashes and control characters are removed.
+    /// * `error` - The optional error code, e.g. `invalid_token` when a token was rejected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPResponse;
+    ///
+    /// let response = HTTPResponse::unauthorized_bearer("api", Some("invalid_token"));
+    ///
+    /// assert_eq!(
+    ///     response.headers.get("WWW-Authenticate"),
+    ///     Some(r#"Bearer realm="api", error="invalid_token""#)
+    /// );
+    /// ```
+    pub fn unauthorized_bearer(realm: &str, error: Option<&str>) -> Self {
+        let mut challenge = format!("Bearer realm=\"{}\"", sanitize(realm));
+
+        if let Some(error) = error {
+            challenge.push_str(&format!(", error=\"{}\"", sanitize(error)));
+        }
+
+        HTTPResponse::unauthorized(challenge)
+    }
+
+    /// Creates a `401 Unauthorized` response refusing a bearer token (RFC 6750), with
+    /// the `invalid_token` error code.
+    ///
+    /// # Arguments
+    ///
+    /// * `realm` - The protection space; quotes, backslashes and control characters are removed.
+    /// * `description` - Why the token was refused, sent to the client, sanitized the same way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPResponse;
+    ///
+    /// let response = HTTPResponse::invalid_bearer_token("api", "The token expired");
+    ///
+    /// assert_eq!(
+    ///     response.headers.get("WWW-Authenticate"),
+    ///     Some(r#"Bearer realm="api", error="invalid_token", error_description="The token expired""#)
+    /// );
+    /// ```
+    pub fn invalid_bearer_token(realm: &str, description: &str) -> Self {
+        HTTPResponse::unauthorized(format!(
+            "Bearer realm=\"{}\", error=\"invalid_token\", error_description=\"{}\"",
+            sanitize(realm),
+            sanitize(description)
+        ))
+    }
+
+    /// Creates a `401 Unauthorized` response with the given challenge.
+    fn unauthorized(challenge: String) -> Self {
+        let mut response = HTTPResponse::plain_text(StatusCode::CODE401, "Unauthorized");
+        response.headers.set("WWW-Authenticate", &challenge);
+
+        response
+    }
+}
+
+/// Removes the characters that can't appear in a quoted challenge parameter.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .collect()
+}
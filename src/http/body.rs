@@ -0,0 +1,136 @@
+use std::{fmt, fs::File, io::Read, sync::mpsc::Receiver};
+
+use super::Event;
+use crate::UpgradeFunction;
+
+/// Represents the body of an HTTP response.
+///
+/// Most responses carry their whole payload in memory as `Body::Bytes`. Streaming
+/// bodies, such as Server-Sent Events, are produced while the response is being
+/// written and keep the connection open until their source is exhausted.
+///
+/// Bodies whose length isn't known in advance are sent with the `chunked` transfer
+/// encoding to HTTP/1.1 clients. HTTP/1.0 clients don't understand it: the body is
+/// sent as it is and the connection is closed after it.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Cursor, Read, Write},
+///     net::TcpStream,
+///     sync::{Arc, RwLock},
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, Body, HTTPRequest, HTTPResponse},
+///     router::Router,
+///     Server,
+/// };
+///
+/// fn stream(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(Body::Stream(Box::new(Cursor::new(b"streamed".to_vec()))));
+///
+///     Ok(response)
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V10, stream);
+/// router.add_route(http::Method::GET, "/", http::Version::V11, stream);
+///
+/// let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+/// let get = |request: &str| -> anyhow::Result<String> {
+///     let mut stream = TcpStream::connect(server.addr()?)?;
+///     stream.write_all(request.as_bytes())?;
+///
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response)?;
+///     Ok(response)
+/// };
+///
+/// let response = get("GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+/// assert!(response.contains("Transfer-Encoding: chunked"));
+///
+/// // Even asking to keep the connection open, an HTTP/1.0 client gets the raw body
+/// let response = get("GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")?;
+/// assert!(!response.contains("Transfer-Encoding"));
+/// assert!(response.contains("Connection: close"));
+/// assert!(response.ends_with("\n\nstreamed"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub enum Body {
+    /// A body held entirely in memory.
+    Bytes(Vec<u8>),
+    /// A `text/event-stream` body, written one event at a time as they are received.
+    /// The stream ends when every sender of the channel has been dropped.
+    EventStream(Receiver<Event>),
+    /// `len` bytes of a file starting at `offset`, streamed from disk while writing.
+    File { file: File, offset: u64, len: u64 },
+    /// A body read from any source while writing, until it reaches its end, e.g. the
+    /// response of an upstream server.
+    Stream(Box<dyn Read + Send>),
+    /// No body: the connection is handed to the callback once the head is sent, see
+    /// [`HTTPResponse::with_upgrade`](super::HTTPResponse::with_upgrade).
+    Upgrade(UpgradeFunction),
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Bytes(bytes) => f.debug_tuple("Bytes").field(bytes).finish(),
+            Body::EventStream(receiver) => f.debug_tuple("EventStream").field(receiver).finish(),
+            Body::File { file, offset, len } => f
+                .debug_struct("File")
+                .field("file", file)
+                .field("offset", offset)
+                .field("len", len)
+                .finish(),
+            Body::Stream(_) => f.debug_tuple("Stream").finish_non_exhaustive(),
+            Body::Upgrade(_) => f.debug_tuple("Upgrade").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl Body {
+    /// Returns the body bytes if the body is held in memory.
+    ///
+    /// # Returns
+    /// An `Option` containing the bytes, or `None` for streaming bodies.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Body::Bytes(bytes) => Some(bytes),
+            Body::EventStream(_) | Body::File { .. } | Body::Stream(_) | Body::Upgrade(_) => None,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Body::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Self {
+        Body::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for Body {
+    fn from(text: String) -> Self {
+        Body::Bytes(text.into_bytes())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(text: &str) -> Self {
+        Body::Bytes(text.as_bytes().to_vec())
+    }
+}
+
+impl From<Receiver<Event>> for Body {
+    fn from(receiver: Receiver<Event>) -> Self {
+        Body::EventStream(receiver)
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
+
+use super::{Body, HTTPResponse, HeaderMap, StatusCode};
+
+/// One part of a `multipart/byteranges` response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteRangePart {
+    /// The media type of the whole resource.
+    pub content_type: String,
+    /// The first byte position of the part, inclusive.
+    pub first: u64,
+    /// The last byte position of the part, inclusive.
+    pub last: u64,
+    /// The length of the whole resource.
+    pub total: u64,
+    /// The bytes between `first` and `last`.
+    pub data: Vec<u8>,
+}
+
+impl HTTPResponse {
+    /// Creates a `206 Partial Content` response carrying several ranges of a resource
+    /// as a `multipart/byteranges` body (RFC 9110 §14.6).
+    ///
+    /// Each part gets its own `Content-Type` and `Content-Range` headers. The boundary
+    /// is randomly generated and never appears inside the parts' data, and the response
+    /// is framed with a `Content-Length`.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The ranges to send, in order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{ByteRangePart, HTTPResponse, StatusCode};
+    ///
+    /// let part = |first: u64, data: &str| ByteRangePart {
+    ///     content_type: "text/plain".to_string(),
+    ///     first,
+    ///     last: first + data.len() as u64 - 1,
+    ///     total: 1000,
+    ///     data: data.into(),
+    /// };
+    ///
+    /// let response = HTTPResponse::byteranges(vec![part(0, "abc"), part(200, "xyz")]);
+    /// let content_type = response.headers.get("Content-Type").unwrap();
+    /// let boundary = content_type.split("boundary=").nth(1).unwrap();
+    /// let body = String::from_utf8(response.body.unwrap().as_bytes().unwrap().to_vec())?;
+    ///
+    /// assert_eq!(response.status_code, StatusCode::CODE206);
+    /// assert_eq!(
+    ///     body,
+    ///     format!(
+    ///         "--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-2/1000\r\n\r\nabc\r\n\
+    ///          --{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 200-202/1000\r\n\r\nxyz\r\n\
+    ///          --{b}--\r\n",
+    ///         b = boundary
+    ///     )
+    /// );
+    /// assert_eq!(response.headers.get("Content-Length"), Some(body.len().to_string().as_str()));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn byteranges(parts: Vec<ByteRangePart>) -> HTTPResponse {
+        let boundary = loop {
+            let boundary = crate::random::hex(16);
+
+            if !parts
+                .iter()
+                .any(|part| contains(&part.data, boundary.as_bytes()))
+            {
+                break boundary;
+            }
+        };
+
+        let mut body = Vec::with_capacity(
+            parts
+                .iter()
+                .map(|part| part.data.len() + part.content_type.len() + 128)
+                .sum::<usize>()
+                + boundary.len()
+                + 8,
+        );
+
+        for part in &parts {
+            body.extend_from_slice(
+                part_head(
+                    &boundary,
+                    &part.content_type,
+                    part.first,
+                    part.last,
+                    part.total,
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(&part.data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+        let mut headers = HeaderMap::new();
+        headers
+            .set(
+                "Content-Type",
+                &format!("multipart/byteranges; boundary={}", boundary),
+            )
+            .set("Content-Length", &body.len().to_string());
+
+        HTTPResponse {
+            status_code: StatusCode::CODE206,
+            headers,
+            body: Some(body.into()),
+            ..HTTPResponse::default()
+        }
+    }
+
+    /// Creates a `206 Partial Content` response carrying several ranges of `file` as a
+    /// `multipart/byteranges` body, read from the file while the response is written.
+    ///
+    /// The data isn't known in advance, so the boundary is random but not checked
+    /// against it; 128 random bits make a collision negligible.
+    ///
+    /// # Parameters
+    /// - `file`: The file holding the resource.
+    /// - `content_type`: The media type of the resource.
+    /// - `total`: The length of the resource.
+    /// - `ranges`: The inclusive `(first, last)` byte positions of the parts, in order.
+    pub(crate) fn file_byteranges(
+        file: File,
+        content_type: &str,
+        total: u64,
+        ranges: &[(u64, u64)],
+    ) -> HTTPResponse {
+        let boundary = crate::random::hex(16);
+
+        let mut segments = VecDeque::with_capacity(2 * ranges.len() + 1);
+        let mut len = 0;
+        for (i, &(first, last)) in ranges.iter().enumerate() {
+            // The line break ending a part goes before the delimiter of the next one
+            let head = match i {
+                0 => part_head(&boundary, content_type, first, last, total),
+                _ => format!(
+                    "\r\n{}",
+                    part_head(&boundary, content_type, first, last, total)
+                ),
+            };
+
+            len += head.len() as u64 + (last - first + 1);
+            segments.push_back(Segment::Text(Cursor::new(head.into_bytes())));
+            segments.push_back(Segment::File {
+                offset: first,
+                len: last - first + 1,
+            });
+        }
+        let end = format!("\r\n--{}--\r\n", boundary);
+        len += end.len() as u64;
+        segments.push_back(Segment::Text(Cursor::new(end.into_bytes())));
+
+        let mut headers = HeaderMap::new();
+        headers
+            .set(
+                "Content-Type",
+                &format!("multipart/byteranges; boundary={}", boundary),
+            )
+            .set("Content-Length", &len.to_string());
+
+        HTTPResponse {
+            status_code: StatusCode::CODE206,
+            headers,
+            body: Some(Body::Stream(Box::new(FileParts { file, segments }))),
+            ..HTTPResponse::default()
+        }
+    }
+}
+
+/// A piece of a `multipart/byteranges` body streamed from a file.
+enum Segment {
+    /// Delimiters and part headers.
+    Text(Cursor<Vec<u8>>),
+    /// The `len` bytes of the file from `offset`.
+    File { offset: u64, len: u64 },
+}
+
+/// Reads the pieces of a `multipart/byteranges` body one after the other, seeking
+/// the file to each part.
+struct FileParts {
+    file: File,
+    segments: VecDeque<Segment>,
+}
+
+impl Read for FileParts {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(segment) = self.segments.front_mut() {
+            let read = match segment {
+                Segment::Text(text) => text.read(buf)?,
+                Segment::File { len: 0, .. } => 0,
+                Segment::File { offset, len } => {
+                    let max = buf.len().min(usize::try_from(*len).unwrap_or(usize::MAX));
+
+                    self.file.seek(SeekFrom::Start(*offset))?;
+                    let read = self.file.read(&mut buf[..max])?;
+                    if read == 0 {
+                        // The file shrank, the announced length can't be met
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    *offset += read as u64;
+                    *len -= read as u64;
+
+                    read
+                }
+            };
+
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            self.segments.pop_front();
+        }
+
+        Ok(0)
+    }
+}
+
+/// Returns the delimiter and headers opening a part of a `multipart/byteranges` body.
+///
+/// # Parameters
+/// - `boundary`: The boundary of the body.
+/// - `content_type`: The media type of the whole resource.
+/// - `first`: The first byte position of the part, inclusive.
+/// - `last`: The last byte position of the part, inclusive.
+/// - `total`: The length of the whole resource.
+fn part_head(boundary: &str, content_type: &str, first: u64, last: u64, total: u64) -> String {
+    format!(
+        "--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+        boundary, content_type, first, last, total
+    )
+}
+
+/// Returns `true` if `needle` appears anywhere in `haystack`.
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
@@ -0,0 +1,197 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+/// Represents the directives of a `Cache-Control` header.
+///
+/// The same type is used to build the header for responses and to inspect the header
+/// sent by clients. Durations are truncated to whole seconds.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use fobserver::http::CacheControl;
+///
+/// let cache_control = CacheControl::new()
+///     .public()
+///     .max_age(Duration::from_secs(3600))
+///     .stale_while_revalidate(Duration::from_secs(60));
+///
+/// assert_eq!(
+///     cache_control.to_string(),
+///     "public, max-age=3600, stale-while-revalidate=60"
+/// );
+/// assert_eq!(cache_control.to_string().parse::<CacheControl>()?, cache_control);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    pub public: bool,
+    pub private: bool,
+    pub no_cache: bool,
+    pub no_store: bool,
+    pub no_transform: bool,
+    pub must_revalidate: bool,
+    pub immutable: bool,
+    pub max_age: Option<Duration>,
+    pub s_maxage: Option<Duration>,
+    pub stale_while_revalidate: Option<Duration>,
+}
+
+impl CacheControl {
+    /// Creates an empty `CacheControl` with no directives.
+    pub fn new() -> Self {
+        CacheControl::default()
+    }
+
+    /// Adds the `public` directive: any cache may store the response.
+    pub fn public(mut self) -> Self {
+        self.public = true;
+
+        self
+    }
+
+    /// Adds the `private` directive: only the client's own cache may store the response.
+    pub fn private(mut self) -> Self {
+        self.private = true;
+
+        self
+    }
+
+    /// Adds the `no-cache` directive: stored responses must be revalidated before use.
+    pub fn no_cache(mut self) -> Self {
+        self.no_cache = true;
+
+        self
+    }
+
+    /// Adds the `no-store` directive: the response must not be stored at all.
+    pub fn no_store(mut self) -> Self {
+        self.no_store = true;
+
+        self
+    }
+
+    /// Adds the `no-transform` directive: intermediaries must not modify the body.
+    pub fn no_transform(mut self) -> Self {
+        self.no_transform = true;
+
+        self
+    }
+
+    /// Adds the `must-revalidate` directive: stale responses must not be used without revalidation.
+    pub fn must_revalidate(mut self) -> Self {
+        self.must_revalidate = true;
+
+        self
+    }
+
+    /// Adds the `immutable` directive: the response will not change while it is fresh.
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+
+        self
+    }
+
+    /// Sets the `max-age` directive.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+
+        self
+    }
+
+    /// Sets the `s-maxage` directive, overriding `max-age` for shared caches.
+    pub fn s_maxage(mut self, s_maxage: Duration) -> Self {
+        self.s_maxage = Some(s_maxage);
+
+        self
+    }
+
+    /// Sets the `stale-while-revalidate` directive.
+    pub fn stale_while_revalidate(mut self, duration: Duration) -> Self {
+        self.stale_while_revalidate = Some(duration);
+
+        self
+    }
+
+    /// Returns `true` if a client asked for a response that is not served from a cache
+    /// without revalidation (`no-cache` or `max-age=0`).
+    pub fn requires_revalidation(&self) -> bool {
+        self.no_cache || self.max_age == Some(Duration::ZERO)
+    }
+}
+
+/// Serializes the directives into a `Cache-Control` header value.
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (self.public, "public"),
+            (self.private, "private"),
+            (self.no_cache, "no-cache"),
+            (self.no_store, "no-store"),
+            (self.no_transform, "no-transform"),
+            (self.must_revalidate, "must-revalidate"),
+            (self.immutable, "immutable"),
+        ];
+        let durations = [
+            (self.max_age, "max-age"),
+            (self.s_maxage, "s-maxage"),
+            (self.stale_while_revalidate, "stale-while-revalidate"),
+        ];
+
+        let directives = flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| name.to_string())
+            .chain(
+                durations
+                    .iter()
+                    .filter_map(|(value, name)| value.map(|v| format!("{}={}", name, v.as_secs()))),
+            )
+            .collect::<Vec<String>>();
+
+        write!(f, "{}", directives.join(", "))
+    }
+}
+
+/// Parses a `Cache-Control` header value.
+///
+/// Directive names are case-insensitive and unknown directives are ignored, as
+/// required by RFC 9111.
+impl FromStr for CacheControl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cache_control = CacheControl::new();
+
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let name = parts.next().unwrap().trim().to_ascii_lowercase();
+            let value = parts.next().map(|v| v.trim().trim_matches('"'));
+
+            let seconds = || -> anyhow::Result<Duration> {
+                let value =
+                    value.ok_or_else(|| anyhow::anyhow!("Missing value for {} directive", name))?;
+
+                Ok(Duration::from_secs(value.parse().map_err(|_| {
+                    anyhow::anyhow!("Invalid value for {} directive: {}", name, value)
+                })?))
+            };
+
+            match name.as_str() {
+                "public" => cache_control.public = true,
+                "private" => cache_control.private = true,
+                "no-cache" => cache_control.no_cache = true,
+                "no-store" => cache_control.no_store = true,
+                "no-transform" => cache_control.no_transform = true,
+                "must-revalidate" => cache_control.must_revalidate = true,
+                "immutable" => cache_control.immutable = true,
+                "max-age" => cache_control.max_age = Some(seconds()?),
+                "s-maxage" => cache_control.s_maxage = Some(seconds()?),
+                "stale-while-revalidate" => cache_control.stale_while_revalidate = Some(seconds()?),
+                _ => {}
+            }
+        }
+
+        Ok(cache_control)
+    }
+}
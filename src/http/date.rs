@@ -0,0 +1,180 @@
+//! Formatting and parsing of HTTP dates (RFC 9110 §5.6.7).
+//!
+//! Dates are always sent as IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`); the
+//! obsolete RFC 850 and asctime formats are accepted when parsing, as the RFC requires.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use fobserver::http::date;
+//!
+//! let cases = [
+//!     (784111777, "Sun, 06 Nov 1994 08:49:37 GMT"),
+//!     (0, "Thu, 01 Jan 1970 00:00:00 GMT"),
+//!     (951782400, "Tue, 29 Feb 2000 00:00:00 GMT"),
+//!     (1709251199, "Thu, 29 Feb 2024 23:59:59 GMT"),
+//!     (4107542400, "Mon, 29 Feb 2100 00:00:00 GMT"), // Not a leap year: normalized
+//! ];
+//!
+//! for (seconds, text) in &cases[..4] {
+//!     let time = UNIX_EPOCH + Duration::from_secs(*seconds);
+//!
+//!     assert_eq!(date::format(time), *text);
+//!     assert_eq!(date::parse(text)?, time);
+//! }
+//!
+//! assert!(date::parse(cases[4].1).is_err());
+//! assert_eq!(date::parse("Sunday, 06-Nov-94 08:49:37 GMT")?, UNIX_EPOCH + Duration::from_secs(784111777));
+//! assert_eq!(date::parse("Sun Nov  6 08:49:37 1994")?, UNIX_EPOCH + Duration::from_secs(784111777));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Formats `time` as an IMF-fixdate in GMT, truncating sub-second precision.
+///
+/// Times before the Unix epoch are formatted as the epoch.
+pub fn format(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+
+    let days = seconds.div_euclid(86400);
+    let time_of_day = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+
+    // 1970-01-01 was a Thursday
+    let weekday = DAYS[(days + 3).rem_euclid(7) as usize];
+
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        weekday,
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    )
+}
+
+/// Parses an HTTP date in IMF-fixdate, RFC 850 or asctime format.
+///
+/// The day of the week is not checked against the date, as the RFC allows.
+///
+/// # Returns
+/// An `anyhow::Result` with the parsed time, or an error if the string is not a valid date.
+pub fn parse(s: &str) -> anyhow::Result<SystemTime> {
+    let s = s.trim();
+    let parts: Vec<&str> = s.split_whitespace().collect();
+
+    let (year, month, day, time) = match parts.as_slice() {
+        // IMF-fixdate: Sun, 06 Nov 1994 08:49:37 GMT
+        [weekday, day, month, year, time, "GMT"] if weekday.ends_with(',') => {
+            (year.parse::<i64>()?, *month, day.parse::<u32>()?, *time)
+        }
+        // RFC 850: Sunday, 06-Nov-94 08:49:37 GMT
+        [weekday, date, time, "GMT"] if weekday.ends_with(',') => {
+            let mut date = date.split('-');
+            let (day, month, year) = match (date.next(), date.next(), date.next(), date.next()) {
+                (Some(day), Some(month), Some(year), None) if year.len() == 2 => (day, month, year),
+                _ => return Err(anyhow::anyhow!("Invalid HTTP date: {}", s)),
+            };
+
+            // Two-digit years are interpreted within the 1970-2069 window
+            let year = year.parse::<i64>()?;
+            let year = if year < 70 { 2000 + year } else { 1900 + year };
+
+            (year, month, day.parse::<u32>()?, *time)
+        }
+        // asctime: Sun Nov  6 08:49:37 1994
+        [_, month, day, time, year] => (year.parse::<i64>()?, *month, day.parse::<u32>()?, *time),
+        _ => return Err(anyhow::anyhow!("Invalid HTTP date: {}", s)),
+    };
+
+    let month = MONTHS
+        .iter()
+        .position(|m| *m == month)
+        .ok_or_else(|| anyhow::anyhow!("Invalid month in HTTP date: {}", s))?
+        as u32
+        + 1;
+
+    if day == 0 || day > days_in_month(year, month) {
+        return Err(anyhow::anyhow!("Invalid day in HTTP date: {}", s));
+    }
+
+    let mut time = time.split(':');
+    let (hour, minute, second) = match (time.next(), time.next(), time.next(), time.next()) {
+        (Some(h), Some(m), Some(s), None) => {
+            (h.parse::<i64>()?, m.parse::<i64>()?, s.parse::<i64>()?)
+        }
+        _ => return Err(anyhow::anyhow!("Invalid time in HTTP date: {}", s)),
+    };
+
+    // 60 is allowed for leap seconds, which are folded into the next minute
+    if hour > 23 || minute > 59 || second > 60 {
+        return Err(anyhow::anyhow!("Invalid time in HTTP date: {}", s));
+    }
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+
+    match seconds >= 0 {
+        true => Ok(UNIX_EPOCH + Duration::from_secs(seconds as u64)),
+        false => Err(anyhow::anyhow!("HTTP date before 1970: {}", s)),
+    }
+}
+
+/// Returns `true` if `year` is a leap year in the proleptic Gregorian calendar.
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Returns the number of days in `month` (1-12) of `year`.
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days between 1970-01-01 and the given date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the `(year, month, day)` date that is `days` days after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
@@ -0,0 +1,154 @@
+use std::path::Path;
+
+use super::HTTPResponse;
+
+impl HTTPResponse {
+    /// Marks the response as a download with the given file name.
+    ///
+    /// Sets `Content-Disposition: attachment` with a quoted ASCII `filename` and, when the
+    /// name contains non-ASCII characters, the RFC 5987 `filename*` form carrying the
+    /// UTF-8 name. Path separators and control characters are stripped from the name.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The name the client should save the file as.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPResponse;
+    ///
+    /// let mut response = HTTPResponse::ok();
+    ///
+    /// response.attachment("report.pdf");
+    /// assert_eq!(
+    ///     response.headers.get("Content-Disposition"),
+    ///     Some(r#"attachment; filename="report.pdf""#)
+    /// );
+    ///
+    /// response.attachment("my \"final\" report.pdf");
+    /// assert_eq!(
+    ///     response.headers.get("Content-Disposition"),
+    ///     Some(r#"attachment; filename="my \"final\" report.pdf""#)
+    /// );
+    ///
+    /// response.attachment("../résumé.pdf");
+    /// assert_eq!(
+    ///     response.headers.get("Content-Disposition"),
+    ///     Some(r#"attachment; filename="..r_sum_.pdf"; filename*=UTF-8''..r%C3%A9sum%C3%A9.pdf"#)
+    /// );
+    /// ```
+    pub fn attachment(&mut self, filename: &str) -> &mut Self {
+        self.headers.set(
+            "Content-Disposition",
+            &content_disposition("attachment", filename),
+        );
+
+        self
+    }
+
+    /// Marks the response as content to be displayed inline, suggesting a file name
+    /// for when the user saves it.
+    ///
+    /// The file name is encoded exactly as in [`HTTPResponse::attachment`].
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The suggested file name.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn inline(&mut self, filename: &str) -> &mut Self {
+        self.headers.set(
+            "Content-Disposition",
+            &content_disposition("inline", filename),
+        );
+
+        self
+    }
+
+    /// Creates a `200 OK` response serving the file at `path` as a download named
+    /// after the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file to send.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `HTTPResponse` or an error if the file can't be read.
+    pub fn download<P: AsRef<Path>>(path: P) -> anyhow::Result<HTTPResponse> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut response = HTTPResponse::from_file(path)?;
+        response.attachment(&filename);
+
+        Ok(response)
+    }
+}
+
+/// Builds a `Content-Disposition` header value of the given type for `filename`.
+fn content_disposition(disposition: &str, filename: &str) -> String {
+    let filename: String = filename
+        .chars()
+        .filter(|c| !c.is_control() && *c != '/' && *c != '\\')
+        .collect();
+
+    if filename.is_empty() {
+        return disposition.to_string();
+    }
+
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect::<String>()
+        .replace('"', "\\\"");
+
+    if filename.is_ascii() {
+        format!("{}; filename=\"{}\"", disposition, fallback)
+    } else {
+        format!(
+            "{}; filename=\"{}\"; filename*=UTF-8''{}",
+            disposition,
+            fallback,
+            encode_ext_value(&filename)
+        )
+    }
+}
+
+/// Percent-encodes a value for an RFC 5987 extended parameter, leaving only `attr-char`s as-is.
+fn encode_ext_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len() * 3);
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'#'
+            | b'$'
+            | b'&'
+            | b'+'
+            | b'-'
+            | b'.'
+            | b'^'
+            | b'_'
+            | b'`'
+            | b'|'
+            | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
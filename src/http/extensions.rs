@@ -0,0 +1,191 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+};
+
+/// Data attached to a single request, one value per type.
+///
+/// Every request starts with empty extensions. Middlewares store what they learn about
+/// the request in them, e.g. the authenticated user or the parsed session, and the
+/// handler reads it back. Unlike [`Args`](crate::args::Args), which every request
+/// shares, the values stay with the request they were inserted in.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::{SocketAddr, TcpStream},
+///     sync::{Arc, Barrier, RwLock},
+///     thread,
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     middleware::Next,
+///     router::Router,
+///     Server,
+/// };
+///
+/// struct User(String);
+///
+/// fn whoami(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let user = request.extensions.get::<User>().unwrap();
+///
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(format!("[{}]", user.0).into());
+///
+///     Ok(response)
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/whoami", http::Version::V11, whoami);
+/// router.add_middleware(
+///     |mut request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next| {
+///         let Some((user, _)) = request.basic_auth() else {
+///             return Ok(HTTPResponse::unauthorized_basic("fobserver"));
+///         };
+///         request.extensions.insert(User(user));
+///
+///         next.run(request, args)
+///     },
+/// );
+///
+/// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+/// server.set_workers(2);
+/// let server = server.start_background()?;
+/// let addr = server.addr()?;
+///
+/// // Both users are served at the same time, each sees their own name
+/// let barrier = Arc::new(Barrier::new(2));
+/// let clients: Vec<_> = ["Zm9iOjE=", "YWxpY2U6Mg=="]
+///     .into_iter()
+///     .map(|credentials| {
+///         let barrier = barrier.clone();
+///
+///         thread::spawn(move || -> anyhow::Result<String> {
+///             barrier.wait();
+///
+///             let mut stream = TcpStream::connect(addr)?;
+///             write!(
+///                 stream,
+///                 "GET /whoami HTTP/1.1\r\nAuthorization: Basic {}\r\nConnection: close\r\n\r\n",
+///                 credentials
+///             )?;
+///
+///             let mut response = String::new();
+///             stream.read_to_string(&mut response)?;
+///
+///             Ok(response)
+///         })
+///     })
+///     .collect();
+///
+/// let responses: Vec<_> = clients.into_iter().map(|client| client.join().unwrap()).collect();
+/// assert!(responses[0].as_ref().unwrap().ends_with("[fob]\r\n0\r\n\r\n"));
+/// assert!(responses[1].as_ref().unwrap().ends_with("[alice]\r\n0\r\n\r\n"));
+///
+/// server.shutdown();
+/// server.join()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+impl Extensions {
+    /// Creates empty extensions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores `value`, replacing the value of the same type if there was one.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to store.
+    ///
+    /// # Returns
+    ///
+    /// Returns the value it replaced, if any.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Returns a reference to the value of type `T`, if there is one.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Returns a mutable reference to the value of type `T`, if there is one.
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// Removes the value of type `T`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the removed value, if there was one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::Extensions;
+    ///
+    /// struct RequestId(u64);
+    ///
+    /// let mut extensions = Extensions::new();
+    /// assert!(extensions.insert(RequestId(1)).is_none());
+    /// assert_eq!(extensions.insert(RequestId(2)).map(|id| id.0), Some(1));
+    /// extensions.insert("a session");
+    ///
+    /// extensions.get_mut::<RequestId>().unwrap().0 += 1;
+    /// assert_eq!(extensions.get::<RequestId>().map(|id| id.0), Some(3));
+    /// assert_eq!(extensions.len(), 2);
+    ///
+    /// assert_eq!(extensions.remove::<RequestId>().map(|id| id.0), Some(3));
+    /// assert!(extensions.get::<RequestId>().is_none());
+    /// assert_eq!(extensions.get::<&str>(), Some(&"a session"));
+    /// ```
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())?
+            .downcast()
+            .ok()
+            .map(|value| *value)
+    }
+
+    /// Returns `true` if there is a value of type `T`.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns the number of values stored.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if no value is stored.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Removes every value.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+
+/// An ordered collection of HTTP headers, used by both requests and responses.
+///
+/// Headers keep the order in which they were inserted, names are matched
+/// case-insensitively and the same name may appear multiple times (e.g. several
+/// `Set-Cookie` headers).
+///
+/// # Example
+///
+/// ```
+/// use fobserver::http::HeaderMap;
+///
+/// let mut headers = HeaderMap::new();
+/// headers
+///     .set("Content-Type", "text/plain")
+///     .append("Set-Cookie", "a=1")
+///     .append("Set-Cookie", "b=2");
+///
+/// assert_eq!(headers.get("content-type"), Some("text/plain"));
+/// assert_eq!(headers.get_all("set-cookie").collect::<Vec<_>>(), ["a=1", "b=2"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<Entry>,
+}
+
+/// A single header line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    value: String,
+    /// Set for headers added through `set_unchecked`, which skip validation.
+    unchecked: bool,
+}
+
+impl Entry {
+    fn new(name: &str, value: &str, unchecked: bool) -> Self {
+        Entry {
+            name: name.to_string(),
+            value: value.to_string(),
+            unchecked,
+        }
+    }
+}
+
+impl HeaderMap {
+    /// Creates an empty `HeaderMap`.
+    pub fn new() -> Self {
+        HeaderMap {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Retrieves the first value of the header with the given name.
+    ///
+    /// # Parameters
+    /// - `name`: The case-insensitive name of the header.
+    ///
+    /// # Returns
+    /// An `Option` containing the value if the header exists, or `None` if it does not.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(name))
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Retrieves every value of the header with the given name, in insertion order.
+    ///
+    /// # Parameters
+    /// - `name`: The case-insensitive name of the header.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.name.eq_ignore_ascii_case(name))
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Parses the `Content-Length` header.
+    ///
+    /// # Returns
+    /// An `Option` containing the length, or `None` if the header is missing, has
+    /// anything but digits or is repeated with different values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPRequest;
+    ///
+    /// let request: HTTPRequest = "POST /upload HTTP/1.1\r\n\
+    ///     host: example.com\r\n\
+    ///     Content-Type: application/json; charset=utf-8\r\n\
+    ///     content-length: 2\r\n\
+    ///     X-Tag: a\r\nX-Tag: b\r\n\r\n{}".parse()?;
+    ///
+    /// assert_eq!(request.headers.content_length(), Some(2));
+    ///
+    /// let signed: HTTPRequest = "POST / HTTP/1.1\r\nContent-Length: +2\r\n\r\n".parse()?;
+    /// assert_eq!(signed.headers.content_length(), None);
+    /// assert_eq!(request.headers.content_type(), Some("application/json"));
+    /// assert_eq!(request.headers.host(), Some("example.com"));
+    /// assert_eq!(request.headers.get_all("x-tag").collect::<Vec<_>>(), ["a", "b"]);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn content_length(&self) -> Option<u64> {
+        let mut lengths = self
+            .get_all("Content-Length")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            // Only digits, `parse` would also take a sign
+            .map(|value| match value.bytes().all(|b| b.is_ascii_digit()) {
+                true => value.parse::<u64>().ok(),
+                false => None,
+            });
+
+        let first = lengths.next()??;
+        match lengths.all(|length| length == Some(first)) {
+            true => Some(first),
+            false => None,
+        }
+    }
+
+    /// Retrieves the media type of the `Content-Type` header, without its parameters.
+    ///
+    /// # Returns
+    /// An `Option` containing e.g. `text/html` for `text/html; charset=utf-8`, or `None`
+    /// if the header is missing.
+    pub fn content_type(&self) -> Option<&str> {
+        self.get("Content-Type")
+            .map(|value| value.split(';').next().unwrap_or_default().trim())
+    }
+
+    /// Retrieves the `Host` header.
+    pub fn host(&self) -> Option<&str> {
+        self.get("Host")
+    }
+
+    /// Returns `true` if at least one header with the given name exists.
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Sets a header, replacing every existing value with the same name.
+    ///
+    /// The header keeps the position of its first occurrence, or is added at the end
+    /// if it was not present yet.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the header.
+    /// - `value`: The value of the header.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set(&mut self, name: &str, value: &str) -> &mut Self {
+        self.insert(Entry::new(name, value, false))
+    }
+
+    /// Sets a header like [`HeaderMap::set`], but exempts it from the validation performed
+    /// before the response is written.
+    ///
+    /// This is an escape hatch for unusual values; the caller is responsible for making
+    /// sure the value can't break the response framing.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the header.
+    /// - `value`: The value of the header.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_unchecked(&mut self, name: &str, value: &str) -> &mut Self {
+        self.insert(Entry::new(name, value, true))
+    }
+
+    /// Replaces every header named like `entry` with it.
+    fn insert(&mut self, entry: Entry) -> &mut Self {
+        match self
+            .entries
+            .iter()
+            .position(|e| e.name.eq_ignore_ascii_case(&entry.name))
+        {
+            Some(index) => {
+                let mut i = index + 1;
+                while i < self.entries.len() {
+                    if self.entries[i].name.eq_ignore_ascii_case(&entry.name) {
+                        self.entries.remove(i);
+                    } else {
+                        i += 1;
+                    }
+                }
+
+                self.entries[index] = entry;
+            }
+            None => self.entries.push(entry),
+        }
+
+        self
+    }
+
+    /// Appends a header, keeping any existing value with the same name.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the header.
+    /// - `value`: The value of the header.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn append(&mut self, name: &str, value: &str) -> &mut Self {
+        self.entries.push(Entry::new(name, value, false));
+
+        self
+    }
+
+    /// Removes every header with the given name.
+    ///
+    /// # Returns
+    /// `true` if at least one header was removed.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.entries.len();
+        self.entries
+            .retain(|entry| !entry.name.eq_ignore_ascii_case(name));
+
+        self.entries.len() != len
+    }
+
+    /// Iterates over all headers as `(name, value)` pairs, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.value.as_str()))
+    }
+
+    /// Returns the number of header lines, counting repeated names separately.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no headers.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Checks that every header can be written without breaking the message framing.
+    ///
+    /// Names must be non-empty tokens and values must not contain CR, LF or NUL, which
+    /// would otherwise allow injecting headers or whole responses. Headers added with
+    /// [`HeaderMap::set_unchecked`] are skipped.
+    ///
+    /// # Returns
+    /// An `anyhow::Result` with an error describing the first invalid header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HeaderMap;
+    ///
+    /// let mut headers = HeaderMap::new();
+    /// headers.set("Location", "/home");
+    /// assert!(headers.validate().is_ok());
+    ///
+    /// headers.set("Location", "/home\r\nSet-Cookie: admin=1");
+    /// assert!(headers.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for entry in self.entries.iter().filter(|entry| !entry.unchecked) {
+            if entry.name.is_empty() || !entry.name.bytes().all(is_token) {
+                return Err(anyhow::anyhow!("Invalid header name: {:?}", entry.name));
+            }
+
+            if entry.value.contains(['\r', '\n', '\0']) {
+                return Err(anyhow::anyhow!(
+                    "Invalid value for header {}: {:?}",
+                    entry.name,
+                    entry.value
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The previous name of [`HeaderMap`].
+pub type Headers = HeaderMap;
+
+/// Converts a `HashMap` of headers into `HeaderMap`, keeping compatibility with code
+/// written against the previous `HashMap<String, String>` field.
+impl From<HashMap<String, String>> for HeaderMap {
+    fn from(map: HashMap<String, String>) -> Self {
+        HeaderMap {
+            entries: map
+                .into_iter()
+                .map(|(name, value)| Entry::new(&name, &value, false))
+                .collect(),
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        HeaderMap {
+            entries: iter
+                .into_iter()
+                .map(|(k, v)| Entry::new(&k.into(), &v.into(), false))
+                .collect(),
+        }
+    }
+}
+
+/// Returns `true` if `byte` can appear in a token (RFC 9110 §5.6.2).
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
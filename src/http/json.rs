@@ -0,0 +1,150 @@
+use serde::Serialize;
+
+use super::{HTTPResponse, HeaderMap, StatusCode};
+
+impl HTTPResponse {
+    /// Creates a response whose body is `value` serialized as JSON.
+    ///
+    /// Sets `Content-Type: application/json`. Serialization errors are returned to the
+    /// caller so they surface as handler errors instead of panics.
+    ///
+    /// # Arguments
+    ///
+    /// * `status_code` - The status code of the response.
+    /// * `value` - The value to serialize.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `HTTPResponse` or the serialization error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     router::Router,
+    ///     testing::TestServer,
+    /// };
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     id: u32,
+    ///     name: String,
+    /// }
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let user = User { id: 1, name: "fob".to_string() };
+    ///
+    ///     HTTPResponse::json(StatusCode::CODE200, &user)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/user", http::Version::V11, handler);
+    ///
+    /// let server = TestServer::new(router, Args::new());
+    /// let response = server.get("/user")?;
+    ///
+    /// assert_eq!(response.status_code, StatusCode::CODE200);
+    /// assert_eq!(response.headers.get("Content-Type"), Some("application/json"));
+    /// assert_eq!(
+    ///     response.body.unwrap().as_bytes(),
+    ///     Some(&br#"{"id":1,"name":"fob"}"#[..])
+    /// );
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn json<T: Serialize + ?Sized>(
+        status_code: StatusCode,
+        value: &T,
+    ) -> anyhow::Result<HTTPResponse> {
+        Ok(HTTPResponse::json_body(
+            status_code,
+            serde_json::to_vec(value)?,
+        ))
+    }
+
+    /// Creates a JSON response like [`HTTPResponse::json`], but pretty-printed for
+    /// easier debugging.
+    ///
+    /// # Arguments
+    ///
+    /// * `status_code` - The status code of the response.
+    /// * `value` - The value to serialize.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `HTTPResponse` or the serialization error.
+    pub fn json_pretty<T: Serialize + ?Sized>(
+        status_code: StatusCode,
+        value: &T,
+    ) -> anyhow::Result<HTTPResponse> {
+        Ok(HTTPResponse::json_body(
+            status_code,
+            serde_json::to_vec_pretty(value)?,
+        ))
+    }
+
+    /// Creates a JSON response like [`HTTPResponse::json`], escaping the HTML-sensitive
+    /// characters `<`, `>` and `&` (and the JavaScript line terminators U+2028/U+2029)
+    /// so the document can be safely embedded inside an HTML page.
+    ///
+    /// # Arguments
+    ///
+    /// * `status_code` - The status code of the response.
+    /// * `value` - The value to serialize.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `HTTPResponse` or the serialization error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::{HTTPResponse, StatusCode};
+    ///
+    /// let response = HTTPResponse::json_escaped(StatusCode::CODE200, &"</script>")?;
+    ///
+    /// assert_eq!(
+    ///     response.body.unwrap().as_bytes(),
+    ///     Some(&br#""\u003c/script\u003e""#[..])
+    /// );
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn json_escaped<T: Serialize + ?Sized>(
+        status_code: StatusCode,
+        value: &T,
+    ) -> anyhow::Result<HTTPResponse> {
+        let json = serde_json::to_string(value)?;
+
+        // The characters below can only appear inside JSON strings, so replacing them
+        // with their unicode escapes keeps the document equivalent.
+        let mut escaped = String::with_capacity(json.len());
+        for c in json.chars() {
+            match c {
+                '<' => escaped.push_str("\\u003c"),
+                '>' => escaped.push_str("\\u003e"),
+                '&' => escaped.push_str("\\u0026"),
+                '\u{2028}' => escaped.push_str("\\u2028"),
+                '\u{2029}' => escaped.push_str("\\u2029"),
+                c => escaped.push(c),
+            }
+        }
+
+        Ok(HTTPResponse::json_body(status_code, escaped.into_bytes()))
+    }
+
+    /// Creates a response with the given status code and an already serialized JSON body.
+    fn json_body(status_code: StatusCode, body: Vec<u8>) -> HTTPResponse {
+        let mut headers = HeaderMap::new();
+        headers.set("Content-Type", "application/json");
+
+        HTTPResponse {
+            status_code,
+            headers,
+            body: Some(body.into()),
+            ..HTTPResponse::default()
+        }
+    }
+}
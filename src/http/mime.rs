@@ -0,0 +1,102 @@
+use std::path::Path;
+
+/// Guesses the media type of a file from its extension.
+///
+/// Text types are reported as UTF-8. Unknown extensions fall back to
+/// `application/octet-stream`.
+pub(crate) fn from_path(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "application/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "wasm" => "application/wasm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A format a response can be rendered in, see [`preferred`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Html,
+    Json,
+    Text,
+}
+
+/// Returns the format an `Accept` header ranks highest among HTML, JSON and plain
+/// text, plain text winning ties and HTML winning over JSON.
+pub(crate) fn preferred(accept: &str) -> Format {
+    let text = quality(accept, "text/plain");
+    let html = quality(accept, "text/html");
+    let json = json_quality(accept);
+
+    if html > text && html >= json {
+        Format::Html
+    } else if json > text {
+        Format::Json
+    } else {
+        Format::Text
+    }
+}
+
+/// Returns `true` if an `Accept` header ranks JSON strictly above plain text and HTML.
+pub(crate) fn prefers_json(accept: &str) -> bool {
+    let json = json_quality(accept);
+
+    json > 0.0 && json > quality(accept, "text/html") && json > quality(accept, "text/plain")
+}
+
+/// Returns the quality an `Accept` header gives to JSON, problem documents included.
+fn json_quality(accept: &str) -> f32 {
+    quality(accept, "application/json").max(quality(accept, "application/problem+json"))
+}
+
+/// Returns the quality of the most specific media range of an `Accept` header
+/// matching `media_type`, 0 if none does.
+fn quality(accept: &str, media_type: &str) -> f32 {
+    let (kind, _) = media_type.split_once('/').unwrap_or_default();
+
+    accept
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';');
+            let range = params.next()?.trim();
+            let specificity = match range.split_once('/')? {
+                _ if range.eq_ignore_ascii_case(media_type) => 2,
+                (t, "*") if t.eq_ignore_ascii_case(kind) => 1,
+                ("*", "*") => 0,
+                _ => return None,
+            };
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            Some((specificity, q))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map_or(0.0, |(_, q)| q)
+}
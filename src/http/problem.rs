@@ -0,0 +1,187 @@
+use serde_json::{Map, Value};
+
+use super::{HTTPResponse, StatusCode};
+
+/// A machine-readable error document, as defined by RFC 9457 (formerly RFC 7807).
+///
+/// Problems are sent with `Content-Type: application/problem+json` and the status
+/// code they describe. Extension members are serialized next to the standard ones.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::http::{FieldError, Problem, StatusCode};
+///
+/// let response = Problem::not_found()
+///     .detail("No user with id 42")
+///     .instance("/users/42")
+///     .into_response();
+///
+/// assert_eq!(response.status_code, StatusCode::CODE404);
+/// assert_eq!(response.headers.get("Content-Type"), Some("application/problem+json"));
+///
+/// let body: serde_json::Value = serde_json::from_slice(response.body.unwrap().as_bytes().unwrap())?;
+/// assert_eq!(body["type"], "about:blank");
+/// assert_eq!(body["title"], "Not Found");
+/// assert_eq!(body["status"], 404);
+/// assert_eq!(body["detail"], "No user with id 42");
+/// assert_eq!(body["instance"], "/users/42");
+///
+/// let response = Problem::validation(vec![FieldError::new("email", "must contain an @")])
+///     .extension("request_id", "abc")
+///     .into_response();
+///
+/// assert_eq!(response.status_code, StatusCode::CODE422);
+///
+/// let body: serde_json::Value = serde_json::from_slice(response.body.unwrap().as_bytes().unwrap())?;
+/// assert_eq!(body["errors"][0]["field"], "email");
+/// assert_eq!(body["errors"][0]["message"], "must contain an @");
+/// assert_eq!(body["request_id"], "abc");
+/// assert!(body.get("detail").is_none());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// A URI identifying the problem type, `about:blank` when the status code says it all.
+    pub r#type: String,
+    /// A short summary of the problem type.
+    pub title: String,
+    /// The status code of the response.
+    pub status: StatusCode,
+    /// An explanation specific to this occurrence of the problem.
+    pub detail: Option<String>,
+    /// A URI identifying this occurrence of the problem.
+    pub instance: Option<String>,
+    /// Additional members of the document.
+    pub extensions: Map<String, Value>,
+}
+
+/// A validation error on a single field of the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The name of the invalid field.
+    pub field: String,
+    /// Why the field is invalid.
+    pub message: String,
+}
+
+impl FieldError {
+    /// Creates a `FieldError` for `field`.
+    pub fn new(field: &str, message: &str) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Problem {
+    /// Creates an `about:blank` problem whose title is the reason phrase of `status`.
+    pub fn new(status: StatusCode) -> Self {
+        Problem {
+            r#type: "about:blank".to_string(),
+            title: status.reason().to_string(),
+            status,
+            detail: None,
+            instance: None,
+            extensions: Map::new(),
+        }
+    }
+
+    /// Creates a `400 Bad Request` problem.
+    pub fn bad_request() -> Self {
+        Problem::new(StatusCode::CODE400)
+    }
+
+    /// Creates a `404 Not Found` problem.
+    pub fn not_found() -> Self {
+        Problem::new(StatusCode::CODE404)
+    }
+
+    /// Creates a `500 Internal Server Error` problem.
+    pub fn internal_error() -> Self {
+        Problem::new(StatusCode::CODE500)
+    }
+
+    /// Creates a `422 Unprocessable Content` problem listing the invalid fields in an
+    /// `errors` member.
+    pub fn validation(errors: Vec<FieldError>) -> Self {
+        let errors = errors
+            .into_iter()
+            .map(|error| {
+                let mut member = Map::new();
+                member.insert("field".to_string(), error.field.into());
+                member.insert("message".to_string(), error.message.into());
+
+                Value::Object(member)
+            })
+            .collect::<Vec<Value>>();
+
+        Problem::new(StatusCode::CODE422).extension("errors", errors)
+    }
+
+    /// Sets the problem type URI.
+    pub fn type_uri(mut self, uri: &str) -> Self {
+        self.r#type = uri.to_string();
+
+        self
+    }
+
+    /// Sets the title.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+
+        self
+    }
+
+    /// Sets the detail.
+    pub fn detail(mut self, detail: &str) -> Self {
+        self.detail = Some(detail.to_string());
+
+        self
+    }
+
+    /// Sets the instance URI.
+    pub fn instance(mut self, instance: &str) -> Self {
+        self.instance = Some(instance.to_string());
+
+        self
+    }
+
+    /// Adds an extension member. Members named like a standard one are ignored.
+    pub fn extension<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.extensions.insert(name.to_string(), value.into());
+
+        self
+    }
+
+    /// Converts the problem into a response with the matching status code.
+    ///
+    /// # Returns
+    ///
+    /// An `HTTPResponse` with `Content-Type: application/problem+json`.
+    pub fn into_response(self) -> HTTPResponse {
+        let mut document = self.extensions;
+
+        document.insert("type".to_string(), self.r#type.into());
+        document.insert("title".to_string(), self.title.into());
+        document.insert("status".to_string(), self.status.code().into());
+        for (name, value) in [("detail", self.detail), ("instance", self.instance)] {
+            match value {
+                Some(value) => document.insert(name.to_string(), value.into()),
+                None => document.remove(name),
+            };
+        }
+
+        let mut response = HTTPResponse {
+            status_code: self.status,
+            body: Some(Value::Object(document).to_string().into()),
+            ..HTTPResponse::default()
+        };
+        response
+            .headers
+            .set("Content-Type", "application/problem+json");
+
+        response
+    }
+}
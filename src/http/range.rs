@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+/// The most ranges a `Range` header may ask for, more are refused as abusive.
+const MAX_RANGES: usize = 16;
+
+/// A single range of a `Range: bytes=...` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `first-last`, both inclusive.
+    FromTo(u64, u64),
+    /// `first-`, up to the end of the resource.
+    From(u64),
+    /// `-length`, the final `length` bytes of the resource.
+    Last(u64),
+}
+
+impl ByteRange {
+    /// Resolves the range against a resource of `len` bytes.
+    ///
+    /// # Parameters
+    /// - `len`: The length of the resource in bytes.
+    ///
+    /// # Returns
+    /// The inclusive `(first, last)` byte positions, clamped to the resource, or `None`
+    /// if the range is not satisfiable.
+    pub fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+        if len == 0 {
+            return None;
+        }
+
+        match *self {
+            ByteRange::FromTo(first, last) if first < len => Some((first, last.min(len - 1))),
+            ByteRange::From(first) if first < len => Some((first, len - 1)),
+            ByteRange::Last(suffix) if suffix > 0 => Some((len - suffix.min(len), len - 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Represents the `Range` header of a request in the `bytes` unit.
+///
+/// Headers asking for more than 16 ranges are refused when parsed, so that the
+/// resource is sent whole rather than split in countless parts.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::http::{ByteRange, Range};
+///
+/// let range: Range = "bytes=0-99, 200-, -50".parse()?;
+///
+/// assert_eq!(
+///     range.ranges,
+///     [ByteRange::FromTo(0, 99), ByteRange::From(200), ByteRange::Last(50)]
+/// );
+/// assert_eq!(range.ranges[2].resolve(1000), Some((950, 999)));
+/// assert_eq!(ByteRange::From(1000).resolve(1000), None);
+///
+/// let many = format!("bytes={}", vec!["0-"; 17].join(","));
+/// assert!(many.parse::<Range>().is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Range {
+    pub ranges: Vec<ByteRange>,
+}
+
+/// Parses a `Range` header value such as `bytes=0-499`.
+impl FromStr for Range {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (unit, set) = s
+            .trim()
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Malformed Range header"))?;
+
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return Err(anyhow::anyhow!("Unsupported range unit: {}", unit));
+        }
+
+        let mut ranges = Vec::new();
+        for spec in set.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (first, last) = spec
+                .split_once('-')
+                .ok_or_else(|| anyhow::anyhow!("Malformed range: {}", spec))?;
+            let (first, last) = (first.trim(), last.trim());
+
+            let range = match (first.is_empty(), last.is_empty()) {
+                (true, false) => ByteRange::Last(last.parse()?),
+                (false, true) => ByteRange::From(first.parse()?),
+                (false, false) => {
+                    let (first, last) = (first.parse()?, last.parse()?);
+                    if last < first {
+                        return Err(anyhow::anyhow!("Invalid range: {}", spec));
+                    }
+                    ByteRange::FromTo(first, last)
+                }
+                (true, true) => return Err(anyhow::anyhow!("Malformed range: {}", spec)),
+            };
+
+            if ranges.len() == MAX_RANGES {
+                return Err(anyhow::anyhow!("Too many ranges"));
+            }
+            ranges.push(range);
+        }
+
+        if ranges.is_empty() {
+            return Err(anyhow::anyhow!("Empty Range header"));
+        }
+
+        Ok(Range { ranges })
+    }
+}
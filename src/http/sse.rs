@@ -0,0 +1,88 @@
+use std::{fmt, time::Duration};
+
+/// Represents a single Server-Sent Event.
+///
+/// Events are serialized following the `text/event-stream` format: optional `event`,
+/// `id` and `retry` fields followed by one `data:` line per line of data and a blank
+/// line terminating the event.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use fobserver::http::Event;
+///
+/// let event = Event::new("first\nsecond")
+///     .event("update")
+///     .id("42")
+///     .retry(Duration::from_secs(3));
+///
+/// assert_eq!(
+///     event.to_string(),
+///     "event: update\nid: 42\nretry: 3000\ndata: first\ndata: second\n\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub retry: Option<Duration>,
+    pub data: String,
+}
+
+impl Event {
+    /// Creates a new event carrying the given data.
+    ///
+    /// # Parameters
+    /// - `data`: The event payload; every line is sent as its own `data:` field.
+    pub fn new(data: &str) -> Self {
+        Event {
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    /// Sets the event type, dispatched by browsers to `addEventListener(name, ...)`.
+    pub fn event(mut self, name: &str) -> Self {
+        self.event = Some(name.to_string());
+
+        self
+    }
+
+    /// Sets the event id, which the client reports back in `Last-Event-ID` on reconnection.
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+
+        self
+    }
+
+    /// Sets the reconnection delay the client should use if the stream is interrupted.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+
+        self
+    }
+}
+
+/// Serializes the event in the `text/event-stream` wire format.
+///
+/// Line breaks are stripped from the `event` and `id` fields since they would
+/// otherwise terminate the field early.
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(event) = &self.event {
+            writeln!(f, "event: {}", event.replace(['\r', '\n'], ""))?;
+        }
+        if let Some(id) = &self.id {
+            writeln!(f, "id: {}", id.replace(['\r', '\n'], ""))?;
+        }
+        if let Some(retry) = &self.retry {
+            writeln!(f, "retry: {}", retry.as_millis())?;
+        }
+        for line in self.data.split('\n') {
+            writeln!(f, "data: {}", line.strip_suffix('\r').unwrap_or(line))?;
+        }
+
+        writeln!(f)
+    }
+}
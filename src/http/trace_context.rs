@@ -0,0 +1,251 @@
+use std::{fmt, str::FromStr};
+
+use super::{HTTPRequest, HeaderMap, ParseError};
+use crate::random;
+
+/// The length of a version `00` `traceparent` header.
+const HEADER_LEN: usize = 55;
+
+/// The trace flag telling that the caller may have recorded its span.
+const SAMPLED: u8 = 0x01;
+
+/// Represents a span of a distributed trace, as propagated by the W3C Trace Context
+/// `traceparent` header.
+///
+/// Parsing a header gives the context of the caller's span. Requests received with a
+/// valid header get a [child](TraceContext::child) of it, the span of the server,
+/// see [`HTTPRequest::trace_context`]. Its own children are what to send downstream,
+/// e.g. through the [`Client`](crate::client::Client); the
+/// [reverse proxy](crate::proxy::proxy_to) does it on its own. Only version `00`
+/// headers are written, and only the `sampled` flag is carried.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::http::TraceContext;
+///
+/// // The examples of the specification
+/// let context: TraceContext = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".parse()?;
+/// assert_eq!(context.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+/// assert_eq!(context.span_id_hex(), "00f067aa0ba902b7");
+/// assert_eq!(context.parent_id, None);
+/// assert!(context.sampled());
+///
+/// let context: TraceContext = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00".parse()?;
+/// assert!(!context.sampled());
+///
+/// // Later versions are read like this one
+/// let future = "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-what-the-future-will-be";
+/// assert_eq!(future.parse::<TraceContext>()?.to_string(), future[..55].replacen("cc", "00", 1));
+///
+/// for invalid in [
+///     "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+///     "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+///     "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+///     "00-4BF92F3577B34DA6A3CE929D0E0E4736-00F067AA0BA902B7-01",
+///     "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+///     "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+///     "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01.extra",
+/// ] {
+///     assert!(invalid.parse::<TraceContext>().is_err(), "{}", invalid);
+/// }
+///
+/// // What a call made while handling the request sends downstream
+/// let child = context.child();
+/// assert_eq!(child.trace_id, context.trace_id);
+/// assert_eq!(child.parent_id, Some(context.span_id));
+/// assert_ne!(child.span_id, context.span_id);
+/// assert_eq!(
+///     child.to_string(),
+///     format!("00-4bf92f3577b34da6a3ce929d0e0e4736-{}-00", child.span_id_hex())
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceContext {
+    /// The id of the trace, shared by every span in it.
+    pub trace_id: [u8; 16],
+    /// The id of the span.
+    pub span_id: [u8; 8],
+    /// The id of the span that started this one, if known.
+    pub parent_id: Option<[u8; 8]>,
+    /// The trace flags, see [`TraceContext::sampled`].
+    pub flags: u8,
+}
+
+impl TraceContext {
+    /// Starts a new trace, not sampled, with random ids.
+    pub fn new() -> Self {
+        let mut trace_id = [0; 16];
+        random::fill(&mut trace_id);
+
+        TraceContext {
+            trace_id,
+            span_id: span_id(),
+            parent_id: None,
+            flags: 0,
+        }
+    }
+
+    /// Returns the context of a new span started by this one, in the same trace.
+    pub fn child(&self) -> Self {
+        TraceContext {
+            trace_id: self.trace_id,
+            span_id: span_id(),
+            parent_id: Some(self.span_id),
+            flags: self.flags,
+        }
+    }
+
+    /// Returns `true` if the caller may have recorded its span, hinting that the
+    /// spans of this trace should be recorded too.
+    pub fn sampled(&self) -> bool {
+        self.flags & SAMPLED != 0
+    }
+
+    /// Returns the id of the trace as lowercase hexadecimal.
+    pub fn trace_id_hex(&self) -> String {
+        to_hex(&self.trace_id)
+    }
+
+    /// Returns the id of the span as lowercase hexadecimal.
+    pub fn span_id_hex(&self) -> String {
+        to_hex(&self.span_id)
+    }
+
+    /// Returns the context of the span of the server handling a request carrying
+    /// `headers`, or `None` if they have no valid `traceparent` header.
+    pub(crate) fn of(headers: &HeaderMap) -> Option<Self> {
+        let mut traceparent = headers.get_all("traceparent");
+
+        // Several headers are as unusable as a malformed one
+        match (traceparent.next(), traceparent.next()) {
+            (Some(value), None) => value.parse().ok().map(|caller: Self| caller.child()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses a `traceparent` header into the context of the span it names.
+impl FromStr for TraceContext {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_matches([' ', '\t']);
+        let invalid = || ParseError::new(format!("Invalid traceparent header: {}", s));
+
+        if !s.is_ascii() || s.len() < HEADER_LEN {
+            return Err(invalid());
+        }
+
+        let version: [u8; 1] = from_hex(&s[..2]).ok_or_else(invalid)?;
+        let len_ok = match version[0] {
+            0xff => false,
+            0x00 => s.len() == HEADER_LEN,
+            // Later versions may append fields
+            _ => s.len() == HEADER_LEN || s.as_bytes()[HEADER_LEN] == b'-',
+        };
+
+        let separators = [2, 35, 52].iter().all(|&i| s.as_bytes()[i] == b'-');
+        if !len_ok || !separators {
+            return Err(invalid());
+        }
+
+        let trace_id: [u8; 16] = from_hex(&s[3..35]).ok_or_else(invalid)?;
+        let span_id: [u8; 8] = from_hex(&s[36..52]).ok_or_else(invalid)?;
+        let flags: [u8; 1] = from_hex(&s[53..55]).ok_or_else(invalid)?;
+
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return Err(invalid());
+        }
+
+        Ok(TraceContext {
+            trace_id,
+            span_id,
+            parent_id: None,
+            flags: flags[0],
+        })
+    }
+}
+
+/// Serializes the context into a version `00` `traceparent` header value.
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id_hex(),
+            self.span_id_hex(),
+            self.flags & SAMPLED
+        )
+    }
+}
+
+impl HTTPRequest {
+    /// Returns the trace context of the span of the server handling the request: a
+    /// child of the one named by its `traceparent` header, or `None` if the request
+    /// has no valid header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fobserver::http::HTTPRequest;
+    ///
+    /// let request: HTTPRequest = "GET / HTTP/1.1\r\n\
+    ///     traceparent: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01\r\n\r\n"
+    ///     .parse()?;
+    ///
+    /// let context = request.trace_context().unwrap();
+    /// assert_eq!(context.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    /// assert_eq!(context.parent_id, Some([0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7]));
+    ///
+    /// let request: HTTPRequest = "GET / HTTP/1.1\r\ntraceparent: nonsense\r\n\r\n".parse()?;
+    /// assert!(request.trace_context().is_none());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn trace_context(&self) -> Option<&TraceContext> {
+        self.trace_context.as_ref()
+    }
+}
+
+/// Returns a random span id, never zero.
+fn span_id() -> [u8; 8] {
+    let mut id = [0; 8];
+
+    while id == [0; 8] {
+        random::fill(&mut id);
+    }
+
+    id
+}
+
+/// Decodes lowercase hexadecimal, as the header requires.
+fn from_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    let digit = |c: u8| match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        _ => None,
+    };
+
+    if s.len() != 2 * N {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+    for (byte, pair) in bytes.iter_mut().zip(s.as_bytes().chunks(2)) {
+        *byte = digit(pair[0])? << 4 | digit(pair[1])?;
+    }
+
+    Some(bytes)
+}
+
+/// Encodes `bytes` as lowercase hexadecimal.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
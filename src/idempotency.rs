@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    hmac,
+    http::{Body, BodySource, HTTPRequest, HTTPResponse, HeaderMap, StatusCode},
+};
+
+/// What an [`IdempotencyStore`] remembers of an idempotency key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// The fingerprint of the request first made with the key, its method, path and
+    /// body hashed.
+    pub fingerprint: String,
+    /// The response to the request, `None` while the handler runs.
+    pub response: Option<StoredResponse>,
+}
+
+/// A response kept to be replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    /// The status code of the response.
+    pub status_code: StatusCode,
+    /// The headers of the response.
+    pub headers: HeaderMap,
+    /// The body of the response, empty if it had none.
+    pub body: Vec<u8>,
+}
+
+impl StoredResponse {
+    /// Copies `response`, unless its body isn't held in memory.
+    pub(crate) fn from_response(response: &HTTPResponse) -> Option<Self> {
+        let body = match &response.body {
+            Some(body) => body.as_bytes()?.to_vec(),
+            None => Vec::new(),
+        };
+
+        Some(StoredResponse {
+            status_code: response.status_code,
+            headers: response.headers.clone(),
+            body,
+        })
+    }
+
+    /// Creates the response to send again.
+    pub(crate) fn to_response(&self) -> HTTPResponse {
+        HTTPResponse {
+            status_code: self.status_code,
+            headers: self.headers.clone(),
+            body: (!self.body.is_empty()).then(|| Body::from(self.body.clone())),
+            ..HTTPResponse::default()
+        }
+    }
+}
+
+/// Where the [`idempotency`](crate::middleware::idempotency) middleware keeps the
+/// keys it saw and the responses given to them. The keys it is given are scoped to
+/// the client that sent them, see [`Idempotency::scope`](crate::middleware::Idempotency::scope).
+pub trait IdempotencyStore: Send + Sync {
+    /// Reserves `key` for the request `fingerprint`, unless the key is already known.
+    /// Checking and reserving must be atomic, so that concurrent requests with the
+    /// same key don't both run the handler.
+    ///
+    /// # Parameters
+    /// - `key`: The idempotency key.
+    /// - `fingerprint`: The fingerprint of the request.
+    /// - `ttl`: How long the reservation lasts unless completed.
+    ///
+    /// # Returns
+    /// A `Result` containing `None` if the key was reserved, the record of the key if
+    /// it was already known, or an error if the store can't be used.
+    fn reserve(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<Option<Record>>;
+
+    /// Stores `record`, with the response of the request, under `key`.
+    ///
+    /// # Parameters
+    /// - `key`: The idempotency key.
+    /// - `record`: The fingerprint of the request and its response.
+    /// - `ttl`: How long the key is remembered.
+    ///
+    /// # Returns
+    /// A `Result` which is an error if the store can't be written.
+    fn complete(&self, key: &str, record: Record, ttl: Duration) -> anyhow::Result<()>;
+
+    /// Forgets `key`, e.g. when the handler failed and the request may be retried.
+    ///
+    /// # Parameters
+    /// - `key`: The idempotency key.
+    ///
+    /// # Returns
+    /// A `Result` which is an error if the store can't be written.
+    fn release(&self, key: &str) -> anyhow::Result<()>;
+}
+
+/// An [`IdempotencyStore`] keeping the keys in memory, lost when the process exits.
+///
+/// Expired keys are purged whenever a key is reserved.
+///
+/// # Example
+///
+/// ```
+/// use std::{thread, time::Duration};
+/// use fobserver::idempotency::{IdempotencyStore, MemoryStore};
+///
+/// let store = MemoryStore::new();
+/// let ttl = Duration::from_millis(10);
+/// assert_eq!(store.reserve("a", "POST /pay", ttl)?, None);
+///
+/// // The key is now taken, by a request still running
+/// let record = store.reserve("a", "POST /pay", ttl)?.unwrap();
+/// assert_eq!(record.response, None);
+///
+/// // Until it expires
+/// thread::sleep(Duration::from_millis(20));
+/// assert_eq!(store.reserve("a", "POST /refund", ttl)?, None);
+///
+/// store.release("a")?;
+/// assert!(store.is_empty());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    records: Mutex<HashMap<String, (Record, Instant)>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of keys stored, expired ones not purged yet included.
+    pub fn len(&self) -> usize {
+        self.records().len()
+    }
+
+    /// Returns `true` if no key is stored.
+    pub fn is_empty(&self) -> bool {
+        self.records().is_empty()
+    }
+
+    /// Locks the records, even if a thread panicked while holding them.
+    fn records(&self) -> MutexGuard<'_, HashMap<String, (Record, Instant)>> {
+        self.records.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl IdempotencyStore for MemoryStore {
+    fn reserve(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        ttl: Duration,
+    ) -> anyhow::Result<Option<Record>> {
+        let now = Instant::now();
+        let mut records = self.records();
+
+        records.retain(|_, (_, expires)| *expires > now);
+        if let Some((record, _)) = records.get(key) {
+            return Ok(Some(record.clone()));
+        }
+
+        let record = Record {
+            fingerprint: fingerprint.to_string(),
+            response: None,
+        };
+        records.insert(key.to_string(), (record, now + ttl));
+
+        Ok(None)
+    }
+
+    fn complete(&self, key: &str, record: Record, ttl: Duration) -> anyhow::Result<()> {
+        self.records()
+            .insert(key.to_string(), (record, Instant::now() + ttl));
+
+        Ok(())
+    }
+
+    fn release(&self, key: &str) -> anyhow::Result<()> {
+        self.records().remove(key);
+
+        Ok(())
+    }
+}
+
+/// Returns the fingerprint of `request`: its method, path and body, hashed.
+///
+/// A body spooled to a file is hashed as it is read, a chunk at a time.
+///
+/// # Returns
+/// A `Result` containing the fingerprint, or an error if the body was spooled to a
+/// file that can't be read.
+pub(crate) fn fingerprint(request: &HTTPRequest) -> anyhow::Result<String> {
+    let mut hasher = hmac::Sha256::new();
+    hasher.update(format!("{} {}\n", request.method, request.path).as_bytes());
+
+    match (&request.body, &request.body_source) {
+        (None, Some(BodySource::File(path, _))) => {
+            let mut file = File::open(path)?;
+            let mut chunk = [0; 8192];
+
+            loop {
+                match file.read(&mut chunk)? {
+                    0 => break,
+                    read => hasher.update(&chunk[..read]),
+                }
+            }
+        }
+        (body, _) => hasher.update(body.as_deref().unwrap_or_default().as_bytes()),
+    }
+    let digest = hasher.finish();
+
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
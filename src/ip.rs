@@ -0,0 +1,195 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// A range of IP addresses sharing a prefix, such as `10.0.0.0/8` or `fe80::/10`.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::ip::IpNet;
+///
+/// let net: IpNet = "192.168.1.0/24".parse()?;
+/// assert!(net.contains("192.168.1.42".parse()?));
+/// assert!(!net.contains("192.168.2.1".parse()?));
+///
+/// // Bits beyond the prefix are ignored
+/// assert_eq!("10.1.2.3/8".parse::<IpNet>()?.to_string(), "10.0.0.0/8");
+///
+/// // A bare address is a network of its own
+/// let host: IpNet = "::1".parse()?;
+/// assert_eq!(host.to_string(), "::1/128");
+///
+/// // IPv4 clients of a dual-stack socket match IPv4 networks
+/// assert!(net.contains("::ffff:192.168.1.42".parse()?));
+///
+/// assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+/// assert!("localhost".parse::<IpNet>().is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    /// Creates the network made of the addresses sharing the first `prefix_len` bits
+    /// of `addr`.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - An address of the network.
+    /// * `prefix_len` - The length of the prefix, at most 32 for IPv4 and 128 for IPv6.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the network, or an error if the prefix is too
+    /// long.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> anyhow::Result<Self> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix_len > max {
+            return Err(anyhow::anyhow!("Invalid prefix length: {}", prefix_len));
+        }
+
+        let addr = match addr {
+            IpAddr::V4(addr) => {
+                Ipv4Addr::from(u32::from(addr) & mask(prefix_len, 32) as u32).into()
+            }
+            IpAddr::V6(addr) => Ipv6Addr::from(u128::from(addr) & mask(prefix_len, 128)).into(),
+        };
+
+        Ok(IpNet { addr, prefix_len })
+    }
+
+    /// Returns the first address of the network.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the length of the prefix.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns `true` if `ip` belongs to the network.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                u32::from(ip) & mask(self.prefix_len, 32) as u32 == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                u128::from(ip) & mask(self.prefix_len, 128) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parses a network in CIDR notation, e.g. `10.0.0.0/8`. A bare address stands for
+/// the network made of that address alone.
+impl FromStr for IpNet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some((addr, prefix_len)) => (
+                addr,
+                Some(
+                    prefix_len
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("Invalid prefix length: {}", prefix_len))?,
+                ),
+            ),
+            None => (s.trim(), None),
+        };
+
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid IP address: {}", addr))?;
+
+        IpNet::new(
+            addr,
+            prefix_len.unwrap_or(if addr.is_ipv4() { 32 } else { 128 }),
+        )
+    }
+}
+
+/// Formats a network in CIDR notation, e.g. `10.0.0.0/8`.
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Returns the mask keeping the first `prefix_len` bits of a `bits`-bit address.
+fn mask(prefix_len: u8, bits: u32) -> u128 {
+    match u32::from(prefix_len) {
+        0 => 0,
+        len => (u128::MAX << (128 - len)) >> (128 - bits),
+    }
+}
+
+/// The clients allowed to connect to a server, by IP address.
+///
+/// A client is refused if its address belongs to a network of `deny`, or if `allow`
+/// is not empty and its address belongs to none of its networks: the deny list wins,
+/// and an empty allow list allows everyone.
+///
+/// The filter applies to whole connections through
+/// [`Server::set_ip_filter`](crate::Server::set_ip_filter), or to some paths only
+/// through the [`ip_filter`](crate::middleware::ip_filter) middleware.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::ip::IpFilter;
+///
+/// let filter = IpFilter {
+///     allow: vec!["10.0.0.0/8".parse()?, "fd00::/8".parse()?],
+///     deny: vec!["10.0.66.0/24".parse()?],
+///     ..IpFilter::default()
+/// };
+///
+/// assert!(filter.allows("10.1.2.3".parse()?));
+/// assert!(filter.allows("fd12::1".parse()?));
+/// assert!(!filter.allows("10.0.66.1".parse()?));
+/// assert!(!filter.allows("192.168.1.1".parse()?));
+///
+/// // Without an allow list, only the denied addresses are refused
+/// let filter = IpFilter {
+///     deny: vec!["203.0.113.0/24".parse()?],
+///     ..IpFilter::default()
+/// };
+///
+/// assert!(filter.allows("192.168.1.1".parse()?));
+/// assert!(!filter.allows("203.0.113.7".parse()?));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IpFilter {
+    /// The networks allowed to connect. When empty, every address is allowed.
+    pub allow: Vec<IpNet>,
+    /// The networks refused, even if they are allowed.
+    pub deny: Vec<IpNet>,
+    /// Answers refused connections with `403 Forbidden` before closing them, instead
+    /// of closing them right away.
+    pub forbidden_response: bool,
+}
+
+impl IpFilter {
+    /// Returns `true` if clients connecting from `ip` are allowed.
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|net| net.contains(ip)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(ip))
+    }
+}
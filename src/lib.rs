@@ -1,18 +1,117 @@
 use std::{
     cmp::min,
-    io::{Read, Write},
-    net::{TcpListener, TcpStream},
-    sync::{Arc, RwLock},
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom, Write},
+    net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, RwLock,
+    },
     thread,
+    time::{Duration, Instant, SystemTime},
 };
 
+use access_log::{AccessLogEntry, AccessLogFormatter};
+use anyhow::Context;
 use args::Args;
-use http::{HTTPRequest, HTTPResponse};
+use buffers::{Buffer, BufferedWriter};
+pub use builder::ServerBuilder;
+use connection::{Connection, Embedded, Transport};
+pub use error::{ConnectionError, ConnectionErrorKind, Error};
+use error_page::ErrorPages;
+use health::{Health, HealthConfig};
+use http::{Body, HTTPRequest, HTTPResponse};
+use ip::IpFilter;
+use limit::{ConnectionLimiter, LimitPolicy, Permit, SharedLimiter};
+use listener::{AcceptFailure, Endpoint, Listener};
+use maintenance::{MaintenanceConfig, MaintenanceHandle};
+use parser::{
+    RequestLineTooLong, RequestParser, RequestTooLarge, SpoolFailed, TempFile,
+    UnsupportedTransferEncoding,
+};
+use pool::WorkerPool;
+use reaper::Reaper;
 use router::Router;
+use sendfile::SendFile;
+use socket2::{SockRef, Socket, TcpKeepalive};
+use stats::{CountingWriter, Stats};
+use watchdog::Watchdog;
 
+pub mod access_log;
 pub mod args;
+#[cfg(feature = "async")]
+mod async_server;
+mod base64;
+mod buffers;
+mod builder;
+pub mod cache;
+pub mod client;
+#[cfg(feature = "compression")]
+pub mod compression;
+mod config;
+mod connection;
+pub mod csrf;
+mod error;
+pub mod error_page;
+pub mod files;
+pub mod health;
+mod hmac;
 pub mod http;
+pub mod idempotency;
+pub mod ip;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+pub mod limit;
+mod listener;
+pub mod maintenance;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod middleware;
+#[cfg(feature = "json")]
+pub mod openapi;
+mod parser;
+mod pool;
+pub mod proxy;
+mod random;
+mod reaper;
 pub mod router;
+mod sendfile;
+pub mod session;
+mod signals;
+mod span;
+pub mod stats;
+#[cfg(unix)]
+pub mod systemd;
+#[cfg(feature = "json")]
+pub mod template;
+pub mod testing;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "tracing")]
+pub mod trace;
+#[cfg(feature = "json")]
+pub mod validation;
+mod watchdog;
+
+/// The capacity of the buffer in which a response is assembled before being sent.
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How often an acceptor waiting for a connection to close checks for shutdown.
+const ACQUIRE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long an overloaded server asks clients to wait before coming back.
+const RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// How often the server checks whether the process was asked to terminate.
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The largest read buffer or write chunk the server can be configured with.
+const MAX_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+/// The smallest stack the threads of the server can be configured with.
+const MIN_STACK_SIZE: usize = 64 * 1024;
 
 /// A type alias for a function that handles HTTP requests.
 ///
@@ -29,18 +128,148 @@ pub mod router;
 /// An `anyhow::Result<HTTPResponse>`, which indicates the success or failure of the
 /// request handling. On success, it returns an `HTTPResponse`, and on failure, it
 /// returns an error wrapped in `anyhow::Error`.
+///
+/// A handler that panics is treated as one returning an error: the panic is logged
+/// and the client receives a `500 Internal Server Error`, while the worker goes on
+/// serving other requests.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::TcpStream,
+///     sync::{Arc, RwLock},
+///     thread,
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     router::Router,
+///     Server,
+/// };
+///
+/// fn panics(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     panic!("Bad input")
+/// }
+///
+/// fn works(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::ok())
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/panic", http::Version::V11, panics);
+/// router.add_route(http::Method::GET, "/", http::Version::V11, works);
+///
+/// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+/// server.set_workers(1);
+///
+/// let addr = server.local_addr()?;
+/// let handle = server.shutdown_handle();
+/// let server = thread::spawn(move || server.start());
+///
+/// let get = |path: &str| -> anyhow::Result<String> {
+///     let mut stream = TcpStream::connect(addr)?;
+///     write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path)?;
+///
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response)?;
+///
+///     Ok(response)
+/// };
+///
+/// assert!(get("/panic")?.starts_with("HTTP/1.1 500 Internal Server Error"));
+///
+/// // The only worker is still alive
+/// assert!(get("/")?.starts_with("HTTP/1.1 200 OK"));
+///
+/// handle.shutdown();
+/// server.join().unwrap()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
 pub type HandlerFunction = fn(HTTPRequest, Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse>;
 
+/// A type alias for a function that handles HTTP requests with the typed state of
+/// the server, see [`Server::new_with_state`].
+///
+/// # Parameters
+/// - `request`: The incoming HTTP request.
+/// - `state`: The state the server was created with.
+///
+/// # Returns
+/// An `anyhow::Result<HTTPResponse>`, exactly like a [`HandlerFunction`].
+pub type StateHandlerFunction<S> = fn(HTTPRequest, Arc<S>) -> anyhow::Result<HTTPResponse>;
+
+/// A type alias for a handler function returning a value sent as JSON, see
+/// [`Router::json_route`](router::Router::json_route).
+///
+/// # Parameters
+/// - `request`: The HTTP request being handled.
+/// - `args`: The arguments shared across handlers.
+///
+/// # Returns
+/// An `anyhow::Result` containing the value to serialize, or the error of the
+/// handler.
+#[cfg(feature = "json")]
+pub type JsonHandlerFunction<T> = fn(HTTPRequest, Arc<RwLock<Args>>) -> anyhow::Result<T>;
+
+/// A type alias for a handler capturing what it needs, such as the handler of a route
+/// added with [`Router::add_state_route`](router::Router::add_state_route), see
+/// [`Router::closure_route`](router::Router::closure_route).
+///
+/// It takes the same parameters and returns the same result as a [`HandlerFunction`].
+pub type ClosureHandlerFunction =
+    dyn Fn(HTTPRequest, Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> + Send + Sync;
+
+/// A type alias for a function building the response sent when a handler fails.
+///
+/// # Parameters
+/// - `error`: The error returned by the handler.
+/// - `request`: The request being handled, without its body.
+///
+/// # Returns
+/// The `HTTPResponse` to send instead of the handler's.
+pub type ErrorHandlerFunction = fn(&anyhow::Error, &HTTPRequest) -> HTTPResponse;
+
+/// A type alias for a function told about the errors ending connections.
+///
+/// # Parameters
+/// - `error`: The error, with the address of the client.
+pub type ConnectionErrorHookFunction = fn(&ConnectionError);
+
+/// A type alias for a function deciding whether a new connection is served, before
+/// anything is read from it.
+///
+/// # Parameters
+/// - `addr`: The address of the client.
+///
+/// # Returns
+/// What the server does with the connection.
+pub type ConnectionFilterFunction = fn(&SocketAddr) -> ConnectionDecision;
+
+/// A type alias for a callback taking over a connection once its response is sent,
+/// see [`HTTPResponse::with_upgrade`].
+///
+/// # Parameters
+/// - `stream`: The connection, now speaking whatever protocol the callback wants.
+pub type UpgradeFunction = Box<dyn FnOnce(TcpStream) + Send>;
+
 /// Represents an HTTP server that listens for incoming connections.
 ///
 /// # Example
 ///
 /// This example demonstrates how to create a server that counts the number of
-/// GET requests received at the root endpoint:
+/// GET requests received at the root endpoint, see [`Server::new_with_state`] for the
+/// same server with typed state:
 ///
-/// ```
+/// ```no_run
 /// use std::sync::{Arc, RwLock};
-/// use fobserver::{Args, HTTPRequest, HTTPResponse, Router, Server};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     router::Router,
+///     Server,
+/// };
 ///
 /// struct Counter {
 ///     value: usize,
@@ -56,25 +285,22 @@ pub type HandlerFunction = fn(HTTPRequest, Arc<RwLock<Args>>) -> anyhow::Result<
 ///     }
 /// }
 ///
-/// fn main() -> anyhow::Result<()> {
+/// fn main() -> Result<(), fobserver::Error> {
 ///     let mut args = Args::new();
-///     args.add_arg("counter", Arc::new(RwLock::new(Counter::new())));
+///     args.insert("counter", Counter::new());
 ///
 ///     let mut router = Router::new();
 ///     fn handler(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
-///         let args = args.read().unwrap();
-///
-///         let binding = args.arg("counter").unwrap();
-///         let mut l = binding.write().unwrap();
-///         let counter = l.downcast_mut::<Counter>().unwrap();
-///
-///         counter.add();
+///         let value = args.read().unwrap().with_mut("counter", |counter: &mut Counter| {
+///             counter.add();
+///             counter.value
+///         })?;
 ///
 ///         let response = HTTPResponse {
 ///             version: http::Version::V11,
 ///             status_code: http::StatusCode::CODE200,
-///             headers: HashMap::new(),
-///             body: Some(format!("Counter value: {}", counter.value).into()),
+///             headers: http::HeaderMap::new(),
+///             body: Some(format!("Counter value: {}", value).into()),
 ///         };
 ///
 ///         Ok(response)
@@ -86,146 +312,5524 @@ pub type HandlerFunction = fn(HTTPRequest, Arc<RwLock<Args>>) -> anyhow::Result<
 ///     server.start()
 /// }
 /// ```
+///
+/// Clients may pipeline requests, sending several of them without waiting for the
+/// responses. Each request is read with its own framing, so a body is never mistaken
+/// for the next request, and the responses are sent in the order of the requests. The
+/// requests following one that closes the connection are discarded:
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::TcpStream,
+///     sync::{Arc, RwLock},
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     router::Router,
+///     Server,
+/// };
+///
+/// fn echo(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(format!("[{} {}]", request.path, request.body.unwrap_or_default()).into());
+///
+///     Ok(response)
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/first", http::Version::V11, echo);
+/// router.add_route(http::Method::POST, "/second", http::Version::V11, echo);
+/// router.add_route(http::Method::GET, "/third", http::Version::V11, echo);
+/// router.add_route(http::Method::GET, "/fourth", http::Version::V11, echo);
+///
+/// let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+///
+/// // All in a single write, the body of the second request looks like a request
+/// let mut stream = TcpStream::connect(server.addr()?)?;
+/// stream.write_all(
+///     b"GET /first HTTP/1.1\r\n\r\n\
+///       POST /second HTTP/1.1\r\nContent-Length: 22\r\n\r\nGET /fourth HTTP/1.1\r\n\
+///       GET /third HTTP/1.1\r\nConnection: close\r\n\r\n\
+///       GET /fourth HTTP/1.1\r\n\r\n",
+/// )?;
+///
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response)?;
+///
+/// assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 3);
+/// let first = response.find("[/first ]").unwrap();
+/// let second = response.find("[/second GET /fourth HTTP/1.1").unwrap();
+/// let third = response.find("[/third ]").unwrap();
+/// assert!(first < second && second < third);
+/// assert!(!response.contains("[/fourth"));
+///
+/// // A body whose length can't be told for sure ends the connection, instead of
+/// // being read as the next request
+/// for (lengths, status) in [
+///     ("Content-Length: abc\r\n", "400 Bad Request"),
+///     ("Content-Length: 5\r\nContent-Length: 7\r\n", "400 Bad Request"),
+///     ("Content-Length: +5\r\n", "400 Bad Request"),
+///     ("Content-Length : 5\r\n", "400 Bad Request"),
+///     // Chunked request bodies aren't supported
+///     ("Transfer-Encoding: chunked\r\n", "501 Not Implemented"),
+/// ] {
+///     let mut stream = TcpStream::connect(server.addr()?)?;
+///     write!(stream, "POST /second HTTP/1.1\r\n{}\r\n5\r\nGET /\r\n0\r\n\r\n", lengths)?;
+///
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response)?;
+///     assert!(response.starts_with(&format!("HTTP/1.1 {}", status)), "{}", lengths);
+///     assert!(response.contains("Connection: close"));
+///     assert_eq!(response.matches("HTTP/1.1").count(), 1);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
 pub struct Server {
-    listener: TcpListener,
+    listeners: Vec<Listener>,
     router: Arc<RwLock<Router>>,
     args: Arc<RwLock<Args>>,
+    config: Arc<ServerConfig>,
+    shutdown: Arc<AtomicBool>,
 }
 
-impl Server {
-    /// Creates a new `Server` instance bound to the specified address.
+/// A handle used to stop a running [`Server`] from another thread.
+///
+/// Handles are obtained through [`Server::shutdown_handle`] and can be cloned freely.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    shutdown: Arc<AtomicBool>,
+    endpoints: Vec<Endpoint>,
+}
+
+impl ShutdownHandle {
+    /// Asks the server to stop accepting new connections.
     ///
-    /// # Arguments
+    /// [`Server::start`] returns shortly after; connections already accepted are
+    /// still served to completion by the workers.
+    pub fn shutdown(&self) {
+        if self.shutdown.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        // Wake up the accept loops with connections of our own
+        for endpoint in &self.endpoints {
+            if let Err(err) = endpoint.connect() {
+                log::warn!("Failed to wake up the server for shutdown: {}", err);
+            }
+        }
+    }
+
+    /// Returns `true` if a shutdown was requested.
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+}
+
+/// A server running on a thread of its own, started with [`Server::start_background`].
+///
+/// Dropping the handle shuts the server down and waits for it to stop, unless the
+/// handle was [detached](ServerHandle::detach).
+pub struct ServerHandle {
+    addrs: Vec<SocketAddr>,
+    shutdown: ShutdownHandle,
+    stats: Arc<Stats>,
+    thread: Option<thread::JoinHandle<Result<ShutdownReport, Error>>>,
+}
+
+impl ServerHandle {
+    /// Returns the address the server listens on, the first one if there are several.
     ///
-    /// * `addr` - The address to bind the server to.
-    /// * `router` - The router for handling HTTP requests.
-    /// * `args` - Arguments to be shared across handlers.
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the address, or an error if the server only
+    /// listens on a Unix socket.
+    pub fn addr(&self) -> anyhow::Result<SocketAddr> {
+        self.addrs
+            .first()
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("The server doesn't listen on a TCP address"))
+    }
+
+    /// Returns a snapshot of the activity of the server, while it runs.
+    pub fn stats(&self) -> stats::ServerStats {
+        self.stats.snapshot()
+    }
+
+    /// Asks the server to stop accepting new connections, without waiting for it.
+    pub fn shutdown(&self) {
+        self.shutdown.shutdown();
+    }
+
+    /// Waits for the server to stop, after a shutdown was requested.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing the `Server` instance or an error.
-    pub fn new(addr: &str, router: Router, args: Args) -> anyhow::Result<Self> {
-        let listener = TcpListener::bind(addr)?;
+    /// Returns a `Result` containing what happened to the connections still open when
+    /// the server stopped, or the error of [`Server::start`], or an error if the
+    /// server panicked.
+    pub fn join(mut self) -> Result<ShutdownReport, Error> {
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("The server panicked").into())),
+            None => Ok(ShutdownReport::default()),
+        }
+    }
+
+    /// Lets the server run once the handle is dropped, until the process exits.
+    pub fn detach(mut self) {
+        self.thread.take();
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+
+        self.shutdown.shutdown();
+
+        match thread.join() {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => log::error!("The server stopped with an error: {}", err),
+            Err(_) => log::error!("The server panicked"),
+        }
+    }
+}
+
+/// What the server does with a new connection when every worker is busy and the
+/// queue of waiting connections is full, or when the limit set with
+/// [`Server::set_max_connections`] is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueuePolicy {
+    /// Stop accepting connections until room frees up.
+    #[default]
+    Block,
+    /// Answer the connection immediately with `503 Service Unavailable` and a
+    /// `Retry-After` header.
+    Reject,
+}
+
+/// What the [connection filter](Server::set_connection_filter) of a server decided
+/// about a new connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionDecision {
+    /// Serve the connection.
+    Accept,
+    /// Close the connection right away, without answering.
+    Reject,
+    /// Answer the connection with a minimal response of this status, then close it.
+    /// TLS connections are closed without answering, since the response can't be
+    /// sent before a handshake.
+    RejectWith(http::StatusCode),
+}
+
+/// How a [`Server`] closes a connection once its last response is sent, see
+/// [`Server::set_lingering_close`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LingerConfig {
+    /// How many unread bytes from the client are discarded at most. Defaults to
+    /// 64 KiB.
+    pub max_bytes: usize,
+    /// How long the client has to stop sending. Defaults to 2 seconds.
+    pub timeout: Duration,
+}
+
+impl Default for LingerConfig {
+    fn default() -> Self {
+        LingerConfig {
+            max_bytes: 64 * 1024,
+            timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// The number of connections currently open on a [`Server`].
+///
+/// Counters are obtained through [`Server::connection_counter`] and can be cloned
+/// freely, for example to report the count as a metric while the server runs.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionCounter {
+    inner: Arc<(Mutex<OpenConnections>, Condvar)>,
+}
+
+/// The connections open on a server, kept so they can be closed on shutdown.
+#[derive(Debug, Default)]
+struct OpenConnections {
+    count: usize,
+    next_id: u64,
+    sockets: HashMap<u64, TrackedSocket>,
+    /// Set once the server is shutting down.
+    draining: bool,
+}
 
-        Ok(Server {
-            listener,
-            router: Arc::new(RwLock::new(router)),
-            args: Arc::new(RwLock::new(args)),
+/// The socket of an open connection.
+#[derive(Debug)]
+struct TrackedSocket {
+    socket: Socket,
+    /// Since when the connection waits for the next request of its client, if it
+    /// does.
+    idle_since: Option<Instant>,
+}
+
+impl ConnectionCounter {
+    /// Returns the number of connections accepted and not yet closed, including those
+    /// waiting for a free worker.
+    pub fn get(&self) -> usize {
+        self.lock().count
+    }
+
+    /// Locks the open connections, even if a thread panicked while holding them.
+    fn lock(&self) -> MutexGuard<'_, OpenConnections> {
+        self.inner.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Counts a new connection if fewer than `max` are open, waiting up to `timeout`
+    /// for one to close otherwise.
+    ///
+    /// # Returns
+    ///
+    /// Returns a guard releasing the connection when dropped, or `None` if the limit
+    /// is still reached.
+    fn acquire(&self, max: Option<usize>, timeout: Duration) -> Option<ConnectionGuard> {
+        let (open, closed) = &*self.inner;
+        let open = open.lock().unwrap_or_else(|err| err.into_inner());
+
+        let (mut open, _) = closed
+            .wait_timeout_while(open, timeout, |open| {
+                max.is_some_and(|max| open.count >= max)
+            })
+            .unwrap_or_else(|err| err.into_inner());
+
+        if max.is_some_and(|max| open.count >= max) {
+            return None;
+        }
+
+        open.count += 1;
+        open.next_id += 1;
+
+        Some(ConnectionGuard {
+            counter: self.clone(),
+            id: open.next_id,
+            permit: None,
         })
     }
 
-    /// Reads an HTTP request from the given TCP stream.
+    /// Waits up to `timeout` for every connection to close.
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `stream` - The TCP stream to read from.
+    /// Returns `true` if no connection is left open.
+    fn wait_idle(&self, timeout: Duration) -> bool {
+        let (open, closed) = &*self.inner;
+        let open = open.lock().unwrap_or_else(|err| err.into_inner());
+
+        let (open, _) = closed
+            .wait_timeout_while(open, timeout, |open| open.count > 0)
+            .unwrap_or_else(|err| err.into_inner());
+
+        open.count == 0
+    }
+
+    /// Returns `true` once the server is shutting down.
+    fn is_draining(&self) -> bool {
+        self.lock().draining
+    }
+
+    /// Closes the connections waiting for the next request of their client for
+    /// `timeout` or longer.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` containing the `HTTPRequest` or an error.
-    fn read_request(mut stream: &TcpStream) -> anyhow::Result<HTTPRequest> {
-        let mut buffer = [0; 4096];
-        let mut dim = 0;
+    /// Returns how many connections were closed.
+    fn reap(&self, timeout: Duration) -> usize {
+        let mut open = self.lock();
+        let mut reaped = 0;
 
-        loop {
-            let len = stream.read(&mut buffer)?;
+        for tracked in open.sockets.values_mut() {
+            if tracked
+                .idle_since
+                .is_some_and(|since| since.elapsed() >= timeout)
+            {
+                let _ = tracked.socket.shutdown(Shutdown::Both);
 
-            dim += len;
+                // Until the connection notices, it must not be counted again
+                tracked.idle_since = None;
+                reaped += 1;
+            }
+        }
 
-            if len < 4096 {
-                break;
+        reaped
+    }
+
+    /// Closes the open connections once the server stopped accepting new ones.
+    ///
+    /// Connections waiting for their next request are closed right away, the others
+    /// once their current request is answered. Those still open after `timeout` are
+    /// closed in the middle of their request.
+    ///
+    /// # Returns
+    ///
+    /// Returns what happened to the connections.
+    fn drain(&self, timeout: Duration) -> ShutdownReport {
+        let start = Instant::now();
+
+        {
+            let mut open = self.lock();
+            open.draining = true;
+
+            for tracked in open
+                .sockets
+                .values()
+                .filter(|tracked| tracked.idle_since.is_some())
+            {
+                let _ = tracked.socket.shutdown(Shutdown::Both);
+            }
+        }
+
+        let mut force_closed = 0;
+
+        if !self.wait_idle(timeout) {
+            let open = self.lock();
+
+            for tracked in open.sockets.values() {
+                let _ = tracked.socket.shutdown(Shutdown::Both);
             }
+
+            force_closed = open.sockets.len();
+            log::warn!("Drain timeout reached, closed {} connections", force_closed);
         }
 
-        let mut request: HTTPRequest = String::from_utf8_lossy(&buffer)[..dim]
-            .to_string()
-            .parse()?;
+        ShutdownReport {
+            force_closed,
+            drain_duration: start.elapsed(),
+        }
+    }
+}
 
-        request.addr = stream.peer_addr()?.ip();
+/// Keeps a connection counted until it is dropped, however the connection ends.
+struct ConnectionGuard {
+    counter: ConnectionCounter,
+    id: u64,
+    /// Releases the connection from the limiter of the server, if any.
+    permit: Option<Permit>,
+}
 
-        Ok(request)
+impl ConnectionGuard {
+    /// Keeps a handle on the socket of the connection, to close it on shutdown.
+    fn track(&self, socket: SockRef<'_>) {
+        match socket.try_clone() {
+            Ok(socket) => {
+                let tracked = TrackedSocket {
+                    socket,
+                    idle_since: None,
+                };
+                self.counter.lock().sockets.insert(self.id, tracked);
+            }
+            Err(err) => log::warn!("Failed to track connection for shutdown: {}", err),
+        }
     }
 
-    /// Writes an HTTP response to the given TCP stream.
+    /// Records whether the connection waits for the next request of its client, or
+    /// for it to stop sending once the last response is sent.
+    ///
+    /// # Returns
+    ///
+    /// Returns `false` if the connection should rather be closed because the server
+    /// is shutting down.
+    fn set_idle(&self, idle: bool) -> bool {
+        let mut open = self.counter.lock();
+
+        if idle && open.draining {
+            return false;
+        }
+
+        if let Some(tracked) = open.sockets.get_mut(&self.id) {
+            tracked.idle_since = idle.then(Instant::now);
+        }
+
+        true
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let (open, closed) = &*self.counter.inner;
+
+        let mut open = open.lock().unwrap_or_else(|err| err.into_inner());
+        open.count -= 1;
+        open.sockets.remove(&self.id);
+        drop(open);
+
+        closed.notify_all();
+    }
+}
+
+/// What happened to the connections still open when a [`Server`] stopped, returned
+/// by [`ServerHandle::join`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShutdownReport {
+    /// The connections closed in the middle of a request because they outlived the
+    /// [drain timeout](Server::set_drain_timeout).
+    pub force_closed: usize,
+    /// How long the server waited for open connections to finish.
+    pub drain_duration: Duration,
+}
+
+/// The settings shared with every connection handled by a `Server`, along with the
+/// counters they update.
+#[derive(Debug, Clone)]
+struct ServerConfig {
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    header_timeout: Option<Duration>,
+    body_timeout: Option<Duration>,
+    keep_alive_idle_timeout: Option<Duration>,
+    handler_timeout: Option<Duration>,
+    tcp_nodelay: bool,
+    tcp_keepalive: Option<Duration>,
+    max_requests_per_connection: usize,
+    read_buffer_size: usize,
+    max_request_bytes: usize,
+    max_request_line_bytes: usize,
+    spool_threshold: Option<usize>,
+    spool_dir: PathBuf,
+    max_spooled_bytes: u64,
+    write_chunk_size: usize,
+    zero_copy: bool,
+    workers: usize,
+    thread_name: String,
+    thread_stack_size: Option<usize>,
+    queue_depth: usize,
+    queue_policy: QueuePolicy,
+    max_connections: Option<usize>,
+    connection_filter: Option<ConnectionFilterFunction>,
+    ip_filter: Option<IpFilter>,
+    limiter: Option<SharedLimiter>,
+    limit_policy: LimitPolicy,
+    drain_timeout: Duration,
+    linger: Option<LingerConfig>,
+    accept_backoff: Duration,
+    error_handler: Option<ErrorHandlerFunction>,
+    error_pages: ErrorPages,
+    connection_error_hook: Option<ConnectionErrorHookFunction>,
+    maintenance: MaintenanceHandle,
+    health: Health,
+    access_log: bool,
+    access_log_formatter: Option<AccessLogFormatter>,
+    #[cfg(feature = "compression")]
+    compression: Option<compression::CompressionConfig>,
+    #[cfg(feature = "json")]
+    problem_details: bool,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+    stats: Arc<Stats>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            read_timeout: Some(Duration::from_secs(30)),
+            write_timeout: Some(Duration::from_secs(30)),
+            header_timeout: Some(Duration::from_secs(10)),
+            body_timeout: Some(Duration::from_secs(60)),
+            keep_alive_idle_timeout: Some(Duration::from_secs(5)),
+            handler_timeout: None,
+            tcp_nodelay: true,
+            tcp_keepalive: None,
+            max_requests_per_connection: 100,
+            read_buffer_size: 4096,
+            max_request_bytes: 16 * 1024 * 1024,
+            max_request_line_bytes: 8 * 1024,
+            spool_threshold: None,
+            spool_dir: std::env::temp_dir(),
+            max_spooled_bytes: 1024 * 1024 * 1024,
+            write_chunk_size: 4096,
+            zero_copy: true,
+            workers: thread::available_parallelism().map_or(4, |n| n.get()),
+            thread_name: "fobserver".to_string(),
+            thread_stack_size: None,
+            queue_depth: 1024,
+            queue_policy: QueuePolicy::Block,
+            max_connections: None,
+            connection_filter: None,
+            ip_filter: None,
+            limiter: None,
+            limit_policy: LimitPolicy::Reject,
+            drain_timeout: Duration::from_secs(30),
+            linger: Some(LingerConfig::default()),
+            accept_backoff: Duration::from_millis(100),
+            error_handler: None,
+            error_pages: ErrorPages::default(),
+            connection_error_hook: None,
+            maintenance: MaintenanceHandle::new(),
+            health: Health::default(),
+            access_log: true,
+            access_log_formatter: None,
+            #[cfg(feature = "compression")]
+            compression: None,
+            #[cfg(feature = "json")]
+            problem_details: false,
+            #[cfg(feature = "tls")]
+            tls: None,
+            stats: Arc::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Prepares a thread of the server, named after its `role` and the prefix set
+    /// with [`Server::set_thread_name`].
+    fn thread(&self, role: &str) -> thread::Builder {
+        let name = match role {
+            "" => self.thread_name.clone(),
+            role => format!("{}-{}", self.thread_name, role),
+        };
+        let builder = thread::Builder::new().name(name);
+
+        match self.thread_stack_size {
+            Some(size) => builder.stack_size(size),
+            None => builder,
+        }
+    }
+
+    /// Creates the response sent for an error detected by the server itself rather
+    /// than by a handler, in the format the request prefers, see [`ErrorPages`].
     ///
     /// # Arguments
     ///
-    /// * `stream` - The TCP stream to write the response to.
-    /// * `response` - The `HTTPResponse` to be sent.
+    /// * `status_code` - The status code of the error.
+    /// * `request` - The request answered, `None` if it couldn't be read.
+    fn error_response(
+        &self,
+        status_code: http::StatusCode,
+        request: Option<&HTTPRequest>,
+    ) -> HTTPResponse {
+        #[cfg(feature = "json")]
+        let problem_details = self.problem_details;
+        #[cfg(not(feature = "json"))]
+        let problem_details = false;
+
+        self.error_pages
+            .response(status_code, request, problem_details)
+    }
+
+    /// Creates the response sent when the handler of `request` failed with `err`.
+    ///
+    /// Uses the error handler if one is set, falling back to a bare
+    /// `500 Internal Server Error` if it panics.
+    fn handler_error_response(&self, err: &anyhow::Error, request: &HTTPRequest) -> HTTPResponse {
+        span::event!(
+            error,
+            "Handler for {} {} failed: {:#}",
+            request.method,
+            request.path,
+            err
+        );
+
+        let handler = match self.error_handler {
+            Some(handler) => handler,
+            None => return self.error_response(http::StatusCode::CODE500, Some(request)),
+        };
+
+        match panic::catch_unwind(AssertUnwindSafe(|| handler(err, request))) {
+            Ok(response) => response,
+            Err(_) => {
+                log::error!("The error handler panicked");
+
+                HTTPResponse::plain_text(
+                    http::StatusCode::CODE500,
+                    http::StatusCode::CODE500.reason(),
+                )
+            }
+        }
+    }
+
+    /// Answers a connection whose request can't be served, before closing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection to answer on.
+    /// * `status_code` - The status explaining why the request was refused.
+    /// * `retry_after` - How long the client should wait before trying again, if it
+    ///   can.
+    /// * `addr` - The IP address of the client, if any.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` indicating success or failure.
-    fn write_response(mut stream: &TcpStream, mut response: HTTPResponse) -> anyhow::Result<()> {
-        response
-            .headers
-            .insert("Transfer-Encoding".to_string(), "chunked".to_string());
+    /// Returns a `Result` indicating whether the response could be sent.
+    fn refuse<W: SendFile>(
+        &self,
+        stream: W,
+        status_code: http::StatusCode,
+        retry_after: Option<Duration>,
+        addr: Option<IpAddr>,
+    ) -> anyhow::Result<()> {
+        let mut response = self.error_response(status_code, None);
+        response.headers.set("Connection", "close");
 
-        // response
-        // .headers
-        // .insert("Keep-Alive".to_string(), "true".to_string());
+        if let Some(retry_after) = retry_after {
+            response
+                .headers
+                .set("Retry-After", &retry_after_seconds(retry_after));
+        }
 
-        let body = response.body.clone();
+        let mut stream = CountingWriter::new(stream);
+        // The request wasn't read, frame the body so that any client can read it
+        let result = Server::write_response(
+            &mut stream,
+            response,
+            false,
+            http::Version::V10,
+            self.write_chunk_size,
+            false,
+        );
+        self.stats.written(stream.count);
 
-        stream.write_all(format!("{}\n\n", response.to_string()).as_bytes())?;
+        let size = result?;
+        self.stats.response(status_code);
+        self.log_access(AccessLogEntry {
+            time: SystemTime::now(),
+            addr,
+            method: None,
+            path: None,
+            version: None,
+            status_code,
+            size,
+            duration: Duration::ZERO,
+        });
 
-        if let Some(bytes) = body {
-            let mut start = 0;
+        Ok(())
+    }
 
-            while start < bytes.len() {
-                let len = min(4096, bytes.len() - start);
+    /// Asks the connection filter of the server, if any, about a new connection from
+    /// `addr`. Connections on Unix sockets are always accepted.
+    ///
+    /// # Returns
+    ///
+    /// Returns the decision of the filter, `Reject` if it panicked.
+    fn decide(&self, addr: Option<SocketAddr>) -> ConnectionDecision {
+        let (Some(filter), Some(addr)) = (self.connection_filter, addr) else {
+            return ConnectionDecision::Accept;
+        };
 
-                stream.write_all(format!("{:X}\r\n", len).as_bytes())?;
-                stream.write_all(&bytes[start..start + len])?;
-                stream.write_all(b"\r\n")?;
+        let decision = match panic::catch_unwind(|| filter(&addr)) {
+            Ok(decision) => decision,
+            Err(_) => {
+                log::error!("The connection filter panicked on {}", addr);
 
-                start += len;
+                ConnectionDecision::Reject
             }
+        };
+
+        if decision != ConnectionDecision::Accept {
+            log::info!("Connection from {} rejected by the connection filter", addr);
         }
 
-        stream.write_all(b"0\r\n\r\n")?;
+        decision
+    }
 
-        Ok(())
+    /// Checks a new connection from `addr` against the IP filter of the server, if any.
+    ///
+    /// # Returns
+    ///
+    /// Returns the filter if it refuses the connection, `None` otherwise.
+    fn filter(&self, addr: Option<IpAddr>) -> Option<&IpFilter> {
+        let (filter, ip) = self.ip_filter.as_ref().zip(addr)?;
+
+        if filter.allows(ip) {
+            return None;
+        }
+
+        log::info!("Connection from {} filtered", ip);
+
+        Some(filter)
     }
 
-    /// Starts the server, listening for incoming requests.
+    /// Asks the limiter of the server, if any, about a new connection from `addr`.
     ///
     /// # Returns
     ///
-    /// Returns a `Result` indicating success or failure.
-    pub fn start(&mut self) -> anyhow::Result<()> {
-        log::info!("Server started on {}", self.listener.local_addr()?);
+    /// Returns the permit of the connection, `None` if it isn't limited, or how long
+    /// the client should wait if the connection is refused.
+    fn admit(&self, addr: Option<IpAddr>) -> Result<Option<Permit>, Duration> {
+        match (&self.limiter, addr) {
+            (Some(limiter), Some(ip)) => limiter.admit(ip).map(Some),
+            _ => Ok(None),
+        }
+    }
 
-        for stream in self.listener.incoming() {
-            let router = self.router.clone();
-            let args = self.args.clone();
+    /// Logs `entry` in the access log, if enabled.
+    fn log_access(&self, entry: AccessLogEntry) {
+        if !self.access_log || !log::log_enabled!(target: access_log::TARGET, log::Level::Info) {
+            return;
+        }
 
-            match stream {
-                Ok(stream) => {
-                    thread::spawn(move || -> anyhow::Result<()> {
-                        // Read request
-                        let request = Server::read_request(&stream)?;
-                        // Find path
-                        let response = match router.read() {
-                            Ok(router) => match router.route(&request) {
-                                Some(function) => function(request, args)?,
-                                None => {
-                                    return Err(anyhow::anyhow!(
-                                        "Error: No associated functions to request -> {:#?}",
-                                        request
-                                    ))
-                                }
-                            },
-                            Err(err) => return Err(anyhow::anyhow!("Error: {}", err)),
-                        };
-                        // Send response and close connection
-                        Server::write_response(&stream, response)
-                    });
-                }
-                Err(err) => return Err(anyhow::anyhow!("Error: {}", err)),
-            }
+        if entry
+            .path
+            .as_deref()
+            .is_some_and(|path| self.health.is_unlogged(path))
+        {
+            return;
         }
 
-        Ok(())
+        let line = match self.access_log_formatter {
+            Some(formatter) => formatter(&entry),
+            None => entry.to_string(),
+        };
+
+        log::info!(target: access_log::TARGET, "{}", line);
+    }
+
+    /// Prepares an accepted connection, performing the TLS handshake if the server
+    /// uses TLS.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the connection, or an error if the handshake failed.
+    fn accept(&self, stream: Connection) -> anyhow::Result<Connection> {
+        #[cfg(feature = "tls")]
+        let stream = match (&self.tls, stream) {
+            (Some(tls), Connection::Plain(stream)) => return tls::accept(tls, stream),
+            (_, stream) => stream,
+        };
+
+        Ok(stream)
+    }
+
+    /// Decides whether the connection stays open once `response` answers `request`.
+    ///
+    /// The request must allow it, see [`HTTPRequest::is_keep_alive`], and so must the
+    /// response: handlers close the connection by setting `Connection: close`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request being answered.
+    /// * `response` - The response of its handler.
+    /// * `served` - How many requests the connection sent, this one included.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the client may send another request on the connection. A
+    /// connection only closed because it reached its maximum number of requests is
+    /// counted in the statistics.
+    fn keep_alive(&self, request: &HTTPRequest, response: &HTTPResponse, served: usize) -> bool {
+        let closed_by_handler = response
+            .headers
+            .get_all("Connection")
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case("close"));
+
+        // HTTP/1.0 clients see the end of a body of unknown length when the
+        // connection closes
+        let delimited_by_close = request.version == http::Version::V10
+            && request.method != http::Method::HEAD
+            && !response.headers.contains("Content-Length")
+            && matches!(response.body, Some(Body::EventStream(_) | Body::Stream(_)));
+
+        let persistent = request.is_keep_alive()
+            // A body without a Content-Length can't be told apart from the next request
+            && !request.headers.contains("Transfer-Encoding")
+            && !delimited_by_close
+            && !closed_by_handler
+            // The server may have started shutting down while the handler ran
+            && !self.stats.connections.is_draining();
+
+        if persistent && served >= self.max_requests_per_connection {
+            self.stats.keep_alive_exhausted();
+
+            return false;
+        }
+
+        persistent
+    }
+
+    /// Returns the `Keep-Alive` header telling the client how long the connection
+    /// may stay idle and how many more requests it may carry, after `served` ones.
+    fn keep_alive_header(&self, served: usize) -> String {
+        let max = self.max_requests_per_connection.saturating_sub(served);
+
+        match self.keep_alive_idle_timeout {
+            Some(timeout) => format!("timeout={}, max={}", timeout.as_secs(), max),
+            None => format!("max={}", max),
+        }
+    }
+
+    /// Records a client closing its connection while a response was written to it,
+    /// which is how browsers cancel page loads rather than a failure of the server.
+    fn client_disconnected(&self) {
+        log::debug!("Client disconnected during the response, closing connection");
+
+        self.stats.client_disconnected();
+    }
+
+    /// Ends a connection once its last response is written, without losing it to a
+    /// reset: shuts down writes, then discards what the client still sends, see
+    /// [`Server::set_lingering_close`].
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection.
+    /// * `guard` - Lets the connection be closed right away on shutdown, if the
+    ///   server accepted it.
+    fn close<S: Transport>(&self, stream: &mut S, guard: Option<&ConnectionGuard>) {
+        // The client may stop waiting as soon as the last byte is out
+        if let Err(err) = stream.flush().and_then(|()| stream.close_write()) {
+            log::debug!("Failed to close connection: {}", err);
+
+            return;
+        }
+
+        // Streams without a socket have no timeout to bound the wait
+        let Some(linger) = self.linger.filter(|_| stream.socket().is_some()) else {
+            return;
+        };
+
+        // Nothing is left to answer, shutdown doesn't wait for the client
+        if guard.is_some_and(|guard| !guard.set_idle(true)) {
+            return;
+        }
+
+        let deadline = Instant::now() + linger.timeout;
+        let mut discarded = 0;
+        let mut scratch = [0; 4096];
+
+        while discarded < linger.max_bytes {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || stream.set_read_timeout(Some(left)).is_err() {
+                break;
+            }
+
+            match stream.read(&mut scratch) {
+                Ok(0) | Err(_) => break,
+                Ok(len) => discarded += len,
+            }
+        }
+
+        if discarded > 0 {
+            log::debug!(
+                "Discarded {} bytes sent before the connection closed",
+                discarded
+            );
+        }
+    }
+
+    /// Reports an error that ended a connection to the hook, or logs it.
+    fn connection_failed(&self, error: ConnectionError) {
+        match self.connection_error_hook {
+            Some(hook) => hook(&error),
+            None => error.log(),
+        }
+    }
+
+    /// Decides whether an acceptor carries on after failing to accept a connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `err` - The error returned by the listener.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing how long to wait before accepting again, or the
+    /// error if the listener can't accept connections anymore.
+    fn accept_failed(&self, err: std::io::Error) -> Result<Duration, Error> {
+        match AcceptFailure::of(&err) {
+            AcceptFailure::Transient => {
+                log::debug!("Failed to accept connection: {}", err);
+
+                Ok(Duration::ZERO)
+            }
+            AcceptFailure::Exhausted => {
+                log::warn!(
+                    "Failed to accept connection, retrying in {:?}: {}",
+                    self.accept_backoff,
+                    err
+                );
+
+                Ok(self.accept_backoff)
+            }
+            AcceptFailure::Fatal => Err(Error::Accept(err)),
+        }
+    }
+
+    /// Returns how long the handler of `request` may run, if it is limited.
+    fn handler_timeout(&self, request: &HTTPRequest, router: &RwLock<Router>) -> Option<Duration> {
+        router
+            .read()
+            .ok()
+            .and_then(|router| router.timeout(request))
+            .or(self.handler_timeout)
+    }
+
+    /// Applies the configured options to the socket of an accepted connection.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating whether the options could be set.
+    fn configure_socket(&self, socket: SockRef<'_>) -> std::io::Result<()> {
+        socket.set_tcp_nodelay(self.tcp_nodelay)?;
+
+        if let Some(idle) = self.tcp_keepalive {
+            socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the configured content coding to `response`.
+    ///
+    /// # Arguments
+    ///
+    /// * `accept_encoding` - The `Accept-Encoding` header of the request, if any.
+    /// * `response` - The response to encode in place.
+    #[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+    fn encode(
+        &self,
+        accept_encoding: Option<&str>,
+        response: &mut HTTPResponse,
+    ) -> anyhow::Result<()> {
+        #[cfg(feature = "compression")]
+        if let Some(compression) = &self.compression {
+            compression.compress(accept_encoding, response)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Server {
+    /// Creates a new `Server` instance bound to the specified address.
+    ///
+    /// The server listens on every address `addr` resolves to, e.g. each element of a
+    /// slice of `SocketAddr`s, sharing the same router, arguments and workers.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address(es) to bind the server to.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Server` instance, or [`Error::Bind`] if an
+    /// address can't be bound.
+    ///
+    /// See [`Server::builder`] to configure the server before binding it.
+    pub fn new<A: ToSocketAddrs>(addr: A, router: Router, args: Args) -> Result<Self, Error> {
+        let listeners = Listener::bind_tcp(addr, true).map_err(Error::Bind)?;
+
+        Ok(Server::with_listeners(listeners, router, args))
+    }
+
+    /// Creates a new `Server` instance bound to the specified address, whose handlers
+    /// share a typed `state` instead of [`Args`].
+    ///
+    /// Routes added with [`Router::add_state_route`] receive the state as an `Arc<S>`,
+    /// with no lookup or downcast. The state must synchronize its own mutations, e.g.
+    /// with atomics or a `Mutex` around the fields that need one. Handlers added with
+    /// [`Router::add_route`] keep working, and receive arguments holding only what
+    /// the server registers itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address(es) to bind the server to.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `state` - The state shared across handlers.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Server` instance, or [`Error::Bind`] if an
+    /// address can't be bound.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{
+    ///         atomic::{AtomicUsize, Ordering},
+    ///         Arc,
+    ///     },
+    /// };
+    /// use fobserver::{
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// struct AppState {
+    ///     counter: AtomicUsize,
+    /// }
+    ///
+    /// fn handler(_: HTTPRequest, state: Arc<AppState>) -> anyhow::Result<HTTPResponse> {
+    ///     let value = state.counter.fetch_add(1, Ordering::SeqCst) + 1;
+    ///
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some(format!("Counter value: {}", value).into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_state_route(http::Method::GET, "/", http::Version::V11, handler);
+    ///
+    /// let state = AppState {
+    ///     counter: AtomicUsize::new(0),
+    /// };
+    /// let server = Server::new_with_state("127.0.0.1:0", router, state)?.start_background()?;
+    ///
+    /// for value in 1..=3 {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///     assert!(response.contains(&format!("Counter value: {}", value)));
+    /// }
+    ///
+    /// server.shutdown();
+    /// server.join()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn new_with_state<A: ToSocketAddrs, S: Send + Sync + 'static>(
+        addr: A,
+        router: Router,
+        state: S,
+    ) -> Result<Self, Error> {
+        let mut args = Args::new();
+        args.insert(router::STATE_ARG, Arc::new(state));
+
+        Server::new(addr, router, args)
+    }
+
+    /// Creates a new `Server` instance accepting connections on an already bound
+    /// listener, e.g. one inherited through [`systemd::listen_fds`].
+    ///
+    /// The listener is switched to blocking mode if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `listener` - The listener to accept connections on.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Server` instance or an error.
+    ///
+    /// # Example
+    ///
+    /// Binding port 0 lets the OS pick a free port, which tests can learn before the
+    /// server starts without racing other processes for it:
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{TcpListener, TcpStream},
+    ///     thread,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0")?;
+    /// let addr = listener.local_addr()?;
+    ///
+    /// let mut server = Server::from_listener(listener, Router::new(), Args::new())?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn from_listener(listener: TcpListener, router: Router, args: Args) -> Result<Self, Error> {
+        listener.set_nonblocking(false)?;
+
+        Ok(Server::with_listeners(
+            vec![Listener::Tcp(listener)],
+            router,
+            args,
+        ))
+    }
+
+    /// Creates a new `Server` instance bound to the specified address, serving a
+    /// router and arguments shared with other servers.
+    ///
+    /// Servers created over the same instances answer with the same routes and
+    /// handlers see the same arguments, e.g. a server listening for HTTP and another
+    /// for HTTPS. Routes added through the lock once the servers started are served
+    /// by all of them.
+    ///
+    /// Each request is dispatched while holding a read lock on the router, so taking
+    /// the write lock waits for the handlers running on every server to return, and
+    /// holds the next requests until it is released. A handler must never take it, it
+    /// would wait for itself. A thread panicking while holding a write lock poisons
+    /// it, and the servers answer every request with an error from then on.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address(es) to bind the server to.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Server` instance, or [`Error::Bind`] if an
+    /// address can't be bound.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn greet(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let greeting = args.read().unwrap().get_ref::<String>("greeting")?;
+    ///
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some(greeting.as_str().into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let router = Arc::new(RwLock::new(Router::new()));
+    /// let mut args = Args::new();
+    /// args.insert_immutable("greeting", "Hello!".to_string())?;
+    /// let args = Arc::new(RwLock::new(args));
+    ///
+    /// let servers = [
+    ///     Server::with_shared("127.0.0.1:0", router.clone(), args.clone())?.start_background()?,
+    ///     Server::with_shared("127.0.0.1:0", router.clone(), args.clone())?.start_background()?,
+    /// ];
+    /// assert_ne!(servers[0].addr()?, servers[1].addr()?);
+    ///
+    /// // Added once both servers run
+    /// router
+    ///     .write()
+    ///     .unwrap()
+    ///     .add_route(http::Method::GET, "/greet", http::Version::V11, greet);
+    ///
+    /// for server in &servers {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     stream.write_all(b"GET /greet HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///     assert!(response.starts_with("HTTP/1.1 200 OK"));
+    ///     assert!(response.contains("Hello!"));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_shared<A: ToSocketAddrs>(
+        addr: A,
+        router: Arc<RwLock<Router>>,
+        args: Arc<RwLock<Args>>,
+    ) -> Result<Self, Error> {
+        let listeners = Listener::bind_tcp(addr, true).map_err(Error::Bind)?;
+
+        Ok(Server::with_shared_listeners(listeners, router, args))
+    }
+
+    /// Creates one `Server` per socket passed to the process through systemd socket
+    /// activation, all sharing the same router and arguments.
+    ///
+    /// The sockets are taken as described in [`systemd::listen_fds`]. TCP sockets and
+    /// Unix sockets bound to a path are served, the socket files of the latter being
+    /// left in place when the servers stop since systemd owns them. Other passed file
+    /// descriptors are left untouched.
+    ///
+    /// Since systemd keeps the sockets open while the service restarts, connections
+    /// arriving in between wait in the kernel instead of being refused. A unit pair
+    /// like the following passes the sockets to the service:
+    ///
+    /// ```ini
+    /// # app.socket
+    /// [Socket]
+    /// ListenStream=8080
+    /// ListenStream=/run/app.sock
+    ///
+    /// [Install]
+    /// WantedBy=sockets.target
+    ///
+    /// # app.service
+    /// [Service]
+    /// ExecStart=/usr/local/bin/app
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the servers, none if the process wasn't socket
+    /// activated, or an error if the variables set by systemd are malformed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{TcpListener, TcpStream},
+    ///     os::{fd::IntoRawFd, unix::net::{UnixListener, UnixStream}},
+    ///     process,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// // Stand in for systemd, passing the sockets starting from descriptor 3
+    /// let tcp = TcpListener::bind("127.0.0.1:0")?;
+    /// let addr = tcp.local_addr()?;
+    /// let path = std::env::temp_dir().join("fobserver-from-systemd.sock");
+    /// let _ = std::fs::remove_file(&path);
+    /// let unix = UnixListener::bind(&path)?;
+    ///
+    /// let (tcp, unix) = (tcp.into_raw_fd(), unix.into_raw_fd());
+    /// assert_eq!((tcp, unix), (3, 4));
+    /// std::env::set_var("LISTEN_PID", process::id().to_string());
+    /// std::env::set_var("LISTEN_FDS", "2");
+    ///
+    /// let servers = Server::from_systemd(Router::new(), Args::new())?;
+    /// assert_eq!(servers.len(), 2);
+    /// assert!(std::env::var("LISTEN_FDS").is_err());
+    ///
+    /// let servers = servers
+    ///     .into_iter()
+    ///     .map(Server::start_background)
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(servers[0].addr()?, addr);
+    ///
+    /// let request = b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n";
+    /// let mut responses = [String::new(), String::new()];
+    ///
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(request)?;
+    /// stream.read_to_string(&mut responses[0])?;
+    ///
+    /// let mut stream = UnixStream::connect(&path)?;
+    /// stream.write_all(request)?;
+    /// stream.read_to_string(&mut responses[1])?;
+    ///
+    /// for response in responses {
+    ///     assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    /// }
+    ///
+    /// // The socket file belongs to systemd
+    /// drop(servers);
+    /// assert!(path.exists());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(unix)]
+    pub fn from_systemd(router: Router, args: Args) -> anyhow::Result<Vec<Server>> {
+        let router = Arc::new(RwLock::new(router));
+        let args = Arc::new(RwLock::new(args));
+
+        let servers = systemd::listeners()?
+            .into_iter()
+            .map(|listener| {
+                log::info!("Adopted socket {} from systemd", listener);
+
+                Server::with_shared_listeners(vec![listener], router.clone(), args.clone())
+            })
+            .collect();
+
+        Ok(servers)
+    }
+
+    /// Returns a builder configuring a `Server` before binding it.
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    /// Creates a `Server` with the default settings accepting connections on
+    /// `listeners`.
+    fn with_listeners(listeners: Vec<Listener>, router: Router, args: Args) -> Self {
+        Server::with_shared_listeners(
+            listeners,
+            Arc::new(RwLock::new(router)),
+            Arc::new(RwLock::new(args)),
+        )
+    }
+
+    /// Creates a `Server` with the default settings accepting connections on
+    /// `listeners`, serving a router and arguments other servers may share.
+    fn with_shared_listeners(
+        listeners: Vec<Listener>,
+        router: Arc<RwLock<Router>>,
+        args: Arc<RwLock<Args>>,
+    ) -> Self {
+        Server {
+            listeners,
+            router,
+            args,
+            config: Arc::new(ServerConfig::default()),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Creates a new `Server` instance listening on a Unix domain socket.
+    ///
+    /// A socket file already present at `path` (e.g. left behind by a crash) is
+    /// replaced, and the file is removed when the server stops. Requests received
+    /// over the socket have no client address.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the socket file.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Server` instance or an error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     os::unix::{fs::PermissionsExt, net::UnixStream},
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn handler(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     assert!(request.addr.is_none());
+    ///
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+    ///
+    /// let path = std::env::temp_dir().join("fobserver-new-unix.sock");
+    /// let mut server = Server::new_unix(&path, router, Args::new())?;
+    /// server.set_unix_permissions(0o660)?;
+    /// assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o777, 0o660);
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// let mut stream = UnixStream::connect(&path)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// assert!(!path.exists());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(unix)]
+    pub fn new_unix<P: AsRef<std::path::Path>>(
+        path: P,
+        router: Router,
+        args: Args,
+    ) -> anyhow::Result<Self> {
+        Ok(Server::with_listeners(
+            vec![Listener::bind_unix(path.as_ref())?],
+            router,
+            args,
+        ))
+    }
+
+    /// Sets the permissions of the socket file of a server created with
+    /// [`Server::new_unix`], which decide who can connect to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The Unix permission bits, e.g. `0o660`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a mutable reference to `self`, or an error if the
+    /// permissions can't be changed.
+    #[cfg(unix)]
+    pub fn set_unix_permissions(&mut self, mode: u32) -> anyhow::Result<&mut Self> {
+        for listener in &self.listeners {
+            listener.set_permissions(mode)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Creates a new `Server` instance bound to the specified address, serving HTTPS.
+    ///
+    /// Every accepted connection goes through a TLS handshake before its requests are
+    /// read; connections failing it are logged and closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to bind the server to.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    /// * `tls` - The certificate chain and private key of the server, and how clients
+    ///   authenticate.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Server` instance, or an error if the address
+    /// can't be bound or the certificate is invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{TcpListener, TcpStream},
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     tls::TlsConfig,
+    ///     Server,
+    /// };
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some("Hello over TLS!".into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+    /// let tls = TlsConfig {
+    ///     cert_chain_pem: certified.cert.pem().into_bytes(),
+    ///     private_key_pem: certified.key_pair.serialize_pem().into_bytes(),
+    ///     client_auth: None,
+    /// };
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+    ///
+    /// let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    /// let mut server = Server::new_tls(&addr.to_string(), router, Args::new(), tls)?;
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // A plain-text client fails the handshake without stopping the server
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let _ = stream.read_to_end(&mut Vec::new());
+    ///
+    /// let mut roots = rustls::RootCertStore::empty();
+    /// roots.add(certified.cert.der().clone())?;
+    /// let config = rustls::ClientConfig::builder()
+    ///     .with_root_certificates(roots)
+    ///     .with_no_client_auth();
+    /// let connection = rustls::ClientConnection::new(Arc::new(config), "localhost".try_into()?)?;
+    ///
+    /// let mut stream = rustls::StreamOwned::new(connection, TcpStream::connect(addr)?);
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(response.contains("Hello over TLS!"));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn new_tls<A: ToSocketAddrs>(
+        addr: A,
+        router: Router,
+        args: Args,
+        tls: tls::TlsConfig,
+    ) -> anyhow::Result<Self> {
+        let mut server = Server::new(addr, router, args)?;
+        Arc::make_mut(&mut server.config).tls = Some(tls.server_config()?);
+
+        Ok(server)
+    }
+
+    /// Returns a handle that can stop the server once it is started.
+    ///
+    /// # Returns
+    ///
+    /// A `ShutdownHandle` that can be sent to other threads.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    ///
+    /// thread::spawn(move || handle.shutdown()).join().unwrap();
+    ///
+    /// assert!(server.join().unwrap().is_ok());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            shutdown: self.shutdown.clone(),
+            endpoints: self
+                .listeners
+                .iter()
+                .filter_map(Listener::endpoint)
+                .collect(),
+        }
+    }
+
+    /// Puts the server in maintenance, or takes it out with `None`.
+    ///
+    /// In maintenance, every request is answered with `503 Service Unavailable`
+    /// without reaching its handler, except the ones to the paths allowlisted in
+    /// `config`. Use a [`MaintenanceHandle`] to switch while the server runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - How requests are answered during the maintenance, or `None` to
+    ///   route them normally.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_maintenance(&mut self, config: Option<MaintenanceConfig>) -> &mut Self {
+        self.config.maintenance.set(config);
+
+        self
+    }
+
+    /// Returns a handle switching the server in and out of maintenance while it runs.
+    ///
+    /// # Returns
+    ///
+    /// Returns the handle, shared with the server.
+    pub fn maintenance_handle(&self) -> MaintenanceHandle {
+        self.config.maintenance.clone()
+    }
+
+    /// Shares the maintenance mode of the server with `handle`, created beforehand,
+    /// e.g. to store it in the [`Args`] of an admin endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The handle switching the server in and out of maintenance.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{SocketAddr, TcpStream},
+    ///     sync::{Arc, RwLock},
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     maintenance::{MaintenanceConfig, MaintenanceHandle},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn hello(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// // An admin endpoint, which would be authenticated, switching maintenance on and off
+    /// fn maintenance(request: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let handle = args.read().unwrap().with("maintenance", MaintenanceHandle::clone)?;
+    ///
+    ///     handle.set(match request.method {
+    ///         http::Method::POST => Some(MaintenanceConfig {
+    ///             retry_after: Some(Duration::from_secs(120)),
+    ///             body: "Back soon".to_string(),
+    ///             allowlist_paths: vec!["/health".to_string(), "/admin/maintenance".to_string()],
+    ///         }),
+    ///         _ => None,
+    ///     });
+    ///
+    ///     Ok(HTTPResponse::no_content())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, hello);
+    /// router.add_route(http::Method::GET, "/health", http::Version::V11, hello);
+    /// router.add_route(http::Method::POST, "/admin/maintenance", http::Version::V11, maintenance);
+    /// router.add_route(http::Method::DELETE, "/admin/maintenance", http::Version::V11, maintenance);
+    ///
+    /// let handle = MaintenanceHandle::new();
+    /// let mut args = Args::new();
+    /// args.insert("maintenance", handle.clone());
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, args)?;
+    /// server.set_maintenance_handle(handle.clone());
+    /// let server = server.start_background()?;
+    ///
+    /// let send = |request: &str| -> anyhow::Result<String> {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     write!(stream, "{} HTTP/1.1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", request)?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     Ok(response)
+    /// };
+    ///
+    /// assert!(send("GET /")?.starts_with("HTTP/1.1 200 OK"));
+    ///
+    /// assert!(send("POST /admin/maintenance")?.starts_with("HTTP/1.1 204 No Content"));
+    /// assert!(handle.is_enabled());
+    ///
+    /// let response = send("GET /")?;
+    /// assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+    /// assert!(response.contains("Retry-After: 120"));
+    /// assert!(response.contains("Back soon"));
+    /// assert!(send("GET /health")?.starts_with("HTTP/1.1 200 OK"));
+    ///
+    /// assert!(send("DELETE /admin/maintenance")?.starts_with("HTTP/1.1 204 No Content"));
+    /// assert!(send("GET /")?.starts_with("HTTP/1.1 200 OK"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_maintenance_handle(&mut self, handle: MaintenanceHandle) -> &mut Self {
+        Arc::make_mut(&mut self.config).maintenance = handle;
+
+        self
+    }
+
+    /// Answers liveness and readiness checks, e.g. from a load balancer or an
+    /// orchestrator, on the paths of `config`.
+    ///
+    /// `GET` and `HEAD` requests to the liveness path are answered with `200 OK` as
+    /// long as the server runs. The ones to the readiness path run every
+    /// [readiness probe](Server::add_readiness_probe) concurrently, and are answered
+    /// with `200 OK` if they all pass, or `503 Service Unavailable` with a JSON body
+    /// listing the probes that failed or timed out. Health checks don't go through the
+    /// router, its middlewares or the maintenance mode, and are left out of the access
+    /// log unless [`HealthConfig::access_log`] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The paths of the endpoints and how long probes may take.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{
+    ///         atomic::{AtomicBool, Ordering},
+    ///         Arc,
+    ///     },
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{args::Args, health::HealthConfig, router::Router, Server};
+    ///
+    /// let disk_full = Arc::new(AtomicBool::new(false));
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server.enable_health_endpoints(HealthConfig {
+    ///     probe_timeout: Duration::from_millis(200),
+    ///     ..HealthConfig::default()
+    /// });
+    /// server.add_readiness_probe("database", |_| Ok(()));
+    /// let full = disk_full.clone();
+    /// server.add_readiness_probe("disk", move |_| match full.load(Ordering::SeqCst) {
+    ///     true => Err("No space left".to_string()),
+    ///     false => Ok(()),
+    /// });
+    /// let server = server.start_background()?;
+    ///
+    /// let get = |path: &str| -> anyhow::Result<String> {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path)?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     Ok(response)
+    /// };
+    ///
+    /// assert!(get("/healthz")?.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(get("/readyz")?.starts_with("HTTP/1.1 200 OK"));
+    ///
+    /// disk_full.store(true, Ordering::SeqCst);
+    /// let response = get("/readyz")?;
+    /// assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+    /// assert!(response.contains("Content-Type: application/json"));
+    /// assert!(response.ends_with(
+    ///     r#"{"status":"unavailable","failed":[{"name":"disk","error":"No space left"}]}"#
+    /// ));
+    ///
+    /// // Liveness doesn't depend on the probes
+    /// assert!(get("/healthz")?.starts_with("HTTP/1.1 200 OK"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn enable_health_endpoints(&mut self, config: HealthConfig) -> &mut Self {
+        Arc::make_mut(&mut self.config).health.enable(config);
+
+        self
+    }
+
+    /// Adds a probe the readiness endpoint consults, see
+    /// [`Server::enable_health_endpoints`].
+    ///
+    /// A probe that doesn't return within [`HealthConfig::probe_timeout`] counts as
+    /// failed; it keeps running on its own thread, so probes should have timeouts of
+    /// their own. It isn't run again until it returns: the checks made meanwhile wait
+    /// for the same run, so a hung probe holds a single thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the probe, reported when it fails.
+    /// * `probe` - Checks a dependency, returning why it isn't usable if it isn't. It
+    ///   receives the arguments shared with the handlers.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::atomic::{AtomicUsize, Ordering},
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{args::Args, health::HealthConfig, router::Router, Server};
+    ///
+    /// static RUNS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server
+    ///     .enable_health_endpoints(HealthConfig {
+    ///         probe_timeout: Duration::from_millis(100),
+    ///         ..HealthConfig::default()
+    ///     })
+    ///     .add_readiness_probe("cache", |_| {
+    ///         RUNS.fetch_add(1, Ordering::SeqCst);
+    ///         thread::sleep(Duration::from_secs(5));
+    ///         Ok(())
+    ///     });
+    /// let server = server.start_background()?;
+    ///
+    /// for _ in 0..3 {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     stream.write_all(b"GET /readyz HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+    ///     assert!(response.contains(r#"{"name":"cache","error":"Timed out"}"#));
+    /// }
+    ///
+    /// // The hung probe was only started once
+    /// assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn add_readiness_probe<F>(&mut self, name: &str, probe: F) -> &mut Self
+    where
+        F: Fn(&Arc<RwLock<Args>>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.config)
+            .health
+            .add_probe(name, Arc::new(probe));
+
+        self
+    }
+
+    /// Returns the address the server listens on.
+    ///
+    /// When the server listens on several addresses, this is the first one; see
+    /// [`Server::local_addrs`] for all of them.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the address, or an error if the server only
+    /// listens on a Unix socket.
+    ///
+    /// # Example
+    ///
+    /// Binding port 0 lets the OS pick a free port, which tests can then connect to:
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     thread,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// let addr = server.local_addr()?;
+    /// assert_ne!(addr.port(), 0);
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        self.local_addrs()
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("The server doesn't listen on a TCP address"))
+    }
+
+    /// Returns the addresses the server listens on.
+    ///
+    /// # Returns
+    ///
+    /// The local address of every TCP listener, in the order they were bound.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{SocketAddr, TcpStream},
+    ///     thread,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let addrs: [SocketAddr; 2] = ["127.0.0.1:0".parse()?, "127.0.0.1:0".parse()?];
+    /// let mut server = Server::new(&addrs[..], Router::new(), Args::new())?;
+    ///
+    /// let addrs = server.local_addrs();
+    /// assert_eq!(addrs.len(), 2);
+    /// assert_ne!(addrs[0].port(), addrs[1].port());
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// for addr in addrs {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///     assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    /// }
+    ///
+    /// // Stops every listener
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|listener| match listener {
+                Listener::Tcp(listener) => listener.local_addr().ok(),
+                #[cfg(unix)]
+                Listener::Unix { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Sets how long the server waits for a client to send data.
+    ///
+    /// A client that stays silent for longer is disconnected, or answered with
+    /// `408 Request Timeout` if it stopped in the middle of a request. Defaults to 30
+    /// seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The timeout, or `None` to wait indefinitely.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{Shutdown, TcpListener, TcpStream},
+    ///     thread,
+    ///     time::{Duration, Instant},
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    /// let mut server = Server::new(&addr.to_string(), Router::new(), Args::new())?;
+    /// server.set_read_timeout(Some(Duration::from_millis(200)));
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // A client stopping in the middle of its headers gets a 408
+    /// let start = Instant::now();
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 408 Request Timeout"));
+    /// drop(stream);
+    ///
+    /// // A body cut short by the client closing its side isn't taken as complete
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc")?;
+    /// stream.shutdown(Shutdown::Write)?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    ///
+    /// // A client that never sends anything is disconnected
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// let mut response = Vec::new();
+    /// stream.read_to_end(&mut response)?;
+    /// assert!(response.is_empty());
+    /// assert!(start.elapsed() < Duration::from_secs(2));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        Arc::make_mut(&mut self.config).read_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long the server waits for a client to accept more response data.
+    ///
+    /// A client that stops reading for longer is disconnected, freeing the worker
+    /// writing to it. This also ends event streams sent to stalled clients. Defaults
+    /// to 30 seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The timeout, or `None` to wait indefinitely.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{TcpListener, TcpStream},
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    ///     time::{Duration, Instant},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn large(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some(vec![0; 64 * 1024 * 1024].into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, large);
+    ///
+    /// let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    /// let mut server = Server::new(&addr.to_string(), router, Args::new())?;
+    /// server.set_workers(1).set_write_timeout(Some(Duration::from_millis(200)));
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // This client reads a few bytes, then stalls the only worker
+    /// let mut stalled = TcpStream::connect(addr)?;
+    /// stalled.write_all(b"GET / HTTP/1.1\r\n\r\n")?;
+    /// stalled.read_exact(&mut [0; 16])?;
+    ///
+    /// // The worker is freed once the write times out
+    /// let start = Instant::now();
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\n\r\n")?;
+    /// stream.read_exact(&mut [0; 16])?;
+    /// assert!(start.elapsed() < Duration::from_secs(5));
+    ///
+    /// drop((stalled, stream));
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        Arc::make_mut(&mut self.config).write_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long a client has to send the complete headers of a request, counted
+    /// from its first byte.
+    ///
+    /// Unlike the read timeout, which a client can reset by sending a byte at a time,
+    /// this bounds the request as a whole. A client exceeding it is answered with
+    /// `408 Request Timeout`. Defaults to 10 seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The timeout, or `None` to only rely on the read timeout.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     thread,
+    ///     time::{Duration, Instant},
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server.set_header_timeout(Some(Duration::from_millis(500)));
+    ///
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // A slow client finishing in time is served
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// for part in ["GET / HTTP/1.1\r\n", "Connection: close\r\n", "\r\n"] {
+    ///     stream.write_all(part.as_bytes())?;
+    ///     thread::sleep(Duration::from_millis(100));
+    /// }
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    ///
+    /// // A client trickling a header forever is cut off
+    /// let start = Instant::now();
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nX-Slow: ")?;
+    ///
+    /// let mut response = [0; 28];
+    /// loop {
+    ///     match stream.read(&mut response) {
+    ///         Ok(_) => break,
+    ///         Err(_) => stream.write_all(b"a")?,
+    ///     }
+    /// }
+    ///
+    /// assert!(response.starts_with(b"HTTP/1.1 408 Request Timeout"));
+    /// assert!(start.elapsed() >= Duration::from_millis(500));
+    /// assert!(start.elapsed() < Duration::from_secs(5));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_header_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        Arc::make_mut(&mut self.config).header_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long a client has to send the body of a request once its headers are
+    /// complete.
+    ///
+    /// A client exceeding it is answered with `408 Request Timeout`. Defaults to 60
+    /// seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The timeout, or `None` to only rely on the read timeout.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_body_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        Arc::make_mut(&mut self.config).body_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long a persistent connection may stay idle between two requests.
+    ///
+    /// Once a response is sent, a client keeping the connection open has this long to
+    /// start its next request before being disconnected. The timeout is advertised in
+    /// the `Keep-Alive` header of the response, in whole seconds, and connections
+    /// closed by it are counted in
+    /// [`ServerStats::idle_timeouts`](stats::ServerStats::idle_timeouts). Defaults to 5
+    /// seconds.
+    ///
+    /// A background thread also scans the idle connections periodically and closes
+    /// those left open past the timeout, counted in
+    /// [`ServerStats::idle_reaped`](stats::ServerStats::idle_reaped), so that none
+    /// lingers if its read timeout doesn't fire. It doesn't run when the timeout is
+    /// `None`.
+    ///
+    /// HTTP/1.1 connections are persistent unless the request or the response carries
+    /// `Connection: close`, HTTP/1.0 ones only if the request carries
+    /// `Connection: keep-alive`. Otherwise the response says `Connection: close` and
+    /// the connection is shut down right after it, without reading any further.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The timeout, or `None` to wait indefinitely.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     thread,
+    ///     time::{Duration, Instant},
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server.set_keep_alive_idle_timeout(Some(Duration::from_secs(1)));
+    ///
+    /// let server = server.start_background()?;
+    /// let addr = server.addr()?;
+    ///
+    /// for (version, connection, keep_alive) in [
+    ///     ("HTTP/1.1", "", true),
+    ///     ("HTTP/1.1", "Connection: close\r\n", false),
+    ///     ("HTTP/1.0", "", false),
+    ///     ("HTTP/1.0", "Connection: keep-alive\r\n", true),
+    /// ] {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     write!(stream, "GET / {}\r\nHost: localhost\r\n{}\r\n", version, connection)?;
+    ///
+    ///     // A persistent connection serves a second request, closing it this time
+    ///     if keep_alive {
+    ///         let close = "Host: localhost\r\nConnection: close\r\n";
+    ///         write!(stream, "GET / {}\r\n{}\r\n", version, close)?;
+    ///     }
+    ///
+    ///     // Either way the server ends the connection after its last response
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     let responses = response.matches("404 Not Found").count();
+    ///     assert_eq!(responses, if keep_alive { 2 } else { 1 });
+    ///     assert!(response.contains("Connection: close"));
+    ///     assert_eq!(
+    ///         response.contains("Connection: keep-alive"),
+    ///         keep_alive && version == "HTTP/1.0"
+    ///     );
+    /// }
+    ///
+    /// // A persistent connection left idle is closed by the server
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+    ///
+    /// let mut response = vec![0; 1024];
+    /// let read = stream.read(&mut response)?;
+    /// assert!(String::from_utf8_lossy(&response[..read]).contains("Keep-Alive: timeout=1"));
+    ///
+    /// let idle = Instant::now();
+    /// assert_eq!(stream.read(&mut response)?, 0);
+    /// assert!(idle.elapsed() >= Duration::from_millis(900));
+    ///
+    /// while server.stats().active_connections > 0 {
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    /// let stats = server.stats();
+    /// assert_eq!(stats.idle_timeouts + stats.idle_reaped, 1);
+    ///
+    /// server.shutdown();
+    /// server.join()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_keep_alive_idle_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        Arc::make_mut(&mut self.config).keep_alive_idle_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how long handlers may run before the client is answered with
+    /// `504 Gateway Timeout`. Disabled by default, routes can override it with
+    /// [`Route::timeout`](router::Route::timeout).
+    ///
+    /// A thread can't be interrupted, so a handler running out of time keeps running
+    /// to completion: its response is thrown away, and the connection is closed so
+    /// nothing it sends can reach the client. Until then it still holds its worker.
+    /// Over TLS, the connection is closed without an answer.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The timeout, or `None` to let handlers run indefinitely.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    ///     time::{Duration, Instant},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn slow(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     // A stuck database call
+    ///     thread::sleep(Duration::from_secs(1));
+    ///
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, slow);
+    /// router
+    ///     .add_route(http::Method::GET, "/report", http::Version::V11, slow)
+    ///     .timeout(Duration::from_secs(5));
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_handler_timeout(Some(Duration::from_millis(200)));
+    /// let server = server.start_background()?;
+    ///
+    /// let get = |path: &str| -> anyhow::Result<String> {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path)?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     Ok(response)
+    /// };
+    ///
+    /// let start = Instant::now();
+    /// assert!(get("/")?.starts_with("HTTP/1.1 504 Gateway Timeout"));
+    /// assert!(start.elapsed() < Duration::from_secs(1));
+    ///
+    /// // The report may take longer
+    /// assert!(get("/report")?.starts_with("HTTP/1.1 200 OK"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_handler_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        Arc::make_mut(&mut self.config).handler_timeout = timeout;
+
+        self
+    }
+
+    /// Sets whether `TCP_NODELAY` is set on accepted connections, sending small
+    /// responses right away instead of waiting to coalesce them with more data.
+    /// Enabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to disable Nagle's algorithm.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_tcp_nodelay(&mut self, enabled: bool) -> &mut Self {
+        Arc::make_mut(&mut self.config).tcp_nodelay = enabled;
+
+        self
+    }
+
+    /// Enables TCP keepalive on accepted connections, so that peers which vanished
+    /// without closing their connection are eventually detected. Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `idle` - How long a connection stays idle before the first probe is sent, at
+    ///   least one second, or `None` to leave keepalive disabled.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_tcp_keepalive(&mut self, idle: Option<Duration>) -> &mut Self {
+        Arc::make_mut(&mut self.config).tcp_keepalive =
+            idle.map(|idle| idle.max(Duration::from_secs(1)));
+
+        self
+    }
+
+    /// Sets how many requests can be served over a single connection.
+    ///
+    /// HTTP/1.1 connections are kept open between requests unless the client (or the
+    /// handler) asks for `Connection: close`; HTTP/1.0 clients must ask for
+    /// `Connection: keep-alive`. Every other response advertises how many requests
+    /// remain in a `Keep-Alive` header, along with the
+    /// [idle timeout](Server::set_keep_alive_idle_timeout), and the last one allowed on a
+    /// connection carries `Connection: close`. Connections closed that way are
+    /// counted in [`ServerStats::keep_alive_exhausted`](stats::ServerStats::keep_alive_exhausted).
+    /// Defaults to 100, and 1 disables persistent connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum number of requests, at least one.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some("Hello!".into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server
+    ///     .set_max_requests_per_connection(2)
+    ///     .set_keep_alive_idle_timeout(Some(Duration::from_secs(1)));
+    ///
+    /// let server = server.start_background()?;
+    /// let addr = server.addr()?;
+    ///
+    /// // Reads a single chunked response, leaving the connection open
+    /// let read_response = |stream: &mut TcpStream| -> anyhow::Result<String> {
+    ///     let mut response = Vec::new();
+    ///     while !response.ends_with(b"0\r\n\r\n") {
+    ///         let mut buffer = [0; 1024];
+    ///         let len = stream.read(&mut buffer)?;
+    ///         anyhow::ensure!(len > 0, "connection closed early");
+    ///
+    ///         response.extend_from_slice(&buffer[..len]);
+    ///     }
+    ///
+    ///     Ok(String::from_utf8(response)?)
+    /// };
+    ///
+    /// let mut stream = TcpStream::connect(addr)?;
+    ///
+    /// stream.write_all(b"GET / HTTP/1.1\r\n\r\n")?;
+    /// let response = read_response(&mut stream)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(!response.contains("Connection: close"));
+    /// assert!(response.contains("Keep-Alive: timeout=1, max=1"));
+    ///
+    /// // Same socket, second and last request
+    /// stream.write_all(b"GET / HTTP/1.1\r\n\r\n")?;
+    /// let response = read_response(&mut stream)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(response.contains("Connection: close"));
+    /// assert!(!response.contains("Keep-Alive"));
+    /// assert_eq!(stream.read(&mut [0; 16])?, 0);
+    ///
+    /// // Three pipelined requests, the connection is closed after the second answer
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\n\r\n".repeat(3).as_slice())?;
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 2);
+    /// let last = response.rfind("HTTP/1.1 200 OK").unwrap();
+    /// assert!(response[last..].contains("Connection: close"));
+    /// assert!(!response[..last].contains("Connection: close"));
+    ///
+    /// // An idle connection is closed once the idle timeout expires
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\n\r\n")?;
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.contains("Keep-Alive: timeout=1, max=1"));
+    ///
+    /// let stats = server.stats();
+    /// assert_eq!(stats.keep_alive_exhausted, 2);
+    /// assert_eq!(stats.idle_timeouts, 1);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_max_requests_per_connection(&mut self, max: usize) -> &mut Self {
+        Arc::make_mut(&mut self.config).max_requests_per_connection = max.max(1);
+
+        self
+    }
+
+    /// Sets the size of the buffer requests are read with. Defaults to 4 KiB.
+    ///
+    /// Read buffers are reused by the following requests handled on the same worker,
+    /// except the ones grown past 256 KiB, which are freed.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size in bytes, between 1 byte and 16 MiB.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn echo(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = request.body.map(Into::into);
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::POST, "/", http::Version::V11, echo);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_workers(1).set_read_buffer_size(1024);
+    /// let server = server.start_background()?;
+    ///
+    /// let post = |body: &str, close: bool| {
+    ///     let connection = if close { "Connection: close\r\n" } else { "" };
+    ///     format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n{}\r\n{}", body.len(), connection, body)
+    /// };
+    ///
+    /// // A request too large for its buffer to be kept, then smaller ones reusing theirs
+    /// let large = "z".repeat(300 * 1024);
+    /// for _ in 0..2 {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     let requests = [post(&large, false), post("first", false), post("second", true)];
+    ///     stream.write_all(requests.concat().as_bytes())?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///     assert_eq!(response.matches("HTTP/1.1 200 OK").count(), 3);
+    ///     assert_eq!(response.matches('z').count(), large.len());
+    ///     let first = response.find("\r\nfirst\r\n").unwrap();
+    ///     assert!(response[first..].contains("\r\nsecond\r\n"));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_read_buffer_size(&mut self, size: usize) -> &mut Self {
+        Arc::make_mut(&mut self.config).read_buffer_size = size.clamp(1, MAX_BUFFER_SIZE);
+
+        self
+    }
+
+    /// Sets the largest request, headers and body included, the server accepts.
+    ///
+    /// Larger requests are answered with `413 Content Too Large` before their body is
+    /// read. Defaults to 16 MiB.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum size in bytes, at least one.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server.set_max_request_bytes(1024);
+    /// let server = server.start_background()?;
+    ///
+    /// let send = |request: &str| -> anyhow::Result<String> {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     stream.write_all(request.as_bytes())?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///     Ok(response)
+    /// };
+    ///
+    /// // Refused from the headers alone, whatever the announced length
+    /// for length in [2048, u64::MAX] {
+    ///     let response = send(&format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", length))?;
+    ///     assert!(response.starts_with("HTTP/1.1 413 Content Too Large"));
+    /// }
+    ///
+    /// let response = send("POST / HTTP/1.1\r\nContent-Length: 3\r\nConnection: close\r\n\r\nabc")?;
+    /// assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_max_request_bytes(&mut self, max: usize) -> &mut Self {
+        Arc::make_mut(&mut self.config).max_request_bytes = max.max(1);
+
+        self
+    }
+
+    /// Sets the longest request line, i.e. the method, the target and the version,
+    /// the server accepts.
+    ///
+    /// Longer lines are answered with `414 URI Too Long` and the connection is closed,
+    /// as soon as the limit is exceeded: the rest of the line and the headers are
+    /// never buffered. The limit applies on top of
+    /// [`Server::set_max_request_bytes`]. Defaults to 8 KiB.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum length in bytes, without the line ending, at least one.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     thread,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server.set_max_request_line_bytes(1024);
+    /// let server = server.start_background()?;
+    ///
+    /// // Answered long before the megabyte is sent, which may fail once it is
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// let mut writer = stream.try_clone()?;
+    /// thread::spawn(move || {
+    ///     let path = "a".repeat(1024 * 1024);
+    ///     let _ = write!(writer, "GET /{} HTTP/1.1\r\n\r\n", path);
+    /// });
+    ///
+    /// let mut response = Vec::new();
+    /// let _ = stream.read_to_end(&mut response);
+    /// assert!(response.starts_with(b"HTTP/1.1 414 URI Too Long"));
+    ///
+    /// // Lines up to the limit are fine
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// let path = "a".repeat(1024 - "GET / HTTP/1.1".len());
+    /// write!(stream, "GET /{} HTTP/1.1\r\nConnection: close\r\n\r\n", path)?;
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_max_request_line_bytes(&mut self, max: usize) -> &mut Self {
+        Arc::make_mut(&mut self.config).max_request_line_bytes = max.max(1);
+
+        self
+    }
+
+    /// Sets the size above which request bodies are written to a temporary file as
+    /// they are received instead of being held in memory. Disabled by default.
+    ///
+    /// Handlers find spooled bodies in [`HTTPRequest::body_source`] rather than in
+    /// [`HTTPRequest::body`]. The file is deleted once the response is written, and a
+    /// failure to write it is answered with `500 Internal Server Error`. Spooled
+    /// bodies are limited by [`Server::set_max_spooled_bytes`] rather than by
+    /// [`Server::set_max_request_bytes`].
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The largest body in bytes kept in memory, or `None` to keep
+    ///   every body in memory.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     fs,
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     path::PathBuf,
+    ///     sync::{Arc, Mutex, RwLock},
+    ///     thread,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, BodySource, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// // The file of the last upload, its announced length and its size on disk
+    /// static UPLOAD: Mutex<Option<(PathBuf, u64, u64)>> = Mutex::new(None);
+    ///
+    /// fn upload(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     anyhow::ensure!(request.body.is_none(), "The body is in memory");
+    ///
+    ///     let Some(BodySource::File(path, len)) = request.body_source else {
+    ///         anyhow::bail!("The body wasn't spooled");
+    ///     };
+    ///     let size = fs::metadata(&path)?.len();
+    ///     *UPLOAD.lock().unwrap() = Some((path, len, size));
+    ///
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::POST, "/upload", http::Version::V11, upload);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_spool_threshold(Some(1024 * 1024)).set_max_spooled_bytes(64 * 1024 * 1024);
+    ///
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// let body = vec![b'x'; 20 * 1024 * 1024];
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// write!(
+    ///     stream,
+    ///     "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+    ///     body.len()
+    /// )?;
+    /// stream.write_all(&body)?;
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    ///
+    /// // The handler saw the whole body on disk, removed once answered
+    /// let (path, len, size) = UPLOAD.lock().unwrap().take().unwrap();
+    /// assert_eq!(len, body.len() as u64);
+    /// assert_eq!(size, body.len() as u64);
+    /// assert!(!path.exists());
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_spool_threshold(&mut self, threshold: Option<usize>) -> &mut Self {
+        Arc::make_mut(&mut self.config).spool_threshold = threshold;
+
+        self
+    }
+
+    /// Sets the directory spooled request bodies are written to. Defaults to the
+    /// temporary directory of the system.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory, which must exist and be writable.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_spool_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        Arc::make_mut(&mut self.config).spool_dir = dir.into();
+
+        self
+    }
+
+    /// Sets the largest body accepted when it is spooled to a file, see
+    /// [`Server::set_spool_threshold`].
+    ///
+    /// Larger requests are answered with `413 Content Too Large` before their body is
+    /// read. Defaults to 1 GiB.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum size in bytes, at least one.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_max_spooled_bytes(&mut self, max: u64) -> &mut Self {
+        Arc::make_mut(&mut self.config).max_spooled_bytes = max.max(1);
+
+        self
+    }
+
+    /// Sets the size of the pieces response bodies are sent in, which is also the
+    /// size of the chunks of responses using the `chunked` transfer encoding.
+    /// Defaults to 4 KiB.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size in bytes, between 1 byte and 16 MiB.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn echo(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = request.body.map(Into::into);
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::POST, "/", http::Version::V11, echo);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_read_buffer_size(7).set_write_chunk_size(5).set_max_request_bytes(1024);
+    ///
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// let send = |body: &str| -> anyhow::Result<String> {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     write!(
+    ///         stream,
+    ///         "POST / HTTP/1.1\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    ///         body.len(),
+    ///         body
+    ///     )?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     Ok(response)
+    /// };
+    ///
+    /// // The body comes back in 5-byte chunks
+    /// let response = send("Hello, small buffers!")?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(response.ends_with("\n5\r\nHello\r\n5\r\n, sma\r\n5\r\nll bu\r\n5\r\nffers\r\n1\r\n!\r\n0\r\n\r\n"));
+    ///
+    /// // Refused from its headers alone, before the body is sent
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"POST / HTTP/1.1\r\nContent-Length: 2048\r\n\r\n")?;
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 413 Content Too Large"));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_write_chunk_size(&mut self, size: usize) -> &mut Self {
+        Arc::make_mut(&mut self.config).write_chunk_size = size.clamp(1, MAX_BUFFER_SIZE);
+
+        self
+    }
+
+    /// Sets whether file bodies, such as those of [`files::serve_dir`] and
+    /// [`HTTPResponse::from_file`], are handed to the kernel with `sendfile(2)`
+    /// instead of being copied through userspace. Enabled by default.
+    ///
+    /// Only plain connections on 64-bit Linux support it, file bodies are copied
+    /// piece by piece everywhere else, with the same result for the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to send files without copying them.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     collections::hash_map::DefaultHasher,
+    ///     fs,
+    ///     hash::{Hash, Hasher},
+    ///     io::{Read, Write},
+    ///     net::SocketAddr,
+    ///     net::TcpStream,
+    ///     path::PathBuf,
+    ///     sync::{Arc, RwLock},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn path() -> PathBuf {
+    ///     std::env::temp_dir().join(format!("fobserver-zero-copy-{}.bin", std::process::id()))
+    /// }
+    ///
+    /// fn download(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     HTTPResponse::from_file(path())
+    /// }
+    ///
+    /// fn hash(data: &[u8]) -> u64 {
+    ///     let mut hasher = DefaultHasher::new();
+    ///     data.hash(&mut hasher);
+    ///     hasher.finish()
+    /// }
+    ///
+    /// fn fetch(addr: SocketAddr) -> anyhow::Result<Vec<u8>> {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     stream.write_all(b"GET /download HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")?;
+    ///
+    ///     let mut response = Vec::new();
+    ///     stream.read_to_end(&mut response)?;
+    ///     let body = response.windows(2).position(|w| w == b"\n\n").unwrap() + 2;
+    ///
+    ///     Ok(response.split_off(body))
+    /// }
+    ///
+    /// // 32 MiB that don't repeat every few bytes
+    /// let data = (0..32 * 1024 * 1024u32)
+    ///     .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+    ///     .collect::<Vec<u8>>();
+    /// fs::write(path(), &data)?;
+    ///
+    /// let mut servers = Vec::new();
+    /// for zero_copy in [true, false] {
+    ///     let mut router = Router::new();
+    ///     router.add_route(http::Method::GET, "/download", http::Version::V11, download);
+    ///
+    ///     let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    ///     server.set_zero_copy(zero_copy);
+    ///     servers.push(server.start_background()?);
+    /// }
+    ///
+    /// let zero_copy = fetch(servers[0].addr()?)?;
+    /// let copied = fetch(servers[1].addr()?)?;
+    ///
+    /// assert_eq!(zero_copy.len(), data.len());
+    /// assert_eq!(hash(&zero_copy), hash(&data));
+    /// assert_eq!(hash(&copied), hash(&zero_copy));
+    ///
+    /// // Both count the whole file as sent
+    /// assert_eq!(servers[0].stats().bytes_written, servers[1].stats().bytes_written);
+    /// # fs::remove_file(path())?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_zero_copy(&mut self, enabled: bool) -> &mut Self {
+        Arc::make_mut(&mut self.config).zero_copy = enabled;
+
+        self
+    }
+
+    /// Sets the number of worker threads handling connections.
+    ///
+    /// Defaults to the number of CPUs available.
+    ///
+    /// # Arguments
+    ///
+    /// * `workers` - The number of workers, at least one.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_workers(&mut self, workers: usize) -> &mut Self {
+        Arc::make_mut(&mut self.config).workers = workers.max(1);
+
+        self
+    }
+
+    /// Sets the prefix of the names of the threads the server spawns, so they can be
+    /// told apart in panic messages, debuggers and profilers. Defaults to
+    /// `fobserver`.
+    ///
+    /// Workers are named `<prefix>-worker-<n>`, the thread accepting connections on
+    /// each listener `<prefix>-acceptor-<n>`, and the thread of
+    /// [`Server::start_background`] is named after the prefix alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix of the names. Nul bytes are removed.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn whoami(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = thread::current().name().map(|name| name.to_string().into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, whoami);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_thread_name("api").set_thread_stack_size(Some(1024 * 1024));
+    /// let server = server.start_background()?;
+    ///
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.contains("api-worker-"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_thread_name(&mut self, prefix: impl Into<String>) -> &mut Self {
+        let mut prefix = prefix.into();
+        prefix.retain(|c| c != '\0');
+        Arc::make_mut(&mut self.config).thread_name = prefix;
+
+        self
+    }
+
+    /// Sets the stack size of the threads the server spawns, at least 64 KiB.
+    /// Defaults to the default of the standard library, 2 MiB unless overridden
+    /// with the `RUST_MIN_STACK` environment variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The stack size in bytes, or `None` for the default.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_thread_stack_size(&mut self, size: Option<usize>) -> &mut Self {
+        Arc::make_mut(&mut self.config).thread_stack_size =
+            size.map(|size| size.max(MIN_STACK_SIZE));
+
+        self
+    }
+
+    /// Sets how many accepted connections can wait for a free worker. Defaults to 1024.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The capacity of the queue.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_queue_depth(&mut self, depth: usize) -> &mut Self {
+        Arc::make_mut(&mut self.config).queue_depth = depth;
+
+        self
+    }
+
+    /// Sets what happens to new connections when the queue is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Whether to wait for room or to answer `503 Service Unavailable`.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_queue_policy(&mut self, policy: QueuePolicy) -> &mut Self {
+        Arc::make_mut(&mut self.config).queue_policy = policy;
+
+        self
+    }
+
+    /// Limits how many connections can be open at once, including those waiting for a
+    /// free worker.
+    ///
+    /// Connections beyond the limit are handled according to the
+    /// [`queue policy`](Server::set_queue_policy): they either wait in the listen backlog
+    /// until a connection closes, or are answered with `503 Service Unavailable`.
+    /// Unlimited by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum number of connections, at least one.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{args::Args, router::Router, QueuePolicy, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server
+    ///     .set_workers(4)
+    ///     .set_max_connections(2)
+    ///     .set_queue_policy(QueuePolicy::Reject);
+    ///
+    /// let addr = server.local_addr()?;
+    /// let counter = server.connection_counter();
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // Two connections take every slot...
+    /// let mut open = vec![TcpStream::connect(addr)?, TcpStream::connect(addr)?];
+    /// while counter.get() < 2 {
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    ///
+    /// // ...so the next three are turned away
+    /// let rejected = (0..3)
+    ///     .map(|_| TcpStream::connect(addr))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    ///
+    /// for mut stream in rejected {
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+    ///     assert!(response.contains("Retry-After: 1"));
+    /// }
+    ///
+    /// // The first two are still served
+    /// for stream in &mut open {
+    ///     stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    /// }
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_max_connections(&mut self, max: usize) -> &mut Self {
+        Arc::make_mut(&mut self.config).max_connections = Some(max.max(1));
+
+        self
+    }
+
+    /// Limits the connections each client can open, e.g. with an
+    /// [`IpRateLimiter`](limit::IpRateLimiter).
+    ///
+    /// The limiter is asked about every connection right after it is accepted, using
+    /// the IP address of the client, and refused connections are handled according
+    /// to the [limit policy](Server::set_limit_policy). Connections on Unix sockets are
+    /// never limited. No limit by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `limiter` - The limiter deciding which connections are served.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{args::Args, limit::IpRateLimiter, router::Router, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server.set_connection_limiter(IpRateLimiter::new(3, Duration::from_secs(60)));
+    ///
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // The first three connections of the minute are served...
+    /// for _ in 0..3 {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///     assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    /// }
+    ///
+    /// // ...and the next ones are told to come back later
+    /// for _ in 0..2 {
+    ///     let mut response = String::new();
+    ///     TcpStream::connect(addr)?.read_to_string(&mut response)?;
+    ///
+    ///     assert!(response.starts_with("HTTP/1.1 429 Too Many Requests"));
+    ///     assert!(response.contains("Retry-After: "));
+    /// }
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_connection_limiter<L: ConnectionLimiter + 'static>(
+        &mut self,
+        limiter: L,
+    ) -> &mut Self {
+        Arc::make_mut(&mut self.config).limiter = Some(SharedLimiter(Arc::new(limiter)));
+
+        self
+    }
+
+    /// Accepts connections only from the clients `filter` allows.
+    ///
+    /// Refused connections are closed right after being accepted, after answering
+    /// `403 Forbidden` if the filter asks for it. Connections on Unix sockets are never
+    /// filtered. To restrict some paths only, use the
+    /// [`ip_filter`](middleware::ip_filter) middleware instead. No filter by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The clients allowed to connect.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{io::Read, net::TcpStream, thread};
+    /// use fobserver::{args::Args, ip::IpFilter, router::Router, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server.set_ip_filter(IpFilter {
+    ///     allow: vec!["10.0.0.0/8".parse()?],
+    ///     forbidden_response: true,
+    ///     ..IpFilter::default()
+    /// });
+    ///
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // 127.0.0.1 isn't allowed
+    /// let mut response = String::new();
+    /// TcpStream::connect(addr)?.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_ip_filter(&mut self, filter: IpFilter) -> &mut Self {
+        Arc::make_mut(&mut self.config).ip_filter = Some(filter);
+
+        self
+    }
+
+    /// Sets a function deciding about every new connection right after it is
+    /// accepted, before anything is read from it. No filter by default.
+    ///
+    /// This is where policies the server doesn't provide plug in, e.g. an external
+    /// blocklist or a tarpit. The filter runs on the thread accepting connections,
+    /// before the [IP filter](Server::set_ip_filter) and the
+    /// [connection limiter](Server::set_connection_limiter), so it must be cheap. A
+    /// filter that panics rejects the connection, the server keeps accepting others.
+    /// Connections on Unix sockets aren't filtered.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Decides whether each connection is served, closed, or answered
+    ///   with an error.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::Read,
+    ///     net::{SocketAddr, TcpStream},
+    /// };
+    /// use fobserver::{
+    ///     args::Args, http::StatusCode, router::Router, ConnectionDecision,
+    ///     ConnectionFilterFunction, Server,
+    /// };
+    ///
+    /// fn silent(addr: &SocketAddr) -> ConnectionDecision {
+    ///     match addr.ip().is_loopback() {
+    ///         true => ConnectionDecision::Reject,
+    ///         false => ConnectionDecision::Accept,
+    ///     }
+    /// }
+    ///
+    /// fn forbidden(addr: &SocketAddr) -> ConnectionDecision {
+    ///     match addr.ip().is_loopback() {
+    ///         true => ConnectionDecision::RejectWith(StatusCode::CODE403),
+    ///         false => ConnectionDecision::Accept,
+    ///     }
+    /// }
+    ///
+    /// fn broken(_: &SocketAddr) -> ConnectionDecision {
+    ///     panic!("Lost the blocklist");
+    /// }
+    ///
+    /// let send = |filter: ConnectionFilterFunction| -> anyhow::Result<String> {
+    ///     let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    ///     server.set_connection_filter(filter);
+    ///     let server = server.start_background()?;
+    ///
+    ///     let mut responses = String::new();
+    ///     for _ in 0..2 {
+    ///         // The server doesn't wait for a request to decide
+    ///         TcpStream::connect(server.addr()?)?.read_to_string(&mut responses)?;
+    ///     }
+    ///     assert_eq!(server.stats().accepted_connections, 2);
+    ///
+    ///     Ok(responses)
+    /// };
+    ///
+    /// // Closed without a word
+    /// assert_eq!(send(silent)?, "");
+    ///
+    /// let responses = send(forbidden)?;
+    /// assert_eq!(responses.matches("HTTP/1.1 403 Forbidden").count(), 2);
+    /// assert!(responses.contains("Connection: close"));
+    /// assert!(responses.contains("Content-Length: 9"));
+    /// assert!(responses.contains("\n\nForbidden"));
+    ///
+    /// // Both connections are closed, the acceptor survives the panics
+    /// assert_eq!(send(broken)?, "");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_connection_filter(&mut self, filter: ConnectionFilterFunction) -> &mut Self {
+        Arc::make_mut(&mut self.config).connection_filter = Some(filter);
+
+        self
+    }
+
+    /// Sets what happens to the connections refused by the
+    /// [connection limiter](Server::set_connection_limiter). Defaults to
+    /// [`LimitPolicy::Reject`].
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Whether to answer `429 Too Many Requests` or to close the
+    ///   connection silently.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_limit_policy(&mut self, policy: LimitPolicy) -> &mut Self {
+        Arc::make_mut(&mut self.config).limit_policy = policy;
+
+        self
+    }
+
+    /// Sets how long the server waits for open connections to close once it stops
+    /// accepting new ones. Defaults to 30 seconds.
+    ///
+    /// On shutdown, persistent connections waiting for their next request are closed
+    /// right away, and the others once their current request is answered. The
+    /// connections still open after the timeout are closed in the middle of their
+    /// request, and counted in the [`ShutdownReport`] returned by
+    /// [`ServerHandle::join`].
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The longest time to wait.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{
+    ///         atomic::{AtomicUsize, Ordering},
+    ///         Arc, RwLock,
+    ///     },
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// static HANDLING: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// fn handle(delay: u64) -> anyhow::Result<HTTPResponse> {
+    ///     HANDLING.fetch_add(1, Ordering::SeqCst);
+    ///     thread::sleep(Duration::from_millis(delay));
+    ///
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// fn fast(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     handle(200)
+    /// }
+    ///
+    /// fn slow(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     handle(5000)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/fast", http::Version::V11, fast);
+    /// router.add_route(http::Method::GET, "/slow", http::Version::V11, slow);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_workers(4).set_drain_timeout(Duration::from_millis(500));
+    /// let server = server.start_background()?;
+    ///
+    /// // A persistent connection, idle once its first response is read
+    /// let mut idle = TcpStream::connect(server.addr()?)?;
+    /// idle.write_all(b"GET /fast HTTP/1.1\r\n\r\n")?;
+    /// let mut response = [0; 1024];
+    /// assert!(idle.read(&mut response)? > 0);
+    ///
+    /// let mut fast = TcpStream::connect(server.addr()?)?;
+    /// fast.write_all(b"GET /fast HTTP/1.1\r\n\r\n")?;
+    /// let mut slow = TcpStream::connect(server.addr()?)?;
+    /// slow.write_all(b"GET /slow HTTP/1.1\r\n\r\n")?;
+    ///
+    /// // Shut down while both requests are being handled
+    /// while HANDLING.load(Ordering::SeqCst) < 3 {
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    /// server.shutdown();
+    ///
+    /// // The idle connection is closed right away
+    /// assert_eq!(idle.read(&mut response)?, 0);
+    ///
+    /// // The fast request completes, on a connection closed afterwards
+    /// let mut response = String::new();
+    /// fast.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(response.contains("Connection: close"));
+    ///
+    /// // The slow one is cut off at the deadline
+    /// let mut response = String::new();
+    /// let _ = slow.read_to_string(&mut response);
+    /// assert!(response.is_empty());
+    ///
+    /// let report = server.join()?;
+    /// assert_eq!(report.force_closed, 1);
+    /// assert!(report.drain_duration < Duration::from_secs(2));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_drain_timeout(&mut self, timeout: Duration) -> &mut Self {
+        Arc::make_mut(&mut self.config).drain_timeout = timeout;
+
+        self
+    }
+
+    /// Sets how connections are closed once their last response is sent.
+    ///
+    /// The server always flushes the response and shuts down its side of the
+    /// connection first, so the client sees the end of the response. A client may
+    /// still be sending, e.g. the body of a request answered with
+    /// `413 Content Too Large` before it was read: closing the socket with unread
+    /// bytes would make the system reset the connection, and the client could lose
+    /// the response. So the server then reads and discards what the client sends,
+    /// until it stops, `max_bytes` were discarded or `timeout` expires, before closing.
+    /// Enabled with the [default settings](LingerConfig::default) by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `linger` - How much to discard and for how long, or `None` to close right
+    ///   after shutting down writes.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{Shutdown, TcpStream},
+    ///     sync::{Arc, RwLock},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     LingerConfig, Server,
+    /// };
+    ///
+    /// fn ignore(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::no_content())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::POST, "/upload", http::Version::V11, ignore);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_max_request_bytes(1024).set_lingering_close(Some(LingerConfig::default()));
+    /// let server = server.start_background()?;
+    ///
+    /// // The body is refused unread, the client finishes sending it without a reset
+    /// for _ in 0..20 {
+    ///     let mut stream = TcpStream::connect(server.addr()?)?;
+    ///     write!(stream, "POST /upload HTTP/1.1\r\nContent-Length: 32768\r\n\r\n")?;
+    ///
+    ///     let mut response = vec![0; 1024];
+    ///     let mut len = stream.read(&mut response)?;
+    ///
+    ///     stream.write_all(&[b'x'; 32768])?;
+    ///     stream.shutdown(Shutdown::Write)?;
+    ///     while let read @ 1.. = stream.read(&mut response[len..])? {
+    ///         len += read;
+    ///     }
+    ///
+    ///     let response = String::from_utf8_lossy(&response[..len]);
+    ///     assert!(response.starts_with("HTTP/1.1 413 Content Too Large"));
+    ///     assert!(response.contains("Connection: close"));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_lingering_close(&mut self, linger: Option<LingerConfig>) -> &mut Self {
+        Arc::make_mut(&mut self.config).linger = linger;
+
+        self
+    }
+
+    /// Sets how long the server waits before accepting connections again once the
+    /// process or the system ran out of file descriptors.
+    ///
+    /// Failing to accept a connection doesn't stop the server: connections aborted by
+    /// their client are skipped, and running out of file descriptors pauses accepting
+    /// for this long so that some can be released meanwhile. Only a listener that
+    /// can't accept connections anymore stops the server. Defaults to 100
+    /// milliseconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `backoff` - How long to wait.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     fs::File,
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", Router::new(), Args::new())?;
+    /// server.set_accept_backoff(Duration::from_millis(50));
+    /// let server = server.start_background()?;
+    ///
+    /// let get = |mut stream: TcpStream| -> anyhow::Result<String> {
+    ///     stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     Ok(response)
+    /// };
+    ///
+    /// // Run out of file descriptors, but for the one of a client
+    /// let path = std::env::current_exe()?;
+    /// let mut files = Vec::new();
+    /// while let Ok(file) = File::open(&path) {
+    ///     files.push(file);
+    /// }
+    /// files.pop();
+    ///
+    /// // The server can't accept connections for now, but keeps trying
+    /// let stream = TcpStream::connect(server.addr()?)?;
+    /// thread::sleep(Duration::from_millis(200));
+    /// drop(files);
+    ///
+    /// assert!(get(stream)?.starts_with("HTTP/1.1 404 Not Found"));
+    /// assert!(get(TcpStream::connect(server.addr()?)?)?.starts_with("HTTP/1.1 404 Not Found"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_accept_backoff(&mut self, backoff: Duration) -> &mut Self {
+        Arc::make_mut(&mut self.config).accept_backoff = backoff;
+
+        self
+    }
+
+    /// Returns a counter of the connections currently open on the server.
+    pub fn connection_counter(&self) -> ConnectionCounter {
+        self.config.stats.connections.clone()
+    }
+
+    /// Returns the number of connections currently open on the server, including those
+    /// waiting for a free worker.
+    pub fn active_connections(&self) -> usize {
+        self.config.stats.connections.get()
+    }
+
+    /// Returns the number of requests currently being handled, from the moment they
+    /// are read until their response is written.
+    ///
+    /// Once the server is started in the background, the same gauge is available in
+    /// the snapshots of [`ServerHandle::stats`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{mpsc, Arc, Mutex, OnceLock, RwLock},
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// static RELEASE: OnceLock<Mutex<mpsc::Receiver<()>>> = OnceLock::new();
+    ///
+    /// fn blocked(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     RELEASE.get().unwrap().lock().unwrap().recv()?;
+    ///
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let (release, receiver) = mpsc::channel();
+    /// RELEASE.get_or_init(|| Mutex::new(receiver));
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, blocked);
+    ///
+    /// let server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// assert_eq!(server.inflight_requests(), 0);
+    /// let server = server.start_background()?;
+    ///
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    /// while server.stats().inflight_requests == 0 {
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    /// let stats = server.stats();
+    /// assert_eq!((stats.active_connections, stats.inflight_requests), (1, 1));
+    ///
+    /// release.send(())?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert_eq!(server.stats().inflight_requests, 0);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn inflight_requests(&self) -> usize {
+        self.config.stats.inflight_requests()
+    }
+
+    /// Returns a snapshot of the activity of the server.
+    ///
+    /// The same snapshot can be served over HTTP while the server runs by mounting
+    /// [`stats::stats_handler`] on the router.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     stats, Server,
+    /// };
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+    /// router.add_route(http::Method::GET, "/stats", http::Version::V11, stats::stats_handler);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// let addr = server.local_addr()?;
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start().map(|()| server));
+    ///
+    /// let get = |path: &str, accept: &str| -> anyhow::Result<String> {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     write!(
+    ///         stream,
+    ///         "GET {} HTTP/1.1\r\nAccept: {}\r\nConnection: close\r\n\r\n",
+    ///         path, accept
+    ///     )?;
+    ///
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response)?;
+    ///
+    ///     Ok(response)
+    /// };
+    ///
+    /// get("/", "*/*")?;
+    /// get("/missing", "*/*")?;
+    ///
+    /// // The request for the statistics is counted, its response not yet
+    /// let response = get("/stats", "application/json")?;
+    /// assert!(response.contains(r#""requests":3,"responses":{"1xx":0,"2xx":1,"3xx":0,"4xx":1,"5xx":0}"#));
+    ///
+    /// let response = get("/stats", "text/plain")?;
+    /// assert!(response.contains("requests 4\nresponses_1xx 0\nresponses_2xx 2\n"));
+    ///
+    /// handle.shutdown();
+    /// let server = server.join().unwrap()?;
+    ///
+    /// let stats = server.stats();
+    /// assert_eq!(stats.accepted_connections, 4);
+    /// assert_eq!(stats.requests, 4);
+    /// assert_eq!(stats.responses, [0, 3, 0, 1, 0]);
+    /// assert!(stats.bytes_read > 0 && stats.bytes_written > 0);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn stats(&self) -> stats::ServerStats {
+        self.config.stats.snapshot()
+    }
+
+    /// Sets the upper bounds, in seconds, of the buckets of the latency histogram
+    /// served by [`metrics_handler`](metrics::metrics_handler). Bounds that aren't
+    /// finite are ignored, the `+Inf` bucket is always there. Defaults to
+    /// [`DEFAULT_BUCKETS`](metrics::DEFAULT_BUCKETS).
+    ///
+    /// The latencies observed so far are discarded, the buckets are meant to be set
+    /// before the server starts.
+    ///
+    /// # Arguments
+    ///
+    /// * `buckets` - The upper bounds of the buckets, in any order.
+    ///
+    /// # Returns
+    ///
+    /// Returns a mutable reference to `self` to allow for method chaining.
+    #[cfg(feature = "metrics")]
+    pub fn set_metrics_buckets(&mut self, buckets: &[f64]) -> &mut Self {
+        self.config.stats.metrics.set_buckets(buckets);
+
+        self
+    }
+
+    /// Marks the server as started and shares its statistics with the handlers.
+    fn register_stats(&self) -> anyhow::Result<()> {
+        self.config.stats.start();
+
+        self.args
+            .write()
+            .map_err(|err| anyhow::anyhow!("Error: {}", err))?
+            .insert(stats::ARG, self.config.stats.clone());
+
+        Ok(())
+    }
+
+    /// Sets the function building the response when a handler returns an error.
+    ///
+    /// The error is always logged. Without an error handler, the client receives a
+    /// `500 Internal Server Error` rendered by the [error pages](Server::set_error_pages);
+    /// a panicking error handler falls back to a plain one.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The error handler.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     router::Router,
+    ///     testing::TestServer,
+    ///     Server,
+    /// };
+    ///
+    /// fn failing(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     anyhow::bail!("database unreachable")
+    /// }
+    ///
+    /// fn error_handler(err: &anyhow::Error, request: &HTTPRequest) -> HTTPResponse {
+    ///     let mut response = HTTPResponse::internal_error();
+    ///     response.body = Some(format!("{} failed: {}", request.path, err).into());
+    ///
+    ///     response
+    /// }
+    ///
+    /// for custom in [false, true] {
+    ///     let mut router = Router::new();
+    ///     router.add_route(http::Method::GET, "/", http::Version::V11, failing);
+    ///
+    ///     let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    ///     if custom {
+    ///         server.set_error_handler(error_handler);
+    ///     }
+    ///
+    ///     let response = TestServer::from_server(&server).get("/")?;
+    ///     assert_eq!(response.status_code, StatusCode::CODE500);
+    ///
+    ///     let body = response.body.unwrap();
+    ///     let body = String::from_utf8_lossy(body.as_bytes().unwrap());
+    ///     assert_eq!(body.contains("/ failed: database unreachable"), custom);
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_error_handler(&mut self, handler: ErrorHandlerFunction) -> &mut Self {
+        Arc::make_mut(&mut self.config).error_handler = Some(handler);
+
+        self
+    }
+
+    /// Sets the templates of the bodies of the errors the server generates itself,
+    /// rendered as HTML, JSON or plain text depending on the `Accept` header of the
+    /// request.
+    ///
+    /// # Arguments
+    ///
+    /// * `pages` - The templates, see [`ErrorPages`].
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// See [`ErrorPages`] for an example.
+    pub fn set_error_pages(&mut self, pages: ErrorPages) -> &mut Self {
+        Arc::make_mut(&mut self.config).error_pages = pages;
+
+        self
+    }
+
+    /// Sets the function told about the errors ending connections, in place of
+    /// logging them with [`ConnectionError::log`].
+    ///
+    /// The hook runs on the thread that served the connection, once it failed to
+    /// read a request, to write a response or anything else. Errors of handlers are
+    /// not connection errors, they are answered by the
+    /// [error handler](Server::set_error_handler). Neither are clients closing the
+    /// connection while their response is written, which are only counted in
+    /// [`ServerStats::client_disconnects`](stats::ServerStats::client_disconnects).
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The function told about each error.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, Mutex, RwLock},
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     ConnectionError, ConnectionErrorKind, Server,
+    /// };
+    ///
+    /// static ERRORS: Mutex<Vec<(ConnectionErrorKind, String)>> = Mutex::new(Vec::new());
+    ///
+    /// fn hook(error: &ConnectionError) {
+    ///     ERRORS.lock().unwrap().push((error.kind, error.to_string()));
+    /// }
+    ///
+    /// fn large(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some(vec![b'a'; 64 * 1024 * 1024].into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/large", http::Version::V11, large);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// server.set_connection_error_hook(hook);
+    /// let server = server.start_background()?;
+    ///
+    /// // A malformed request is answered, then reported
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"NONSENSE\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    ///
+    /// // A client leaving in the middle of a response is only counted
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"GET /large HTTP/1.1\r\n\r\n")?;
+    /// stream.read_exact(&mut [0; 1024])?;
+    /// drop(stream);
+    ///
+    /// while server.stats().client_disconnects == 0 {
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    ///
+    /// let errors = ERRORS.lock().unwrap();
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(errors[0].0, ConnectionErrorKind::Read);
+    /// assert!(errors[0].1.starts_with("Connection from 127.0.0.1 failed: Failed to read request"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_connection_error_hook(&mut self, hook: ConnectionErrorHookFunction) -> &mut Self {
+        Arc::make_mut(&mut self.config).connection_error_hook = Some(hook);
+
+        self
+    }
+
+    /// Enables or disables the access log.
+    ///
+    /// When enabled, a line is logged at info level under the
+    /// [`access_log::TARGET`] target for every response sent, including the ones
+    /// generated by the server itself. Enabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to log responses.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{SocketAddr, TcpListener, TcpStream},
+    ///     sync::Mutex,
+    ///     thread,
+    /// };
+    /// use fobserver::{access_log::AccessLogEntry, args::Args, router::Router, Server};
+    ///
+    /// struct Capture(Mutex<Vec<String>>);
+    ///
+    /// impl log::Log for Capture {
+    ///     fn enabled(&self, metadata: &log::Metadata) -> bool {
+    ///         metadata.target() == fobserver::access_log::TARGET
+    ///     }
+    ///
+    ///     fn log(&self, record: &log::Record) {
+    ///         if self.enabled(record.metadata()) {
+    ///             self.0.lock().unwrap().push(record.args().to_string());
+    ///         }
+    ///     }
+    ///
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: Capture = Capture(Mutex::new(Vec::new()));
+    /// log::set_logger(&LOGGER).unwrap();
+    /// log::set_max_level(log::LevelFilter::Info);
+    ///
+    /// fn json(entry: &AccessLogEntry) -> String {
+    ///     format!("{{\"status\":{},\"size\":{}}}", entry.status_code.code(), entry.size)
+    /// }
+    ///
+    /// let fetch = |addr: SocketAddr| -> anyhow::Result<()> {
+    ///     let mut stream = TcpStream::connect(addr)?;
+    ///     stream.write_all(b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///     stream.read_to_end(&mut Vec::new())?;
+    ///
+    ///     Ok(())
+    /// };
+    ///
+    /// let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    /// let mut server = Server::new(&addr.to_string(), Router::new(), Args::new())?;
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    /// fetch(addr)?;
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    ///
+    /// let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    /// let mut server = Server::new(&addr.to_string(), Router::new(), Args::new())?;
+    /// server.set_access_log_formatter(json);
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    /// fetch(addr)?;
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    ///
+    /// let lines = LOGGER.0.lock().unwrap();
+    /// assert_eq!(lines.len(), 2);
+    /// assert!(lines[0].starts_with("127.0.0.1 ["));
+    /// assert!(lines[0].contains("] \"GET /missing HTTP/1.1\" 404 9 "));
+    /// assert_eq!(lines[1], r#"{"status":404,"size":9}"#);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn set_access_log(&mut self, enabled: bool) -> &mut Self {
+        Arc::make_mut(&mut self.config).access_log = enabled;
+
+        self
+    }
+
+    /// Sets the function formatting the lines of the access log.
+    ///
+    /// Defaults to the [`Display`](std::fmt::Display) implementation of
+    /// [`AccessLogEntry`].
+    ///
+    /// # Arguments
+    ///
+    /// * `formatter` - The formatter.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_access_log_formatter(&mut self, formatter: AccessLogFormatter) -> &mut Self {
+        Arc::make_mut(&mut self.config).access_log_formatter = Some(formatter);
+
+        self
+    }
+
+    /// Enables gzip compression of the responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Which responses are compressed, and how much.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, config: compression::CompressionConfig) -> &mut Self {
+        Arc::make_mut(&mut self.config).compression = Some(config);
+
+        self
+    }
+
+    /// Renders the errors generated by the server itself (e.g. a response refused
+    /// because of invalid headers) as `application/problem+json` documents when the
+    /// client's `Accept` header prefers JSON over text, unless a JSON template is set
+    /// with [`Server::set_error_pages`].
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to send problem documents.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to `self` to allow for method chaining.
+    #[cfg(feature = "json")]
+    pub fn set_problem_details(&mut self, enabled: bool) -> &mut Self {
+        Arc::make_mut(&mut self.config).problem_details = enabled;
+
+        self
+    }
+
+    /// Reads the next HTTP request from the given TCP stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The TCP stream to read from.
+    /// * `buffer` - The bytes received on the connection but not consumed yet. Bytes
+    ///   following the request are left in it for the next call.
+    /// * `idle` - How long to wait for the request to start.
+    /// * `config` - The settings of the server.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the parsed `HTTPRequest`, `None` if the client
+    /// closed the connection or went idle before starting one, or an error, which is a
+    /// [`RequestTooLarge`] if the request exceeds the configured size, a
+    /// [`RequestLineTooLong`] if its request line does, or a
+    /// [`SpoolFailed`] if its body can't be written to disk.
+    fn read_request<S: Transport>(
+        stream: &mut S,
+        buffer: &mut Vec<u8>,
+        idle: Option<Duration>,
+        config: &ServerConfig,
+    ) -> anyhow::Result<Option<HTTPRequest>> {
+        let mut chunk = Buffer::zeroed(config.read_buffer_size);
+        let mut parser = RequestParser::new(buffer, config)?;
+
+        while let Some(timeout) = parser.read_timeout(buffer, idle, config)? {
+            stream.set_read_timeout(timeout)?;
+
+            let len = match stream.read(&mut chunk) {
+                Ok(len) => len,
+                // The client went idle without starting a request
+                Err(err) if buffer.is_empty() && is_timeout(&err) => {
+                    config.stats.idle_timeout();
+
+                    return Ok(None);
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            if len == 0 {
+                break;
+            }
+
+            parser.feed(buffer, &chunk[..len], config)?;
+        }
+
+        let mut request = match parser.finish(buffer)? {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+
+        request.addr = stream.peer_ip()?;
+        request.tls = stream.is_tls();
+        #[cfg(feature = "tls")]
+        {
+            request.peer_certificate = stream.peer_certificate();
+        }
+
+        Ok(Some(request))
+    }
+
+    /// Writes an HTTP response to the given TCP stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The TCP stream to write the response to.
+    /// * `response` - The `HTTPResponse` to be sent.
+    /// * `head` - Whether the response answers a `HEAD` request, and must be sent
+    ///   without its body.
+    /// * `version` - The version of the request. HTTP/1.0 clients can't decode the
+    ///   `chunked` transfer encoding: bodies in memory get a `Content-Length`, the
+    ///   others end when the connection is closed.
+    /// * `chunk_size` - The size of the pieces the body is sent in.
+    /// * `zero_copy` - Whether file bodies may be sent without copying them through
+    ///   userspace, when the stream supports it.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the number of body bytes sent.
+    fn write_response<W: SendFile>(
+        stream: W,
+        mut response: HTTPResponse,
+        head: bool,
+        version: http::Version,
+        chunk_size: usize,
+        zero_copy: bool,
+    ) -> anyhow::Result<u64> {
+        if !response.headers.contains("Date") {
+            response
+                .headers
+                .set("Date", &http::date::format(SystemTime::now()));
+        }
+
+        if let Some(Body::EventStream(_)) = response.body {
+            response
+                .headers
+                .set("Content-Type", "text/event-stream")
+                .set("Cache-Control", "no-cache");
+        }
+
+        // 204 and 304 responses never have a body, not even an empty chunked one, and
+        // neither do answers to HEAD requests
+        let bodiless = head
+            || matches!(
+                response.status_code,
+                http::StatusCode::CODE204 | http::StatusCode::CODE304
+            );
+        if bodiless {
+            response.body = None;
+        }
+
+        let len = match &response.body {
+            Some(Body::File { len, .. }) => Some(*len),
+            Some(Body::Bytes(bytes)) if version == http::Version::V10 => Some(bytes.len() as u64),
+            _ => None,
+        };
+        if let Some(len) = len {
+            if !response.headers.contains("Content-Length") {
+                response.headers.set("Content-Length", &len.to_string());
+            }
+        }
+
+        // Bodies whose length is already known by the handler are sent as-is
+        let chunked = !bodiless
+            && version != http::Version::V10
+            && !response.headers.contains("Content-Length");
+
+        if chunked {
+            response.headers.set("Transfer-Encoding", "chunked");
+        }
+
+        // Take ownership of the body so the payload is never duplicated
+        let body = response.body.take();
+
+        // Coalesce the head and the chunk framing into as few writes as possible
+        let mut stream = BufferedWriter::with_capacity(WRITE_BUFFER_SIZE, stream);
+
+        response.write_head(&mut stream)?;
+
+        let mut size = 0;
+
+        match body {
+            Some(Body::Bytes(bytes)) => {
+                let mut start = 0;
+
+                while start < bytes.len() {
+                    let len = min(chunk_size, bytes.len() - start);
+
+                    size += Server::write_data(&mut stream, &bytes[start..start + len], chunked)?;
+
+                    start += len;
+                }
+            }
+            Some(Body::EventStream(receiver)) => {
+                // Blocks until every sender has been dropped
+                for event in receiver {
+                    size += Server::write_data(&mut stream, event.to_string().as_bytes(), chunked)?;
+                    stream.flush()?;
+                }
+            }
+            Some(Body::File {
+                mut file,
+                offset,
+                len,
+            }) => {
+                // Handed to the kernel when nothing has to be added around the data
+                let sent = match zero_copy && !chunked {
+                    true => {
+                        stream.flush()?;
+                        stream.get_mut().send_file(&file, offset, len)?
+                    }
+                    false => None,
+                };
+
+                size += match sent {
+                    Some(sent) => sent,
+                    None => {
+                        file.seek(SeekFrom::Start(offset))?;
+
+                        Server::write_reader(&mut stream, file.take(len), chunk_size, chunked)?
+                    }
+                };
+            }
+            Some(Body::Stream(reader)) => {
+                size += Server::write_reader(&mut stream, reader, chunk_size, chunked)?;
+            }
+            // Upgrades are sent like answers to HEAD requests, without a body
+            Some(Body::Upgrade(_)) | None => {}
+        }
+
+        if chunked {
+            stream.write_all(b"0\r\n\r\n")?;
+        }
+
+        stream.flush()?;
+
+        Ok(size)
+    }
+
+    /// Writes a response body read from `reader` until its end, one piece at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The buffered stream to write the data to.
+    /// * `reader` - The source of the body.
+    /// * `chunk_size` - The size of the pieces the body is sent in.
+    /// * `chunked` - Whether the body uses the `chunked` transfer encoding.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the number of bytes written.
+    fn write_reader<W: Write, R: Read>(
+        stream: &mut W,
+        mut reader: R,
+        chunk_size: usize,
+        chunked: bool,
+    ) -> anyhow::Result<u64> {
+        let mut buffer = Buffer::zeroed(chunk_size);
+        let mut size = 0;
+
+        loop {
+            let read = reader.read(&mut buffer)?;
+
+            if read == 0 {
+                return Ok(size);
+            }
+
+            size += Server::write_data(stream, &buffer[..read], chunked)?;
+        }
+    }
+
+    /// Writes a piece of the response body, framing it as a chunk if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The buffered stream to write the data to.
+    /// * `data` - The data to write, which must not be empty when `chunked`.
+    /// * `chunked` - Whether the body uses the `chunked` transfer encoding.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the length of `data`.
+    fn write_data<W: Write>(stream: &mut W, data: &[u8], chunked: bool) -> anyhow::Result<u64> {
+        if chunked {
+            write!(stream, "{:X}\r\n", data.len())?;
+            stream.write_all(data)?;
+            stream.write_all(b"\r\n")?;
+        } else {
+            stream.write_all(data)?;
+        }
+
+        Ok(data.len() as u64)
+    }
+
+    /// Handles a single accepted connection: sets it up, then serves it until it is not
+    /// persistent anymore.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The accepted connection.
+    /// * `guard` - Keeps the connection counted, and tells when it's idle.
+    /// * `watchdog` - Answers the requests whose handler runs for too long.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    /// * `config` - The settings of the server.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating success or failure.
+    fn handle_connection(
+        stream: Connection,
+        guard: &ConnectionGuard,
+        watchdog: &Watchdog,
+        router: &RwLock<Router>,
+        args: Arc<RwLock<Args>>,
+        config: &ServerConfig,
+    ) -> anyhow::Result<()> {
+        stream.set_read_timeout(config.read_timeout)?;
+        stream.set_write_timeout(config.write_timeout)?;
+
+        if let Some(socket) = stream.tcp_socket() {
+            config.configure_socket(socket)?;
+        }
+
+        let mut stream = match config.accept(stream) {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Handshake failed: {}", err);
+
+                return Ok(());
+            }
+        };
+
+        match Server::serve(
+            &mut stream,
+            Some(guard),
+            Some(watchdog),
+            router,
+            args,
+            config,
+        )? {
+            Some(upgrade) => Server::upgrade(stream, upgrade),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads each request of a connection, dispatches it and writes the response, until
+    /// the connection is not persistent anymore.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection.
+    /// * `guard` - Keeps the connection counted and tells when it's idle, if the
+    ///   server accepted it.
+    /// * `watchdog` - Answers the requests whose handler runs for too long, if any.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    /// * `config` - The settings of the server.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the callback of the response that upgraded the
+    /// connection, if any.
+    fn serve<S: Transport>(
+        stream: &mut S,
+        guard: Option<&ConnectionGuard>,
+        watchdog: Option<&Watchdog>,
+        router: &RwLock<Router>,
+        args: Arc<RwLock<Args>>,
+        config: &ServerConfig,
+    ) -> anyhow::Result<Option<UpgradeFunction>> {
+        let addr = stream.peer_ip().ok().flatten();
+        let mut buffer = Buffer::with_capacity(config.read_buffer_size);
+        let mut served = 0;
+
+        loop {
+            // Between two requests the client only has the idle timeout to send more
+            let idle = match served {
+                0 => config.read_timeout,
+                _ => config.keep_alive_idle_timeout,
+            };
+
+            // A persistent connection waiting for more can be closed on shutdown
+            let waiting = served > 0 && buffer.is_empty();
+            if waiting && guard.is_some_and(|guard| !guard.set_idle(true)) {
+                return Ok(None);
+            }
+
+            // Read request
+            let request = match Server::read_request(stream, &mut buffer, idle, config) {
+                Ok(Some(request)) => request,
+                Ok(None) => return Ok(None),
+                Err(err) => {
+                    // The connection itself failed, nobody is left to answer
+                    if let Some(status_code) = read_error_status(&err) {
+                        config
+                            .refuse(&mut *stream, status_code, None, addr)
+                            .context(ConnectionErrorKind::Write)?;
+                        config.close(stream, guard);
+                    }
+
+                    return Err(err.context(ConnectionErrorKind::Read));
+                }
+            };
+            served += 1;
+
+            if let Some(guard) = guard.filter(|_| waiting) {
+                guard.set_idle(false);
+            }
+
+            // Counted until the response is written, even if the handler panics
+            let inflight = config.stats.inflight();
+
+            // Handlers run on this thread, their events land in the request span
+            let request_span = span::Span::request(&request);
+            let _request = request_span.enter();
+
+            if let Some(hints) = Server::early_hints(&request, router) {
+                match stream.write_all(&hints).and_then(|()| stream.flush()) {
+                    Err(err) if is_disconnect(&err) => {
+                        config.client_disconnected();
+
+                        return Ok(None);
+                    }
+                    result => result.context(ConnectionErrorKind::Write)?,
+                }
+                config.stats.written(hints.len() as u64);
+            }
+
+            let deadline = config
+                .handler_timeout(&request, router)
+                .and_then(|timeout| {
+                    watchdog?.watch(stream.socket()?, timeout, addr, !stream.is_tls())
+                });
+
+            let exchange = Server::respond(request, router, args.clone(), config, served)?;
+            let keep_alive = exchange.keep_alive;
+            let (status_code, duration) = (exchange.response.status_code, exchange.duration);
+
+            // The watchdog answered in place of the handler and closed the connection
+            if deadline.is_some_and(|deadline| !deadline.finish()) {
+                return Ok(None);
+            }
+
+            // Send response
+            let upgrade = match exchange.send(&mut *stream, config) {
+                Err(err) if err.downcast_ref::<std::io::Error>().is_some_and(is_timeout) => {
+                    log::warn!("Client stopped reading the response, closing connection");
+
+                    return Ok(None);
+                }
+                // Dropping the body on the way out also stops its producer, e.g. the
+                // senders of an event stream
+                Err(err)
+                    if err
+                        .downcast_ref::<std::io::Error>()
+                        .is_some_and(is_disconnect) =>
+                {
+                    config.client_disconnected();
+
+                    return Ok(None);
+                }
+                result => result.context(ConnectionErrorKind::Write)?,
+            };
+            drop(inflight);
+            request_span.record_response(status_code, duration);
+
+            if let Some(upgrade) = upgrade {
+                // The callback reads from the socket, what was received past the
+                // request is lost to it
+                if !buffer.is_empty() {
+                    log::warn!(
+                        "Dropping {} bytes received before the upgrade",
+                        buffer.len()
+                    );
+                }
+
+                return Ok(Some(upgrade));
+            }
+
+            if !keep_alive {
+                // Requests pipelined after the last one are never answered
+                if !buffer.is_empty() {
+                    log::debug!(
+                        "Discarding {} bytes received after the last request",
+                        buffer.len()
+                    );
+                }
+
+                config.close(stream, guard);
+
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Returns the `103 Early Hints` interim response to send before handling
+    /// `request`, if its route has hints, see [`Route::early_hints`](router::Route::early_hints).
+    fn early_hints(request: &HTTPRequest, router: &RwLock<Router>) -> Option<Buffer> {
+        // Interim responses only exist since HTTP/1.1
+        if request.version != http::Version::V11 {
+            return None;
+        }
+
+        let hints = router.read().ok()?.early_hints(request)?;
+
+        let mut head = Buffer::with_capacity(256);
+        // Writing to a vector can't fail
+        let _ = hints.write_head(&mut *head);
+
+        Some(head)
+    }
+
+    /// Hands a connection to the callback of the response that upgraded it.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection.
+    /// * `upgrade` - The callback taking over the connection.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating whether the connection could be handed over.
+    fn upgrade(stream: Connection, upgrade: UpgradeFunction) -> anyhow::Result<()> {
+        let Some(stream) = stream.into_tcp() else {
+            log::warn!("Only plain TCP connections can be upgraded, closing connection");
+
+            return Ok(());
+        };
+
+        stream.set_read_timeout(None)?;
+        stream.set_write_timeout(None)?;
+        upgrade(stream);
+
+        Ok(())
+    }
+
+    /// Dispatches a request to its handler and prepares the response.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to answer.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    /// * `config` - The settings of the server.
+    /// * `served` - How many requests the connection sent, this one included.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the response ready to be sent, or an error if the
+    /// router is unavailable or the response can't be encoded.
+    fn respond(
+        request: HTTPRequest,
+        router: &RwLock<Router>,
+        args: Arc<RwLock<Args>>,
+        config: &ServerConfig,
+        served: usize,
+    ) -> anyhow::Result<Exchange> {
+        let time = SystemTime::now();
+        let start = Instant::now();
+        let spooled = TempFile::of(&request);
+
+        config.stats.request();
+
+        #[cfg(feature = "metrics")]
+        let route = router
+            .read()
+            .ok()
+            .and_then(|router| router.pattern(&request));
+
+        let (head, mut response) = Server::dispatch(request, router, args, config)?;
+
+        let upgrade = matches!(response.body, Some(Body::Upgrade(_)));
+
+        let keep_alive = !upgrade && config.keep_alive(&head, &response, served);
+        if upgrade {
+            // The handler chose the Connection header of the new protocol
+        } else if !keep_alive {
+            response.headers.set("Connection", "close");
+        } else {
+            if head.version == http::Version::V10 {
+                response.headers.set("Connection", "keep-alive");
+            }
+            if !response.headers.contains("Keep-Alive") {
+                response
+                    .headers
+                    .set("Keep-Alive", &config.keep_alive_header(served));
+            }
+        }
+
+        Ok(Exchange {
+            head,
+            response,
+            keep_alive,
+            time,
+            duration: start.elapsed(),
+            #[cfg(feature = "metrics")]
+            route,
+            _spooled: spooled,
+        })
+    }
+
+    /// Runs a request through the router and turns the outcome into a response,
+    /// whether the handler succeeds, fails or panics.
+    ///
+    /// This is the whole handling of a request short of the connection, shared with
+    /// [`TestServer`](testing::TestServer).
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to answer.
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    /// * `config` - The settings of the server.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the request without its body and the encoded
+    /// response, or an error if the router is unavailable or the response can't be
+    /// encoded.
+    fn dispatch(
+        request: HTTPRequest,
+        router: &RwLock<Router>,
+        args: Arc<RwLock<Args>>,
+        config: &ServerConfig,
+    ) -> anyhow::Result<(HTTPRequest, HTTPResponse)> {
+        // The body is moved into the handler, keep the rest for later
+        let head = HTTPRequest {
+            path: request.path.clone(),
+            headers: request.headers.clone(),
+            body: None,
+            body_source: request.body_source.clone(),
+            extensions: http::Extensions::new(),
+            #[cfg(feature = "tls")]
+            peer_certificate: request.peer_certificate.clone(),
+            ..request
+        };
+        // Find path, a panicking handler is answered like a failing one. Health checks
+        // are answered even in maintenance
+        let answered = config
+            .health
+            .response(&head, &args, || config.thread("probe"))
+            .or_else(|| config.maintenance.response(&head));
+        let mut response = match (router.read(), answered) {
+            // A client really speaking HTTP/2 or HTTP/3 wouldn't get this far, one
+            // only claiming to can't be answered in its version
+            (Ok(_), _) if matches!(head.version, http::Version::V20 | http::Version::V30) => {
+                let mut response = config.error_response(http::StatusCode::CODE505, Some(&head));
+                response.headers.set("Connection", "close");
+
+                response
+            }
+            (Ok(_), Some(response)) => response,
+            (Ok(router), None) => {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    router.dispatch_with(request, args, &|status_code, request| {
+                        config.error_response(status_code, Some(request))
+                    })
+                }))
+                .unwrap_or_else(|payload| {
+                    Err(Error::Handler(anyhow::anyhow!(
+                        "Handler panicked: {}",
+                        panic_message(&*payload)
+                    )))
+                });
+
+                match result {
+                    Ok(response) => response,
+                    Err(Error::Handler(err)) => config.handler_error_response(&err, &head),
+                    Err(err) => config.handler_error_response(&err.into(), &head),
+                }
+            }
+            (Err(err), _) => return Err(anyhow::anyhow!("Error: {}", err)),
+        };
+
+        // Never let a handler-provided header split the response
+        if let Err(err) = response.headers.validate() {
+            log::error!("Refusing to send response: {}", err);
+
+            response = config.error_response(http::StatusCode::CODE500, Some(&head));
+        }
+
+        // Responses are framed as HTTP/1.1 whatever the handler set, see `Version`
+        response.version = http::Version::V11;
+
+        config.encode(head.headers.get("Accept-Encoding"), &mut response)?;
+
+        Ok((head, response))
+    }
+
+    /// Starts the server, listening for incoming requests.
+    ///
+    /// Connections are handled by a pool of worker threads, see [`Server::set_workers`],
+    /// and accepted by one thread per listening address. Runs until a shutdown is
+    /// requested through a [`ShutdownHandle`], or until one of the listeners fails,
+    /// then waits for the open connections to close up to the
+    /// [drain timeout](Server::set_drain_timeout).
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating success or failure.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{TcpListener, TcpStream},
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn slow(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     thread::sleep(Duration::from_millis(20));
+    ///
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, slow);
+    ///
+    /// let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    /// let mut server = Server::new(&addr.to_string(), router, Args::new())?;
+    /// server.set_workers(2).set_queue_depth(4);
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// // Many more concurrent connections than workers
+    /// let clients = (0..32)
+    ///     .map(|_| {
+    ///         thread::spawn(move || -> anyhow::Result<String> {
+    ///             let mut stream = TcpStream::connect(addr)?;
+    ///             stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    ///             let mut response = String::new();
+    ///             stream.read_to_string(&mut response)?;
+    ///
+    ///             Ok(response)
+    ///         })
+    ///     })
+    ///     .collect::<Vec<_>>();
+    ///
+    /// for client in clients {
+    ///     assert!(client.join().unwrap()?.starts_with("HTTP/1.1 200 OK"));
+    /// }
+    ///
+    /// handle.shutdown();
+    /// assert!(server.join().unwrap().is_ok());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.run().map(drop)
+    }
+
+    /// Runs the server like [`Server::start`], then drains the connections left open.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing what happened to the connections left open, or
+    /// an error if the server failed.
+    fn run(&mut self) -> Result<ShutdownReport, Error> {
+        self.register_stats()?;
+
+        for listener in &self.listeners {
+            log::info!("Server started on {}", listener);
+        }
+
+        let watchdog = Watchdog::spawn(self.config.clone())?;
+        let reaper = Reaper::spawn(self.config.clone()).inspect_err(|_| watchdog.stop())?;
+        let stop = || {
+            watchdog.stop();
+            if let Some(reaper) = &reaper {
+                reaper.stop();
+            }
+        };
+
+        let pool = {
+            let router = self.router.clone();
+            let args = self.args.clone();
+            let config = self.config.clone();
+            let watchdog = watchdog.clone();
+
+            WorkerPool::new(
+                self.config.workers,
+                self.config.queue_depth,
+                |n| self.config.thread(&format!("worker-{}", n)),
+                // The guard is dropped with the connection, even if the handler panics
+                move |(stream, guard): (Connection, ConnectionGuard)| {
+                    let addr = stream.peer_ip().ok().flatten();
+
+                    // Errors are reported within the span of their connection
+                    let connection_span = span::Span::connection(addr);
+                    let _connection = connection_span.enter();
+
+                    if let Err(err) = Server::handle_connection(
+                        stream,
+                        &guard,
+                        &watchdog,
+                        &router,
+                        args.clone(),
+                        &config,
+                    ) {
+                        config.connection_failed(ConnectionError::new(err, addr));
+                    }
+                },
+            )
+        }
+        .inspect_err(|_| stop())?;
+
+        let server = &*self;
+        let handle = server.shutdown_handle();
+
+        // One acceptor per listener, all feeding the same workers
+        let results = thread::scope(|scope| {
+            let acceptors = server
+                .listeners
+                .iter()
+                .enumerate()
+                .map(|(n, listener)| {
+                    let (pool, handle) = (&pool, &handle);
+
+                    let acceptor = server
+                        .config
+                        .thread(&format!("acceptor-{}", n))
+                        .spawn_scoped(scope, move || {
+                            let result = server.accept_loop(listener, pool);
+
+                            // A failing listener takes the others down with it
+                            if result.is_err() {
+                                handle.shutdown();
+                            }
+
+                            result
+                        });
+
+                    // The acceptors already running must not wait for this one
+                    if acceptor.is_err() {
+                        handle.shutdown();
+                    }
+
+                    acceptor
+                })
+                .collect::<Vec<_>>();
+
+            acceptors
+                .into_iter()
+                .map(|acceptor| match acceptor {
+                    Ok(acceptor) => acceptor
+                        .join()
+                        .unwrap_or_else(|_| Err(anyhow::anyhow!("An acceptor panicked").into())),
+                    Err(err) => Err(anyhow::Error::new(err)
+                        .context("Failed to spawn an acceptor")
+                        .into()),
+                })
+                .collect::<Vec<Result<(), Error>>>()
+        });
+
+        for listener in &self.listeners {
+            listener.unlink();
+        }
+
+        let report = self
+            .config
+            .stats
+            .connections
+            .drain(self.config.drain_timeout);
+        stop();
+
+        log::info!("Server stopped");
+
+        results.into_iter().collect::<Result<(), Error>>()?;
+
+        Ok(report)
+    }
+
+    /// Starts the server on a thread of its own, returning right away.
+    ///
+    /// This is the simplest way to run a server next to the rest of an application,
+    /// or in a test: the handle tells where the server listens and stops it when
+    /// dropped.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the handle of the server, or an error if the
+    /// thread can't be spawned. Errors of the server itself are returned by
+    /// [`ServerHandle::join`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let server = Server::new("127.0.0.1:0", Router::new(), Args::new())?.start_background()?;
+    ///
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    ///
+    /// server.shutdown();
+    /// server.join()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn start_background(self) -> Result<ServerHandle, Error> {
+        let addrs = self.local_addrs();
+        let shutdown = self.shutdown_handle();
+        let stats = self.config.stats.clone();
+
+        let mut server = self;
+        let thread = server.config.thread("").spawn(move || server.run())?;
+
+        Ok(ServerHandle {
+            addrs,
+            shutdown,
+            stats,
+            thread: Some(thread),
+        })
+    }
+
+    /// Serves a connection accepted by the application rather than by the server:
+    /// reads each request sent on `stream`, dispatches it and writes the response,
+    /// until the connection is not persistent anymore or the stream ends.
+    ///
+    /// This is how the server is embedded in an accept loop of its own, e.g. behind a
+    /// custom TLS termination, or driven through an in-memory stream in tests. The
+    /// listeners of the server are not used, and neither is any of its threads: the
+    /// connection is served on the calling thread.
+    ///
+    /// The stream has no socket the server knows of, so the read timeouts, the
+    /// [handler timeout](Server::set_handler_timeout) and upgrades are not
+    /// available, and the connection isn't counted or drained on shutdown.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection, read from and written to by the server.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating success, or the error that ended the connection.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{self, Cursor, Read, Write},
+    ///     sync::{Arc, RwLock},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// // Requests are read from one buffer, responses written to another
+    /// struct Pipe {
+    ///     input: Cursor<Vec<u8>>,
+    ///     output: Vec<u8>,
+    /// }
+    ///
+    /// impl Read for Pipe {
+    ///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    ///         self.input.read(buf)
+    ///     }
+    /// }
+    ///
+    /// impl Write for Pipe {
+    ///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    ///         self.output.write(buf)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// fn hello(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.headers.set("Content-Length", "5");
+    ///     response.body = Some(request.path[1..].to_string().into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/hello", http::Version::V11, hello);
+    /// router.add_route(http::Method::GET, "/world", http::Version::V11, hello);
+    ///
+    /// let server = Server::new("127.0.0.1:0", router, Args::new())?;
+    ///
+    /// // Two requests on the same connection, then the client goes away
+    /// let mut pipe = Pipe {
+    ///     input: Cursor::new(b"GET /hello HTTP/1.1\r\n\r\nGET /world HTTP/1.1\r\n\r\n".to_vec()),
+    ///     output: Vec::new(),
+    /// };
+    /// server.serve_connection(&mut pipe)?;
+    ///
+    /// let output = String::from_utf8(pipe.output)?;
+    /// let responses = output.split("HTTP/1.1 200 OK\n").skip(1).collect::<Vec<_>>();
+    /// assert_eq!(responses.len(), 2);
+    /// assert!(responses[0].ends_with("\n\nhello"));
+    /// assert!(responses[1].ends_with("\n\nworld"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn serve_connection<S: Read + Write>(&self, stream: &mut S) -> Result<(), ConnectionError> {
+        let connection_span = span::Span::connection(None);
+        let _connection = connection_span.enter();
+
+        let mut stream = Embedded(stream);
+
+        match Server::serve(
+            &mut stream,
+            None,
+            None,
+            &self.router,
+            self.args.clone(),
+            &self.config,
+        ) {
+            Ok(Some(_)) => {
+                log::warn!("Only plain TCP connections can be upgraded, closing connection");
+
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(err) => Err(ConnectionError::new(err, None)),
+        }
+    }
+
+    /// Starts the server like [`Server::start`], stopping it gracefully when the
+    /// process is asked to terminate: on `SIGINT` or `SIGTERM` on Unix, e.g. sent by
+    /// systemd or Docker, and on Ctrl-C on Windows.
+    ///
+    /// Once a signal is received, no new connection is accepted and the requests in
+    /// flight are completed. The method returns when every connection is closed, or
+    /// after the [drain timeout](Server::set_drain_timeout).
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating success or failure, or an error if the signal
+    /// handlers can't be installed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     process::{self, Command},
+    ///     sync::{
+    ///         atomic::{AtomicBool, Ordering},
+    ///         Arc, RwLock,
+    ///     },
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// static HANDLING: AtomicBool = AtomicBool::new(false);
+    ///
+    /// fn slow(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     HANDLING.store(true, Ordering::SeqCst);
+    ///     thread::sleep(Duration::from_millis(500));
+    ///
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, slow);
+    ///
+    /// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+    /// let addr = server.local_addr()?;
+    /// let server = thread::spawn(move || server.start_with_signals());
+    ///
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    /// // Terminate the process while the request is being handled
+    /// while !HANDLING.load(Ordering::SeqCst) {
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    /// Command::new("kill")
+    ///     .args(["-TERM", &process::id().to_string()])
+    ///     .status()?;
+    ///
+    /// // The request still completes, then the server returns
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    ///
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn start_with_signals(&mut self) -> Result<(), Error> {
+        signals::install()?;
+
+        // Signal handlers can't do much, watch for what they record instead
+        let handle = self.shutdown_handle();
+        let watcher = self.config.thread("signals").spawn(move || {
+            while !handle.is_shutdown() {
+                if signals::received() {
+                    log::info!("Termination requested, shutting down");
+                    handle.shutdown();
+                }
+
+                thread::sleep(SIGNAL_POLL_INTERVAL);
+            }
+        })?;
+
+        let result = self.start();
+
+        // The server may have stopped on its own, the watcher must not outlive it
+        self.shutdown.store(true, Ordering::SeqCst);
+        let _ = watcher.join();
+
+        result
+    }
+
+    /// Accepts connections on `listener` and hands them to `pool` until a shutdown is
+    /// requested.
+    ///
+    /// # Arguments
+    ///
+    /// * `listener` - The listener to accept connections on.
+    /// * `pool` - The workers handling the connections.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating success or failure.
+    fn accept_loop(
+        &self,
+        listener: &Listener,
+        pool: &WorkerPool<(Connection, ConnectionGuard)>,
+    ) -> Result<(), Error> {
+        while !self.shutdown.load(Ordering::SeqCst) {
+            let stream = match listener.accept() {
+                Ok(stream) => stream,
+                Err(err) => {
+                    thread::sleep(self.config.accept_failed(err)?);
+
+                    continue;
+                }
+            };
+
+            // The connection may be the one waking us up for shutdown
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+
+            self.config.stats.accepted();
+
+            // Unwanted and misbehaving clients are turned away before taking any room
+            let peer = stream.peer_addr().ok().flatten();
+            match self.config.decide(peer) {
+                ConnectionDecision::Accept => {}
+                ConnectionDecision::Reject => continue,
+                ConnectionDecision::RejectWith(status_code) => {
+                    self.reject(stream, status_code, None);
+
+                    continue;
+                }
+            }
+
+            let addr = peer.map(|peer| peer.ip());
+            if let Some(filter) = self.config.filter(addr) {
+                if filter.forbidden_response {
+                    self.reject(stream, http::StatusCode::CODE403, None);
+                }
+
+                continue;
+            }
+
+            let permit = match self.config.admit(addr) {
+                Ok(permit) => permit,
+                Err(retry_after) => {
+                    log::warn!("Too many connections from {:?}, refusing connection", addr);
+
+                    if self.config.limit_policy == LimitPolicy::Reject {
+                        self.reject(stream, http::StatusCode::CODE429, Some(retry_after));
+                    }
+
+                    continue;
+                }
+            };
+
+            let max = self.config.max_connections;
+            let policy = self.config.queue_policy;
+
+            let connections = &self.config.stats.connections;
+
+            let mut guard = match connections.acquire(max, Duration::ZERO) {
+                Some(guard) => guard,
+                None if policy == QueuePolicy::Reject => {
+                    log::warn!("Too many open connections, rejecting connection");
+                    self.reject(stream, http::StatusCode::CODE503, Some(RETRY_AFTER));
+
+                    continue;
+                }
+                None => loop {
+                    // Keep an eye on shutdown requests while waiting
+                    if let Some(guard) = connections.acquire(max, ACQUIRE_POLL_INTERVAL) {
+                        break guard;
+                    }
+
+                    if self.shutdown.load(Ordering::SeqCst) {
+                        return Ok(());
+                    }
+                },
+            };
+            guard.permit = permit;
+            guard.track(stream.socket());
+
+            match policy {
+                QueuePolicy::Block => pool.execute((stream, guard))?,
+                QueuePolicy::Reject => {
+                    if let Err((stream, _)) = pool.try_execute((stream, guard)) {
+                        log::warn!("Every worker is busy, rejecting connection");
+                        self.reject(stream, http::StatusCode::CODE503, Some(RETRY_AFTER));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Answers a connection the server won't serve, before closing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The connection to reject.
+    /// * `status_code` - Why the connection is rejected.
+    /// * `retry_after` - How long the client should wait before trying again, if it
+    ///   can.
+    fn reject(
+        &self,
+        mut stream: Connection,
+        status_code: http::StatusCode,
+        retry_after: Option<Duration>,
+    ) {
+        // The response can't be sent before a TLS handshake
+        #[cfg(feature = "tls")]
+        if self.config.tls.is_some() {
+            return;
+        }
+
+        let addr = stream.peer_ip().ok().flatten();
+
+        if let Err(err) = self
+            .config
+            .refuse(&mut stream, status_code, retry_after, addr)
+        {
+            log::debug!("Failed to reject connection: {}", err);
+        }
+    }
+}
+
+/// A response prepared by [`Server::respond`], waiting to be sent.
+struct Exchange {
+    /// The request being answered, without its body.
+    head: HTTPRequest,
+    response: HTTPResponse,
+    /// Whether the connection stays open once the response is sent.
+    keep_alive: bool,
+    /// When the request was received.
+    time: SystemTime,
+    /// How long the handler took.
+    duration: Duration,
+    /// The path of the route matching the request, if any.
+    #[cfg(feature = "metrics")]
+    route: Option<String>,
+    /// The file the request body was spooled to, deleted once the response is sent.
+    _spooled: Option<TempFile>,
+}
+
+impl Exchange {
+    /// Writes the response to `stream` and logs it in the access log.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the callback taking over the connection if the
+    /// response upgrades it, see [`HTTPResponse::with_upgrade`].
+    fn send<W: SendFile>(
+        mut self,
+        stream: W,
+        config: &ServerConfig,
+    ) -> anyhow::Result<Option<UpgradeFunction>> {
+        let status_code = self.response.status_code;
+
+        let upgrade = match self.response.body.take() {
+            Some(Body::Upgrade(upgrade)) => Some(upgrade),
+            body => {
+                self.response.body = body;
+
+                None
+            }
+        };
+
+        let mut stream = CountingWriter::new(stream);
+        let result = Server::write_response(
+            &mut stream,
+            self.response,
+            self.head.method == http::Method::HEAD || upgrade.is_some(),
+            self.head.version,
+            config.write_chunk_size,
+            config.zero_copy,
+        );
+        config.stats.written(stream.count);
+
+        let size = result?;
+        config.stats.response(status_code);
+        #[cfg(feature = "metrics")]
+        config.stats.metrics.observe(
+            self.head.method,
+            status_code,
+            self.route.as_deref(),
+            self.duration,
+        );
+
+        config.log_access(AccessLogEntry {
+            time: self.time,
+            addr: self.head.addr,
+            method: Some(self.head.method),
+            path: Some(self.head.path),
+            version: Some(self.head.version),
+            status_code,
+            size,
+            duration: self.duration,
+        });
+
+        Ok(upgrade)
+    }
+}
+
+/// Returns the status answering a request that couldn't be read because of `err`, or
+/// `None` if the connection itself failed.
+fn read_error_status(err: &anyhow::Error) -> Option<http::StatusCode> {
+    if err.is::<SpoolFailed>() {
+        return Some(http::StatusCode::CODE500);
+    }
+
+    match err.downcast_ref::<std::io::Error>() {
+        Some(io_err) if is_timeout(io_err) => Some(http::StatusCode::CODE408),
+        Some(_) => None,
+        None if err.is::<RequestLineTooLong>() => Some(http::StatusCode::CODE414),
+        None if err.is::<RequestTooLarge>() => Some(http::StatusCode::CODE413),
+        None if err.is::<UnsupportedTransferEncoding>() => Some(http::StatusCode::CODE501),
+        None => Some(http::StatusCode::CODE400),
+    }
+}
+
+/// Formats `duration` as the value of a `Retry-After` header.
+pub(crate) fn retry_after_seconds(duration: Duration) -> String {
+    // Whole seconds, rounded up so the client doesn't come back too early
+    let seconds = duration.as_secs() + u64::from(duration.subsec_nanos() > 0);
+
+    seconds.to_string()
+}
+
+/// Returns `true` if `err` was caused by a socket timeout.
+fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+/// Returns `true` if `err` was caused by the client closing the connection.
+fn is_disconnect(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// Returns the message a panic was raised with, if it has one.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown cause", String::as_str),
     }
 }
@@ -0,0 +1,538 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// What a [`ConnectionLimiter`] decided about a new connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// The connection is served.
+    Accept,
+    /// The connection is refused, and the client should wait `retry_after` before
+    /// connecting again.
+    Refuse { retry_after: Duration },
+}
+
+/// Decides which connections a [`Server`](crate::Server) accepts from each client.
+///
+/// The limiter is asked about every connection right after it is accepted, using the
+/// IP address of the client; connections on Unix sockets are never limited. What
+/// happens to refused connections depends on the
+/// [`LimitPolicy`](crate::Server::set_limit_policy) of the server.
+///
+/// [`IpRateLimiter`] covers the common cases, implement the trait to substitute a
+/// policy of your own.
+///
+/// # Example
+///
+/// ```
+/// use std::{net::IpAddr, time::Duration};
+/// use fobserver::limit::{Admission, ConnectionLimiter};
+///
+/// /// Refuses a single known client.
+/// struct Blocklist(IpAddr);
+///
+/// impl ConnectionLimiter for Blocklist {
+///     fn admit(&self, ip: IpAddr) -> Admission {
+///         if ip != self.0 {
+///             return Admission::Accept;
+///         }
+///
+///         Admission::Refuse {
+///             retry_after: Duration::from_secs(3600),
+///         }
+///     }
+/// }
+///
+/// let blocklist = Blocklist("10.0.0.1".parse()?);
+/// assert_ne!(blocklist.admit("10.0.0.1".parse()?), Admission::Accept);
+/// assert_eq!(blocklist.admit("10.0.0.2".parse()?), Admission::Accept);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait ConnectionLimiter: Send + Sync {
+    /// Decides whether a new connection from `ip` is served.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address of the client.
+    ///
+    /// # Returns
+    ///
+    /// Returns whether the connection is accepted or refused.
+    fn admit(&self, ip: IpAddr) -> Admission;
+
+    /// Called once a connection accepted from `ip` is closed. Does nothing by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - The IP address of the client.
+    fn release(&self, ip: IpAddr) {
+        let _ = ip;
+    }
+}
+
+/// Lets a limiter be shared, e.g. to keep inspecting it once given to a server.
+impl<L: ConnectionLimiter + ?Sized> ConnectionLimiter for Arc<L> {
+    fn admit(&self, ip: IpAddr) -> Admission {
+        (**self).admit(ip)
+    }
+
+    fn release(&self, ip: IpAddr) {
+        (**self).release(ip)
+    }
+}
+
+/// What the server does with a connection refused by its [`ConnectionLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitPolicy {
+    /// Answer the connection immediately with `429 Too Many Requests` and a
+    /// `Retry-After` header.
+    #[default]
+    Reject,
+    /// Close the connection without answering.
+    Drop,
+}
+
+/// A limiter allowing each IP address a number of new connections per time window,
+/// and optionally a number of connections open at once.
+///
+/// Addresses are forgotten once their window is over and their connections are
+/// closed, so the limiter doesn't grow with every client ever seen.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use fobserver::limit::{Admission, ConnectionLimiter, IpRateLimiter};
+///
+/// let limiter = IpRateLimiter::new(2, Duration::from_secs(60)).max_concurrent(1);
+/// let (client, other) = ("10.0.0.1".parse()?, "10.0.0.2".parse()?);
+///
+/// // One connection at a time...
+/// assert_eq!(limiter.admit(client), Admission::Accept);
+/// assert_ne!(limiter.admit(client), Admission::Accept);
+/// limiter.release(client);
+///
+/// // ...and two per minute
+/// assert_eq!(limiter.admit(client), Admission::Accept);
+/// limiter.release(client);
+/// match limiter.admit(client) {
+///     Admission::Refuse { retry_after } => assert!(retry_after <= Duration::from_secs(60)),
+///     Admission::Accept => panic!("The third connection must be refused"),
+/// }
+///
+/// // Other clients are not affected
+/// assert_eq!(limiter.admit(other), Admission::Accept);
+/// assert_eq!(limiter.clients(), 2);
+///
+/// // Addresses are forgotten once their window is over
+/// let limiter = IpRateLimiter::new(1, Duration::from_millis(50));
+/// limiter.admit(client);
+/// limiter.release(client);
+/// std::thread::sleep(Duration::from_millis(100));
+///
+/// assert_eq!(limiter.admit(other), Admission::Accept);
+/// assert_eq!(limiter.clients(), 1);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct IpRateLimiter {
+    max_connections: u32,
+    window: Duration,
+    max_concurrent: Option<usize>,
+    state: Mutex<State>,
+}
+
+/// The addresses tracked by an [`IpRateLimiter`].
+#[derive(Debug)]
+struct State {
+    clients: HashMap<IpAddr, Client>,
+    /// When addresses were last forgotten.
+    cleaned: Instant,
+}
+
+/// The connections of a single address.
+#[derive(Debug)]
+struct Client {
+    /// When the current window started.
+    window_start: Instant,
+    /// The connections accepted during the current window.
+    connections: u32,
+    /// The connections currently open.
+    open: usize,
+}
+
+impl IpRateLimiter {
+    /// Creates a limiter allowing each address `max_connections` new connections
+    /// every `window`, without limiting how many are open at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_connections` - The connections allowed per window, at least one.
+    /// * `window` - The length of a window.
+    pub fn new(max_connections: u32, window: Duration) -> Self {
+        IpRateLimiter {
+            max_connections: max_connections.max(1),
+            window,
+            max_concurrent: None,
+            state: Mutex::new(State {
+                clients: HashMap::new(),
+                cleaned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Also limits how many connections each address can have open at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The connections allowed at once, at least one.
+    pub fn max_concurrent(mut self, max: usize) -> Self {
+        self.max_concurrent = Some(max.max(1));
+
+        self
+    }
+
+    /// Returns the number of addresses currently tracked.
+    pub fn clients(&self) -> usize {
+        self.lock().clients.len()
+    }
+
+    /// Locks the tracked addresses, even if a thread panicked while holding them.
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns `true` if `client` has nothing left worth remembering at `now`.
+    fn is_stale(&self, client: &Client, now: Instant) -> bool {
+        client.open == 0 && now.duration_since(client.window_start) >= self.window
+    }
+}
+
+impl ConnectionLimiter for IpRateLimiter {
+    fn admit(&self, ip: IpAddr) -> Admission {
+        let now = Instant::now();
+        let mut state = self.lock();
+
+        // Forget stale addresses once per window, so the table stays bounded
+        if now.duration_since(state.cleaned) >= self.window {
+            state
+                .clients
+                .retain(|_, client| !self.is_stale(client, now));
+            state.cleaned = now;
+        }
+
+        let client = state.clients.entry(ip).or_insert(Client {
+            window_start: now,
+            connections: 0,
+            open: 0,
+        });
+
+        let elapsed = now.duration_since(client.window_start);
+        if elapsed >= self.window {
+            client.window_start = now;
+            client.connections = 0;
+        }
+
+        if self.max_concurrent.is_some_and(|max| client.open >= max) {
+            return Admission::Refuse {
+                retry_after: Duration::from_secs(1),
+            };
+        }
+
+        if client.connections >= self.max_connections {
+            return Admission::Refuse {
+                retry_after: self.window.saturating_sub(elapsed),
+            };
+        }
+
+        client.connections += 1;
+        client.open += 1;
+
+        Admission::Accept
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let now = Instant::now();
+        let mut state = self.lock();
+
+        if let Some(client) = state.clients.get_mut(&ip) {
+            client.open = client.open.saturating_sub(1);
+
+            if self.is_stale(client, now) {
+                state.clients.remove(&ip);
+            }
+        }
+    }
+}
+
+/// A limiter of the requests made under each key, e.g. a client address or an API
+/// key, with a token bucket: every key starts with `capacity` tokens, each request
+/// takes one, and one is given back every `refill_interval`. Used by the
+/// [`rate_limit`](crate::middleware::rate_limit) middleware.
+///
+/// At most [`max_keys`](RequestRateLimiter::max_keys) buckets are kept; when a new
+/// key comes in, the bucket used the longest ago is forgotten. A forgotten key
+/// starts over with a full bucket, which is what its bucket would have refilled to
+/// unless the table is too small for the traffic.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     sync::{Arc, Mutex},
+///     time::{Duration, Instant},
+/// };
+/// use fobserver::limit::RequestRateLimiter;
+///
+/// // Time only moves when the test says so
+/// let now = Arc::new(Mutex::new(Instant::now()));
+/// let clock = now.clone();
+/// let limiter = RequestRateLimiter::new(2, Duration::from_secs(10))
+///     .max_keys(2)
+///     .clock(move || *clock.lock().unwrap());
+///
+/// let status = limiter.acquire("alice");
+/// assert!(status.allowed);
+/// assert_eq!(status.remaining, 1);
+///
+/// let status = limiter.acquire("alice");
+/// assert_eq!((status.allowed, status.remaining), (true, 0));
+/// assert_eq!(status.reset, Duration::from_secs(20));
+///
+/// let status = limiter.acquire("alice");
+/// assert!(!status.allowed);
+/// assert_eq!(status.retry_after, Some(Duration::from_secs(10)));
+///
+/// // A token comes back every 10 seconds
+/// *now.lock().unwrap() += Duration::from_secs(10);
+/// assert!(limiter.acquire("alice").allowed);
+/// assert!(!limiter.acquire("alice").allowed);
+///
+/// // The least recently used key makes room for new ones
+/// limiter.acquire("bob");
+/// limiter.acquire("carol");
+/// assert_eq!(limiter.keys(), 2);
+/// assert_eq!(limiter.acquire("alice").remaining, 1);
+/// ```
+pub struct RequestRateLimiter {
+    capacity: u32,
+    refill_interval: Duration,
+    max_keys: usize,
+    clock: Box<dyn Fn() -> Instant + Send + Sync>,
+    buckets: Mutex<Buckets>,
+}
+
+impl fmt::Debug for RequestRateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestRateLimiter")
+            .field("capacity", &self.capacity)
+            .field("refill_interval", &self.refill_interval)
+            .field("max_keys", &self.max_keys)
+            .finish()
+    }
+}
+
+/// What a [`RequestRateLimiter`] decided about a request, with the state of its
+/// bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// Whether the request may proceed.
+    pub allowed: bool,
+    /// The capacity of the bucket.
+    pub limit: u32,
+    /// The tokens left in the bucket.
+    pub remaining: u32,
+    /// How long until the bucket is full again.
+    pub reset: Duration,
+    /// How long until the next token, if the request was refused.
+    pub retry_after: Option<Duration>,
+}
+
+/// The buckets of a [`RequestRateLimiter`], by key.
+#[derive(Debug, Default)]
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    /// The keys by when they were last used, oldest first.
+    recency: BTreeMap<u64, String>,
+    /// Incremented whenever a key is used.
+    tick: u64,
+}
+
+/// The tokens of a single key.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    /// When `tokens` was last brought up to date.
+    updated: Instant,
+    /// When the key was last used, its entry in `recency`.
+    tick: u64,
+}
+
+/// The default number of keys a [`RequestRateLimiter`] remembers.
+const DEFAULT_MAX_KEYS: usize = 10_000;
+
+impl RequestRateLimiter {
+    /// Creates a limiter giving each key `capacity` tokens, and one more every
+    /// `refill_interval`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The requests allowed in a burst, at least one.
+    /// * `refill_interval` - How long it takes to get a token back.
+    pub fn new(capacity: u32, refill_interval: Duration) -> Self {
+        RequestRateLimiter {
+            capacity: capacity.max(1),
+            refill_interval,
+            max_keys: DEFAULT_MAX_KEYS,
+            clock: Box::new(Instant::now),
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    /// Sets how many keys are remembered at most, 10000 by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The number of keys, at least one.
+    pub fn max_keys(mut self, max: usize) -> Self {
+        self.max_keys = max.max(1);
+
+        self
+    }
+
+    /// Replaces the clock the limiter reads the time from, e.g. to control it in
+    /// tests.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - Returns the current time.
+    pub fn clock(mut self, clock: impl Fn() -> Instant + Send + Sync + 'static) -> Self {
+        self.clock = Box::new(clock);
+
+        self
+    }
+
+    /// Returns the number of keys currently remembered.
+    pub fn keys(&self) -> usize {
+        self.lock().buckets.len()
+    }
+
+    /// Takes a token from the bucket of `key`, if there is one left.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - What the request is limited by.
+    ///
+    /// # Returns
+    ///
+    /// Returns whether the request is allowed, and the state of the bucket after it.
+    pub fn acquire(&self, key: &str) -> RateLimitStatus {
+        let now = (self.clock)();
+        let capacity = f64::from(self.capacity);
+        let interval = self.refill_interval.as_secs_f64();
+
+        let mut state = self.lock();
+        state.tick += 1;
+        let tick = state.tick;
+
+        let previous = state.buckets.get(key).map(|bucket| bucket.tick);
+        match previous {
+            Some(previous) => {
+                state.recency.remove(&previous);
+            }
+            None => {
+                if state.buckets.len() >= self.max_keys {
+                    if let Some((_, oldest)) = state.recency.pop_first() {
+                        state.buckets.remove(&oldest);
+                    }
+                }
+
+                state.buckets.insert(
+                    key.to_string(),
+                    Bucket {
+                        tokens: capacity,
+                        updated: now,
+                        tick,
+                    },
+                );
+            }
+        }
+        state.recency.insert(tick, key.to_string());
+
+        let Some(bucket) = state.buckets.get_mut(key) else {
+            unreachable!("The bucket was just inserted");
+        };
+        bucket.tick = tick;
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = match interval > 0.0 {
+            true => (bucket.tokens + elapsed / interval).min(capacity),
+            false => capacity,
+        };
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let until = |tokens: f64| Duration::from_secs_f64((tokens.max(0.0) * interval).max(0.0));
+
+        RateLimitStatus {
+            allowed,
+            limit: self.capacity,
+            remaining: bucket.tokens as u32,
+            reset: until(capacity - bucket.tokens),
+            retry_after: (!allowed).then(|| until(1.0 - bucket.tokens)),
+        }
+    }
+
+    /// Locks the buckets, even if a thread panicked while holding them.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Buckets> {
+        self.buckets.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// The limiter of a server, shared with every connection.
+#[derive(Clone)]
+pub(crate) struct SharedLimiter(pub(crate) Arc<dyn ConnectionLimiter>);
+
+impl fmt::Debug for SharedLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedLimiter").finish_non_exhaustive()
+    }
+}
+
+impl SharedLimiter {
+    /// Asks the limiter about a new connection from `ip`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a permit releasing the connection when dropped, or how long the client
+    /// should wait if the connection is refused.
+    pub(crate) fn admit(&self, ip: IpAddr) -> Result<Permit, Duration> {
+        match self.0.admit(ip) {
+            Admission::Accept => Ok(Permit {
+                limiter: self.0.clone(),
+                ip,
+            }),
+            Admission::Refuse { retry_after } => Err(retry_after),
+        }
+    }
+}
+
+/// Keeps a connection known to the limiter until it is dropped.
+pub(crate) struct Permit {
+    limiter: Arc<dyn ConnectionLimiter>,
+    ip: IpAddr,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release(self.ip);
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    fmt, io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+#[cfg(unix)]
+use std::{
+    fs,
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::connection::Connection;
+
+/// How many connections can wait in the kernel to be accepted.
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// The raw errors telling that the process or the system ran out of file descriptors
+/// or buffers, which `io::ErrorKind` doesn't classify.
+#[cfg(unix)]
+const EXHAUSTION_ERRORS: [i32; 2] = [
+    23, // ENFILE
+    24, // EMFILE
+];
+#[cfg(windows)]
+const EXHAUSTION_ERRORS: [i32; 2] = [
+    10024, // WSAEMFILE
+    10055, // WSAENOBUFS
+];
+#[cfg(not(any(unix, windows)))]
+const EXHAUSTION_ERRORS: [i32; 0] = [];
+
+/// What an acceptor does after failing to accept a connection.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum AcceptFailure {
+    /// Only the pending connection failed, e.g. because the client went away.
+    Transient,
+    /// The process ran out of resources, which may be released if given time.
+    Exhausted,
+    /// The listener can't accept connections anymore.
+    Fatal,
+}
+
+impl AcceptFailure {
+    /// Classifies the error returned when accepting a connection.
+    pub(crate) fn of(err: &io::Error) -> Self {
+        if err
+            .raw_os_error()
+            .is_some_and(|code| EXHAUSTION_ERRORS.contains(&code))
+        {
+            return AcceptFailure::Exhausted;
+        }
+
+        match err.kind() {
+            io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            // Firewall rules refusing the connection on Linux
+            | io::ErrorKind::PermissionDenied => AcceptFailure::Transient,
+            io::ErrorKind::OutOfMemory => AcceptFailure::Exhausted,
+            _ => AcceptFailure::Fatal,
+        }
+    }
+}
+
+/// A socket the server accepts connections on.
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        path: PathBuf,
+        /// Whether the server created the socket file, and removes it when it stops.
+        owned: bool,
+    },
+}
+
+/// Where a client can reach a [`Listener`], used to wake it up.
+#[derive(Debug, Clone)]
+pub(crate) enum Endpoint {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Listener {
+    /// Binds every address `addr` resolves to.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address(es) to bind.
+    /// * `reuse_address` - Whether to set `SO_REUSEADDR` before binding, so that a
+    ///   restarted server can bind while connections of the previous one linger in
+    ///   `TIME_WAIT`. Ignored on Windows, where the option lets other sockets take over
+    ///   the port.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the listeners, or an error if an address can't
+    /// be bound or `addr` resolves to nothing.
+    pub(crate) fn bind_tcp<A: ToSocketAddrs>(
+        addr: A,
+        reuse_address: bool,
+    ) -> io::Result<Vec<Self>> {
+        let listeners = addr
+            .to_socket_addrs()?
+            .map(|addr| {
+                let socket =
+                    Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+                if cfg!(not(windows)) {
+                    socket.set_reuse_address(reuse_address)?;
+                }
+
+                socket.bind(&addr.into())?;
+                socket.listen(LISTEN_BACKLOG)?;
+
+                Ok(Listener::Tcp(socket.into()))
+            })
+            .collect::<io::Result<Vec<Listener>>>()?;
+
+        if listeners.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "No address to bind to",
+            ));
+        }
+
+        Ok(listeners)
+    }
+
+    /// Binds a Unix socket at `path`, replacing a socket file left behind by a
+    /// previous run.
+    #[cfg(unix)]
+    pub(crate) fn bind_unix(path: &Path) -> anyhow::Result<Self> {
+        if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            fs::remove_file(path)?;
+        }
+
+        Ok(Listener::Unix {
+            listener: UnixListener::bind(path)?,
+            path: path.to_path_buf(),
+            owned: true,
+        })
+    }
+
+    /// Sets the permissions of the socket file of a Unix listener.
+    #[cfg(unix)]
+    pub(crate) fn set_permissions(&self, mode: u32) -> anyhow::Result<()> {
+        match self {
+            Listener::Unix { path, .. } => {
+                Ok(fs::set_permissions(path, fs::Permissions::from_mode(mode))?)
+            }
+            Listener::Tcp(_) => Err(anyhow::anyhow!("The server doesn't use a Unix socket")),
+        }
+    }
+
+    /// Waits for the next connection.
+    pub(crate) fn accept(&self) -> io::Result<Connection> {
+        match self {
+            Listener::Tcp(listener) => Ok(Connection::Plain(listener.accept()?.0)),
+            #[cfg(unix)]
+            Listener::Unix { listener, .. } => Ok(Connection::Unix(listener.accept()?.0)),
+        }
+    }
+
+    /// Returns where a client can connect to this listener.
+    pub(crate) fn endpoint(&self) -> Option<Endpoint> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().ok().map(Endpoint::Tcp),
+            #[cfg(unix)]
+            Listener::Unix { path, .. } => Some(Endpoint::Unix(path.clone())),
+        }
+    }
+
+    /// Removes the socket file of a Unix listener, unless it was inherited.
+    pub(crate) fn unlink(&self) {
+        #[cfg(unix)]
+        if let Listener::Unix {
+            path, owned: true, ..
+        } = self
+        {
+            if let Err(err) = fs::remove_file(path) {
+                log::warn!("Failed to remove {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{}", addr),
+                Err(_) => write!(f, "an unknown address"),
+            },
+            #[cfg(unix)]
+            Listener::Unix { path, .. } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl Endpoint {
+    /// Opens and immediately drops a connection to the endpoint.
+    pub(crate) fn connect(&self) -> io::Result<()> {
+        match self {
+            Endpoint::Tcp(addr) => {
+                let mut addr = *addr;
+
+                // A wildcard address can't be connected to, its loopback counterpart can
+                if addr.ip().is_unspecified() {
+                    addr.set_ip(match addr {
+                        SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                        SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                    });
+                }
+
+                TcpStream::connect_timeout(&addr, Duration::from_secs(1)).map(drop)
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => UnixStream::connect(path).map(drop),
+        }
+    }
+}
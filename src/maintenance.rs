@@ -0,0 +1,96 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::http::{HTTPRequest, HTTPResponse, StatusCode};
+
+/// How a [`Server`](crate::Server) in maintenance answers requests.
+///
+/// Every request is answered with `503 Service Unavailable` without reaching its
+/// handler, except the ones to the allowlisted paths.
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceConfig {
+    /// How long clients should wait before coming back, sent as `Retry-After`.
+    pub retry_after: Option<Duration>,
+    /// The plain text body of the responses.
+    pub body: String,
+    /// The paths still served by their handlers, e.g. `/health`. Matched exactly,
+    /// without the query string.
+    pub allowlist_paths: Vec<String>,
+}
+
+/// A handle switching a [`Server`](crate::Server) in and out of maintenance while it
+/// runs.
+///
+/// Handles are shared with a server through
+/// [`Server::set_maintenance_handle`](crate::Server::set_maintenance_handle), or
+/// obtained from it with [`Server::maintenance_handle`](crate::Server::maintenance_handle),
+/// and can be cloned freely, e.g. into the [`Args`](crate::args::Args) of an admin
+/// endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceHandle {
+    config: Arc<RwLock<Option<Arc<MaintenanceConfig>>>>,
+}
+
+impl MaintenanceHandle {
+    /// Creates a handle, out of maintenance.
+    pub fn new() -> Self {
+        MaintenanceHandle::default()
+    }
+
+    /// Puts the server in maintenance, or takes it out with `None`.
+    ///
+    /// The change applies to the requests dispatched afterwards, requests being
+    /// handled are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - How requests are answered during the maintenance, or `None` to
+    ///   route them normally again.
+    pub fn set(&self, config: Option<MaintenanceConfig>) {
+        let config = config.map(Arc::new);
+
+        match self.config.write() {
+            Ok(mut current) => *current = config,
+            Err(poisoned) => *poisoned.into_inner() = config,
+        }
+    }
+
+    /// Returns `true` if the server is in maintenance.
+    pub fn is_enabled(&self) -> bool {
+        self.current().is_some()
+    }
+
+    /// Returns the settings of the maintenance in progress, if any.
+    fn current(&self) -> Option<Arc<MaintenanceConfig>> {
+        match self.config.read() {
+            Ok(config) => config.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Answers `request` in place of its handler if the server is in maintenance.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `503 Service Unavailable` response, or `None` if the request is
+    /// routed normally.
+    pub(crate) fn response(&self, request: &HTTPRequest) -> Option<HTTPResponse> {
+        let config = self.current()?;
+
+        let path = request.path.split(['?', '#']).next().unwrap_or_default();
+        if config.allowlist_paths.iter().any(|allowed| allowed == path) {
+            return None;
+        }
+
+        let mut response = HTTPResponse::plain_text(StatusCode::CODE503, &config.body);
+        if let Some(retry_after) = config.retry_after {
+            response
+                .headers
+                .set("Retry-After", &crate::retry_after_seconds(retry_after));
+        }
+
+        Some(response)
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+
+use crate::{
+    args::Args,
+    http::{HTTPRequest, HTTPResponse, Method, StatusCode},
+    stats::{self, Stats},
+};
+
+/// The default upper bounds of the latency histogram buckets, in seconds.
+pub const DEFAULT_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The `route` label of the requests matching no route.
+const UNMATCHED: &str = "unmatched";
+
+/// A handler serving the metrics of the server it is mounted on, in the Prometheus
+/// text exposition format.
+///
+/// The metrics are:
+///
+/// * `fobserver_requests_total`, a counter of the responses sent to requests, by
+///   `method`, `status` class (e.g. `2xx`) and `route`.
+/// * `fobserver_request_duration_seconds`, a histogram of how long requests took to
+///   be answered, by `route`. Its buckets are set with
+///   [`Server::set_metrics_buckets`](crate::Server::set_metrics_buckets).
+/// * `fobserver_active_connections` and `fobserver_inflight_requests`, gauges of the
+///   connections open and the requests being handled.
+/// * `fobserver_received_bytes_total` and `fobserver_sent_bytes_total`, counters of
+///   the bytes exchanged with clients.
+///
+/// The `route` label is the path the matching route was registered with, or
+/// `unmatched`, so that clients requesting random paths can't make the number of
+/// series grow without bounds. Requests refused before being read, e.g. by the
+/// [connection limit](crate::Server::set_max_connections), aren't counted.
+///
+/// # Arguments
+///
+/// * `request` - The request being handled.
+/// * `args` - The arguments shared across handlers, in which the server registers its
+///   statistics.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the response, or an error if the handler is called
+/// outside of a server.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::TcpStream,
+///     sync::{Arc, RwLock},
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     metrics,
+///     router::Router,
+///     Server,
+/// };
+///
+/// fn hello(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::no_content())
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/hello", http::Version::V11, hello);
+/// router.add_route(
+///     http::Method::GET,
+///     "/metrics",
+///     http::Version::V11,
+///     metrics::metrics_handler,
+/// );
+///
+/// let mut server = Server::new("127.0.0.1:0", router, Args::new())?;
+/// server.set_metrics_buckets(&[0.5, 1.0]);
+/// let server = server.start_background()?;
+///
+/// let get = |path: &str| -> anyhow::Result<String> {
+///     let mut stream = TcpStream::connect(server.addr()?)?;
+///     write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path)?;
+///
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response)?;
+///
+///     Ok(response)
+/// };
+///
+/// get("/hello")?;
+/// get("/hello")?;
+/// get("/missing/1")?;
+/// get("/missing/2")?;
+///
+/// let response = get("/metrics")?;
+/// assert!(response.contains("Content-Type: text/plain; version=0.0.4; charset=utf-8"));
+/// assert!(response.contains(r#"fobserver_requests_total{method="GET",status="2xx",route="/hello"} 2"#));
+/// assert!(response.contains(r#"fobserver_requests_total{method="GET",status="4xx",route="unmatched"} 2"#));
+/// assert!(response.contains(r#"fobserver_request_duration_seconds_bucket{route="/hello",le="0.5"} 2"#));
+/// assert!(response.contains(r#"fobserver_request_duration_seconds_bucket{route="/hello",le="+Inf"} 2"#));
+/// assert!(response.contains(r#"fobserver_request_duration_seconds_count{route="unmatched"} 2"#));
+/// assert!(!response.contains("/missing"));
+///
+/// // The scrape itself is in flight, on the only open connection
+/// assert!(response.contains("\nfobserver_active_connections 1\n"));
+/// assert!(response.contains("\nfobserver_inflight_requests 1\n"));
+///
+/// let sent = response
+///     .lines()
+///     .find_map(|line| line.strip_prefix("fobserver_sent_bytes_total "))
+///     .unwrap();
+/// assert!(sent.parse::<u64>()? > 0);
+///
+/// server.shutdown();
+/// server.join()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn metrics_handler(
+    _request: HTTPRequest,
+    args: Arc<RwLock<Args>>,
+) -> anyhow::Result<HTTPResponse> {
+    let body = args
+        .read()
+        .map_err(|err| anyhow::anyhow!("Error: {}", err))?
+        .with(stats::ARG, |stats: &Arc<Stats>| stats.render_metrics())
+        .context("The server metrics are not available")?;
+
+    let mut response = HTTPResponse {
+        status_code: StatusCode::CODE200,
+        body: Some(body.into()),
+        ..HTTPResponse::default()
+    };
+    response
+        .headers
+        .set("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+        .set("Cache-Control", "no-store");
+
+    Ok(response)
+}
+
+/// The labeled metrics of a server, the others are read from its [`Stats`].
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    inner: Mutex<Series>,
+}
+
+/// The series observed so far.
+#[derive(Debug)]
+struct Series {
+    buckets: Vec<f64>,
+    /// The requests, by method, status class and route.
+    requests: BTreeMap<(String, u16, String), u64>,
+    /// The latency histogram, by route.
+    latency: BTreeMap<String, Histogram>,
+}
+
+/// The observations of one latency histogram.
+#[derive(Debug)]
+struct Histogram {
+    /// The observations falling in each bucket, not cumulated.
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            inner: Mutex::new(Series {
+                buckets: DEFAULT_BUCKETS.to_vec(),
+                requests: BTreeMap::new(),
+                latency: BTreeMap::new(),
+            }),
+        }
+    }
+}
+
+impl Metrics {
+    /// Replaces the upper bounds of the latency buckets, discarding the latencies
+    /// observed so far.
+    pub(crate) fn set_buckets(&self, buckets: &[f64]) {
+        let mut buckets: Vec<f64> = buckets
+            .iter()
+            .copied()
+            .filter(|bound| bound.is_finite())
+            .collect();
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+
+        let mut series = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        series.buckets = buckets;
+        series.latency.clear();
+    }
+
+    /// Records a response sent with `status_code` to a request for `route`, answered
+    /// in `duration`.
+    pub(crate) fn observe(
+        &self,
+        method: Method,
+        status_code: StatusCode,
+        route: Option<&str>,
+        duration: Duration,
+    ) {
+        let route = route.unwrap_or(UNMATCHED);
+        let class = (status_code.code() / 100).clamp(1, 5);
+        let seconds = duration.as_secs_f64();
+
+        let mut series = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        let Series {
+            buckets,
+            requests,
+            latency,
+        } = &mut *series;
+
+        *requests
+            .entry((method.to_string(), class, route.to_string()))
+            .or_default() += 1;
+
+        let histogram = latency
+            .entry(route.to_string())
+            .or_insert_with(|| Histogram {
+                counts: vec![0; buckets.len()],
+                sum: 0.0,
+                count: 0,
+            });
+        if let Some(bucket) = buckets.iter().position(|bound| seconds <= *bound) {
+            histogram.counts[bucket] += 1;
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    /// Renders the labeled series in the text exposition format.
+    fn render(&self, out: &mut String) -> std::fmt::Result {
+        let series = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+
+        writeln!(
+            out,
+            "# HELP fobserver_requests_total The requests answered, by method, status class and route."
+        )?;
+        writeln!(out, "# TYPE fobserver_requests_total counter")?;
+        for ((method, class, route), count) in &series.requests {
+            writeln!(
+                out,
+                "fobserver_requests_total{{method=\"{}\",status=\"{}xx\",route=\"{}\"}} {}",
+                method,
+                class,
+                escape(route),
+                count
+            )?;
+        }
+
+        writeln!(
+            out,
+            "# HELP fobserver_request_duration_seconds How long requests took to be answered, by route."
+        )?;
+        writeln!(out, "# TYPE fobserver_request_duration_seconds histogram")?;
+        for (route, histogram) in &series.latency {
+            let route = escape(route);
+
+            let mut cumulated = 0;
+            for (bound, count) in series.buckets.iter().zip(&histogram.counts) {
+                cumulated += count;
+                writeln!(
+                    out,
+                    "fobserver_request_duration_seconds_bucket{{route=\"{}\",le=\"{}\"}} {}",
+                    route, bound, cumulated
+                )?;
+            }
+            writeln!(
+                out,
+                "fobserver_request_duration_seconds_bucket{{route=\"{}\",le=\"+Inf\"}} {}",
+                route, histogram.count
+            )?;
+            writeln!(
+                out,
+                "fobserver_request_duration_seconds_sum{{route=\"{}\"}} {}",
+                route, histogram.sum
+            )?;
+            writeln!(
+                out,
+                "fobserver_request_duration_seconds_count{{route=\"{}\"}} {}",
+                route, histogram.count
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Stats {
+    /// Renders every metric of the server in the text exposition format.
+    fn render_metrics(&self) -> String {
+        let stats = self.snapshot();
+        let mut out = String::new();
+
+        let _ = self.metrics.render(&mut out);
+
+        let unlabeled = [
+            (
+                "fobserver_active_connections",
+                "gauge",
+                "The connections currently open.",
+                stats.active_connections as u64,
+            ),
+            (
+                "fobserver_inflight_requests",
+                "gauge",
+                "The requests currently being handled.",
+                stats.inflight_requests as u64,
+            ),
+            (
+                "fobserver_received_bytes_total",
+                "counter",
+                "The bytes received from clients.",
+                stats.bytes_read,
+            ),
+            (
+                "fobserver_sent_bytes_total",
+                "counter",
+                "The bytes sent to clients, headers included.",
+                stats.bytes_written,
+            ),
+        ];
+        for (name, kind, help, value) in unlabeled {
+            let _ = write!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+            );
+        }
+
+        out
+    }
+}
+
+/// Escapes a label value, as the exposition format requires.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
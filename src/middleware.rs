@@ -0,0 +1,242 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    args::Args,
+    http::{HTTPRequest, HTTPResponse, StatusCode},
+};
+
+mod bearer;
+mod cors;
+mod csrf;
+mod idempotency;
+mod ip_filter;
+mod rate_limit;
+mod security;
+mod session;
+
+pub use bearer::bearer_auth;
+pub use cors::{cors, AllowedOrigins, Cors};
+pub use csrf::{csrf, Csrf, CsrfStrategy};
+pub use idempotency::{idempotency, ConcurrentRequests, Idempotency, ScopeFunction};
+pub use ip_filter::ip_filter;
+pub use rate_limit::rate_limit;
+pub use security::{security_headers, FrameOptions, SecurityHeaders};
+pub use session::{sessions, SessionConfig};
+
+/// A layer wrapped around request handling.
+///
+/// Middlewares registered on a [`Router`](crate::router::Router) run in registration
+/// order for every request, before the route is even looked up. Each one receives the
+/// request and decides whether to pass it on by calling [`Next::run`] (possibly after
+/// modifying it) or to answer it directly; whatever it does after `next.run` returns
+/// acts on the response, which is how post-handler middlewares are written.
+///
+/// Any closure with the signature of [`Middleware::handle`] is a middleware.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     middleware::Next,
+///     router::Router,
+/// };
+///
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::ok())
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+/// router.add_middleware(
+///     |request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next| {
+///         let mut response = next.run(request, args)?;
+///         response.headers.set("X-Powered-By", "fobserver");
+///
+///         Ok(response)
+///     },
+/// );
+///
+/// let request = "GET / HTTP/1.1\r\n\r\n".parse()?;
+/// let response = router.dispatch(request, Arc::new(RwLock::new(Args::new())))?;
+///
+/// assert_eq!(response.headers.get("X-Powered-By"), Some("fobserver"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Handles `request`, usually by delegating to `next`.
+    ///
+    /// # Parameters
+    /// - `request`: The incoming HTTP request.
+    /// - `args`: The arguments shared across handlers.
+    /// - `next`: The rest of the chain, ending with the route's handler.
+    ///
+    /// # Returns
+    /// An `anyhow::Result<HTTPResponse>`, exactly like a handler.
+    fn handle(
+        &self,
+        request: HTTPRequest,
+        args: Arc<RwLock<Args>>,
+        next: Next<'_>,
+    ) -> anyhow::Result<HTTPResponse>;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(HTTPRequest, Arc<RwLock<Args>>, Next<'_>) -> anyhow::Result<HTTPResponse> + Send + Sync,
+{
+    fn handle(
+        &self,
+        request: HTTPRequest,
+        args: Arc<RwLock<Args>>,
+        next: Next<'_>,
+    ) -> anyhow::Result<HTTPResponse> {
+        self(request, args, next)
+    }
+}
+
+/// The remaining part of a middleware chain.
+pub struct Next<'a> {
+    middlewares: &'a [Arc<dyn Middleware>],
+    endpoint: &'a dyn Fn(HTTPRequest, Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse>,
+    error: &'a dyn Fn(StatusCode, &HTTPRequest) -> HTTPResponse,
+}
+
+impl<'a> Next<'a> {
+    /// Creates the chain made of `middlewares` followed by `endpoint`, whose errors
+    /// are rendered with `error`.
+    pub(crate) fn new(
+        middlewares: &'a [Arc<dyn Middleware>],
+        endpoint: &'a dyn Fn(HTTPRequest, Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse>,
+        error: &'a dyn Fn(StatusCode, &HTTPRequest) -> HTTPResponse,
+    ) -> Self {
+        Next {
+            middlewares,
+            endpoint,
+            error,
+        }
+    }
+
+    /// Creates the response refusing `request` with `status_code`, rendered like the
+    /// errors the server generates itself: in the format the request prefers, see
+    /// [`ErrorPages`](crate::error_page::ErrorPages), or as a problem document if the
+    /// server sends them.
+    ///
+    /// # Parameters
+    /// - `status_code`: The status code of the error.
+    /// - `request`: The request refused.
+    ///
+    /// # Returns
+    /// The response to send instead of passing the request on.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     sync::{Arc, RwLock},
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     limit::RequestRateLimiter,
+    ///     middleware::{rate_limit, Next},
+    ///     router::Router,
+    ///     testing::TestServer,
+    /// };
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+    /// router.add_route(http::Method::GET, "/admin", http::Version::V11, handler);
+    /// router.add_middleware(
+    ///     |request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next| {
+    ///         if request.path.starts_with("/admin") {
+    ///             return Ok(next.error(StatusCode::CODE403, &request));
+    ///         }
+    ///
+    ///         next.run(request, args)
+    ///     },
+    /// );
+    /// router.add_middleware(rate_limit(
+    ///     RequestRateLimiter::new(1, Duration::from_secs(60)),
+    ///     "/",
+    ///     |_: &HTTPRequest| Some("everyone".to_string()),
+    /// ));
+    ///
+    /// let server = TestServer::new(router, Args::new());
+    /// let get = |path: &str| {
+    ///     server.request(format!("GET {} HTTP/1.1\r\nAccept: application/json\r\n\r\n", path).parse()?)
+    /// };
+    ///
+    /// let response = get("/admin")?;
+    /// assert_eq!(response.status_code, StatusCode::CODE403);
+    /// assert_eq!(response.headers.get("Content-Type"), Some("application/json"));
+    /// assert_eq!(
+    ///     response.body.unwrap().as_bytes(),
+    ///     Some(&br#"{"status":403,"reason":"Forbidden","path":"/admin"}"#[..])
+    /// );
+    ///
+    /// // The middlewares of the crate refuse requests the same way
+    /// assert_eq!(get("/")?.status_code, StatusCode::CODE200);
+    /// let response = get("/")?;
+    /// assert_eq!(response.status_code, StatusCode::CODE429);
+    /// assert_eq!(response.headers.get("Content-Type"), Some("application/json"));
+    /// assert_eq!(response.headers.get("Retry-After"), Some("60"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn error(&self, status_code: StatusCode, request: &HTTPRequest) -> HTTPResponse {
+        (self.error)(status_code, request)
+    }
+
+    /// Passes the request to the next middleware, or to the handler if this was the last one.
+    ///
+    /// # Parameters
+    /// - `request`: The request to pass on.
+    /// - `args`: The arguments shared across handlers.
+    ///
+    /// # Returns
+    /// The response produced by the rest of the chain.
+    pub fn run(
+        self,
+        request: HTTPRequest,
+        args: Arc<RwLock<Args>>,
+    ) -> anyhow::Result<HTTPResponse> {
+        match self.middlewares.split_first() {
+            Some((middleware, middlewares)) => middleware.handle(
+                request,
+                args,
+                Next {
+                    middlewares,
+                    endpoint: self.endpoint,
+                    error: self.error,
+                },
+            ),
+            None => (self.endpoint)(request, args),
+        }
+    }
+}
+
+/// Returns `true` if `path` is under `prefix`, matching whole path segments.
+/// `prefix` has no trailing `/`, the empty prefix covering every path.
+fn covers(prefix: &str, path: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+}
+
+/// Returns the value of the cookie named `name`, if the request carries it.
+fn cookie(request: &HTTPRequest, name: &str) -> Option<String> {
+    request
+        .headers
+        .get_all("Cookie")
+        .flat_map(|header| header.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(cookie, _)| *cookie == name)
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
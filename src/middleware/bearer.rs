@@ -0,0 +1,113 @@
+use std::sync::{Arc, RwLock};
+
+use super::{Middleware, Next};
+use crate::{
+    args::Args,
+    http::{HTTPRequest, HTTPResponse},
+};
+
+/// Creates a middleware requiring an `Authorization: Bearer` token (RFC 6750) on every
+/// request.
+///
+/// `validator` checks the token and returns what it proves, e.g. the claims of a JSON
+/// Web Token, see `jwt::Hs256` behind the `jwt` feature. They are stored in the
+/// [extensions](crate::http::HTTPRequest::extensions) of the request for the
+/// handlers to read. Requests without a token are answered `401 Unauthorized` with a
+/// bare challenge; those whose token is refused get `error="invalid_token"` with the
+/// message of the validator's error as `error_description`, so it must not reveal
+/// more than the client may know.
+///
+/// # Parameters
+/// - `realm`: The protection space named in the challenges.
+/// - `validator`: Checks a token and returns its claims, or why it is refused.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     middleware::bearer_auth,
+///     router::Router,
+///     testing::TestServer,
+/// };
+///
+/// struct ApiKey(&'static str);
+///
+/// fn handler(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let key = request.extensions.get::<ApiKey>().unwrap();
+///
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(key.0.into());
+///
+///     Ok(response)
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+/// router.add_middleware(bearer_auth("api", |token| match token {
+///     "s3cr3t" => Ok(ApiKey("ci")),
+///     _ => anyhow::bail!("Unknown key"),
+/// }));
+///
+/// let server = TestServer::new(router, Args::new());
+///
+/// let response = server.get("/")?;
+/// assert_eq!(response.status_code, StatusCode::CODE401);
+/// assert_eq!(response.headers.get("WWW-Authenticate"), Some(r#"Bearer realm="api""#));
+///
+/// let response =
+///     server.request("GET / HTTP/1.1\r\nAuthorization: Bearer nope\r\n\r\n".parse()?)?;
+/// assert_eq!(
+///     response.headers.get("WWW-Authenticate"),
+///     Some(r#"Bearer realm="api", error="invalid_token", error_description="Unknown key""#)
+/// );
+///
+/// let response =
+///     server.request("GET / HTTP/1.1\r\nAuthorization: Bearer s3cr3t\r\n\r\n".parse()?)?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"ci"[..]));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn bearer_auth<C, F>(realm: &str, validator: F) -> impl Middleware
+where
+    C: Send + Sync + 'static,
+    F: Fn(&str) -> anyhow::Result<C> + Send + Sync,
+{
+    let realm = realm.to_string();
+
+    move |mut request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next<'_>| {
+        let Some(token) = request.bearer_token() else {
+            let challenge = HTTPResponse::unauthorized_bearer(&realm, None);
+
+            return Ok(refuse(&next, &request, challenge));
+        };
+
+        match validator(&token) {
+            Ok(claims) => {
+                request.extensions.insert(claims);
+
+                next.run(request, args)
+            }
+            Err(err) => {
+                log::info!("Bearer token refused for {}: {}", request.path, err);
+
+                let challenge = HTTPResponse::invalid_bearer_token(&realm, &err.to_string());
+
+                Ok(refuse(&next, &request, challenge))
+            }
+        }
+    }
+}
+
+/// Returns the error response refusing `request`, rendered by `next`, with the
+/// `WWW-Authenticate` header of `challenge`.
+fn refuse(next: &Next<'_>, request: &HTTPRequest, challenge: HTTPResponse) -> HTTPResponse {
+    let mut response = next.error(challenge.status_code, request);
+    if let Some(value) = challenge.headers.get("WWW-Authenticate") {
+        response.headers.set("WWW-Authenticate", value);
+    }
+
+    response
+}
@@ -0,0 +1,247 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use super::{Middleware, Next};
+use crate::{
+    args::Args,
+    http::{HTTPRequest, HTTPResponse, Method},
+};
+
+/// The origins allowed to make cross-origin requests.
+#[derive(Debug, Clone)]
+pub enum AllowedOrigins {
+    /// Every origin is allowed.
+    Any,
+    /// Only the listed origins are allowed. An entry can start its host with `*.` to
+    /// allow every subdomain, e.g. `https://*.example.com`.
+    List(Vec<String>),
+    /// The origins for which the predicate returns `true` are allowed.
+    Predicate(fn(&str) -> bool),
+}
+
+impl AllowedOrigins {
+    /// Returns `true` if `origin` is allowed.
+    fn allows(&self, origin: &str) -> bool {
+        match self {
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(origins) => {
+                origins
+                    .iter()
+                    .any(|allowed| match allowed.split_once("://*.") {
+                        Some((scheme, domain)) => origin
+                            .strip_prefix(scheme)
+                            .and_then(|rest| rest.strip_prefix("://"))
+                            .and_then(|host| host.strip_suffix(domain))
+                            .is_some_and(|subdomain| {
+                                subdomain.len() > 1 && subdomain.ends_with('.')
+                            }),
+                        None => allowed.eq_ignore_ascii_case(origin),
+                    })
+            }
+            AllowedOrigins::Predicate(predicate) => predicate(origin),
+        }
+    }
+}
+
+/// Configuration of the [`cors`] middleware.
+#[derive(Debug, Clone)]
+pub struct Cors {
+    /// The origins allowed to make requests.
+    pub origins: AllowedOrigins,
+    /// The methods allowed in preflight requests.
+    pub methods: Vec<Method>,
+    /// The request headers allowed in preflight requests. When empty, the headers
+    /// requested by the client are allowed.
+    pub headers: Vec<String>,
+    /// The response headers exposed to the client's scripts.
+    pub expose_headers: Vec<String>,
+    /// Allows requests carrying credentials (cookies, HTTP authentication). The
+    /// request's origin is then echoed instead of sending `*`.
+    pub credentials: bool,
+    /// How long the client can cache a preflight response.
+    pub max_age: Option<Duration>,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors {
+            origins: AllowedOrigins::Any,
+            methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ],
+            headers: Vec::new(),
+            expose_headers: Vec::new(),
+            credentials: false,
+            max_age: Some(Duration::from_secs(600)),
+        }
+    }
+}
+
+impl Cors {
+    /// Returns the `Access-Control-Allow-Origin` value for `origin`, if it is allowed.
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        match self.origins {
+            AllowedOrigins::Any if !self.credentials => Some("*".to_string()),
+            _ if self.origins.allows(origin) => Some(origin.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Adds the headers shared by preflight and actual responses.
+    fn add_common_headers(&self, response: &mut HTTPResponse, allow_origin: &str) {
+        response
+            .headers
+            .set("Access-Control-Allow-Origin", allow_origin);
+
+        if allow_origin != "*" {
+            response.add_vary("Origin");
+        }
+
+        if self.credentials {
+            response
+                .headers
+                .set("Access-Control-Allow-Credentials", "true");
+        }
+    }
+
+    /// Answers a preflight request.
+    fn preflight(&self, request: &HTTPRequest, origin: &str, method: &str) -> HTTPResponse {
+        let mut response = HTTPResponse::no_content();
+
+        let method_allowed = method
+            .parse::<Method>()
+            .is_ok_and(|method| self.methods.contains(&method));
+
+        if let (Some(allow_origin), true) = (self.allow_origin(origin), method_allowed) {
+            self.add_common_headers(&mut response, &allow_origin);
+
+            let methods = self
+                .methods
+                .iter()
+                .map(|method| method.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            response
+                .headers
+                .set("Access-Control-Allow-Methods", &methods);
+
+            let headers = match self.headers.is_empty() {
+                true => HTTPRequest::get_header(request, "Access-Control-Request-Headers"),
+                false => Some(self.headers.join(", ")),
+            };
+            if let Some(headers) = headers {
+                response
+                    .headers
+                    .set("Access-Control-Allow-Headers", &headers);
+            }
+
+            if let Some(max_age) = self.max_age {
+                response
+                    .headers
+                    .set("Access-Control-Max-Age", &max_age.as_secs().to_string());
+            }
+        }
+
+        response
+    }
+}
+
+/// Creates a middleware handling Cross-Origin Resource Sharing.
+///
+/// Preflight requests (`OPTIONS` with `Access-Control-Request-Method`) are answered
+/// directly with `204 No Content` and the allowed methods and headers, without
+/// reaching the router. Other requests carrying an `Origin` are handled normally and
+/// get `Access-Control-Allow-Origin` (plus `Vary: Origin` when the value depends on
+/// the origin) added to their response. Requests from disallowed origins are served
+/// without any CORS header, so the browser blocks them.
+///
+/// With the default configuration every origin is allowed and responses get
+/// `Access-Control-Allow-Origin: *`.
+///
+/// # Parameters
+/// - `config`: The CORS policy.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     middleware::{cors, AllowedOrigins, Cors},
+///     router::Router,
+/// };
+///
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::ok())
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/api", http::Version::V11, handler);
+/// router.add_middleware(cors(Cors {
+///     origins: AllowedOrigins::List(vec!["https://*.example.com".to_string()]),
+///     credentials: true,
+///     ..Cors::default()
+/// }));
+///
+/// let args = Arc::new(RwLock::new(Args::new()));
+/// let send = |request: &str| router.dispatch(request.parse()?, args.clone());
+///
+/// // Preflight
+/// let response = send(
+///     "OPTIONS /api HTTP/1.1\r\nOrigin: https://app.example.com\r\n\
+///      Access-Control-Request-Method: PUT\r\nAccess-Control-Request-Headers: X-Token\r\n\r\n",
+/// )?;
+/// assert_eq!(response.status_code, StatusCode::CODE204);
+/// assert_eq!(response.headers.get("Access-Control-Allow-Origin"), Some("https://app.example.com"));
+/// assert_eq!(response.headers.get("Access-Control-Allow-Headers"), Some("X-Token"));
+/// assert_eq!(response.headers.get("Access-Control-Allow-Credentials"), Some("true"));
+///
+/// // Credentialed request from an allowed origin
+/// let response = send("GET /api HTTP/1.1\r\nOrigin: https://app.example.com\r\n\r\n")?;
+/// assert_eq!(response.headers.get("Access-Control-Allow-Origin"), Some("https://app.example.com"));
+/// assert_eq!(response.headers.get("Vary"), Some("Origin"));
+///
+/// // Disallowed origin
+/// let response = send("GET /api HTTP/1.1\r\nOrigin: https://evil.com\r\n\r\n")?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.headers.get("Access-Control-Allow-Origin"), None);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn cors(config: Cors) -> impl Middleware {
+    move |request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next<'_>| {
+        let origin = match HTTPRequest::get_header(&request, "Origin") {
+            Some(origin) => origin,
+            None => return next.run(request, args),
+        };
+
+        if request.method == Method::OPTIONS {
+            if let Some(method) = HTTPRequest::get_header(&request, "Access-Control-Request-Method")
+            {
+                return Ok(config.preflight(&request, &origin, &method));
+            }
+        }
+
+        let mut response = next.run(request, args)?;
+
+        if let Some(allow_origin) = config.allow_origin(&origin) {
+            config.add_common_headers(&mut response, &allow_origin);
+
+            if !config.expose_headers.is_empty() {
+                response.headers.set(
+                    "Access-Control-Expose-Headers",
+                    &config.expose_headers.join(", "),
+                );
+            }
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::{Arc, RwLock};
+
+use super::{Middleware, Next};
+use crate::{
+    args::Args,
+    csrf::{self, CsrfToken},
+    hmac,
+    http::{HTTPRequest, Method, StatusCode},
+    session::Session,
+};
+
+/// Where the [`csrf`] middleware keeps the token requests are checked against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsrfStrategy {
+    /// In the session, see [`csrf::token_for`]. Needs the
+    /// [`sessions`](super::sessions) middleware registered before this one.
+    Session,
+    /// In a cookie the client sends back along with the token (double-submit
+    /// cookie), no session needed. The cookie isn't `HttpOnly`, so that scripts can
+    /// copy it into the header.
+    DoubleSubmitCookie {
+        /// The name of the cookie.
+        cookie_name: String,
+        /// Only lets the cookie be sent over HTTPS.
+        secure: bool,
+    },
+}
+
+/// Configuration of the [`csrf`] middleware.
+#[derive(Debug, Clone)]
+pub struct Csrf {
+    /// Where the token is kept.
+    pub strategy: CsrfStrategy,
+    /// The request header carrying the token.
+    pub header: String,
+    /// The field of `application/x-www-form-urlencoded` bodies carrying the token.
+    pub field: String,
+}
+
+impl Default for Csrf {
+    fn default() -> Self {
+        Csrf {
+            strategy: CsrfStrategy::Session,
+            header: "X-CSRF-Token".to_string(),
+            field: "csrf_token".to_string(),
+        }
+    }
+}
+
+impl Csrf {
+    /// Returns the token sent with `request`, from the header or else the form.
+    fn submitted(&self, request: &HTTPRequest) -> Option<String> {
+        if let Some(token) = request.headers.get(&self.header) {
+            return Some(token.trim().to_string());
+        }
+
+        let content_type = request.headers.get("Content-Type")?;
+        if !content_type
+            .trim()
+            .to_ascii_lowercase()
+            .starts_with("application/x-www-form-urlencoded")
+        {
+            return None;
+        }
+
+        // Tokens are hexadecimal, they are never percent-encoded
+        request
+            .body
+            .as_deref()?
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(name, _)| *name == self.field)
+            .map(|(_, token)| token.to_string())
+    }
+}
+
+/// Creates a middleware protecting against cross-site request forgery.
+///
+/// `POST`, `PUT`, `PATCH` and `DELETE` requests must carry the expected token, in
+/// the header or the form field of `config`, or they are answered `403 Forbidden`.
+/// Other requests pass through, and get a token created if they have none yet so
+/// that the pages they render can embed it; it is found in the extensions of the
+/// request as a [`CsrfToken`]. Tokens are compared in constant time.
+///
+/// # Parameters
+/// - `config`: Where the token is kept and how it is sent.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     csrf::CsrfToken,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     middleware::{csrf, sessions, Csrf, CsrfStrategy, SessionConfig},
+///     router::Router,
+///     session::MemoryStore,
+///     testing::TestServer,
+/// };
+///
+/// fn form(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let token = request.extensions.get::<CsrfToken>().unwrap();
+///
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(token.0.clone().into());
+///
+///     Ok(response)
+/// }
+///
+/// fn transfer(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::no_content())
+/// }
+///
+/// for strategy in [
+///     CsrfStrategy::Session,
+///     CsrfStrategy::DoubleSubmitCookie {
+///         cookie_name: "csrf".to_string(),
+///         secure: false,
+///     },
+/// ] {
+///     let mut router = Router::new();
+///     router.add_route(http::Method::GET, "/form", http::Version::V11, form);
+///     router.add_route(http::Method::POST, "/transfer", http::Version::V11, transfer);
+///     router.add_middleware(sessions(Arc::new(MemoryStore::new()), SessionConfig::default()));
+///     router.add_middleware(csrf(Csrf {
+///         strategy,
+///         ..Csrf::default()
+///     }));
+///
+///     let server = TestServer::new(router, Args::new());
+///
+///     // The page gets a token, and the cookie remembering it
+///     let response = server.get("/form")?;
+///     let cookie = response.headers.get("Set-Cookie").unwrap().split(';').next().unwrap().to_string();
+///     let token = String::from_utf8(response.body.unwrap().as_bytes().unwrap().to_vec())?;
+///
+///     let post = |headers: &str, body: &str| -> anyhow::Result<StatusCode> {
+///         let request = format!(
+///             "POST /transfer HTTP/1.1\r\nCookie: {}\r\n{}Content-Length: {}\r\n\r\n{}",
+///             cookie,
+///             headers,
+///             body.len(),
+///             body
+///         );
+///
+///         Ok(server.request(request.parse()?)?.status_code)
+///     };
+///     let form = "Content-Type: application/x-www-form-urlencoded\r\n";
+///
+///     // Accepted from the form or the header
+///     assert_eq!(post(form, &format!("amount=10&csrf_token={}", token))?, StatusCode::CODE204);
+///     assert_eq!(post(&format!("X-CSRF-Token: {}\r\n", token), "")?, StatusCode::CODE204);
+///
+///     // Missing token
+///     assert_eq!(post(form, "amount=10")?, StatusCode::CODE403);
+///
+///     // Wrong token
+///     assert_eq!(post(form, &format!("csrf_token={}", "0".repeat(64)))?, StatusCode::CODE403);
+///     assert_eq!(post("X-CSRF-Token: guess\r\n", "")?, StatusCode::CODE403);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn csrf(config: Csrf) -> impl Middleware {
+    move |mut request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next<'_>| {
+        let unsafe_method = matches!(
+            request.method,
+            Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+        );
+
+        let (expected, new_cookie) = match &config.strategy {
+            CsrfStrategy::Session => {
+                let Some(session) = request.extensions.get::<Session>() else {
+                    anyhow::bail!("The csrf middleware needs the sessions middleware before it");
+                };
+
+                match unsafe_method {
+                    true => (session.get::<String>(csrf::SESSION_KEY), None),
+                    false => (Some(csrf::token_for(session)), None),
+                }
+            }
+            CsrfStrategy::DoubleSubmitCookie { cookie_name, .. } => {
+                match (super::cookie(&request, cookie_name), unsafe_method) {
+                    (Some(token), _) => (Some(token), None),
+                    (None, true) => (None, None),
+                    (None, false) => {
+                        let token = csrf::new_token();
+                        (Some(token.clone()), Some(token))
+                    }
+                }
+            }
+        };
+
+        if unsafe_method {
+            let valid = match (&expected, config.submitted(&request)) {
+                (Some(expected), Some(submitted)) => {
+                    hmac::constant_time_eq(expected.as_bytes(), submitted.as_bytes())
+                }
+                _ => false,
+            };
+
+            if !valid {
+                log::info!("Request to {} refused, invalid CSRF token", request.path);
+
+                return Ok(next.error(StatusCode::CODE403, &request));
+            }
+        }
+
+        if let Some(token) = expected {
+            request.extensions.insert(CsrfToken(token));
+        }
+
+        let mut response = next.run(request, args)?;
+
+        if let (
+            Some(token),
+            CsrfStrategy::DoubleSubmitCookie {
+                cookie_name,
+                secure,
+            },
+        ) = (new_cookie, &config.strategy)
+        {
+            let mut cookie = format!("{}={}; Path=/; SameSite=Strict", cookie_name, token);
+            if *secure {
+                cookie.push_str("; Secure");
+            }
+
+            response.headers.append("Set-Cookie", &cookie);
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{Middleware, Next};
+use crate::{
+    args::Args,
+    http::{HTTPRequest, Method, StatusCode},
+    idempotency::{self, IdempotencyStore, Record, StoredResponse},
+};
+
+/// How often a request waiting for a concurrent one with the same key checks on it.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// What the [`idempotency`] middleware does with a request whose key is used by a
+/// request still being handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcurrentRequests {
+    /// Answers `409 Conflict` right away.
+    Reject,
+    /// Waits up to the given time for the other request to finish and replays its
+    /// response, answering `409 Conflict` if it doesn't finish in time.
+    Wait(Duration),
+}
+
+/// Returns who sent a request, the scope of its idempotency key, see
+/// [`Idempotency::scope`].
+pub type ScopeFunction = Arc<dyn Fn(&HTTPRequest) -> String + Send + Sync>;
+
+/// Configuration of the [`idempotency`] middleware.
+#[derive(Clone)]
+pub struct Idempotency {
+    /// The header carrying the key.
+    pub header: String,
+    /// Returns who a key belongs to, e.g. the address of the client or an API key:
+    /// the same key sent by two clients stands for two different requests. Defaults
+    /// to the address of the client.
+    pub scope: ScopeFunction,
+    /// How long keys and their responses are remembered.
+    pub ttl: Duration,
+    /// How long a key stays reserved while its request is handled, in case the
+    /// process dies before releasing it. Handlers running longer may see a retry
+    /// handled concurrently.
+    pub reservation_ttl: Duration,
+    /// What to do with concurrent requests with the same key.
+    pub concurrent: ConcurrentRequests,
+}
+
+impl fmt::Debug for Idempotency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Idempotency")
+            .field("header", &self.header)
+            .field("ttl", &self.ttl)
+            .field("reservation_ttl", &self.reservation_ttl)
+            .field("concurrent", &self.concurrent)
+            .finish()
+    }
+}
+
+impl Default for Idempotency {
+    fn default() -> Self {
+        Idempotency {
+            header: "Idempotency-Key".to_string(),
+            scope: Arc::new(|request: &HTTPRequest| {
+                request
+                    .addr
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default()
+            }),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            reservation_ttl: Duration::from_secs(60),
+            concurrent: ConcurrentRequests::Reject,
+        }
+    }
+}
+
+/// Creates a middleware replaying the response to `POST`, `PUT`, `PATCH` and `DELETE`
+/// requests under `prefix` made again with the same idempotency key, instead of
+/// handling them twice.
+///
+/// Keys are scoped by [`Idempotency::scope`], clients can't replay the responses of
+/// each other. The first request with a key is handled and its response stored in
+/// `store`, along with a fingerprint of the request: its method, path and body. A
+/// request with the same key and fingerprint gets the stored response back, with an
+/// `Idempotent-Replayed: true` header, without calling the handler; one with the
+/// same key but another fingerprint is answered `422 Unprocessable Content`.
+/// Requests made while the first is being handled are answered as
+/// [`Idempotency::concurrent`] says. Responses with a `5xx` status code, streamed
+/// bodies or a failed or panicking handler aren't stored, the key can be used again.
+/// Requests without the header aren't affected. `prefix` matches whole path segments, like in
+/// [`ip_filter`](super::ip_filter).
+///
+/// # Parameters
+/// - `store`: Where the keys and responses are kept.
+/// - `config`: The header, how long keys last and how to handle concurrent requests.
+/// - `prefix`: The paths the middleware applies to.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     sync::{
+///         atomic::{AtomicUsize, Ordering},
+///         Arc, RwLock,
+///     },
+///     thread,
+///     time::Duration,
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     idempotency::MemoryStore,
+///     middleware::{idempotency, Idempotency},
+///     router::Router,
+///     testing::TestServer,
+/// };
+///
+/// static PAYMENTS: AtomicUsize = AtomicUsize::new(0);
+///
+/// fn pay(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     if request.body.as_deref() == Some("crash") && PAYMENTS.load(Ordering::SeqCst) == 3 {
+///         panic!("payment provider unreachable");
+///     }
+///     let id = PAYMENTS.fetch_add(1, Ordering::SeqCst) + 1;
+///     thread::sleep(Duration::from_millis(50));
+///
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(format!("payment {}", id).into());
+///     response.headers.set("Location", &format!("/payments/{}", id));
+///
+///     Ok(response)
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::POST, "/payments", http::Version::V11, pay);
+/// router.add_middleware(idempotency(
+///     Arc::new(MemoryStore::new()),
+///     Idempotency {
+///         ttl: Duration::from_millis(500),
+///         scope: Arc::new(|request: &HTTPRequest| {
+///             request.headers.get("X-Api-Key").unwrap_or_default().to_string()
+///         }),
+///         ..Idempotency::default()
+///     },
+///     "/payments",
+/// ));
+///
+/// let server = TestServer::new(router, Args::new());
+/// let pay_as = |client: &str, key: &str, body: &str| {
+///     server.request(
+///         format!(
+///             "POST /payments HTTP/1.1\r\nX-Api-Key: {}\r\nIdempotency-Key: {}\r\nContent-Length: {}\r\n\r\n{}",
+///             client,
+///             key,
+///             body.len(),
+///             body
+///         )
+///         .parse()?,
+///     )
+/// };
+/// let pay = |key: &str, body: &str| pay_as("alice", key, body);
+///
+/// let first = pay("k1", "10 EUR")?;
+/// assert_eq!(first.status_code, StatusCode::CODE200);
+///
+/// // The retry gets the same response, without paying twice
+/// let replay = pay("k1", "10 EUR")?;
+/// assert_eq!(replay.status_code, StatusCode::CODE200);
+/// assert_eq!(replay.headers.get("Location"), Some("/payments/1"));
+/// assert_eq!(replay.headers.get("Idempotent-Replayed"), Some("true"));
+/// assert_eq!(replay.body.unwrap().as_bytes(), first.body.unwrap().as_bytes());
+/// assert_eq!(PAYMENTS.load(Ordering::SeqCst), 1);
+///
+/// // Reusing the key for another payment is refused
+/// assert_eq!(pay("k1", "99 EUR")?.status_code, StatusCode::CODE422);
+///
+/// // The same key from another client is another payment
+/// let other = pay_as("bob", "k1", "99 EUR")?;
+/// assert_eq!(other.status_code, StatusCode::CODE200);
+/// assert_eq!(other.headers.get("Location"), Some("/payments/2"));
+/// assert_eq!(other.headers.get("Idempotent-Replayed"), None);
+///
+/// // Only one of two concurrent requests is handled
+/// thread::scope(|scope| -> anyhow::Result<()> {
+///     let handle = scope.spawn(|| pay("k2", "5 EUR"));
+///     thread::sleep(Duration::from_millis(20));
+///
+///     assert_eq!(pay("k2", "5 EUR")?.status_code, StatusCode::CODE409);
+///     assert_eq!(handle.join().unwrap()?.status_code, StatusCode::CODE200);
+///     Ok(())
+/// })?;
+/// assert_eq!(PAYMENTS.load(Ordering::SeqCst), 3);
+///
+/// // A handler panicking doesn't leave the key reserved
+/// assert_eq!(pay("k3", "crash")?.status_code, StatusCode::CODE500);
+/// assert_eq!(pay("k3", "crash")?.status_code, StatusCode::CODE500);
+/// assert_eq!(PAYMENTS.load(Ordering::SeqCst), 3);
+///
+/// // Once expired, the key can be used again
+/// thread::sleep(Duration::from_millis(600));
+/// assert_eq!(pay("k1", "99 EUR")?.status_code, StatusCode::CODE200);
+/// assert_eq!(PAYMENTS.load(Ordering::SeqCst), 4);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn idempotency(
+    store: Arc<dyn IdempotencyStore>,
+    config: Idempotency,
+    prefix: &str,
+) -> impl Middleware {
+    let prefix = prefix.trim_end_matches('/').to_string();
+
+    move |request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next<'_>| {
+        let unsafe_method = matches!(
+            request.method,
+            Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+        );
+        if !unsafe_method || !super::covers(&prefix, &request.path) {
+            return next.run(request, args);
+        }
+        let Some(key) = request.headers.get(&config.header) else {
+            return next.run(request, args);
+        };
+        let key = scoped(&(config.scope)(&request), key);
+
+        let fingerprint = idempotency::fingerprint(&request)?;
+
+        let started = Instant::now();
+        while let Some(record) = store.reserve(&key, &fingerprint, config.reservation_ttl)? {
+            if record.fingerprint != fingerprint {
+                log::info!("Idempotency key {} reused for another request", key);
+
+                return Ok(next.error(StatusCode::CODE422, &request));
+            }
+
+            if let Some(response) = &record.response {
+                let mut response = response.to_response();
+                response.headers.set("Idempotent-Replayed", "true");
+
+                return Ok(response);
+            }
+
+            match config.concurrent {
+                ConcurrentRequests::Wait(timeout) if started.elapsed() < timeout => {
+                    thread::sleep(POLL_INTERVAL);
+                }
+                _ => return Ok(next.error(StatusCode::CODE409, &request)),
+            }
+        }
+
+        // Released if the handler fails or panics
+        let mut reservation = Reservation {
+            store: &*store,
+            key: &key,
+            held: true,
+        };
+        let response = next.run(request, args)?;
+
+        let stored = StoredResponse::from_response(&response)
+            .filter(|stored| stored.status_code.code() < 500);
+        match stored {
+            Some(stored) => {
+                let record = Record {
+                    fingerprint,
+                    response: Some(stored),
+                };
+                store.complete(&key, record, config.ttl)?;
+                reservation.held = false;
+            }
+            None => reservation.release()?,
+        }
+
+        Ok(response)
+    }
+}
+
+/// A key reserved in an [`IdempotencyStore`] while its request is handled, released
+/// when dropped unless the response was stored.
+struct Reservation<'a> {
+    store: &'a dyn IdempotencyStore,
+    key: &'a str,
+    /// Whether the key still has to be released.
+    held: bool,
+}
+
+impl Reservation<'_> {
+    /// Releases the key now, reporting if the store can't be written.
+    fn release(mut self) -> anyhow::Result<()> {
+        self.held = false;
+
+        self.store.release(self.key)
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.held {
+            if let Err(err) = self.store.release(self.key) {
+                log::error!("Failed to release idempotency key {}: {}", self.key, err);
+            }
+        }
+    }
+}
+
+/// Returns the key `key` sent by `scope` is stored under, the length of `scope`
+/// telling where it ends.
+fn scoped(scope: &str, key: &str) -> String {
+    format!("{}:{}{}", scope.len(), scope, key)
+}
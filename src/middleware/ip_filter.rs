@@ -0,0 +1,86 @@
+use std::sync::{Arc, RwLock};
+
+use super::{Middleware, Next};
+use crate::{
+    args::Args,
+    http::{HTTPRequest, StatusCode},
+    ip::IpFilter,
+};
+
+/// Creates a middleware answering `403 Forbidden` to the requests under `prefix` made
+/// by clients the filter doesn't allow.
+///
+/// `prefix` matches whole path segments: `/admin` covers `/admin` and `/admin/users`
+/// but not `/administrator`, and `/` covers every path. Requests received on Unix
+/// sockets carry no address and are always allowed. To refuse clients before they
+/// send anything, use [`Server::set_ip_filter`](crate::Server::set_ip_filter)
+/// instead.
+///
+/// # Parameters
+/// - `filter`: The clients allowed. `forbidden_response` is ignored, refused requests
+///   are always answered.
+/// - `prefix`: The paths the filter applies to.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     ip::IpFilter,
+///     middleware::ip_filter,
+///     router::Router,
+///     testing::TestServer,
+/// };
+///
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::ok())
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+/// router.add_route(http::Method::GET, "/admin/users", http::Version::V11, handler);
+/// router.add_middleware(ip_filter(
+///     IpFilter {
+///         allow: vec!["10.0.0.0/8".parse()?],
+///         ..IpFilter::default()
+///     },
+///     "/admin",
+/// ));
+///
+/// let server = TestServer::new(router, Args::new());
+/// let request = |path: &str, addr: &str| -> anyhow::Result<HTTPResponse> {
+///     let mut request: HTTPRequest = format!("GET {} HTTP/1.1\r\n\r\n", path).parse()?;
+///     request.addr = Some(addr.parse()?);
+///
+///     server.request(request)
+/// };
+///
+/// let response = request("/admin/users", "192.168.1.1")?;
+/// assert_eq!(response.status_code, StatusCode::CODE403);
+///
+/// let response = request("/admin/users", "10.0.0.1")?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+///
+/// // Other paths are open to everyone
+/// let response = request("/", "192.168.1.1")?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn ip_filter(filter: IpFilter, prefix: &str) -> impl Middleware {
+    let prefix = prefix.trim_end_matches('/').to_string();
+
+    move |request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next<'_>| {
+        let covered = super::covers(&prefix, &request.path);
+
+        match request.addr {
+            Some(ip) if covered && !filter.allows(ip) => {
+                log::info!("Request from {} to {} filtered", ip, request.path);
+
+                Ok(next.error(StatusCode::CODE403, &request))
+            }
+            _ => next.run(request, args),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::{Arc, RwLock};
+
+use super::{Middleware, Next};
+use crate::{
+    args::Args,
+    http::{HTTPRequest, HTTPResponse, StatusCode},
+    limit::{RateLimitStatus, RequestRateLimiter},
+    retry_after_seconds,
+};
+
+/// Creates a middleware limiting the requests under `prefix` with `limiter`, each
+/// request counting against the key `key` returns for it.
+///
+/// Requests over the limit are answered `429 Too Many Requests` with a `Retry-After`
+/// header. Every limited response, refused or not, carries the `RateLimit-Limit`,
+/// `RateLimit-Remaining` and `RateLimit-Reset` headers, the reset being the seconds
+/// until the bucket is full again. Requests for which `key` returns `None` aren't
+/// limited. `prefix` matches whole path segments, like in
+/// [`ip_filter`](super::ip_filter).
+///
+/// # Parameters
+/// - `limiter`: The buckets of the keys.
+/// - `prefix`: The paths the limit applies to.
+/// - `key`: Returns what a request is limited by, e.g. the address of the client or
+///   an API key.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     sync::{Arc, Mutex, RwLock},
+///     time::{Duration, Instant},
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     limit::RequestRateLimiter,
+///     middleware::rate_limit,
+///     router::Router,
+///     testing::TestServer,
+/// };
+///
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::ok())
+/// }
+///
+/// let now = Arc::new(Mutex::new(Instant::now()));
+/// let clock = now.clone();
+/// let limiter = RequestRateLimiter::new(2, Duration::from_secs(10))
+///     .clock(move || *clock.lock().unwrap());
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+/// router.add_route(http::Method::GET, "/api/items", http::Version::V11, handler);
+/// router.add_middleware(rate_limit(limiter, "/api", |request: &HTTPRequest| {
+///     request.headers.get("X-Api-Key").map(str::to_string)
+/// }));
+///
+/// let server = TestServer::new(router, Args::new());
+/// let request = |path: &str| server.request(format!("GET {} HTTP/1.1\r\nX-Api-Key: fob\r\n\r\n", path).parse()?);
+///
+/// let response = request("/api/items")?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.headers.get("RateLimit-Limit"), Some("2"));
+/// assert_eq!(response.headers.get("RateLimit-Remaining"), Some("1"));
+/// assert_eq!(response.headers.get("RateLimit-Reset"), Some("10"));
+///
+/// assert_eq!(request("/api/items")?.headers.get("RateLimit-Remaining"), Some("0"));
+///
+/// let response = request("/api/items")?;
+/// assert_eq!(response.status_code, StatusCode::CODE429);
+/// assert_eq!(response.headers.get("Retry-After"), Some("10"));
+/// assert_eq!(response.headers.get("RateLimit-Remaining"), Some("0"));
+/// assert_eq!(response.headers.get("RateLimit-Reset"), Some("20"));
+///
+/// // Other paths aren't limited
+/// let response = request("/")?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.headers.get("RateLimit-Limit"), None);
+///
+/// // A token comes back after 10 seconds
+/// *now.lock().unwrap() += Duration::from_secs(10);
+/// assert_eq!(request("/api/items")?.status_code, StatusCode::CODE200);
+/// assert_eq!(request("/api/items")?.status_code, StatusCode::CODE429);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn rate_limit<F>(limiter: RequestRateLimiter, prefix: &str, key: F) -> impl Middleware
+where
+    F: Fn(&HTTPRequest) -> Option<String> + Send + Sync + 'static,
+{
+    let prefix = prefix.trim_end_matches('/').to_string();
+
+    move |request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next<'_>| {
+        if !super::covers(&prefix, &request.path) {
+            return next.run(request, args);
+        }
+        let Some(key) = key(&request) else {
+            return next.run(request, args);
+        };
+
+        let status = limiter.acquire(&key);
+
+        let mut response = match status.retry_after {
+            Some(retry_after) => {
+                log::info!("Request from {} to {} rate limited", key, request.path);
+
+                let mut response = next.error(StatusCode::CODE429, &request);
+                response
+                    .headers
+                    .set("Retry-After", &retry_after_seconds(retry_after));
+
+                response
+            }
+            None => next.run(request, args)?,
+        };
+        set_headers(&mut response, &status);
+
+        Ok(response)
+    }
+}
+
+/// Sets the `RateLimit-*` headers of `response` from `status`.
+fn set_headers(response: &mut HTTPResponse, status: &RateLimitStatus) {
+    response
+        .headers
+        .set("RateLimit-Limit", &status.limit.to_string());
+    response
+        .headers
+        .set("RateLimit-Remaining", &status.remaining.to_string());
+    response
+        .headers
+        .set("RateLimit-Reset", &retry_after_seconds(status.reset));
+}
@@ -0,0 +1,260 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use super::{Middleware, Next};
+use crate::{args::Args, http::HTTPRequest};
+
+/// The framing policy sent through `X-Frame-Options` and the CSP `frame-ancestors` directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameOptions {
+    /// The page can't be displayed in a frame at all.
+    Deny,
+    /// The page can only be framed by pages of the same origin.
+    SameOrigin,
+}
+
+/// Configuration of the hardening headers added by [`security_headers`].
+///
+/// Every header can be disabled individually; the defaults send `nosniff`, deny
+/// framing and use the `strict-origin-when-cross-origin` referrer policy.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::{TcpListener, TcpStream},
+///     sync::{Arc, RwLock},
+///     thread,
+///     time::Duration,
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     middleware::{security_headers, SecurityHeaders},
+///     router::Router,
+///     Server,
+/// };
+///
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     Ok(HTTPResponse::ok())
+/// }
+///
+/// fn router() -> Router {
+///     let mut router = Router::new();
+///     router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+///     router.add_middleware(security_headers(SecurityHeaders {
+///         hsts: Some(Duration::from_secs(31_536_000)),
+///         hsts_include_subdomains: true,
+///         ..SecurityHeaders::default()
+///     }));
+///
+///     router
+/// }
+///
+/// // Over plain HTTP, HSTS isn't sent
+/// let server = Server::new("127.0.0.1:0", router(), Args::new())?.start_background()?;
+/// let mut stream = TcpStream::connect(server.addr()?)?;
+/// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response)?;
+/// assert!(response.contains("X-Content-Type-Options: nosniff"));
+/// assert!(!response.contains("Strict-Transport-Security"));
+/// server.shutdown();
+/// server.join()?;
+///
+/// // Over TLS, it is
+/// #[cfg(feature = "tls")]
+/// {
+///     use fobserver::tls::TlsConfig;
+///
+///     let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+///     let tls = TlsConfig {
+///         cert_chain_pem: certified.cert.pem().into_bytes(),
+///         private_key_pem: certified.key_pair.serialize_pem().into_bytes(),
+///         client_auth: None,
+///     };
+///
+///     let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+///     let mut server = Server::new_tls(&addr.to_string(), router(), Args::new(), tls)?;
+///     let handle = server.shutdown_handle();
+///     let server = thread::spawn(move || server.start());
+///
+///     let mut roots = rustls::RootCertStore::empty();
+///     roots.add(certified.cert.der().clone())?;
+///     let config = rustls::ClientConfig::builder()
+///         .with_root_certificates(roots)
+///         .with_no_client_auth();
+///     let connection =
+///         rustls::ClientConnection::new(Arc::new(config), "localhost".try_into()?)?;
+///
+///     let mut stream = rustls::StreamOwned::new(connection, TcpStream::connect(addr)?);
+///     stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response)?;
+///     assert!(response.contains("X-Content-Type-Options: nosniff"));
+///     assert!(response.contains("Strict-Transport-Security: max-age=31536000; includeSubDomains"));
+///
+///     handle.shutdown();
+///     server.join().unwrap()?;
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityHeaders {
+    /// Sends `X-Content-Type-Options: nosniff`.
+    pub content_type_options: bool,
+    /// Sends `X-Frame-Options` and the matching CSP `frame-ancestors` directive.
+    pub frame_options: Option<FrameOptions>,
+    /// The value of the `Referrer-Policy` header.
+    pub referrer_policy: Option<String>,
+    /// The `max-age` of the `Strict-Transport-Security` header, only sent on requests
+    /// received over TLS since browsers ignore it on plain-HTTP responses.
+    pub hsts: Option<Duration>,
+    /// Adds `includeSubDomains` to `Strict-Transport-Security`.
+    pub hsts_include_subdomains: bool,
+    /// The value of the `Content-Security-Policy` header.
+    pub content_security_policy: Option<String>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        SecurityHeaders {
+            content_type_options: true,
+            frame_options: Some(FrameOptions::Deny),
+            referrer_policy: Some("strict-origin-when-cross-origin".to_string()),
+            hsts: None,
+            hsts_include_subdomains: false,
+            content_security_policy: None,
+        }
+    }
+}
+
+impl SecurityHeaders {
+    /// Returns the configured headers as `(name, value)` pairs, but
+    /// `Strict-Transport-Security`, see [`SecurityHeaders::hsts`].
+    fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+
+        if self.content_type_options {
+            headers.push(("X-Content-Type-Options", "nosniff".to_string()));
+        }
+
+        let frame_ancestors = match self.frame_options {
+            Some(FrameOptions::Deny) => {
+                headers.push(("X-Frame-Options", "DENY".to_string()));
+                Some("frame-ancestors 'none'")
+            }
+            Some(FrameOptions::SameOrigin) => {
+                headers.push(("X-Frame-Options", "SAMEORIGIN".to_string()));
+                Some("frame-ancestors 'self'")
+            }
+            None => None,
+        };
+
+        if let Some(policy) = &self.referrer_policy {
+            headers.push(("Referrer-Policy", policy.clone()));
+        }
+
+        let policy = match (&self.content_security_policy, frame_ancestors) {
+            (Some(policy), Some(ancestors)) if !policy.contains("frame-ancestors") => Some(
+                format!("{}; {}", policy.trim_end_matches([';', ' ']), ancestors),
+            ),
+            (Some(policy), _) => Some(policy.clone()),
+            (None, Some(ancestors)) => Some(ancestors.to_string()),
+            (None, None) => None,
+        };
+
+        if let Some(policy) = policy {
+            headers.push(("Content-Security-Policy", policy));
+        }
+
+        headers
+    }
+
+    /// Returns the value of the `Strict-Transport-Security` header, if configured.
+    fn hsts(&self) -> Option<String> {
+        let max_age = self.hsts?;
+
+        let mut value = format!("max-age={}", max_age.as_secs());
+        if self.hsts_include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+
+        Some(value)
+    }
+}
+
+/// Creates a middleware adding the configured security headers to every response.
+///
+/// Headers already set by the handler are left untouched, so individual routes can
+/// override any of them. `Strict-Transport-Security` is only added to the responses
+/// of requests received over TLS.
+///
+/// # Parameters
+/// - `config`: The headers to send.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     middleware::{security_headers, SecurityHeaders},
+///     router::Router,
+///     testing::TestServer,
+/// };
+///
+/// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.headers.set("X-Frame-Options", "SAMEORIGIN");
+///
+///     Ok(response)
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+/// router.add_middleware(security_headers(SecurityHeaders {
+///     referrer_policy: None,
+///     content_security_policy: Some("default-src 'self'".to_string()),
+///     ..SecurityHeaders::default()
+/// }));
+///
+/// let response = TestServer::new(router, Args::new()).get("/")?;
+///
+/// assert_eq!(response.headers.get("X-Content-Type-Options"), Some("nosniff"));
+/// assert_eq!(response.headers.get("X-Frame-Options"), Some("SAMEORIGIN"));
+/// assert_eq!(response.headers.get("Referrer-Policy"), None);
+/// assert_eq!(response.headers.get("Strict-Transport-Security"), None);
+/// assert_eq!(
+///     response.headers.get("Content-Security-Policy"),
+///     Some("default-src 'self'; frame-ancestors 'none'")
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn security_headers(config: SecurityHeaders) -> impl Middleware {
+    let headers = config.headers();
+    let hsts = config.hsts();
+
+    move |request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next<'_>| {
+        let tls = request.is_tls();
+        let mut response = next.run(request, args)?;
+
+        for (name, value) in &headers {
+            if !response.headers.contains(name) {
+                response.headers.set(name, value);
+            }
+        }
+
+        if let Some(hsts) = hsts.as_ref().filter(|_| tls) {
+            if !response.headers.contains("Strict-Transport-Security") {
+                response.headers.set("Strict-Transport-Security", hsts);
+            }
+        }
+
+        Ok(response)
+    }
+}
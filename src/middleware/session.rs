@@ -0,0 +1,184 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use super::{Middleware, Next};
+use crate::{
+    args::Args,
+    http::HTTPRequest,
+    session::{Session, SessionStore},
+};
+
+/// Configuration of the [`sessions`] middleware.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// The name of the cookie carrying the session id.
+    pub cookie_name: String,
+    /// How long a session lives without requests; every request made with it
+    /// extends it by as much.
+    pub ttl: Duration,
+    /// The path the cookie is sent for.
+    pub path: String,
+    /// Only lets the cookie be sent over HTTPS.
+    pub secure: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            cookie_name: "session".to_string(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            path: "/".to_string(),
+            secure: false,
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Returns the `Set-Cookie` value giving the client the session `id`, or removing
+    /// its cookie if `id` is `None`.
+    fn cookie(&self, id: Option<&str>) -> String {
+        let max_age = match id {
+            Some(_) => self.ttl.as_secs(),
+            None => 0,
+        };
+
+        let mut cookie = format!(
+            "{}={}; Path={}; Max-Age={}; HttpOnly; SameSite=Lax",
+            self.cookie_name,
+            id.unwrap_or_default(),
+            self.path,
+            max_age
+        );
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+
+        cookie
+    }
+}
+
+/// Creates a middleware giving every request a [`Session`], kept in `store` and
+/// identified by a cookie.
+///
+/// The session is loaded from the id in the cookie and stored in the
+/// [extensions](crate::http::HTTPRequest::extensions) of the request; ids the store
+/// doesn't know are ignored and a new session is started. Once the handler returns,
+/// the session is saved and the cookie refreshed, `HttpOnly` and `SameSite=Lax`. New
+/// sessions are only saved once written to. Failing to read or write the store
+/// fails the request.
+///
+/// # Parameters
+/// - `store`: Where the sessions are kept.
+/// - `config`: The cookie and lifetime of the sessions.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     middleware::{sessions, SessionConfig},
+///     router::Router,
+///     session::{MemoryStore, Session},
+///     testing::TestServer,
+/// };
+///
+/// fn login(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let session = request.extensions.get::<Session>().unwrap();
+///     session.renew();
+///     session.insert("user", "fob");
+///     session.insert("visits", 0);
+///
+///     Ok(HTTPResponse::no_content())
+/// }
+///
+/// fn home(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let session = request.extensions.get::<Session>().unwrap();
+///     let Some(user) = session.get::<String>("user") else {
+///         return Ok(HTTPResponse::no_content());
+///     };
+///     let visits = session.get::<u32>("visits").unwrap_or_default() + 1;
+///     session.insert("visits", visits);
+///
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(format!("{} {}", user, visits).into());
+///
+///     Ok(response)
+/// }
+///
+/// let store = Arc::new(MemoryStore::new());
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::POST, "/login", http::Version::V11, login);
+/// router.add_route(http::Method::GET, "/", http::Version::V11, home);
+/// router.add_middleware(sessions(store.clone(), SessionConfig::default()));
+///
+/// let server = TestServer::new(router, Args::new());
+///
+/// // A planted id isn't taken over
+/// let response = server.request(
+///     "POST /login HTTP/1.1\r\nCookie: session=chosen-by-attacker\r\n\r\n".parse()?,
+/// )?;
+/// let cookie = response.headers.get("Set-Cookie").unwrap();
+/// assert!(cookie.ends_with("; Path=/; Max-Age=86400; HttpOnly; SameSite=Lax"));
+/// assert!(!cookie.contains("chosen-by-attacker"));
+///
+/// // Replaying the cookie gives the session back
+/// let session = cookie.split(';').next().unwrap();
+/// let response = server.request(format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", session).parse()?)?;
+/// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"fob 1"[..]));
+///
+/// // Logging in again renews the id, the old one stops working
+/// let response = server.request(format!("POST /login HTTP/1.1\r\nCookie: {}\r\n\r\n", session).parse()?)?;
+/// assert!(!response.headers.get("Set-Cookie").unwrap().starts_with(session));
+/// let response = server.request(format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", session).parse()?)?;
+/// assert_eq!(response.status_code, StatusCode::CODE204);
+/// assert!(response.headers.get("Set-Cookie").unwrap().contains("Max-Age=0"));
+///
+/// // Reading alone doesn't create a session
+/// let response = server.get("/")?;
+/// assert_eq!(response.status_code, StatusCode::CODE204);
+/// assert_eq!(response.headers.get("Set-Cookie"), None);
+/// assert_eq!(store.len(), 1);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn sessions(store: Arc<dyn SessionStore>, config: SessionConfig) -> impl Middleware {
+    move |mut request: HTTPRequest, args: Arc<RwLock<Args>>, next: Next<'_>| {
+        let cookie = super::cookie(&request, &config.cookie_name);
+
+        let session = match &cookie {
+            Some(id) => match store.get(id)? {
+                Some(data) => Session::load(id.clone(), data),
+                None => Session::default(),
+            },
+            None => Session::default(),
+        };
+        request.extensions.insert(session.clone());
+
+        let mut response = next.run(request, args)?;
+
+        let state = session.take();
+        for id in &state.stale {
+            store.delete(id)?;
+        }
+
+        match &state.id {
+            Some(id) => {
+                store.set(id, state.data, config.ttl)?;
+                response
+                    .headers
+                    .append("Set-Cookie", &config.cookie(Some(id)));
+            }
+            // The client holds an id that is no longer valid
+            None if cookie.is_some() => {
+                response.headers.append("Set-Cookie", &config.cookie(None));
+            }
+            None => {}
+        }
+
+        Ok(response)
+    }
+}
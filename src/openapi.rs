@@ -0,0 +1,384 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    args::Args,
+    http::{HTTPRequest, HTTPResponse, Method, StatusCode, Version},
+    router::{Handler, Route, Router},
+};
+
+/// The version of the OpenAPI specification the documents follow.
+const OPENAPI_VERSION: &str = "3.0.3";
+
+/// The general information about an API, heading its OpenAPI description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiInfo {
+    /// The name of the API.
+    pub title: String,
+    /// The version of the API, not of the specification.
+    pub version: String,
+    /// What the API is for.
+    pub description: Option<String>,
+}
+
+impl ApiInfo {
+    /// Creates the information of the API `title` at `version`, without description.
+    pub fn new(title: &str, version: &str) -> Self {
+        ApiInfo {
+            title: title.to_string(),
+            version: version.to_string(),
+            description: None,
+        }
+    }
+}
+
+/// The metadata describing a route in the OpenAPI description of its router.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RouteDoc {
+    summary: Option<String>,
+    description: Option<String>,
+    request_content_types: Vec<String>,
+    response_content_types: Vec<String>,
+    /// The descriptions of the parameters, by name.
+    params: Vec<(String, String)>,
+}
+
+impl Route {
+    /// Sets the short summary of what the route does, for its
+    /// [OpenAPI description](Router::openapi).
+    ///
+    /// # Parameters
+    /// - `summary`: The summary.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn summary(&mut self, summary: &str) -> &mut Self {
+        self.doc.summary = Some(summary.to_string());
+
+        self
+    }
+
+    /// Sets the longer explanation of what the route does, for its
+    /// [OpenAPI description](Router::openapi).
+    ///
+    /// # Parameters
+    /// - `description`: The description, CommonMark is allowed.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn description(&mut self, description: &str) -> &mut Self {
+        self.doc.description = Some(description.to_string());
+
+        self
+    }
+
+    /// Declares a content type the route accepts in request bodies, for its
+    /// [OpenAPI description](Router::openapi). Can be called several times.
+    ///
+    /// # Parameters
+    /// - `content_type`: The media type, e.g. `application/json`.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn request_content_type(&mut self, content_type: &str) -> &mut Self {
+        self.doc
+            .request_content_types
+            .push(content_type.to_string());
+
+        self
+    }
+
+    /// Declares a content type the route answers with, for its
+    /// [OpenAPI description](Router::openapi). Can be called several times.
+    ///
+    /// # Parameters
+    /// - `content_type`: The media type, e.g. `application/json`.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn response_content_type(&mut self, content_type: &str) -> &mut Self {
+        self.doc
+            .response_content_types
+            .push(content_type.to_string());
+
+        self
+    }
+
+    /// Describes the parameter `name`, for the [OpenAPI description](Router::openapi)
+    /// of the route: a path parameter if the path has a `:name` segment, a query
+    /// parameter otherwise.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the parameter.
+    /// - `description`: What the parameter is for.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn param(&mut self, name: &str, description: &str) -> &mut Self {
+        self.doc
+            .params
+            .push((name.to_string(), description.to_string()));
+
+        self
+    }
+}
+
+impl Router {
+    /// Describes the routes of the router as an OpenAPI 3.0 JSON document.
+    ///
+    /// Every route appears under its path, `:name` segments becoming `{name}` path
+    /// parameters, with the metadata set on it with [`Route::summary`],
+    /// [`Route::description`], [`Route::request_content_type`],
+    /// [`Route::response_content_type`] and [`Route::param`]. Routes without
+    /// metadata get a minimal operation. `CONNECT` routes, which OpenAPI can't
+    /// describe, are left out, and routes registered for several HTTP versions appear
+    /// once.
+    ///
+    /// # Parameters
+    /// - `info`: The general information about the API.
+    ///
+    /// # Returns
+    /// The document, pretty-printed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     openapi::ApiInfo,
+    ///     router::Router,
+    /// };
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router
+    ///     .add_route(http::Method::GET, "/users", http::Version::V11, handler)
+    ///     .summary("List the users")
+    ///     .param("page", "The page of results")
+    ///     .response_content_type("application/json");
+    /// router
+    ///     .add_route(http::Method::POST, "/users", http::Version::V11, handler)
+    ///     .summary("Create a user")
+    ///     .description("The user gets an id, returned in the `Location` header.")
+    ///     .request_content_type("application/json");
+    /// router
+    ///     .add_route(http::Method::GET, "/users/:id/posts/:post", http::Version::V11, handler)
+    ///     .param("id", "The id of the user");
+    /// router.add_route(http::Method::DELETE, "/users/:id", http::Version::V11, handler);
+    /// router.add_route(http::Method::DELETE, "/users/:id", http::Version::V10, handler);
+    /// router.add_route(http::Method::GET, "/health", http::Version::V11, handler);
+    ///
+    /// let info = ApiInfo {
+    ///     description: Some("The users of the shop".to_string()),
+    ///     ..ApiInfo::new("Users", "1.2.0")
+    /// };
+    ///
+    /// let golden = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/openapi.json"));
+    /// assert_eq!(router.openapi(&info), golden.trim_end().replace("\r\n", "\n"));
+    ///
+    /// // `:id` segments are required path parameters
+    /// let document: serde_json::Value = serde_json::from_str(&router.openapi(&info))?;
+    /// assert!(document["paths"].get("/users/:id").is_none());
+    /// let parameter = &document["paths"]["/users/{id}"]["delete"]["parameters"][0];
+    /// assert_eq!(parameter["name"], "id");
+    /// assert_eq!(parameter["in"], "path");
+    /// assert_eq!(parameter["required"], true);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn openapi(&self, info: &ApiInfo) -> String {
+        let mut paths: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+
+        for (method, path, route) in self.routes() {
+            if method == Method::CONNECT {
+                continue;
+            }
+
+            let (template, names) = path_template(path);
+            paths
+                .entry(template)
+                .or_default()
+                .entry(method.to_string().to_lowercase())
+                .or_insert_with(|| operation(&route.doc, &names));
+        }
+
+        let mut info_object = json!({
+            "title": info.title,
+            "version": info.version,
+        });
+        if let Some(description) = &info.description {
+            info_object["description"] = json!(description);
+        }
+
+        let document = json!({
+            "openapi": OPENAPI_VERSION,
+            "info": info_object,
+            "paths": paths,
+        });
+
+        serde_json::to_string_pretty(&document).unwrap_or_default()
+    }
+
+    /// Adds a `GET` route serving the [OpenAPI description](Router::openapi) of the
+    /// router at `path`.
+    ///
+    /// The description is generated once, now: routes added afterwards, this one
+    /// included, don't appear in it.
+    ///
+    /// # Parameters
+    /// - `path`: The path to serve the description at, e.g. `/openapi.json`.
+    /// - `info`: The general information about the API.
+    ///
+    /// # Returns
+    /// A mutable reference to the route, to set it up further.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     openapi::ApiInfo,
+    ///     router::Router,
+    ///     testing::TestServer,
+    /// };
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/items/:id", http::Version::V11, handler);
+    /// router.serve_openapi("/docs/openapi.json", &ApiInfo::new("Items", "1.0.0"));
+    ///
+    /// let server = TestServer::new(router, Args::new());
+    /// let response = server.get("/docs/openapi.json")?;
+    /// assert_eq!(response.status_code, StatusCode::CODE200);
+    /// assert_eq!(response.headers.get("Content-Type"), Some("application/json"));
+    ///
+    /// let document: serde_json::Value = serde_json::from_slice(response.body.unwrap().as_bytes().unwrap())?;
+    /// assert_eq!(document["paths"]["/items/{id}"]["get"]["parameters"][0]["name"], "id");
+    /// assert!(document["paths"].get("/docs/openapi.json").is_none());
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn serve_openapi(&mut self, path: &str, info: &ApiInfo) -> &mut Route {
+        let document = self.openapi(info);
+
+        let handler = move |_: HTTPRequest, _: Arc<RwLock<Args>>| {
+            let mut response = HTTPResponse {
+                status_code: StatusCode::CODE200,
+                body: Some(document.clone().into()),
+                ..HTTPResponse::default()
+            };
+            response.headers.set("Content-Type", "application/json");
+
+            Ok(response)
+        };
+
+        self.insert(
+            Method::GET,
+            path,
+            Version::V11,
+            Handler::Closure(Arc::new(handler)),
+        )
+    }
+}
+
+/// Turns the `:name` segments of `path` into `{name}` ones.
+///
+/// # Returns
+/// The path in OpenAPI syntax, and the names of its parameters in order.
+fn path_template(path: &str) -> (String, Vec<String>) {
+    let mut names = Vec::new();
+
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) if !name.is_empty() => {
+                names.push(name.to_string());
+                format!("{{{}}}", name)
+            }
+            _ => segment.to_string(),
+        })
+        .collect();
+
+    (segments.join("/"), names)
+}
+
+/// Describes a route with `doc` as an OpenAPI operation, its path having the
+/// parameters `names`.
+fn operation(doc: &RouteDoc, names: &[String]) -> Value {
+    let mut operation = Map::new();
+
+    if let Some(summary) = &doc.summary {
+        operation.insert("summary".to_string(), json!(summary));
+    }
+    if let Some(description) = &doc.description {
+        operation.insert("description".to_string(), json!(description));
+    }
+
+    let describe = |name: &str| {
+        doc.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, description)| description.clone())
+    };
+    let parameter = |name: &str, location: &str| {
+        let mut parameter = json!({
+            "name": name,
+            "in": location,
+            "required": location == "path",
+            "schema": { "type": "string" },
+        });
+        if let Some(description) = describe(name) {
+            parameter["description"] = json!(description);
+        }
+
+        parameter
+    };
+
+    let mut parameters: Vec<Value> = names.iter().map(|name| parameter(name, "path")).collect();
+    parameters.extend(
+        doc.params
+            .iter()
+            .filter(|(name, _)| !names.contains(name))
+            .map(|(name, _)| parameter(name, "query")),
+    );
+    if !parameters.is_empty() {
+        operation.insert("parameters".to_string(), Value::Array(parameters));
+    }
+
+    if !doc.request_content_types.is_empty() {
+        operation.insert(
+            "requestBody".to_string(),
+            json!({ "content": content(&doc.request_content_types) }),
+        );
+    }
+
+    let mut response = json!({ "description": "Response" });
+    if !doc.response_content_types.is_empty() {
+        response["content"] = content(&doc.response_content_types);
+    }
+    operation.insert("responses".to_string(), json!({ "default": response }));
+
+    Value::Object(operation)
+}
+
+/// Describes the media types `content_types`, without schemas.
+fn content(content_types: &[String]) -> Value {
+    let content: Map<String, Value> = content_types
+        .iter()
+        .map(|content_type| (content_type.clone(), json!({})))
+        .collect();
+
+    Value::Object(content)
+}
@@ -0,0 +1,430 @@
+use std::{
+    cmp::min,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    http::{BodySource, HTTPRequest},
+    random, ServerConfig,
+};
+
+/// Tracks a request being received, independently of how its bytes are read.
+///
+/// The transport reads into a buffer for as long as [`RequestParser::read_timeout`]
+/// asks for more data, hands every piece to [`RequestParser::feed`], then takes the
+/// request out of the buffer with [`RequestParser::finish`].
+///
+/// Bodies larger than the spool threshold of the server are written to a temporary
+/// file as they arrive, only the headers and the bytes following the body are kept
+/// in the buffer.
+pub(crate) struct RequestParser {
+    /// The length of the request, known once its headers are complete.
+    expected: Option<usize>,
+    /// The length of the headers, once complete.
+    head_len: usize,
+    /// When the headers, then the body, must have been received.
+    deadline: Option<Instant>,
+    /// The file the body is written to, if it is spooled.
+    spool: Option<Spool>,
+}
+
+impl RequestParser {
+    /// Starts receiving a request, which may already be partly in `buffer`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the parser, or an error if the buffered headers
+    /// are malformed.
+    pub(crate) fn new(buffer: &mut Vec<u8>, config: &ServerConfig) -> anyhow::Result<Self> {
+        let deadline = match buffer.is_empty() {
+            true => None,
+            false => deadline(config.header_timeout),
+        };
+        let mut parser = RequestParser {
+            expected: None,
+            head_len: 0,
+            deadline,
+            spool: None,
+        };
+        parser.parse_head(buffer, config)?;
+
+        Ok(parser)
+    }
+
+    /// Tells whether more data must be read, and how long to wait for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The bytes received so far.
+    /// * `idle` - How long to wait for the first byte of the request.
+    /// * `config` - The settings of the server.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the request is complete, or the timeout of the next read.
+    /// Fails with [`RequestLineTooLong`] or [`RequestTooLarge`] if the request line or
+    /// the request exceeds the configured size, or with a timeout once a deadline has
+    /// passed.
+    pub(crate) fn read_timeout(
+        &self,
+        buffer: &[u8],
+        idle: Option<Duration>,
+        config: &ServerConfig,
+    ) -> anyhow::Result<Option<Option<Duration>>> {
+        // Checked on its own, a long request line must not be buffered up to the
+        // size of a whole request
+        if request_line_too_long(buffer, config.max_request_line_bytes) {
+            return Err(RequestLineTooLong.into());
+        }
+
+        // Refuse before buffering more than allowed, spooled bodies were checked when
+        // the spool was created
+        let buffered = match self.spool {
+            Some(_) => self.head_len,
+            None => self.expected.unwrap_or(buffer.len()),
+        };
+        if buffered > config.max_request_bytes {
+            return Err(RequestTooLarge.into());
+        }
+
+        if self
+            .expected
+            .is_some_and(|expected| self.received(buffer) >= expected)
+        {
+            return Ok(None);
+        }
+
+        if buffer.is_empty() {
+            return Ok(Some(idle));
+        }
+
+        // Once the request has started, no read may outlast the deadline
+        let timeout = match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+
+                if remaining.is_zero() {
+                    return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+                }
+
+                Some(
+                    config
+                        .read_timeout
+                        .map_or(remaining, |timeout| timeout.min(remaining)),
+                )
+            }
+            None => config.read_timeout,
+        };
+
+        Ok(Some(timeout))
+    }
+
+    /// Appends `data` read from the client to `buffer`, or to the spooled body.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating whether the headers received so far are valid,
+    /// or failing with [`SpoolFailed`] if the body can't be written to disk.
+    pub(crate) fn feed(
+        &mut self,
+        buffer: &mut Vec<u8>,
+        data: &[u8],
+        config: &ServerConfig,
+    ) -> anyhow::Result<()> {
+        if buffer.is_empty() {
+            self.deadline = deadline(config.header_timeout);
+        }
+        config.stats.read(data.len());
+
+        let rest = match (&mut self.spool, self.expected) {
+            (Some(spool), Some(expected)) => {
+                let remaining = expected - self.head_len - spool.written as usize;
+                let (body, rest) = data.split_at(min(remaining, data.len()));
+                spool.write(body)?;
+
+                rest
+            }
+            _ => data,
+        };
+        buffer.extend_from_slice(rest);
+
+        if self.expected.is_none() {
+            self.parse_head(buffer, config)?;
+        }
+
+        Ok(())
+    }
+
+    /// Looks for the end of the headers in `buffer`, starting to spool the body if
+    /// it is over the threshold.
+    fn parse_head(&mut self, buffer: &mut Vec<u8>, config: &ServerConfig) -> anyhow::Result<()> {
+        let Some((head_len, body_len)) = request_len(buffer)? else {
+            return Ok(());
+        };
+
+        // Checked before any arithmetic, the client chooses the length
+        let spooled = config.spool_threshold.is_some_and(|threshold| {
+            body_len > threshold as u64 && body_len <= config.max_spooled_bytes
+        });
+        if !spooled && body_len > config.max_request_bytes as u64 {
+            return Err(RequestTooLarge.into());
+        }
+        let expected = usize::try_from(body_len)
+            .ok()
+            .and_then(|body_len| head_len.checked_add(body_len))
+            .ok_or(RequestTooLarge)?;
+
+        self.expected = Some(expected);
+        self.head_len = head_len;
+        self.deadline = deadline(config.body_timeout);
+
+        if spooled {
+            let mut spool = Spool::create(&config.spool_dir)?;
+            let end = min(expected, buffer.len());
+
+            spool.write(&buffer[head_len..end])?;
+            buffer.drain(head_len..end);
+            self.spool = Some(spool);
+        }
+
+        Ok(())
+    }
+
+    /// Returns how many bytes of the request have been received.
+    fn received(&self, buffer: &[u8]) -> usize {
+        buffer.len()
+            + self
+                .spool
+                .as_ref()
+                .map_or(0, |spool| spool.written as usize)
+    }
+
+    /// Takes the request out of `buffer`, leaving the bytes following it.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the parsed `HTTPRequest`, `None` if nothing was
+    /// received, or an error if the request is malformed.
+    pub(crate) fn finish(self, buffer: &mut Vec<u8>) -> anyhow::Result<Option<HTTPRequest>> {
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+
+        if self
+            .expected
+            .is_some_and(|expected| self.received(buffer) < expected)
+        {
+            return Err(IncompleteBody.into());
+        }
+
+        if let Some(spool) = self.spool {
+            let mut request: HTTPRequest =
+                String::from_utf8_lossy(&buffer[..self.head_len]).parse()?;
+            buffer.drain(..self.head_len);
+            request.body_source = Some(spool.finish()?);
+
+            return Ok(Some(request));
+        }
+
+        let len = self
+            .expected
+            .map_or(buffer.len(), |expected| min(expected, buffer.len()));
+        // Parsed in place, the buffer keeps its allocation for the next request
+        let request = String::from_utf8_lossy(&buffer[..len]).parse()?;
+        buffer.drain(..len);
+
+        Ok(Some(request))
+    }
+}
+
+/// Computes the length of the headers and of the body of the request starting at the
+/// beginning of `data`.
+///
+/// # Returns
+///
+/// Returns `None` if the headers are not complete yet, or an error if they are
+/// malformed, including a `Content-Length` that isn't a number or disagrees with
+/// another one: guessing would read the body as the next request.
+fn request_len(data: &[u8]) -> anyhow::Result<Option<(usize, u64)>> {
+    let head_len = match data.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(position) => position + 4,
+        None => match data.windows(2).position(|w| w == b"\n\n") {
+            Some(position) => position + 2,
+            None => return Ok(None),
+        },
+    };
+
+    let head: HTTPRequest = String::from_utf8_lossy(&data[..head_len]).parse()?;
+    if head.headers.contains("Transfer-Encoding") {
+        return Err(UnsupportedTransferEncoding.into());
+    }
+
+    let body_len = match head.headers.contains("Content-Length") {
+        true => head
+            .headers
+            .content_length()
+            .ok_or_else(|| anyhow::anyhow!("Invalid Content-Length"))?,
+        false => 0,
+    };
+
+    Ok(Some((head_len, body_len)))
+}
+
+/// Tells whether the request line at the start of `data` is longer than `max` bytes,
+/// not counting its line ending, even if it isn't complete yet.
+fn request_line_too_long(data: &[u8], max: usize) -> bool {
+    // Only the bytes that can belong to an acceptable line are scanned
+    let scanned = &data[..min(data.len(), max.saturating_add(2))];
+
+    match scanned.iter().position(|&byte| byte == b'\n') {
+        Some(end) => end - usize::from(end > 0 && data[end - 1] == b'\r') > max,
+        None => data.len() > max.saturating_add(1),
+    }
+}
+
+/// Returns the instant `timeout` from now, if any.
+fn deadline(timeout: Option<Duration>) -> Option<Instant> {
+    timeout.map(|timeout| Instant::now() + timeout)
+}
+
+/// The error returned when a request exceeds the configured maximum size.
+#[derive(Debug)]
+pub(crate) struct RequestTooLarge;
+
+impl fmt::Display for RequestTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The request exceeds the maximum size")
+    }
+}
+
+impl std::error::Error for RequestTooLarge {}
+
+/// The error returned when the connection is closed before the whole body was received.
+#[derive(Debug)]
+pub(crate) struct IncompleteBody;
+
+impl fmt::Display for IncompleteBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The connection closed before the end of the body")
+    }
+}
+
+impl std::error::Error for IncompleteBody {}
+
+/// The error returned when a request body is sent with a transfer coding, such as
+/// `chunked`, which the server doesn't decode.
+#[derive(Debug)]
+pub(crate) struct UnsupportedTransferEncoding;
+
+impl fmt::Display for UnsupportedTransferEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Request bodies with a Transfer-Encoding aren't supported"
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedTransferEncoding {}
+
+/// The error returned when a request line exceeds the configured maximum length.
+#[derive(Debug)]
+pub(crate) struct RequestLineTooLong;
+
+impl fmt::Display for RequestLineTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The request line exceeds the maximum length")
+    }
+}
+
+impl std::error::Error for RequestLineTooLong {}
+
+/// A request body being written to a temporary file.
+struct Spool {
+    file: BufWriter<File>,
+    path: TempFile,
+    /// How many bytes of the body were written.
+    written: u64,
+}
+
+impl Spool {
+    /// Creates a new temporary file in `dir`.
+    fn create(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(format!("fobserver-{}.body", random::hex(16)));
+        let file = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(SpoolFailed)?;
+
+        Ok(Spool {
+            file: BufWriter::new(file),
+            path: TempFile(Some(path)),
+            written: 0,
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.file.write_all(data).map_err(SpoolFailed)?;
+        self.written += data.len() as u64;
+
+        Ok(())
+    }
+
+    /// Flushes the file and hands it over to the request.
+    fn finish(mut self) -> anyhow::Result<BodySource> {
+        self.file.flush().map_err(SpoolFailed)?;
+
+        let path = self.path.0.take().unwrap_or_default();
+
+        Ok(BodySource::File(path, self.written))
+    }
+}
+
+/// A temporary file, deleted when dropped.
+pub(crate) struct TempFile(Option<PathBuf>);
+
+impl TempFile {
+    /// Takes charge of the file the body of `request` was spooled to, if any.
+    pub(crate) fn of(request: &HTTPRequest) -> Option<Self> {
+        request
+            .body_source
+            .as_ref()
+            .map(|BodySource::File(path, _)| TempFile(Some(path.clone())))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let Some(path) = self.0.take() else {
+            return;
+        };
+
+        // Handlers may have moved the file to keep it
+        if let Err(err) = fs::remove_file(&path) {
+            if err.kind() != io::ErrorKind::NotFound {
+                log::warn!("Failed to delete {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// The error returned when a request body can't be written to its temporary file.
+#[derive(Debug)]
+pub(crate) struct SpoolFailed(io::Error);
+
+impl fmt::Display for SpoolFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to spool the request body: {}", self.0)
+    }
+}
+
+impl std::error::Error for SpoolFailed {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// A fixed set of worker threads processing items from a bounded queue.
+///
+/// Workers stop once the pool is dropped and the queue is empty; the pool doesn't
+/// wait for them.
+pub(crate) struct WorkerPool<T> {
+    sender: SyncSender<T>,
+}
+
+impl<T: Send + 'static> WorkerPool<T> {
+    /// Spawns `size` workers calling `handler` on every queued item.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The number of worker threads, at least one.
+    /// * `queue_depth` - How many items can wait for a free worker.
+    /// * `thread` - Prepares the thread of the worker with the given index.
+    /// * `handler` - The function processing each item.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the pool, or an error if no thread can be spawned.
+    /// Workers failing to spawn are logged, the pool runs with the others.
+    pub(crate) fn new<B, F>(
+        size: usize,
+        queue_depth: usize,
+        thread: B,
+        handler: F,
+    ) -> anyhow::Result<Self>
+    where
+        B: Fn(usize) -> thread::Builder,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let handler = Arc::new(handler);
+        let mut spawned = 0;
+        let mut failure = None;
+
+        for n in 0..size.max(1) {
+            let receiver = receiver.clone();
+            let handler = handler.clone();
+
+            match thread(n).spawn(move || WorkerPool::work(&receiver, &*handler)) {
+                Ok(_) => spawned += 1,
+                Err(err) => {
+                    log::error!("Failed to spawn worker {}: {}", n, err);
+                    failure = Some(err);
+                }
+            }
+        }
+
+        match failure {
+            Some(err) if spawned == 0 => {
+                Err(anyhow::Error::new(err).context("Failed to spawn any worker"))
+            }
+            Some(_) => {
+                log::warn!("Running with {} of {} workers", spawned, size.max(1));
+
+                Ok(WorkerPool { sender })
+            }
+            None => Ok(WorkerPool { sender }),
+        }
+    }
+
+    /// Runs the loop of a single worker.
+    fn work<F: Fn(T)>(receiver: &Mutex<Receiver<T>>, handler: &F) {
+        loop {
+            // The lock is released as soon as an item is received
+            let item = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => return,
+            };
+
+            match item {
+                Ok(item) => {
+                    // A panicking handler must not take the worker down with it
+                    if panic::catch_unwind(AssertUnwindSafe(|| handler(item))).is_err() {
+                        log::error!("A worker panicked while handling a connection");
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Queues `item`, waiting for room if the queue is full.
+    pub(crate) fn execute(&self, item: T) -> anyhow::Result<()> {
+        self.sender
+            .send(item)
+            .map_err(|_| anyhow::anyhow!("Every worker has stopped"))
+    }
+
+    /// Queues `item` if there is room for it.
+    ///
+    /// # Returns
+    ///
+    /// Returns the item back if the queue is full or every worker has stopped.
+    pub(crate) fn try_execute(&self, item: T) -> Result<(), T> {
+        self.sender.try_send(item).map_err(|err| match err {
+            TrySendError::Full(item) | TrySendError::Disconnected(item) => item,
+        })
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Cursor, Read, Write},
+    time::Duration,
+};
+
+use crate::{
+    client::{self, Chunked},
+    http::{Body, BodySource, HTTPRequest, HTTPResponse, HeaderMap, Method, StatusCode, Version},
+};
+
+/// How long connecting to the upstream server may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the upstream server may stay silent while sending its response.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The headers describing a single connection, never forwarded by a proxy.
+const HOP_BY_HOP: [&str; 7] = [
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Connection",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+/// Forwards `request` to the HTTP server at `upstream` and answers with its response.
+///
+/// The request is sent with `Host` set to `upstream`, the address of the client
+/// appended to `X-Forwarded-For` and `X-Forwarded-Proto` set to `http` unless the
+/// request already carries one, e.g. set by a handler behind TLS. The response body
+/// is streamed back to the client as it is received. Hop-by-hop headers, like
+/// `Connection` or `Upgrade` and those listed in `Connection`, are stripped in both
+/// directions. With the `trace-context` feature, the upstream server gets a
+/// `traceparent` header naming a child of the
+/// [trace context](crate::http::HTTPRequest::trace_context) of the request.
+///
+/// An upstream server that can't be reached or sends a malformed response is
+/// answered with `502 Bad Gateway`, one that doesn't answer in time with
+/// `504 Gateway Timeout`. A new connection is opened for every request.
+///
+/// # Arguments
+///
+/// * `request` - The request to forward.
+/// * `upstream` - The address of the upstream server, e.g. `127.0.0.1:8080`.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `HTTPResponse` of the upstream server, or the
+/// one describing why it couldn't be reached.
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::{TcpListener, TcpStream},
+///     sync::{Arc, OnceLock, RwLock},
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     proxy,
+///     router::Router,
+///     Server,
+/// };
+///
+/// fn upstream(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.headers.set("Upgrade", "h2c").set("X-Upstream", "yes");
+///     response.body = Some(
+///         format!(
+///             "{} {:?} {:?} {:?} {:?}",
+///             request.headers.get("Host").unwrap_or_default(),
+///             request.headers.get("X-Forwarded-For"),
+///             request.headers.get("X-Forwarded-Proto"),
+///             request.headers.get("X-Private"),
+///             request.body,
+///         )
+///         .into(),
+///     );
+///
+///     Ok(response)
+/// }
+///
+/// static UPSTREAM: OnceLock<String> = OnceLock::new();
+///
+/// fn ingress(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     proxy::proxy_to(request, UPSTREAM.get().unwrap())
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::POST, "/echo", http::Version::V11, upstream);
+/// let backend = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+/// UPSTREAM.get_or_init(|| backend.addr().unwrap().to_string());
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::POST, "/echo", http::Version::V11, ingress);
+/// let front = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+///
+/// let mut stream = TcpStream::connect(front.addr()?)?;
+/// stream.write_all(
+///     b"POST /echo HTTP/1.1\r\nHost: example.com\r\nConnection: close, X-Private\r\n\
+///       X-Private: secret\r\nContent-Length: 5\r\n\r\nhello",
+/// )?;
+///
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response)?;
+///
+/// assert!(response.starts_with("HTTP/1.1 200 OK"));
+/// assert!(response.contains("X-Upstream: yes"));
+/// assert!(!response.contains("Upgrade"));
+/// assert!(response.contains(&format!(
+///     "{} Some(\"127.0.0.1\") Some(\"http\") None Some(\"hello\")",
+///     UPSTREAM.get().unwrap()
+/// )));
+///
+/// // Nothing listens on the upstream anymore
+/// let closed = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+/// let request: HTTPRequest = "GET / HTTP/1.1\r\n\r\n".parse()?;
+///
+/// let response = proxy::proxy_to(request, &closed.to_string())?;
+/// assert_eq!(response.status_code, StatusCode::CODE502);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn proxy_to(request: HTTPRequest, upstream: &str) -> anyhow::Result<HTTPResponse> {
+    let head = request.method == Method::HEAD;
+    let request = forwarded(request, upstream);
+
+    match exchange(&request, upstream, head) {
+        Ok(response) => Ok(response),
+        Err(err) => {
+            log::warn!("Failed to proxy request to {}: {}", upstream, err);
+
+            Ok(match err.kind() {
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                    HTTPResponse::plain_text(StatusCode::CODE504, "Gateway Timeout")
+                }
+                _ => HTTPResponse::plain_text(StatusCode::CODE502, "Bad Gateway"),
+            })
+        }
+    }
+}
+
+/// Prepares `request` to be sent to `upstream`.
+fn forwarded(mut request: HTTPRequest, upstream: &str) -> HTTPRequest {
+    strip_hop_by_hop(&mut request.headers);
+
+    // The body is sent right away, no need to wait for a 100 Continue
+    request.headers.remove("Expect");
+    request.headers.set("Host", upstream);
+
+    if let Some(addr) = request.addr {
+        let mut chain: Vec<_> = request.headers.get_all("X-Forwarded-For").collect();
+        let addr = addr.to_string();
+        chain.push(&addr);
+
+        let chain = chain.join(", ");
+        request.headers.set("X-Forwarded-For", &chain);
+    }
+
+    if !request.headers.contains("X-Forwarded-Proto") {
+        request.headers.set("X-Forwarded-Proto", "http");
+    }
+
+    // The upstream call is a span of its own, invalid contexts aren't passed on
+    #[cfg(feature = "trace-context")]
+    match request.trace_context() {
+        Some(context) => {
+            let traceparent = context.child().to_string();
+            request.headers.set("traceparent", &traceparent);
+        }
+        None => {
+            request.headers.remove("traceparent");
+            request.headers.remove("tracestate");
+        }
+    }
+
+    match &request.body {
+        Some(body) => {
+            let len = body.len().to_string();
+            request.headers.set("Content-Length", &len);
+        }
+        None => {
+            request.headers.remove("Content-Length");
+        }
+    }
+
+    // The upstream connection is used for this request only
+    request.headers.set("Connection", "close");
+    request.version = Version::V11;
+
+    request
+}
+
+/// Removes the headers describing the connection they were received on.
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<String> = headers
+        .get_all("Connection")
+        .flat_map(|value| value.split(','))
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .collect();
+
+    for name in HOP_BY_HOP
+        .iter()
+        .copied()
+        .chain(listed.iter().map(String::as_str))
+    {
+        headers.remove(name);
+    }
+}
+
+/// Sends `request` to `upstream` and reads the head of its response, leaving the
+/// body to be streamed.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the response, or an error if the upstream server
+/// can't be reached or doesn't answer with a valid response in time.
+fn exchange(request: &HTTPRequest, upstream: &str, head: bool) -> io::Result<HTTPResponse> {
+    let mut stream = client::connect(upstream, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_write_timeout(Some(READ_TIMEOUT))?;
+
+    stream.write_all(&request.to_bytes())?;
+    if let Some(BodySource::File(path, _)) = &request.body_source {
+        io::copy(&mut File::open(path)?, &mut stream)?;
+    }
+    stream.flush()?;
+
+    let (mut response, leftover) = client::read_head(&mut stream)?;
+
+    // The body is decoded here, and framed again when sent to the client
+    let chunked = client::is_chunked(&response.headers);
+    strip_hop_by_hop(&mut response.headers);
+
+    if chunked {
+        // The length is given by the chunks, a Content-Length would be wrong
+        response.headers.remove("Content-Length");
+    }
+
+    let bodiless = head
+        || response.status_code.is_informational()
+        || matches!(
+            response.status_code,
+            StatusCode::CODE204 | StatusCode::CODE304
+        );
+    if bodiless {
+        return Ok(response);
+    }
+
+    let body = Cursor::new(leftover).chain(stream);
+
+    response.body = Some(Body::Stream(if chunked {
+        Box::new(Chunked::new(BufReader::new(body)))
+    } else {
+        match response.headers.content_length() {
+            Some(len) => Box::new(body.take(len)),
+            // The upstream server closes the connection once the body is sent
+            None => Box::new(body),
+        }
+    }));
+
+    Ok(response)
+}
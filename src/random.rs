@@ -0,0 +1,44 @@
+use std::{
+    collections::hash_map::RandomState,
+    fs::File,
+    hash::{BuildHasher, Hasher},
+    io::Read,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Fills `buffer` with random bytes.
+///
+/// Reads from the operating system's random source when available and falls back
+/// to the randomly seeded hasher of the standard library otherwise.
+pub(crate) fn fill(buffer: &mut [u8]) {
+    if let Ok(mut urandom) = File::open("/dev/urandom") {
+        if urandom.read_exact(buffer).is_ok() {
+            return;
+        }
+    }
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    for chunk in buffer.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+
+        let bytes = hasher.finish().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+/// Returns `len` random bytes encoded as lowercase hexadecimal (`2 * len` characters).
+pub(crate) fn hex(len: usize) -> String {
+    let mut buffer = vec![0; len];
+    fill(&mut buffer);
+
+    buffer.iter().map(|b| format!("{:02x}", b)).collect()
+}
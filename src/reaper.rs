@@ -0,0 +1,76 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::ServerConfig;
+
+/// The longest time between two scans of the idle connections.
+const MAX_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The shortest time between two scans of the idle connections.
+const MIN_SCAN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A thread closing the persistent connections idle for longer than the
+/// [keep-alive idle timeout](crate::Server::set_keep_alive_idle_timeout).
+///
+/// Connections waiting for their next request are normally closed by the read
+/// timeout of their socket, which only fires while a read is blocked on it. The
+/// reaper closes the ones it missed, going through the open connections recorded by
+/// the [`ConnectionCounter`](crate::ConnectionCounter) of the server. It gives them
+/// one scan interval more than the timeout, so that the read timeout normally wins.
+pub(crate) struct Reaper {
+    /// Set when the reaper stops, notified so that it doesn't finish its wait.
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Reaper {
+    /// Spawns the reaper thread, if the server has a keep-alive idle timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The settings of the server, with its open connections.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the reaper, `None` if idle connections are kept
+    /// open indefinitely, or an error if the thread can't be spawned.
+    pub(crate) fn spawn(config: Arc<ServerConfig>) -> anyhow::Result<Option<Self>> {
+        let Some(timeout) = config.keep_alive_idle_timeout else {
+            return Ok(None);
+        };
+
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let interval = (timeout / 4).clamp(MIN_SCAN_INTERVAL, MAX_SCAN_INTERVAL);
+
+        let reaper = Reaper {
+            stopped: stopped.clone(),
+        };
+        config.thread("reaper").spawn(move || {
+            let (lock, changed) = &*stopped;
+            let mut stopped = lock.lock().unwrap_or_else(|err| err.into_inner());
+
+            while !*stopped {
+                (stopped, _) = changed
+                    .wait_timeout(stopped, interval)
+                    .unwrap_or_else(|err| err.into_inner());
+
+                let reaped = config.stats.connections.reap(timeout + interval);
+                if reaped > 0 {
+                    log::debug!("Closed {} idle connections", reaped);
+                    config.stats.idle_reaped(reaped);
+                }
+            }
+        })?;
+
+        Ok(Some(reaper))
+    }
+
+    /// Stops the reaper thread.
+    pub(crate) fn stop(&self) {
+        let (lock, changed) = &*self.stopped;
+
+        *lock.lock().unwrap_or_else(|err| err.into_inner()) = true;
+        changed.notify_one();
+    }
+}
@@ -1,14 +1,47 @@
-use std::collections::HashMap;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context;
 
 use crate::{
-    http::{HTTPRequest, Method, Version},
-    HandlerFunction,
+    args::Args,
+    cache::CacheHandle,
+    http::{HTTPRequest, HTTPResponse, Method, StatusCode, Version},
+    middleware::{Middleware, Next},
+    ClosureHandlerFunction, Error, HandlerFunction, StateHandlerFunction,
 };
+#[cfg(feature = "json")]
+use crate::{openapi::RouteDoc, validation::Validator, JsonHandlerFunction};
+
+/// The name under which [`Server::new_with_state`](crate::Server::new_with_state)
+/// registers the state of the server in its [`Args`].
+pub(crate) const STATE_ARG: &str = "fobserver::state";
 
 /// A struct to manage HTTP routes and their associated handler functions.
-#[derive(Debug)]
 pub struct Router {
-    routes: HashMap<(Method, String, Version), HandlerFunction>,
+    routes: HashMap<(Method, String, Version), Route>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    cache: CacheHandle,
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field("routes", &self.routes)
+            .field("middlewares", &self.middlewares.len())
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Router {
@@ -16,6 +49,8 @@ impl Router {
     pub fn new() -> Self {
         Router {
             routes: HashMap::new(),
+            middlewares: Vec::new(),
+            cache: CacheHandle::new(),
         }
     }
 
@@ -29,15 +64,172 @@ impl Router {
     /// - `path`: A string slice that represents the path for the route.
     /// - `version`: The HTTP version associated with the route.
     /// - `handler`: A `HandlerFunction` that will be invoked when the route is matched.
+    ///
+    /// # Returns
+    /// A mutable reference to the route, to set it up further.
     pub fn add_route(
         &mut self,
         method: Method,
         path: &str,
         version: Version,
         handler: HandlerFunction,
-    ) {
-        self.routes
-            .insert((method, path.to_string(), version), handler);
+    ) -> &mut Route {
+        self.insert(method, path, version, Handler::Function(handler))
+    }
+
+    /// Adds a new route whose handler receives the typed state of the server, see
+    /// [`Server::new_with_state`](crate::Server::new_with_state).
+    ///
+    /// # Parameters
+    /// - `method`: The HTTP method (e.g., GET, POST) for the route.
+    /// - `path`: A string slice that represents the path for the route.
+    /// - `version`: The HTTP version associated with the route.
+    /// - `handler`: A `StateHandlerFunction` that will be invoked when the route is
+    ///   matched. Requests are answered with an error if the server wasn't created
+    ///   with a state of type `S`.
+    ///
+    /// # Returns
+    /// A mutable reference to the route, to set it up further.
+    pub fn add_state_route<S: Send + Sync + 'static>(
+        &mut self,
+        method: Method,
+        path: &str,
+        version: Version,
+        handler: StateHandlerFunction<S>,
+    ) -> &mut Route {
+        let handler = move |request: HTTPRequest, args: Arc<RwLock<Args>>| {
+            let state = args
+                .read()
+                .map_err(|err| anyhow::anyhow!("Error: {}", err))?
+                .with(STATE_ARG, Arc::<S>::clone)
+                .context("The route expects the state the server was created with")?;
+
+            handler(request, state)
+        };
+
+        self.insert(method, path, version, Handler::Closure(Arc::new(handler)))
+    }
+
+    /// Adds a new route whose handler returns a value, sent as a `200 OK` response
+    /// with the value serialized as JSON, see [`HTTPResponse::json`].
+    ///
+    /// Errors of the handler, and values that can't be serialized, are handler
+    /// errors, answered like those of any other route. The route is added for both
+    /// HTTP/1.0 and HTTP/1.1.
+    ///
+    /// # Parameters
+    /// - `method`: The HTTP method (e.g., GET, POST) for the route.
+    /// - `path`: A string slice that represents the path for the route.
+    /// - `handler`: A `JsonHandlerFunction` returning the value to send.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     collections::HashMap,
+    ///     sync::{Arc, RwLock},
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{HTTPRequest, Method, StatusCode},
+    ///     router::Router,
+    ///     testing::TestServer,
+    /// };
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     id: u32,
+    ///     name: String,
+    /// }
+    ///
+    /// fn user(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<User> {
+    ///     Ok(User { id: 1, name: "fob".to_string() })
+    /// }
+    ///
+    /// fn broken(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HashMap<(u8, u8), u8>> {
+    ///     // JSON objects can't have tuples as keys
+    ///     Ok(HashMap::from([((0, 0), 0)]))
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router
+    ///     .json_route(Method::GET, "/user", user)
+    ///     .json_route(Method::GET, "/broken", broken);
+    /// let server = TestServer::new(router, Args::new());
+    ///
+    /// let response = server.get("/user")?;
+    /// assert_eq!(response.status_code, StatusCode::CODE200);
+    /// assert_eq!(response.headers.get("Content-Type"), Some("application/json"));
+    /// assert_eq!(
+    ///     response.body.unwrap().as_bytes(),
+    ///     Some(&br#"{"id":1,"name":"fob"}"#[..])
+    /// );
+    ///
+    /// // HTTP/1.0 clients get the same response
+    /// let response = server.request("GET /user HTTP/1.0\r\n\r\n".parse()?)?;
+    /// assert_eq!(response.status_code, StatusCode::CODE200);
+    /// assert_eq!(
+    ///     response.body.unwrap().as_bytes(),
+    ///     Some(&br#"{"id":1,"name":"fob"}"#[..])
+    /// );
+    ///
+    /// // Serialization errors are answered like handler errors, without panicking
+    /// assert_eq!(server.get("/broken")?.status_code, StatusCode::CODE500);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn json_route<T: serde::Serialize + 'static>(
+        &mut self,
+        method: Method,
+        path: &str,
+        handler: JsonHandlerFunction<T>,
+    ) -> &mut Self {
+        let handler: ClosureHandler = Arc::new(move |request: HTTPRequest, args| {
+            HTTPResponse::json(StatusCode::CODE200, &handler(request, args)?)
+        });
+
+        for version in [Version::V10, Version::V11] {
+            self.insert(method, path, version, Handler::Closure(handler.clone()));
+        }
+
+        self
+    }
+
+    /// Registers a route calling `handler`, replacing the one with the same method,
+    /// path and version if there was one.
+    pub(crate) fn insert(
+        &mut self,
+        method: Method,
+        path: &str,
+        version: Version,
+        handler: Handler,
+    ) -> &mut Route {
+        let route = Route {
+            handler,
+            timeout: None,
+            cache: None,
+            cache_key_headers: vec!["Accept-Encoding".to_string()],
+            early_hints: Vec::new(),
+            #[cfg(feature = "tls")]
+            client_cert: false,
+            #[cfg(feature = "json")]
+            doc: RouteDoc::default(),
+            #[cfg(feature = "json")]
+            validator: None,
+        };
+
+        // A route added again replaces the previous one, timeout included
+        match self.routes.entry((method, path.to_string(), version)) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(route);
+                entry.into_mut()
+            }
+            Entry::Vacant(entry) => entry.insert(route),
+        }
     }
 
     /// Retrieves the handler function for a given HTTP request.
@@ -51,12 +243,591 @@ impl Router {
     ///
     /// # Returns
     /// An `Option<&HandlerFunction>`, which will be `Some(handler)` if a matching route is found,
-    /// or `None` if there is no match.
+    /// or `None` if there is no match. Routes whose handler isn't a `HandlerFunction`,
+    /// such as those added with [`Router::add_state_route`], are found with
+    /// [`Router::closure_route`] instead.
     pub fn route(&self, request: &HTTPRequest) -> Option<&HandlerFunction> {
-        self.routes.get(&(
-            request.method.clone(),
-            request.path.clone(),
-            request.version.clone(),
-        ))
+        match &self.find(request)?.handler {
+            Handler::Function(handler) => Some(handler),
+            Handler::Closure(_) => None,
+        }
+    }
+
+    /// Retrieves the handler of the route matching `request`, when it isn't a
+    /// [`HandlerFunction`]: routes added with [`Router::add_state_route`] or
+    /// [`Router::serve_openapi`], whose handlers capture what they need.
+    ///
+    /// # Parameters
+    /// - `request`: The request to find the route of.
+    ///
+    /// # Returns
+    /// An `Option` containing the handler, called with the arguments of the server,
+    /// or `None` if no route matches or the route was added with [`Router::add_route`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     router::Router,
+    /// };
+    ///
+    /// fn handler(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// fn counter(_: HTTPRequest, _: Arc<u32>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.add_route(http::Method::GET, "/", http::Version::V11, handler);
+    /// router.add_state_route(http::Method::GET, "/counter", http::Version::V11, counter);
+    ///
+    /// let get = |path: &str| -> anyhow::Result<HTTPRequest> {
+    ///     Ok(format!("GET {} HTTP/1.1\r\n\r\n", path).parse()?)
+    /// };
+    ///
+    /// assert!(router.route(&get("/")?).is_some());
+    /// assert!(router.closure_route(&get("/")?).is_none());
+    ///
+    /// assert!(router.route(&get("/counter")?).is_none());
+    /// let counter = router.closure_route(&get("/counter")?).unwrap();
+    ///
+    /// // Without the state the server is created with, the handler fails
+    /// assert!(counter(get("/counter")?, Arc::new(RwLock::new(Args::new()))).is_err());
+    ///
+    /// let response = router.route(&get("/")?).unwrap()(get("/")?, Arc::new(RwLock::new(Args::new())))?;
+    /// assert_eq!(response.status_code, StatusCode::CODE200);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn closure_route(&self, request: &HTTPRequest) -> Option<&ClosureHandlerFunction> {
+        match &self.find(request)?.handler {
+            Handler::Function(_) => None,
+            Handler::Closure(handler) => Some(handler.as_ref()),
+        }
+    }
+
+    /// Returns the handler timeout of the route matching `request`, if it overrides
+    /// the one of the server.
+    pub(crate) fn timeout(&self, request: &HTTPRequest) -> Option<Duration> {
+        self.find(request)?.timeout
+    }
+
+    /// Returns the `103 Early Hints` response to send before invoking the handler of
+    /// the route matching `request`, if it has hints.
+    pub(crate) fn early_hints(&self, request: &HTTPRequest) -> Option<HTTPResponse> {
+        let route = self.find(request)?;
+
+        if route.early_hints.is_empty() {
+            return None;
+        }
+
+        let mut response = HTTPResponse {
+            status_code: StatusCode::CODE103,
+            ..HTTPResponse::default()
+        };
+        for link in &route.early_hints {
+            response.headers.append("Link", link);
+        }
+
+        Some(response)
+    }
+
+    /// Returns the path the route matching `request` was registered with, bounding
+    /// the values a label of the request can take.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn pattern(&self, request: &HTTPRequest) -> Option<String> {
+        self.find(request)?;
+
+        Some(request.path.clone())
+    }
+
+    /// Returns the routes, with the method and path they were registered with.
+    #[cfg(feature = "json")]
+    pub(crate) fn routes(&self) -> impl Iterator<Item = (Method, &str, &Route)> {
+        self.routes
+            .iter()
+            .map(|((method, path, _), route)| (*method, path.as_str(), route))
+    }
+
+    /// Returns the route matching the method, path and version of `request`.
+    fn find(&self, request: &HTTPRequest) -> Option<&Route> {
+        self.routes
+            .get(&(request.method, request.path.clone(), request.version))
+    }
+
+    /// Adds a middleware to the router.
+    ///
+    /// Middlewares wrap the handling of every request in the order they were added,
+    /// and run even when no route matches the request.
+    ///
+    /// # Parameters
+    /// - `middleware`: The middleware to add.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn add_middleware<M: Middleware + 'static>(&mut self, middleware: M) -> &mut Self {
+        self.middlewares.push(Arc::new(middleware));
+
+        self
+    }
+
+    /// Returns a handle on the responses cached for the routes set up with
+    /// [`Route::cache`].
+    ///
+    /// See [`CacheHandle`] for an example.
+    pub fn cache_handle(&self) -> CacheHandle {
+        self.cache.clone()
+    }
+
+    /// Sets how many responses the router caches at most, and how many bytes they
+    /// take at most. The least recently used responses are evicted to make room for
+    /// new ones. By default, up to 1024 responses and 16 MiB are cached.
+    ///
+    /// # Parameters
+    /// - `max_entries`: The most responses cached at once.
+    /// - `max_bytes`: The most bytes of headers and bodies cached at once.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn set_cache_limits(&mut self, max_entries: usize, max_bytes: usize) -> &mut Self {
+        self.cache.set_limits(max_entries, max_bytes);
+
+        self
+    }
+
+    /// Handles a request by running it through the middlewares and the matching route.
+    ///
+    /// # Parameters
+    /// - `request`: The request to handle.
+    /// - `args`: The arguments shared across handlers.
+    ///
+    /// Requests matching no route are answered with `404 Not Found`.
+    ///
+    /// # Returns
+    /// A `Result` with the response, or [`Error::Handler`] if the handler or a
+    /// middleware failed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::{TcpListener, TcpStream},
+    ///     thread,
+    /// };
+    /// use fobserver::{args::Args, router::Router, Server};
+    ///
+    /// let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    /// let mut server = Server::new(&addr.to_string(), Router::new(), Args::new())?;
+    ///
+    /// let handle = server.shutdown_handle();
+    /// let server = thread::spawn(move || server.start());
+    ///
+    /// let mut stream = TcpStream::connect(addr)?;
+    /// stream.write_all(b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    ///
+    /// handle.shutdown();
+    /// server.join().unwrap()?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn dispatch(
+        &self,
+        request: HTTPRequest,
+        args: Arc<RwLock<Args>>,
+    ) -> Result<HTTPResponse, Error> {
+        self.dispatch_with(request, args, &|status_code, _| {
+            HTTPResponse::plain_text(status_code, status_code.reason())
+        })
+    }
+
+    /// Handles a request like [`Router::dispatch`], rendering the errors it answers
+    /// itself, such as `404 Not Found` for requests matching no route, with `error`.
+    pub(crate) fn dispatch_with(
+        &self,
+        request: HTTPRequest,
+        args: Arc<RwLock<Args>>,
+        error: &dyn Fn(StatusCode, &HTTPRequest) -> HTTPResponse,
+    ) -> Result<HTTPResponse, Error> {
+        let endpoint = |request: HTTPRequest, args: Arc<RwLock<Args>>| match self.find(&request) {
+            #[cfg(feature = "tls")]
+            Some(route) if route.client_cert && request.peer_certificate().is_none() => {
+                Ok(error(StatusCode::CODE403, &request))
+            }
+            Some(route) => {
+                #[cfg(feature = "json")]
+                let request = match &route.validator {
+                    Some(validator) => match validator.check(request, error) {
+                        Ok(request) => request,
+                        Err(response) => return Ok(response),
+                    },
+                    None => request,
+                };
+
+                match route.cache {
+                    Some(ttl) => {
+                        self.cache
+                            .fetch(request, &route.cache_key_headers, ttl, |request| {
+                                route.handler.call(request, args)
+                            })
+                    }
+                    None => route.handler.call(request, args),
+                }
+            }
+            None => {
+                log::trace!("No route matches request -> {:#?}", request);
+
+                Ok(error(StatusCode::CODE404, &request))
+            }
+        };
+
+        Next::new(&self.middlewares, &endpoint, error)
+            .run(request, args)
+            .map_err(Error::Handler)
+    }
+}
+
+/// A [`StateHandlerFunction`] wrapped into a closure taking the arguments of the server.
+pub(crate) type ClosureHandler = Arc<ClosureHandlerFunction>;
+
+/// The function answering the requests of a route.
+pub(crate) enum Handler {
+    /// A handler added with [`Router::add_route`].
+    Function(HandlerFunction),
+    /// A handler capturing what it needs, e.g. one added with
+    /// [`Router::add_state_route`], fetching the state first.
+    Closure(ClosureHandler),
+}
+
+impl fmt::Debug for Handler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Handler::Function(handler) => f.debug_tuple("Function").field(handler).finish(),
+            Handler::Closure(_) => f.write_str("Closure"),
+        }
+    }
+}
+
+impl Handler {
+    /// Answers `request`.
+    fn call(&self, request: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+        match self {
+            Handler::Function(handler) => handler(request, args),
+            Handler::Closure(handler) => handler(request, args),
+        }
+    }
+}
+
+/// A route registered with [`Router::add_route`] or [`Router::add_state_route`].
+#[derive(Debug)]
+pub struct Route {
+    handler: Handler,
+    timeout: Option<Duration>,
+    cache: Option<Duration>,
+    cache_key_headers: Vec<String>,
+    early_hints: Vec<String>,
+    #[cfg(feature = "tls")]
+    client_cert: bool,
+    #[cfg(feature = "json")]
+    pub(crate) doc: RouteDoc,
+    #[cfg(feature = "json")]
+    pub(crate) validator: Option<Validator>,
+}
+
+impl Route {
+    /// Sets how long the handler of the route may run, instead of the
+    /// [handler timeout](crate::Server::set_handler_timeout) of the server.
+    ///
+    /// # Parameters
+    /// - `timeout`: The longest time the handler may run.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     sync::{Arc, RwLock},
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    /// };
+    ///
+    /// fn report(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router
+    ///     .add_route(http::Method::GET, "/report", http::Version::V11, report)
+    ///     .timeout(Duration::from_secs(120));
+    /// ```
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+
+        self
+    }
+
+    /// Caches the responses of the handler for `ttl`, answering the same requests
+    /// without invoking it again until then.
+    ///
+    /// Only `GET` and `HEAD` requests are answered from the cache, the others always
+    /// reach the handler. Responses are cached by method, path with query string,
+    /// version and the values of the [key headers](Route::cache_key_headers), in the
+    /// router, after the middlewares. Only successful responses held in memory are
+    /// cached: those setting a cookie, marked `Cache-Control: no-store` or `private`,
+    /// or streamed are not. Cached responses are sent with an `Age` header.
+    ///
+    /// The cache is bounded by [`Router::set_cache_limits`] and can be purged through
+    /// [`Router::cache_handle`].
+    ///
+    /// # Parameters
+    /// - `ttl`: How long a response is served from the cache.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     sync::{
+    ///         atomic::{AtomicUsize, Ordering},
+    ///         Arc, RwLock,
+    ///     },
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    /// };
+    ///
+    /// static CALLS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// fn stats(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     CALLS.fetch_add(1, Ordering::SeqCst);
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// fn session(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     CALLS.fetch_add(1, Ordering::SeqCst);
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.headers.set("Set-Cookie", "id=1");
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// for path in ["/a", "/b", "/c"] {
+    ///     router
+    ///         .add_route(http::Method::GET, path, http::Version::V11, stats)
+    ///         .cache(Duration::from_millis(300));
+    /// }
+    /// router
+    ///     .add_route(http::Method::GET, "/session", http::Version::V11, session)
+    ///     .cache(Duration::from_secs(30));
+    /// router
+    ///     .add_route(http::Method::POST, "/orders", http::Version::V11, stats)
+    ///     .cache(Duration::from_secs(30));
+    /// router.set_cache_limits(2, 1024 * 1024);
+    ///
+    /// let args = Arc::new(RwLock::new(Args::new()));
+    /// let send = |method: &str, path: &str| -> anyhow::Result<HTTPResponse> {
+    ///     let request = format!("{} {} HTTP/1.1\r\n\r\n", method, path).parse()?;
+    ///     Ok(router.dispatch(request, args.clone())?)
+    /// };
+    /// let get = |path: &str| send("GET", path);
+    /// let calls = || CALLS.load(Ordering::SeqCst);
+    ///
+    /// // A hit doesn't reach the handler
+    /// get("/a")?;
+    /// let response = get("/a")?;
+    /// assert_eq!(calls(), 1);
+    /// assert_eq!(response.headers.get("Age"), Some("0"));
+    ///
+    /// // Past the limit, the least recently used response is evicted
+    /// get("/b")?;
+    /// get("/a")?;
+    /// get("/c")?;
+    /// assert_eq!(calls(), 3);
+    /// get("/a")?;
+    /// assert_eq!(calls(), 3);
+    /// get("/b")?;
+    /// assert_eq!(calls(), 4);
+    ///
+    /// // Responses expire
+    /// thread::sleep(Duration::from_millis(400));
+    /// get("/b")?;
+    /// assert_eq!(calls(), 5);
+    ///
+    /// // Responses setting cookies are never cached
+    /// get("/session")?;
+    /// get("/session")?;
+    /// assert_eq!(calls(), 7);
+    ///
+    /// // Neither are the responses of other methods, every POST reaches its handler
+    /// send("POST", "/orders")?;
+    /// let response = send("POST", "/orders")?;
+    /// assert_eq!(calls(), 9);
+    /// assert_eq!(response.headers.get("Age"), None);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn cache(&mut self, ttl: Duration) -> &mut Self {
+        self.cache = Some(ttl);
+
+        self
+    }
+
+    /// Sets the request headers whose values select different cached responses,
+    /// `Accept-Encoding` by default.
+    ///
+    /// # Parameters
+    /// - `headers`: The names of the headers, e.g. `["Accept-Encoding", "Accept-Language"]`.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    pub fn cache_key_headers(&mut self, headers: &[&str]) -> &mut Self {
+        self.cache_key_headers = headers.iter().map(|name| name.to_string()).collect();
+
+        self
+    }
+
+    /// Sends a `103 Early Hints` interim response with `links` as soon as a request
+    /// for the route is read, before the handler runs, so that browsers can start
+    /// loading the resources of the page while it is rendered.
+    ///
+    /// Hints are only sent to HTTP/1.1 clients, HTTP/1.0 ones don't expect interim
+    /// responses. They carry no body, and don't replace the `Link` headers the final
+    /// response may set.
+    ///
+    /// # Parameters
+    /// - `links`: The values of the `Link` headers, e.g. `</style.css>; rel=preload; as=style`.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{
+    ///     io::{Read, Write},
+    ///     net::TcpStream,
+    ///     sync::{Arc, RwLock},
+    ///     thread,
+    ///     time::Duration,
+    /// };
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse},
+    ///     router::Router,
+    ///     Server,
+    /// };
+    ///
+    /// fn page(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     // Rendering takes a while
+    ///     thread::sleep(Duration::from_millis(200));
+    ///
+    ///     let mut response = HTTPResponse::ok();
+    ///     response.body = Some("<html></html>".into());
+    ///
+    ///     Ok(response)
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router
+    ///     .add_route(http::Method::GET, "/", http::Version::V11, page)
+    ///     .early_hints(&["</style.css>; rel=preload; as=style", "</app.js>; rel=preload; as=script"]);
+    /// router.add_route(http::Method::GET, "/", http::Version::V10, page);
+    ///
+    /// let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+    ///
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+    ///
+    /// // The hints arrive while the handler is still running
+    /// let mut hints = [0; 1024];
+    /// let len = stream.read(&mut hints)?;
+    /// let hints = String::from_utf8_lossy(&hints[..len]).into_owned();
+    /// assert_eq!(
+    ///     hints,
+    ///     "HTTP/1.1 103 Early Hints\n\
+    ///      Link: </style.css>; rel=preload; as=style\n\
+    ///      Link: </app.js>; rel=preload; as=script\n\n"
+    /// );
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    ///
+    /// // Not to HTTP/1.0 clients
+    /// let mut stream = TcpStream::connect(server.addr()?)?;
+    /// stream.write_all(b"GET / HTTP/1.0\r\n\r\n")?;
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response)?;
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(!response.contains("103"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn early_hints(&mut self, links: &[&str]) -> &mut Self {
+        self.early_hints = links.iter().map(|link| link.to_string()).collect();
+
+        self
+    }
+
+    /// Answers `403 Forbidden` to clients that didn't authenticate with a
+    /// certificate, for servers where it is
+    /// [optional](crate::tls::ClientAuthPolicy::Optional). The error is rendered like
+    /// the other errors of the server, see
+    /// [`Server::set_error_pages`](crate::Server::set_error_pages).
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// See [`ClientAuth`](crate::tls::ClientAuth) for a complete example.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    ///     router::Router,
+    ///     testing::TestServer,
+    /// };
+    ///
+    /// fn admin(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router
+    ///     .add_route(http::Method::GET, "/admin", http::Version::V11, admin)
+    ///     .require_client_cert();
+    /// let server = TestServer::new(router, Args::new());
+    ///
+    /// let response = server.request("GET /admin HTTP/1.1\r\nAccept: application/json\r\n\r\n".parse()?)?;
+    /// assert_eq!(response.status_code, StatusCode::CODE403);
+    /// assert_eq!(response.headers.get("Content-Type"), Some("application/json"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn require_client_cert(&mut self) -> &mut Self {
+        self.client_cert = true;
+
+        self
     }
 }
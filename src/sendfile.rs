@@ -0,0 +1,115 @@
+use std::{fs::File, io, io::Write};
+
+use socket2::Socket;
+
+/// A destination response bodies are written to, which may be able to receive the
+/// contents of a file without them being copied through userspace.
+pub(crate) trait SendFile: Write {
+    /// Sends `len` bytes of `file` starting at `offset`, stopping early if the file
+    /// ends first.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the number of bytes sent, or `None` if nothing
+    /// was sent because the destination doesn't support it and the caller must copy
+    /// the file itself.
+    fn send_file(&mut self, _file: &File, _offset: u64, _len: u64) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+impl<W: SendFile + ?Sized> SendFile for &mut W {
+    fn send_file(&mut self, file: &File, offset: u64, len: u64) -> io::Result<Option<u64>> {
+        (**self).send_file(file, offset, len)
+    }
+}
+
+impl SendFile for Vec<u8> {}
+
+impl SendFile for &Socket {}
+
+/// Sends `len` bytes of `file` starting at `offset` to `socket` with `sendfile(2)`,
+/// on the platforms supporting it.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the number of bytes sent, or `None` if the socket
+/// or the file doesn't support it.
+#[cfg(unix)]
+pub(crate) fn send<S: std::os::fd::AsRawFd>(
+    socket: &S,
+    file: &File,
+    offset: u64,
+    len: u64,
+) -> io::Result<Option<u64>> {
+    sys::send(socket.as_raw_fd(), file, offset, len)
+}
+
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+mod sys {
+    use std::{
+        cmp::min,
+        fs::File,
+        io,
+        os::{
+            fd::{AsRawFd, RawFd},
+            raw::c_int,
+        },
+    };
+
+    /// The most `sendfile` transfers in one call.
+    const MAX_COUNT: u64 = 0x7fff_f000;
+
+    extern "C" {
+        fn sendfile(out_fd: c_int, in_fd: c_int, offset: *mut i64, count: usize) -> isize;
+    }
+
+    pub(super) fn send(
+        socket: RawFd,
+        file: &File,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<Option<u64>> {
+        let Ok(mut offset) = i64::try_from(offset) else {
+            return Ok(None);
+        };
+        let mut sent = 0;
+
+        while sent < len {
+            let count = min(len - sent, MAX_COUNT) as usize;
+
+            // Safety: both descriptors are open for the duration of the call, and the
+            // offset points to a live integer
+            let result = unsafe { sendfile(socket, file.as_raw_fd(), &mut offset, count) };
+
+            match result {
+                -1 => {
+                    let err = io::Error::last_os_error();
+
+                    match err.kind() {
+                        io::ErrorKind::Interrupted => continue,
+                        // Not a regular file, or a socket `sendfile` can't write to
+                        io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported if sent == 0 => {
+                            return Ok(None)
+                        }
+                        _ => return Err(err),
+                    }
+                }
+                // The file is shorter than expected
+                0 => break,
+                written => sent += written as u64,
+            }
+        }
+
+        Ok(Some(sent))
+    }
+}
+
+#[cfg(all(unix, not(all(target_os = "linux", target_pointer_width = "64"))))]
+mod sys {
+    use std::{fs::File, io, os::fd::RawFd};
+
+    pub(super) fn send(_: RawFd, _: &File, _: u64, _: u64) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+}
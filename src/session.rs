@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{csrf, random};
+
+/// The number of random bytes in a session id.
+const ID_LEN: usize = 32;
+
+/// The data of a session, by key.
+pub type SessionData = HashMap<String, String>;
+
+/// Where the [`sessions`](crate::middleware::sessions) middleware keeps the data of
+/// the sessions between requests.
+pub trait SessionStore: Send + Sync {
+    /// Loads the session `id`.
+    ///
+    /// # Parameters
+    /// - `id`: The id of the session.
+    ///
+    /// # Returns
+    /// A `Result` containing the data of the session, `None` if there is no such
+    /// session or it expired, or an error if the store can't be read.
+    fn get(&self, id: &str) -> anyhow::Result<Option<SessionData>>;
+
+    /// Saves the session `id`, replacing its data if it exists.
+    ///
+    /// # Parameters
+    /// - `id`: The id of the session.
+    /// - `data`: The data of the session.
+    /// - `ttl`: How long the session lives unless saved again.
+    ///
+    /// # Returns
+    /// A `Result` which is an error if the store can't be written.
+    fn set(&self, id: &str, data: SessionData, ttl: Duration) -> anyhow::Result<()>;
+
+    /// Deletes the session `id`, if it exists.
+    ///
+    /// # Parameters
+    /// - `id`: The id of the session.
+    ///
+    /// # Returns
+    /// A `Result` which is an error if the store can't be written.
+    fn delete(&self, id: &str) -> anyhow::Result<()>;
+}
+
+/// A [`SessionStore`] keeping the sessions in memory, lost when the process exits.
+///
+/// Expired sessions are purged whenever a session is saved.
+///
+/// # Example
+///
+/// ```
+/// use std::{collections::HashMap, thread, time::Duration};
+/// use fobserver::session::{MemoryStore, SessionStore};
+///
+/// let store = MemoryStore::new();
+/// store.set("a", HashMap::from([("user".to_string(), "fob".to_string())]), Duration::from_millis(10))?;
+/// assert_eq!(store.get("a")?.unwrap()["user"], "fob");
+///
+/// thread::sleep(Duration::from_millis(20));
+/// assert_eq!(store.get("a")?, None);
+///
+/// store.set("b", HashMap::new(), Duration::from_secs(60))?;
+/// assert_eq!(store.len(), 1);
+///
+/// store.delete("b")?;
+/// assert!(store.is_empty());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, (SessionData, Instant)>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of sessions stored, expired ones not purged yet included.
+    pub fn len(&self) -> usize {
+        self.sessions().len()
+    }
+
+    /// Returns `true` if no session is stored.
+    pub fn is_empty(&self) -> bool {
+        self.sessions().is_empty()
+    }
+
+    /// Locks the sessions, even if a thread panicked while holding them.
+    fn sessions(&self) -> MutexGuard<'_, HashMap<String, (SessionData, Instant)>> {
+        self.sessions.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn get(&self, id: &str) -> anyhow::Result<Option<SessionData>> {
+        let mut sessions = self.sessions();
+
+        match sessions.get(id) {
+            Some((data, expires)) if *expires > Instant::now() => Ok(Some(data.clone())),
+            Some(_) => {
+                sessions.remove(id);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, id: &str, data: SessionData, ttl: Duration) -> anyhow::Result<()> {
+        let now = Instant::now();
+        let mut sessions = self.sessions();
+
+        sessions.retain(|_, (_, expires)| *expires > now);
+        sessions.insert(id.to_string(), (data, now + ttl));
+
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> anyhow::Result<()> {
+        self.sessions().remove(id);
+
+        Ok(())
+    }
+}
+
+/// The session of a request, found in its
+/// [extensions](crate::http::HTTPRequest::extensions) when the
+/// [`sessions`](crate::middleware::sessions) middleware is used.
+///
+/// Clones share the same session. A request without a session gets an empty one,
+/// only saved, and sent to the client, once something is written in it.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    inner: Arc<Mutex<State>>,
+}
+
+/// The state of a session while a request is handled.
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    /// The id of the session, `None` until it is first written.
+    pub(crate) id: Option<String>,
+    pub(crate) data: SessionData,
+    /// The ids the session was known by, to delete from the store.
+    pub(crate) stale: Vec<String>,
+}
+
+impl Session {
+    /// Creates the session `id`, loaded with `data`.
+    pub(crate) fn load(id: String, data: SessionData) -> Self {
+        Session {
+            inner: Arc::new(Mutex::new(State {
+                id: Some(id),
+                data,
+                stale: Vec::new(),
+            })),
+        }
+    }
+
+    /// Returns the state of the session, once the request was handled.
+    pub(crate) fn take(&self) -> State {
+        std::mem::take(&mut *self.state())
+    }
+
+    /// Returns the id of the session, or `None` if it was never written.
+    pub fn id(&self) -> Option<String> {
+        self.state().id.clone()
+    }
+
+    /// Returns the value of `key` parsed as a `T`, or `None` if there is none or it
+    /// can't be parsed.
+    ///
+    /// # Parameters
+    /// - `key`: The key of the value.
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.state().data.get(key)?.parse().ok()
+    }
+
+    /// Stores `value` under `key`, replacing the previous value, and creates the
+    /// session if it didn't exist.
+    ///
+    /// # Parameters
+    /// - `key`: The key of the value.
+    /// - `value`: The value, stored as its string representation.
+    pub fn insert(&self, key: &str, value: impl ToString) {
+        let mut state = self.state();
+
+        state.id.get_or_insert_with(new_id);
+        state.data.insert(key.to_string(), value.to_string());
+    }
+
+    /// Removes the value of `key`.
+    ///
+    /// # Returns
+    /// The removed value, if there was one.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        self.state().data.remove(key)
+    }
+
+    /// Gives the session a new id, keeping its data; the old id stops working.
+    ///
+    /// Call it whenever the privileges of the client change, e.g. when it logs in, so
+    /// that an attacker who planted or learned the previous id can't use the session
+    /// (session fixation). The [CSRF token](crate::csrf::token_for) is dropped too,
+    /// the next one asked for is new.
+    pub fn renew(&self) {
+        let mut state = self.state();
+
+        state.data.remove(csrf::SESSION_KEY);
+
+        if let Some(old) = state.id.replace(new_id()) {
+            state.stale.push(old);
+        }
+    }
+
+    /// Deletes the session and its data, e.g. when the client logs out. Writing to
+    /// it afterwards creates a new session.
+    pub fn destroy(&self) {
+        let mut state = self.state();
+
+        if let Some(old) = state.id.take() {
+            state.stale.push(old);
+        }
+        state.data.clear();
+    }
+
+    /// Locks the state, even if a handler panicked while holding it.
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Returns a new random session id.
+fn new_id() -> String {
+    random::hex(ID_LEN)
+}
@@ -0,0 +1,96 @@
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Set once the process is asked to terminate.
+static RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the handlers recording termination requests: `SIGINT` and `SIGTERM` on
+/// Unix, console control events (e.g. Ctrl-C) on Windows.
+///
+/// Any request recorded before is forgotten.
+///
+/// # Returns
+///
+/// Returns a `Result` indicating whether the handlers could be installed.
+pub(crate) fn install() -> io::Result<()> {
+    RECEIVED.store(false, Ordering::SeqCst);
+
+    sys::install()
+}
+
+/// Returns `true` if the process was asked to terminate since the handlers were
+/// installed.
+pub(crate) fn received() -> bool {
+    RECEIVED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::{io, os::raw::c_int, sync::atomic::Ordering};
+
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+    const SIG_ERR: usize = !0;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+    }
+
+    extern "C" fn on_signal(_: c_int) {
+        // Storing to an atomic is all a signal handler can safely do
+        super::RECEIVED.store(true, Ordering::SeqCst);
+    }
+
+    pub(super) fn install() -> io::Result<()> {
+        for signum in [SIGINT, SIGTERM] {
+            // Safety: the handler is async-signal-safe and lives for the whole program
+            if unsafe { signal(signum, on_signal as extern "C" fn(c_int) as usize) } == SIG_ERR {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::{io, sync::atomic::Ordering};
+
+    type HandlerRoutine = unsafe extern "system" fn(u32) -> i32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<HandlerRoutine>, add: i32) -> i32;
+    }
+
+    unsafe extern "system" fn on_control(_: u32) -> i32 {
+        super::RECEIVED.store(true, Ordering::SeqCst);
+
+        // Handled, the process isn't terminated right away
+        1
+    }
+
+    pub(super) fn install() -> io::Result<()> {
+        // Safety: the handler lives for the whole program
+        if unsafe { SetConsoleCtrlHandler(Some(on_control), 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::io;
+
+    pub(super) fn install() -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Signals are not supported on this platform",
+        ))
+    }
+}
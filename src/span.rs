@@ -0,0 +1,122 @@
+use std::{marker::PhantomData, net::IpAddr, time::Duration};
+
+use crate::http::{HTTPRequest, StatusCode};
+
+/// Logs an event through `tracing` when the feature is enabled, inside the current
+/// span, and through `log` otherwise.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::$level!($($arg)+);
+    }};
+}
+
+pub(crate) use event;
+
+/// The span of a connection or of a request, doing nothing unless the `tracing`
+/// feature is enabled.
+#[derive(Debug, Clone)]
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    inner: tracing::Span,
+}
+
+/// Keeps a span entered until it is dropped.
+pub(crate) struct Entered<'a> {
+    #[cfg(feature = "tracing")]
+    _inner: tracing::span::Entered<'a>,
+    _span: PhantomData<&'a Span>,
+}
+
+impl Span {
+    /// Opens the span of a connection from `addr`.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn connection(addr: Option<IpAddr>) -> Self {
+        #[cfg(feature = "tracing")]
+        {
+            let inner =
+                tracing::info_span!(crate::trace::CONNECTION_SPAN, peer = tracing::field::Empty);
+
+            if let Some(addr) = addr {
+                inner.record("peer", tracing::field::display(addr));
+            }
+
+            Span { inner }
+        }
+
+        #[cfg(not(feature = "tracing"))]
+        Span {}
+    }
+
+    /// Opens the span of `request`, within the current span.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn request(request: &HTTPRequest) -> Self {
+        Span {
+            #[cfg(feature = "tracing")]
+            inner: tracing::info_span!(
+                crate::trace::REQUEST_SPAN,
+                method = %request.method,
+                path = %request.path,
+                version = %request.version,
+                status = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+                trace_id = tracing::field::Empty,
+                span_id = tracing::field::Empty,
+            ),
+        }
+        .with_trace_context(request)
+    }
+
+    /// Records the ids of the trace context of `request`, if it has one.
+    #[cfg_attr(
+        not(all(feature = "tracing", feature = "trace-context")),
+        allow(unused_variables)
+    )]
+    fn with_trace_context(self, request: &HTTPRequest) -> Self {
+        #[cfg(all(feature = "tracing", feature = "trace-context"))]
+        if let Some(context) = request.trace_context() {
+            self.inner
+                .record("trace_id", tracing::field::display(context.trace_id_hex()));
+            self.inner
+                .record("span_id", tracing::field::display(context.span_id_hex()));
+        }
+
+        self
+    }
+
+    /// Enters the span, making it the parent of the events of the current thread.
+    pub(crate) fn enter(&self) -> Entered<'_> {
+        Entered {
+            #[cfg(feature = "tracing")]
+            _inner: self.inner.enter(),
+            _span: PhantomData,
+        }
+    }
+
+    /// Enters the span every time `future` is polled.
+    #[cfg(feature = "async")]
+    pub(crate) fn instrument<F: std::future::Future>(
+        self,
+        future: F,
+    ) -> impl std::future::Future<Output = F::Output> {
+        #[cfg(feature = "tracing")]
+        return tracing::Instrument::instrument(future, self.inner);
+
+        #[cfg(not(feature = "tracing"))]
+        future
+    }
+
+    /// Records the status of the response answering the request of the span, and how
+    /// long the handler took to produce it.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn record_response(&self, status_code: StatusCode, latency: Duration) {
+        #[cfg(feature = "tracing")]
+        {
+            self.inner.record("status", status_code.code());
+            self.inner
+                .record("latency_ms", latency.as_secs_f64() * 1000.0);
+        }
+    }
+}
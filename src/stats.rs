@@ -0,0 +1,329 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, OnceLock, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+use crate::{
+    args::Args,
+    http::{self, HTTPRequest, HTTPResponse, StatusCode},
+    sendfile::SendFile,
+    ConnectionCounter,
+};
+
+/// The name under which a server registers its statistics in the [`Args`] shared with
+/// its handlers.
+pub(crate) const ARG: &str = "fobserver::stats";
+
+/// A snapshot of the activity of a [`Server`](crate::Server).
+///
+/// Snapshots are taken with [`Server::stats`](crate::Server::stats), or served over
+/// HTTP by [`stats_handler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerStats {
+    /// The connections accepted since the server was created, rejected ones included.
+    pub accepted_connections: u64,
+    /// The connections currently open, including those waiting for a free worker.
+    pub active_connections: usize,
+    /// The requests currently being handled, from the moment they are read until
+    /// their response is written.
+    pub inflight_requests: usize,
+    /// The requests read and dispatched to the router.
+    pub requests: u64,
+    /// The responses sent, by status class: the first element counts `1xx` responses,
+    /// the last one `5xx` responses. Responses generated by the server itself, such as
+    /// `400 Bad Request` or `503 Service Unavailable`, are counted too.
+    pub responses: [u64; 5],
+    /// The bytes received from clients.
+    pub bytes_read: u64,
+    /// The bytes sent to clients, headers included.
+    pub bytes_written: u64,
+    /// The persistent connections closed after serving their maximum number of
+    /// requests, see
+    /// [`Server::set_max_requests_per_connection`](crate::Server::set_max_requests_per_connection).
+    pub keep_alive_exhausted: u64,
+    /// The connections closed for staying idle before their first request or between
+    /// two, see
+    /// [`Server::set_keep_alive_idle_timeout`](crate::Server::set_keep_alive_idle_timeout).
+    pub idle_timeouts: u64,
+    /// The connections closed by their client while a response was being written to
+    /// them, e.g. because a browser cancelled a page load.
+    pub client_disconnects: u64,
+    /// The persistent connections found idle past the
+    /// [keep-alive idle timeout](crate::Server::set_keep_alive_idle_timeout) by the
+    /// periodic scan of the server and closed by it, because their own read timeout
+    /// didn't close them in time.
+    pub idle_reaped: u64,
+    /// How long the server has been running, zero if it wasn't started.
+    pub uptime: Duration,
+}
+
+impl ServerStats {
+    /// Renders the snapshot as a JSON object.
+    fn to_json(&self) -> String {
+        format!(
+            concat!(
+                r#"{{"accepted_connections":{},"active_connections":{},"#,
+                r#""inflight_requests":{},"requests":{},"#,
+                r#""responses":{{"1xx":{},"2xx":{},"3xx":{},"4xx":{},"5xx":{}}},"#,
+                r#""bytes_read":{},"bytes_written":{},"#,
+                r#""keep_alive_exhausted":{},"idle_timeouts":{},"idle_reaped":{},"#,
+                r#""client_disconnects":{},"uptime_seconds":{:.3}}}"#
+            ),
+            self.accepted_connections,
+            self.active_connections,
+            self.inflight_requests,
+            self.requests,
+            self.responses[0],
+            self.responses[1],
+            self.responses[2],
+            self.responses[3],
+            self.responses[4],
+            self.bytes_read,
+            self.bytes_written,
+            self.keep_alive_exhausted,
+            self.idle_timeouts,
+            self.idle_reaped,
+            self.client_disconnects,
+            self.uptime.as_secs_f64()
+        )
+    }
+}
+
+/// Formats the snapshot as one `name value` line per counter.
+impl fmt::Display for ServerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "accepted_connections {}", self.accepted_connections)?;
+        writeln!(f, "active_connections {}", self.active_connections)?;
+        writeln!(f, "inflight_requests {}", self.inflight_requests)?;
+        writeln!(f, "requests {}", self.requests)?;
+
+        for (class, count) in self.responses.iter().enumerate() {
+            writeln!(f, "responses_{}xx {}", class + 1, count)?;
+        }
+
+        writeln!(f, "bytes_read {}", self.bytes_read)?;
+        writeln!(f, "bytes_written {}", self.bytes_written)?;
+        writeln!(f, "keep_alive_exhausted {}", self.keep_alive_exhausted)?;
+        writeln!(f, "idle_timeouts {}", self.idle_timeouts)?;
+        writeln!(f, "idle_reaped {}", self.idle_reaped)?;
+        writeln!(f, "client_disconnects {}", self.client_disconnects)?;
+        write!(f, "uptime_seconds {:.3}", self.uptime.as_secs_f64())
+    }
+}
+
+/// A handler serving the statistics of the server it is mounted on.
+///
+/// The snapshot is sent as JSON when the `Accept` header of the request prefers it,
+/// and as plain text otherwise.
+///
+/// # Arguments
+///
+/// * `request` - The request being handled.
+/// * `args` - The arguments shared across handlers, in which the server registers its
+///   statistics.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the response, or an error if the handler is called
+/// outside of a server.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::{http, router::Router, stats};
+///
+/// let mut router = Router::new();
+/// router.add_route(
+///     http::Method::GET,
+///     "/stats",
+///     http::Version::V11,
+///     stats::stats_handler,
+/// );
+/// ```
+pub fn stats_handler(
+    request: HTTPRequest,
+    args: Arc<RwLock<Args>>,
+) -> anyhow::Result<HTTPResponse> {
+    let stats = args
+        .read()
+        .map_err(|err| anyhow::anyhow!("Error: {}", err))?
+        .with(ARG, |stats: &Arc<Stats>| stats.snapshot())
+        .context("The server statistics are not available")?;
+
+    let mut response = match request.headers.get("Accept") {
+        Some(accept) if http::mime::prefers_json(accept) => {
+            let mut response = HTTPResponse {
+                status_code: StatusCode::CODE200,
+                body: Some(stats.to_json().into()),
+                ..HTTPResponse::default()
+            };
+            response.headers.set("Content-Type", "application/json");
+
+            response
+        }
+        _ => HTTPResponse::plain_text(StatusCode::CODE200, &stats.to_string()),
+    };
+    response.headers.set("Cache-Control", "no-store");
+
+    Ok(response)
+}
+
+/// The counters of a server, updated by every connection.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    started: OnceLock<Instant>,
+    accepted: AtomicU64,
+    pub(crate) connections: ConnectionCounter,
+    inflight: AtomicUsize,
+    requests: AtomicU64,
+    responses: [AtomicU64; 5],
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    keep_alive_exhausted: AtomicU64,
+    idle_timeouts: AtomicU64,
+    idle_reaped: AtomicU64,
+    client_disconnects: AtomicU64,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: crate::metrics::Metrics,
+}
+
+impl Stats {
+    /// Records that the server started, the first time it does.
+    pub(crate) fn start(&self) {
+        let _ = self.started.set(Instant::now());
+    }
+
+    /// Records an accepted connection.
+    pub(crate) fn accepted(&self) {
+        self.accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a request being handled, until the returned guard is dropped.
+    pub(crate) fn inflight(&self) -> InFlight<'_> {
+        self.inflight.fetch_add(1, Ordering::Relaxed);
+
+        InFlight { stats: self }
+    }
+
+    /// Returns the number of requests being handled.
+    pub(crate) fn inflight_requests(&self) -> usize {
+        self.inflight.load(Ordering::Relaxed)
+    }
+
+    /// Records a request dispatched to the router.
+    pub(crate) fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a response sent with `status_code`.
+    pub(crate) fn response(&self, status_code: StatusCode) {
+        let class = usize::from(status_code.code() / 100).clamp(1, 5);
+
+        self.responses[class - 1].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records `len` bytes received from a client.
+    pub(crate) fn read(&self, len: usize) {
+        self.bytes_read.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Records `len` bytes sent to a client.
+    pub(crate) fn written(&self, len: u64) {
+        self.bytes_written.fetch_add(len, Ordering::Relaxed);
+    }
+
+    /// Records a persistent connection closed after its last permitted request.
+    pub(crate) fn keep_alive_exhausted(&self) {
+        self.keep_alive_exhausted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a connection closed for staying idle.
+    pub(crate) fn idle_timeout(&self) {
+        self.idle_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records `count` idle connections closed by the reaper.
+    pub(crate) fn idle_reaped(&self, count: usize) {
+        self.idle_reaped.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Records a connection closed by its client during a response.
+    pub(crate) fn client_disconnected(&self) {
+        self.client_disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the current value of every counter.
+    pub(crate) fn snapshot(&self) -> ServerStats {
+        ServerStats {
+            accepted_connections: self.accepted.load(Ordering::Relaxed),
+            active_connections: self.connections.get(),
+            inflight_requests: self.inflight_requests(),
+            requests: self.requests.load(Ordering::Relaxed),
+            responses: self
+                .responses
+                .each_ref()
+                .map(|count| count.load(Ordering::Relaxed)),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            keep_alive_exhausted: self.keep_alive_exhausted.load(Ordering::Relaxed),
+            idle_timeouts: self.idle_timeouts.load(Ordering::Relaxed),
+            idle_reaped: self.idle_reaped.load(Ordering::Relaxed),
+            client_disconnects: self.client_disconnects.load(Ordering::Relaxed),
+            uptime: self.started.get().map_or(Duration::ZERO, Instant::elapsed),
+        }
+    }
+}
+
+/// Keeps a request counted as in flight until it is dropped, however its handling ends.
+pub(crate) struct InFlight<'a> {
+    stats: &'a Stats,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.stats.inflight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A writer counting the bytes going through it.
+pub(crate) struct CountingWriter<W> {
+    inner: W,
+    pub(crate) count: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    /// Wraps `inner`, starting from zero.
+    pub(crate) fn new(inner: W) -> Self {
+        CountingWriter { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.count += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: SendFile> SendFile for CountingWriter<W> {
+    fn send_file(&mut self, file: &File, offset: u64, len: u64) -> io::Result<Option<u64>> {
+        let sent = self.inner.send_file(file, offset, len)?;
+        self.count += sent.unwrap_or(0);
+
+        Ok(sent)
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    env,
+    net::TcpListener,
+    ops::Range,
+    os::fd::{FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    process,
+};
+
+use anyhow::Context;
+use socket2::{Socket, Type};
+
+use crate::listener::Listener;
+
+/// The first file descriptor passed by the service manager.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Takes the TCP listeners passed to the process through systemd socket activation.
+///
+/// The sockets are read from the `LISTEN_FDS` and `LISTEN_PID` environment
+/// variables; they are ignored if `LISTEN_PID` names another process. Passed file
+/// descriptors that are not TCP sockets are left untouched. Each listener can then be
+/// served with [`Server::from_listener`](crate::Server::from_listener), or every
+/// passed socket at once with [`Server::from_systemd`](crate::Server::from_systemd).
+///
+/// The variables are removed once read, so that child processes don't take the
+/// sockets too, and later calls find nothing.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the listeners, empty if the process wasn't socket
+/// activated, or an error if the variables are malformed.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::systemd;
+///
+/// // Not started by systemd
+/// std::env::remove_var("LISTEN_FDS");
+/// assert!(systemd::listen_fds()?.is_empty());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn listen_fds() -> anyhow::Result<Vec<TcpListener>> {
+    let listeners = adopt(false)?
+        .into_iter()
+        .filter_map(|listener| match listener {
+            Listener::Tcp(listener) => Some(listener),
+            _ => None,
+        })
+        .collect();
+
+    Ok(listeners)
+}
+
+/// Takes the TCP and Unix listeners passed to the process through systemd socket
+/// activation, see [`listen_fds`].
+pub(crate) fn listeners() -> anyhow::Result<Vec<Listener>> {
+    adopt(true)
+}
+
+/// Takes the listening sockets passed by the service manager.
+///
+/// # Arguments
+///
+/// * `unix` - Whether to take Unix sockets too, or leave them untouched.
+fn adopt(unix: bool) -> anyhow::Result<Vec<Listener>> {
+    let mut listeners = Vec::new();
+
+    for fd in passed_fds()? {
+        // Safety: the service manager passed the descriptor for this process to own
+        let socket = unsafe { Socket::from_raw_fd(fd) };
+
+        match listener(socket, unix) {
+            Ok(listener) => listeners.push(listener),
+            // Anything else isn't ours to take, nor to close
+            Err(socket) => {
+                let _ = socket.into_raw_fd();
+            }
+        }
+    }
+
+    Ok(listeners)
+}
+
+/// Reads the file descriptors passed to this process from the environment, and
+/// removes the variables.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the descriptors, empty if the process wasn't socket
+/// activated, or an error if the variables are malformed.
+fn passed_fds() -> anyhow::Result<Range<RawFd>> {
+    let (Ok(pid), Ok(fds)) = (env::var("LISTEN_PID"), env::var("LISTEN_FDS")) else {
+        return Ok(0..0);
+    };
+
+    let pid: u32 = pid
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid LISTEN_PID: {}", pid))?;
+    if pid != process::id() {
+        return Ok(0..0);
+    }
+
+    let end = fds
+        .trim()
+        .parse::<RawFd>()
+        .ok()
+        .filter(|count| *count >= 0)
+        .and_then(|count| LISTEN_FDS_START.checked_add(count))
+        .with_context(|| format!("Invalid LISTEN_FDS: {}", fds))?;
+
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+
+    Ok(LISTEN_FDS_START..end)
+}
+
+/// Turns a passed socket into a listener, if it is a stream socket bound to an inet
+/// address, or to a path if `unix` is set.
+///
+/// # Returns
+///
+/// Returns the listener, or the socket back if it can't be served.
+fn listener(socket: Socket, unix: bool) -> Result<Listener, Socket> {
+    if !socket.r#type().is_ok_and(|kind| kind == Type::STREAM) {
+        return Err(socket);
+    }
+
+    let Ok(addr) = socket.local_addr() else {
+        return Err(socket);
+    };
+
+    if addr.as_socket().is_some() && socket.set_nonblocking(false).is_ok() {
+        return Ok(Listener::Tcp(socket.into()));
+    }
+
+    match addr.as_pathname().map(|path| path.to_path_buf()) {
+        Some(path) if unix && socket.set_nonblocking(false).is_ok() => Ok(Listener::Unix {
+            listener: OwnedFd::from(socket).into(),
+            path,
+            // The service manager created the file, and keeps it across restarts
+            owned: false,
+        }),
+        _ => Err(socket),
+    }
+}
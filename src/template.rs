@@ -0,0 +1,288 @@
+#[cfg(feature = "templates")]
+use std::collections::HashMap;
+use std::{error, fmt, sync::Arc};
+
+use serde::Serialize;
+
+use crate::{
+    args::Args,
+    http::{HTTPResponse, StatusCode},
+};
+
+/// The name of the argument holding the renderer, see [`register`].
+const ARG: &str = "fobserver::renderer";
+
+/// A template engine, turning a named template and a context into HTML.
+///
+/// Renderers are shared by every request: [`register`] one in the arguments of the
+/// server, and handlers fetch it with [`renderer`] to answer with
+/// [`HTTPResponse::render`]. `TinyTemplateRenderer`, behind the `templates` feature,
+/// is ready to use.
+///
+/// # Example
+///
+/// A server-rendered page, escaping what it is given:
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     router::Router,
+///     template::{self, Renderer, TemplateNotFound},
+///     testing::TestServer,
+///     Server,
+/// };
+/// use serde::Serialize;
+///
+/// /// Replaces `{{name}}` with the escaped `name` of the context.
+/// struct Greeter;
+///
+/// impl Renderer for Greeter {
+///     fn render(&self, template: &str, context: &serde_json::Value) -> anyhow::Result<String> {
+///         if template != "hello" {
+///             return Err(TemplateNotFound::new(template).into());
+///         }
+///
+///         let name = context["name"].as_str().unwrap_or_default();
+///         Ok(format!("<p>Hello {}!</p>", name.replace('&', "&amp;").replace('<', "&lt;")))
+///     }
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Hello<'a> {
+///     name: &'a str,
+/// }
+///
+/// fn hello(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let renderer = template::renderer(&args.read().unwrap())?;
+///
+///     HTTPResponse::render(&*renderer, "hello", &Hello { name: "<fob>" })
+/// }
+///
+/// fn missing(_: HTTPRequest, args: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let renderer = template::renderer(&args.read().unwrap())?;
+///
+///     HTTPResponse::render(&*renderer, "missing", &())
+/// }
+///
+/// // Missing templates are told apart from failed renderings
+/// fn error_handler(err: &anyhow::Error, _: &HTTPRequest) -> HTTPResponse {
+///     match err.downcast_ref::<TemplateNotFound>() {
+///         Some(_) => HTTPResponse::not_found(),
+///         None => HTTPResponse::internal_error(),
+///     }
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::GET, "/", http::Version::V11, hello);
+/// router.add_route(http::Method::GET, "/missing", http::Version::V11, missing);
+///
+/// let mut args = Args::new();
+/// template::register(&mut args, Greeter)?;
+///
+/// let mut server = Server::new("127.0.0.1:0", router, args)?;
+/// server.set_error_handler(error_handler);
+/// let server = TestServer::from_server(&server);
+///
+/// let response = server.get("/")?;
+/// assert_eq!(response.headers.get("Content-Type"), Some("text/html; charset=utf-8"));
+/// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"<p>Hello &lt;fob>!</p>"[..]));
+///
+/// assert_eq!(server.get("/missing")?.status_code, StatusCode::CODE404);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait Renderer: Send + Sync {
+    /// Renders the template named `template` with `context`.
+    ///
+    /// # Parameters
+    /// - `template`: The name of the template.
+    /// - `context`: The values the template refers to.
+    ///
+    /// # Returns
+    /// A `Result` containing the rendered HTML, or an error: a [`TemplateNotFound`]
+    /// if there is no such template, anything else if rendering failed.
+    fn render(&self, template: &str, context: &serde_json::Value) -> anyhow::Result<String>;
+}
+
+/// The error of a [`Renderer`] asked for a template it doesn't know, which error
+/// handlers usually answer with `404 Not Found`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateNotFound {
+    /// The name of the template.
+    pub name: String,
+}
+
+impl TemplateNotFound {
+    /// Creates the error of the missing template `name`.
+    pub fn new(name: &str) -> Self {
+        TemplateNotFound {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TemplateNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No template named \"{}\"", self.name)
+    }
+}
+
+impl error::Error for TemplateNotFound {}
+
+/// Stores `renderer` in `args`, for handlers to fetch with [`renderer`].
+///
+/// # Parameters
+/// - `args`: The arguments of the server.
+/// - `renderer`: The template engine.
+///
+/// # Returns
+/// A `Result` which is an error if a renderer is already registered.
+pub fn register(args: &mut Args, renderer: impl Renderer + 'static) -> anyhow::Result<()> {
+    let renderer: Arc<dyn Renderer> = Arc::new(renderer);
+    args.insert_immutable(ARG, renderer)?;
+
+    Ok(())
+}
+
+/// Returns the renderer stored in `args` by [`register`].
+///
+/// # Parameters
+/// - `args`: The arguments of the server.
+///
+/// # Returns
+/// A `Result` containing the renderer, or an error if none was registered.
+pub fn renderer(args: &Args) -> anyhow::Result<Arc<dyn Renderer>> {
+    let renderer = args.get_ref::<Arc<dyn Renderer>>(ARG)?;
+
+    Ok(Arc::clone(&renderer))
+}
+
+impl HTTPResponse {
+    /// Creates a `200 OK` HTML response rendering `template` with `context`.
+    ///
+    /// Sets `Content-Type: text/html; charset=utf-8`.
+    ///
+    /// # Arguments
+    ///
+    /// * `renderer` - The template engine.
+    /// * `template` - The name of the template.
+    /// * `context` - The values the template refers to.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `HTTPResponse`, or the error of the renderer,
+    /// see [`Renderer::render`].
+    pub fn render<T: Serialize + ?Sized>(
+        renderer: &dyn Renderer,
+        template: &str,
+        context: &T,
+    ) -> anyhow::Result<HTTPResponse> {
+        let context = serde_json::to_value(context)?;
+        let html = renderer.render(template, &context)?;
+
+        let mut response = HTTPResponse {
+            status_code: StatusCode::CODE200,
+            body: Some(html.into()),
+            ..HTTPResponse::default()
+        };
+        response
+            .headers
+            .set("Content-Type", "text/html; charset=utf-8");
+
+        Ok(response)
+    }
+}
+
+/// A [`Renderer`] backed by [TinyTemplate](https://docs.rs/tinytemplate), which
+/// escapes the values it prints as HTML.
+///
+/// # Example
+///
+/// ```
+/// use fobserver::{
+///     http::HTTPResponse,
+///     template::{Renderer, TemplateNotFound, TinyTemplateRenderer},
+/// };
+/// use serde_json::json;
+///
+/// let mut renderer = TinyTemplateRenderer::new();
+/// renderer.add_template(
+///     "list",
+///     "<h1>{title}</h1><ul>{{ for item in items }}<li>{item}</li>{{ endfor }}</ul>",
+/// )?;
+///
+/// let context = json!({
+///     "title": "Fish & <Chips>",
+///     "items": ["cod", "\"haddock\""],
+/// });
+/// let response = HTTPResponse::render(&renderer, "list", &context)?;
+/// assert_eq!(
+///     response.body.unwrap().as_bytes(),
+///     Some(&b"<h1>Fish &amp; &lt;Chips&gt;</h1><ul><li>cod</li><li>&quot;haddock&quot;</li></ul>"[..])
+/// );
+///
+/// // Missing templates and failed renderings are different errors
+/// let err = renderer.render("nope", &context).unwrap_err();
+/// assert!(err.downcast_ref::<TemplateNotFound>().is_some());
+///
+/// let err = renderer.render("list", &json!({})).unwrap_err();
+/// assert!(err.downcast_ref::<TemplateNotFound>().is_none());
+///
+/// // Templates are checked when added
+/// assert!(renderer.add_template("broken", "{{ for x in items }}").is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[cfg(feature = "templates")]
+#[derive(Debug, Default, Clone)]
+pub struct TinyTemplateRenderer {
+    templates: HashMap<String, String>,
+}
+
+#[cfg(feature = "templates")]
+impl TinyTemplateRenderer {
+    /// Creates a renderer without templates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the template `name`, replacing the one of the same name if there was one.
+    /// Templates can call each other by name.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the template.
+    /// - `text`: The source of the template.
+    ///
+    /// # Returns
+    /// A `Result` containing a mutable reference to `self` to allow for method
+    /// chaining, or an error if the template doesn't compile.
+    pub fn add_template(&mut self, name: &str, text: &str) -> anyhow::Result<&mut Self> {
+        tinytemplate::TinyTemplate::new()
+            .add_template(name, text)
+            .map_err(|err| anyhow::anyhow!("Invalid template \"{}\": {}", name, err))?;
+
+        self.templates.insert(name.to_string(), text.to_string());
+
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "templates")]
+impl Renderer for TinyTemplateRenderer {
+    fn render(&self, template: &str, context: &serde_json::Value) -> anyhow::Result<String> {
+        if !self.templates.contains_key(template) {
+            return Err(TemplateNotFound::new(template).into());
+        }
+
+        let mut engine = tinytemplate::TinyTemplate::new();
+        for (name, text) in &self.templates {
+            engine
+                .add_template(name, text)
+                .map_err(|err| anyhow::anyhow!("Invalid template \"{}\": {}", name, err))?;
+        }
+
+        engine
+            .render(template, context)
+            .map_err(|err| anyhow::anyhow!("Failed to render \"{}\": {}", template, err))
+    }
+}
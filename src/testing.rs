@@ -0,0 +1,137 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    args::Args,
+    http::{HTTPRequest, HTTPResponse, Method, Version},
+    router::Router,
+    Server, ServerConfig,
+};
+
+/// Runs requests through the handling pipeline of a server without any socket.
+///
+/// Requests go through the same code as on a running [`Server`]: middlewares, route
+/// lookup and fallback, handler panics, the error handler and response encoding.
+/// Only the transport is left out, so responses are returned as they would be
+/// written, before framing.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, RwLock};
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse, StatusCode},
+///     router::Router,
+///     testing::TestServer,
+/// };
+///
+/// fn echo(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.body = request.body.map(Into::into);
+///
+///     Ok(response)
+/// }
+///
+/// fn panics(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     panic!("Bad input")
+/// }
+///
+/// let mut router = Router::new();
+/// router.add_route(http::Method::POST, "/echo", http::Version::V11, echo);
+/// router.add_route(http::Method::GET, "/panic", http::Version::V11, panics);
+///
+/// let server = TestServer::new(router, Args::new());
+///
+/// let response = server.post("/echo", "Hello")?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"Hello"[..]));
+///
+/// assert_eq!(server.get("/panic")?.status_code, StatusCode::CODE500);
+/// assert_eq!(server.get("/missing")?.status_code, StatusCode::CODE404);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct TestServer {
+    router: Arc<RwLock<Router>>,
+    args: Arc<RwLock<Args>>,
+    config: Arc<ServerConfig>,
+}
+
+impl TestServer {
+    /// Creates a test server with the default settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `router` - The router for handling HTTP requests.
+    /// * `args` - Arguments to be shared across handlers.
+    pub fn new(router: Router, args: Args) -> Self {
+        TestServer {
+            router: Arc::new(RwLock::new(router)),
+            args: Arc::new(RwLock::new(args)),
+            config: Arc::default(),
+        }
+    }
+
+    /// Creates a test server sharing the router, arguments and settings of `server`,
+    /// e.g. its error handler or compression.
+    ///
+    /// # Arguments
+    ///
+    /// * `server` - The server to mirror.
+    pub fn from_server(server: &Server) -> Self {
+        TestServer {
+            router: server.router.clone(),
+            args: server.args.clone(),
+            config: server.config.clone(),
+        }
+    }
+
+    /// Sends a `GET` request for `path`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the response, or an error if `path` can't be
+    /// requested.
+    pub fn get(&self, path: &str) -> anyhow::Result<HTTPResponse> {
+        self.send(Method::GET, path, None)
+    }
+
+    /// Sends a `POST` request for `path` carrying `body`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the response, or an error if `path` can't be
+    /// requested.
+    pub fn post(&self, path: &str, body: &str) -> anyhow::Result<HTTPResponse> {
+        self.send(Method::POST, path, Some(body))
+    }
+
+    /// Sends `request` as it is.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the response, or an error if the router is
+    /// unavailable or the response can't be encoded.
+    pub fn request(&self, request: HTTPRequest) -> anyhow::Result<HTTPResponse> {
+        Server::dispatch(request, &self.router, self.args.clone(), &self.config)
+            .map(|(_, response)| response)
+    }
+
+    /// Sends a request made of `method`, `path` and `body`, from a local client.
+    fn send(&self, method: Method, path: &str, body: Option<&str>) -> anyhow::Result<HTTPResponse> {
+        let mut request: HTTPRequest =
+            format!("{} {} {}\r\n\r\n", method, path, Version::V11).parse()?;
+        request.addr = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        if let Some(body) = body {
+            request
+                .headers
+                .set("Content-Length", &body.len().to_string());
+            request.body = Some(body.to_string());
+        }
+
+        self.request(request)
+    }
+}
@@ -0,0 +1,484 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::Arc,
+};
+
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConnection, StreamOwned,
+};
+
+use crate::connection::Connection;
+
+/// The certificate and private key used by a server accepting HTTPS connections.
+///
+/// See [`Server::new_tls`](crate::Server::new_tls).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// The PEM-encoded certificate chain, starting with the certificate of the server.
+    pub cert_chain_pem: Vec<u8>,
+    /// The PEM-encoded private key of the certificate, in PKCS#8, PKCS#1 or SEC1 format.
+    pub private_key_pem: Vec<u8>,
+    /// How clients authenticate with a certificate, `None` to not ask them for one.
+    pub client_auth: Option<ClientAuth>,
+}
+
+/// The verification of client certificates, also known as mutual TLS.
+///
+/// Clients presenting a certificate not issued by one of the authorities fail the
+/// handshake. Handlers find the certificate of the others with
+/// [`HTTPRequest::peer_certificate`](crate::http::HTTPRequest::peer_certificate).
+///
+/// # Example
+///
+/// ```
+/// use std::{
+///     io::{Read, Write},
+///     net::{SocketAddr, TcpStream},
+///     sync::{Arc, RwLock},
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, HTTPRequest, HTTPResponse},
+///     router::Router,
+///     tls::{ClientAuth, ClientAuthPolicy, TlsConfig},
+///     Server,
+/// };
+/// use rcgen::{
+///     BasicConstraints, CertificateParams, CertifiedKey, DnType, ExtendedKeyUsagePurpose, IsCa,
+///     KeyPair,
+/// };
+/// use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+///
+/// fn whoami(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(match request.peer_certificate() {
+///         Some(cert) => format!("{} {:?}", cert.subject, cert.dns_names).into(),
+///         None => "anonymous".into(),
+///     });
+///
+///     Ok(response)
+/// }
+///
+/// // A certificate for `name`, signed by `issuer` or self-signed
+/// fn certificate(name: &str, issuer: Option<&CertifiedKey>) -> anyhow::Result<CertifiedKey> {
+///     let key_pair = KeyPair::generate()?;
+///     let mut params = CertificateParams::new(vec![format!("{}.internal", name)])?;
+///     params.distinguished_name.push(DnType::CommonName, name);
+///     params.distinguished_name.push(DnType::OrganizationName, "Example");
+///
+///     let cert = match issuer {
+///         Some(issuer) => {
+///             params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+///             params.signed_by(&key_pair, &issuer.cert, &issuer.key_pair)?
+///         }
+///         None => {
+///             params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+///             params.self_signed(&key_pair)?
+///         }
+///     };
+///
+///     Ok(CertifiedKey { cert, key_pair })
+/// }
+///
+/// let ca = certificate("ca", None)?;
+/// let client = certificate("billing", Some(&ca))?;
+/// let untrusted = certificate("intruder", Some(&certificate("other-ca", None)?))?;
+///
+/// let server_cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+/// let start = |policy: ClientAuthPolicy| -> anyhow::Result<_> {
+///     let tls = TlsConfig {
+///         cert_chain_pem: server_cert.cert.pem().into_bytes(),
+///         private_key_pem: server_cert.key_pair.serialize_pem().into_bytes(),
+///         client_auth: Some(ClientAuth {
+///             ca_pem: ca.cert.pem().into_bytes(),
+///             policy,
+///         }),
+///     };
+///
+///     // Only the invoices require a certificate
+///     let mut router = Router::new();
+///     router.add_route(http::Method::GET, "/", http::Version::V11, whoami);
+///     router
+///         .add_route(http::Method::GET, "/invoices", http::Version::V11, whoami)
+///         .require_client_cert();
+///
+///     Ok(Server::new_tls("127.0.0.1:0", router, Args::new(), tls)?.start_background()?)
+/// };
+///
+/// let get = |addr: SocketAddr, path: &str, client: Option<&CertifiedKey>| -> anyhow::Result<String> {
+///     let mut roots = rustls::RootCertStore::empty();
+///     roots.add(server_cert.cert.der().clone())?;
+///     let config = rustls::ClientConfig::builder().with_root_certificates(roots);
+///     let config = match client {
+///         Some(client) => config.with_client_auth_cert(
+///             vec![CertificateDer::from(client.cert.der().to_vec())],
+///             PrivatePkcs8KeyDer::from(client.key_pair.serialize_der()).into(),
+///         )?,
+///         None => config.with_no_client_auth(),
+///     };
+///     let connection = rustls::ClientConnection::new(Arc::new(config), "localhost".try_into()?)?;
+///
+///     let mut stream = rustls::StreamOwned::new(connection, TcpStream::connect(addr)?);
+///     write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path)?;
+///     let mut response = String::new();
+///     stream.read_to_string(&mut response)?;
+///
+///     Ok(response)
+/// };
+///
+/// let optional = start(ClientAuthPolicy::Optional)?;
+/// let addr = optional.addr()?;
+///
+/// // Without a certificate, only the open route answers
+/// assert!(get(addr, "/", None)?.contains("anonymous"));
+/// assert!(get(addr, "/invoices", None)?.starts_with("HTTP/1.1 403 Forbidden"));
+///
+/// // With one, the identity of the client reaches the handler
+/// let response = get(addr, "/invoices", Some(&client))?;
+/// assert!(response.starts_with("HTTP/1.1 200 OK"));
+/// assert!(response.contains(r#"O=Example,CN=billing ["billing.internal"]"#));
+///
+/// // A certificate from another authority fails the handshake
+/// assert!(get(addr, "/", Some(&untrusted)).is_err());
+///
+/// // Once required, clients can't connect without a certificate
+/// let required = start(ClientAuthPolicy::Required)?;
+/// let addr = required.addr()?;
+/// assert!(get(addr, "/", None).is_err());
+/// assert!(get(addr, "/", Some(&client))?.contains("CN=billing"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientAuth {
+    /// The PEM-encoded certificates of the authorities issuing client certificates.
+    pub ca_pem: Vec<u8>,
+    /// Whether clients must present a certificate.
+    pub policy: ClientAuthPolicy,
+}
+
+/// Whether clients must present a certificate, see [`ClientAuth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuthPolicy {
+    /// Clients without a certificate fail the handshake.
+    Required,
+    /// Clients may connect without a certificate, routes requiring one answer them
+    /// with `403 Forbidden`, see
+    /// [`Route::require_client_cert`](crate::router::Route::require_client_cert).
+    Optional,
+}
+
+/// The certificate a client authenticated with, see [`ClientAuth`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PeerCertificate {
+    /// The subject of the certificate as a distinguished name string (RFC 4514),
+    /// e.g. `O=Example,CN=billing`.
+    pub subject: String,
+    /// The common name of the subject, if any.
+    pub common_name: Option<String>,
+    /// The DNS names of the subject alternative name extension.
+    pub dns_names: Vec<String>,
+    /// The URIs of the subject alternative name extension, e.g. SPIFFE IDs.
+    pub uris: Vec<String>,
+    /// The DER-encoded certificate.
+    pub der: Vec<u8>,
+}
+
+impl ClientAuth {
+    /// Builds the verifier of client certificates.
+    fn verifier(&self) -> anyhow::Result<Arc<dyn rustls::server::danger::ClientCertVerifier>> {
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(&self.ca_pem) {
+            roots.add(cert?)?;
+        }
+        anyhow::ensure!(!roots.is_empty(), "No client certificate authority found");
+
+        let builder = WebPkiClientVerifier::builder(Arc::new(roots));
+        let verifier = match self.policy {
+            ClientAuthPolicy::Required => builder.build()?,
+            ClientAuthPolicy::Optional => builder.allow_unauthenticated().build()?,
+        };
+
+        Ok(verifier)
+    }
+}
+
+impl PeerCertificate {
+    /// Reads the identity in a certificate already verified by the handshake.
+    fn from_der(der: &CertificateDer<'_>) -> anyhow::Result<Self> {
+        let cert = webpki::EndEntityCert::try_from(der)?;
+
+        let mut rdns = Vec::new();
+        let mut common_name = None;
+        let mut rest = cert.subject();
+        while !rest.is_empty() {
+            let (mut set, next) = der_element(rest, SET)?;
+            rest = next;
+
+            let mut attributes = Vec::new();
+            while !set.is_empty() {
+                let (attribute, next) = der_element(set, SEQUENCE)?;
+                set = next;
+
+                let (oid, value) = der_element(attribute, OID)?;
+                let value = der_string(value)?;
+
+                if oid == COMMON_NAME {
+                    common_name = Some(value.clone());
+                }
+                attributes.push(format!(
+                    "{}={}",
+                    attribute_name(oid),
+                    escape_dn_value(&value)
+                ));
+            }
+
+            rdns.push(attributes.join("+"));
+        }
+
+        // Distinguished names are written starting from the last element
+        rdns.reverse();
+
+        Ok(PeerCertificate {
+            subject: rdns.join(","),
+            common_name,
+            dns_names: cert.valid_dns_names().map(String::from).collect(),
+            uris: cert.valid_uri_names().map(String::from).collect(),
+            der: der.to_vec(),
+        })
+    }
+}
+
+/// The DER tags found in distinguished names.
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OID: u8 = 0x06;
+
+/// The object identifier of the common name attribute, 2.5.4.3.
+const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// Splits the DER element at the start of `data`, which must have the given `tag`,
+/// into its content and the elements following it.
+fn der_element(data: &[u8], tag: u8) -> anyhow::Result<(&[u8], &[u8])> {
+    let (found, content, rest) = der_any(data)?;
+    anyhow::ensure!(found == tag, "Unexpected DER tag {:#04x}", found);
+
+    Ok((content, rest))
+}
+
+/// Splits the DER element at the start of `data` into its tag, its content and the
+/// elements following it.
+fn der_any(data: &[u8]) -> anyhow::Result<(u8, &[u8], &[u8])> {
+    let truncated = || anyhow::anyhow!("Truncated DER element");
+
+    let (&tag, rest) = data.split_first().ok_or_else(truncated)?;
+    let (&first, rest) = rest.split_first().ok_or_else(truncated)?;
+
+    // Short lengths fit in the first byte, long ones are spread over the next ones
+    let (len, rest) = match first {
+        0..=0x7f => (usize::from(first), rest),
+        0x81..=0x84 => {
+            let count = usize::from(first & 0x7f);
+            anyhow::ensure!(rest.len() >= count, "Truncated DER element");
+
+            let len = rest[..count]
+                .iter()
+                .fold(0, |len, &byte| len << 8 | usize::from(byte));
+
+            (len, &rest[count..])
+        }
+        _ => anyhow::bail!("Unsupported DER length"),
+    };
+    anyhow::ensure!(rest.len() >= len, "Truncated DER element");
+
+    Ok((tag, &rest[..len], &rest[len..]))
+}
+
+/// Decodes the DER string at the start of `data`.
+fn der_string(data: &[u8]) -> anyhow::Result<String> {
+    let (tag, content, _) = der_any(data)?;
+
+    Ok(match tag {
+        // BMPString, UTF-16 big-endian
+        0x1e => String::from_utf16_lossy(
+            &content
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+                .collect::<Vec<u16>>(),
+        ),
+        // UTF8String, PrintableString, IA5String and the like
+        _ => String::from_utf8_lossy(content).into_owned(),
+    })
+}
+
+/// Returns the short name of the attribute with the given object identifier, or the
+/// identifier itself in dotted form.
+fn attribute_name(oid: &[u8]) -> String {
+    let name = match oid {
+        [0x55, 0x04, 0x03] => "CN",
+        [0x55, 0x04, 0x06] => "C",
+        [0x55, 0x04, 0x07] => "L",
+        [0x55, 0x04, 0x08] => "ST",
+        [0x55, 0x04, 0x09] => "STREET",
+        [0x55, 0x04, 0x0a] => "O",
+        [0x55, 0x04, 0x0b] => "OU",
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19] => "DC",
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01] => "UID",
+        _ => {
+            let mut arcs = Vec::new();
+            let mut arc = 0u64;
+
+            for &byte in oid {
+                arc = arc << 7 | u64::from(byte & 0x7f);
+
+                if byte & 0x80 == 0 {
+                    arcs.push(arc);
+                    arc = 0;
+                }
+            }
+
+            // The first byte holds the first two arcs
+            let mut dotted = match arcs.first() {
+                Some(&first) if first < 80 => format!("{}.{}", first / 40, first % 40),
+                Some(&first) => format!("2.{}", first - 80),
+                None => String::new(),
+            };
+            for arc in arcs.iter().skip(1) {
+                dotted.push_str(&format!(".{}", arc));
+            }
+
+            return dotted;
+        }
+    };
+
+    name.to_string()
+}
+
+/// Escapes the characters with a special meaning in distinguished name strings.
+fn escape_dn_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let last = value.chars().count().saturating_sub(1);
+
+    for (i, c) in value.chars().enumerate() {
+        let leading = i == 0 && (c == ' ' || c == '#');
+        let trailing = i == last && c == ' ';
+
+        if leading || trailing || matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+impl TlsConfig {
+    /// Builds the rustls configuration of the server.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the configuration, or an error if the certificate
+    /// chain or the private key is invalid.
+    pub(crate) fn server_config(&self) -> anyhow::Result<Arc<rustls::ServerConfig>> {
+        let cert_chain = CertificateDer::pem_slice_iter(&self.cert_chain_pem)
+            .collect::<Result<Vec<CertificateDer>, _>>()?;
+        anyhow::ensure!(!cert_chain.is_empty(), "No certificate found in the chain");
+
+        let private_key = PrivateKeyDer::from_pem_slice(&self.private_key_pem)?;
+
+        let builder = rustls::ServerConfig::builder();
+        let builder = match &self.client_auth {
+            Some(client_auth) => builder.with_client_cert_verifier(client_auth.verifier()?),
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_single_cert(cert_chain, private_key)?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(Arc::new(config))
+    }
+}
+
+/// An encrypted connection, closed with a `close_notify` alert when dropped.
+pub(crate) struct TlsStream {
+    stream: StreamOwned<ServerConnection, TcpStream>,
+    /// The certificate the client authenticated with, if any.
+    peer_certificate: Option<Arc<PeerCertificate>>,
+}
+
+impl TlsStream {
+    /// Returns the certificate the client authenticated with, if any.
+    pub(crate) fn peer_certificate(&self) -> Option<Arc<PeerCertificate>> {
+        self.peer_certificate.clone()
+    }
+
+    /// Returns the underlying socket.
+    pub(crate) fn socket(&self) -> &TcpStream {
+        &self.stream.sock
+    }
+
+    /// Sends the `close_notify` alert, then shuts down the writing half of the socket.
+    pub(crate) fn close_write(&mut self) -> io::Result<()> {
+        self.stream.conn.send_close_notify();
+        self.stream.conn.complete_io(&mut self.stream.sock)?;
+
+        self.stream.sock.shutdown(Shutdown::Write)
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        // Let the client tell a complete response from a truncated one
+        self.stream.conn.send_close_notify();
+
+        let _ = self.stream.conn.complete_io(&mut self.stream.sock);
+    }
+}
+
+/// Performs the TLS handshake on an accepted connection.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the encrypted connection, or an error if the
+/// handshake failed.
+pub(crate) fn accept(
+    config: &Arc<rustls::ServerConfig>,
+    mut stream: TcpStream,
+) -> anyhow::Result<Connection> {
+    let mut connection = ServerConnection::new(config.clone())?;
+
+    while connection.is_handshaking() {
+        connection.complete_io(&mut stream)?;
+    }
+
+    // Verified during the handshake, the first certificate is the client's own
+    let peer_certificate = connection
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .map(PeerCertificate::from_der)
+        .transpose()?
+        .map(Arc::new);
+
+    Ok(Connection::Tls(Box::new(TlsStream {
+        stream: StreamOwned::new(connection, stream),
+        peer_certificate,
+    })))
+}
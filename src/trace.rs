@@ -0,0 +1,161 @@
+//! The spans opened by the server when the `tracing` feature is enabled.
+//!
+//! Every connection gets a [`CONNECTION_SPAN`] span, recording the `peer` address of
+//! the client, and every request a [`REQUEST_SPAN`] span within it, recording the
+//! `method`, `path` and `version` of the request, then the `status` of the response
+//! and the `latency_ms` of the handler once it is sent. Malformed requests and
+//! failing handlers are reported as events within these spans. With the
+//! `trace-context` feature, request spans also record the `trace_id` and `span_id`
+//! of the [trace context](crate::http::HTTPRequest::trace_context) of requests
+//! carrying a valid `traceparent` header.
+//!
+//! Handlers run on the thread that entered the request span, so their own events
+//! land in it without any setup. Without a subscriber, events are forwarded to the
+//! `log` crate like the rest of the server's logs.
+//!
+//! # Example
+//!
+//! ```
+//! use std::{
+//!     fmt,
+//!     io::{Read, Write},
+//!     net::TcpStream,
+//!     sync::{Arc, Mutex, RwLock},
+//! };
+//! use fobserver::{
+//!     args::Args,
+//!     http::{self, HTTPRequest, HTTPResponse},
+//!     router::Router,
+//!     trace, Server,
+//! };
+//! use tracing::{
+//!     field::{Field, Visit},
+//!     span, Event, Metadata, Subscriber,
+//! };
+//!
+//! /// The fields of a span or an event, as text.
+//! #[derive(Default)]
+//! struct Fields(Vec<(String, String)>);
+//!
+//! impl Fields {
+//!     fn get(&self, name: &str) -> Option<&str> {
+//!         self.0.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+//!     }
+//! }
+//!
+//! impl Visit for Fields {
+//!     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+//!         self.0.push((field.name().to_string(), format!("{:?}", value)));
+//!     }
+//! }
+//!
+//! struct CapturedSpan {
+//!     name: &'static str,
+//!     parent: Option<u64>,
+//!     fields: Fields,
+//! }
+//!
+//! // Spans are identified by their position, plus one
+//! static SPANS: Mutex<Vec<CapturedSpan>> = Mutex::new(Vec::new());
+//! static EVENTS: Mutex<Vec<(Option<u64>, Fields)>> = Mutex::new(Vec::new());
+//!
+//! thread_local! {
+//!     static ENTERED: std::cell::RefCell<Vec<u64>> = Default::default();
+//! }
+//!
+//! fn current() -> Option<u64> {
+//!     ENTERED.with(|entered| entered.borrow().last().copied())
+//! }
+//!
+//! struct Capture;
+//!
+//! impl Subscriber for Capture {
+//!     fn enabled(&self, _: &Metadata<'_>) -> bool {
+//!         true
+//!     }
+//!
+//!     fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+//!         let mut fields = Fields::default();
+//!         attributes.record(&mut fields);
+//!
+//!         let mut spans = SPANS.lock().unwrap();
+//!         spans.push(CapturedSpan {
+//!             name: attributes.metadata().name(),
+//!             parent: current(),
+//!             fields,
+//!         });
+//!
+//!         span::Id::from_u64(spans.len() as u64)
+//!     }
+//!
+//!     fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+//!         values.record(&mut SPANS.lock().unwrap()[id.into_u64() as usize - 1].fields);
+//!     }
+//!
+//!     fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+//!
+//!     fn event(&self, event: &Event<'_>) {
+//!         let mut fields = Fields::default();
+//!         event.record(&mut fields);
+//!
+//!         EVENTS.lock().unwrap().push((current(), fields));
+//!     }
+//!
+//!     fn enter(&self, id: &span::Id) {
+//!         ENTERED.with(|entered| entered.borrow_mut().push(id.into_u64()));
+//!     }
+//!
+//!     fn exit(&self, _: &span::Id) {
+//!         ENTERED.with(|entered| entered.borrow_mut().pop());
+//!     }
+//! }
+//!
+//! fn users(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+//!     tracing::info!("Loading users");
+//!
+//!     Ok(HTTPResponse::ok())
+//! }
+//!
+//! tracing::subscriber::set_global_default(Capture)?;
+//!
+//! let mut router = Router::new();
+//! router.add_route(http::Method::GET, "/users", http::Version::V11, users);
+//!
+//! let server = Server::new("127.0.0.1:0", router, Args::new())?.start_background()?;
+//!
+//! let mut stream = TcpStream::connect(server.addr()?)?;
+//! stream.write_all(b"GET /users HTTP/1.1\r\nConnection: close\r\n\r\n")?;
+//! let mut response = String::new();
+//! stream.read_to_string(&mut response)?;
+//! drop(server);
+//!
+//! let spans = SPANS.lock().unwrap();
+//! let request = spans.iter().position(|span| span.name == trace::REQUEST_SPAN).unwrap();
+//!
+//! let fields = &spans[request].fields;
+//! assert_eq!(fields.get("method"), Some("GET"));
+//! assert_eq!(fields.get("path"), Some("/users"));
+//! assert_eq!(fields.get("version"), Some("HTTP/1.1"));
+//! assert_eq!(fields.get("status"), Some("200"));
+//! assert!(fields.get("latency_ms").is_some());
+//!
+//! // The request span belongs to the span of its connection
+//! let connection = &spans[spans[request].parent.unwrap() as usize - 1];
+//! assert_eq!(connection.name, trace::CONNECTION_SPAN);
+//! assert_eq!(connection.fields.get("peer"), Some("127.0.0.1"));
+//!
+//! // The events of the handler land in the request span
+//! let events = EVENTS.lock().unwrap();
+//! let (span, _) = events
+//!     .iter()
+//!     .find(|(_, fields)| fields.get("message") == Some("Loading users"))
+//!     .unwrap();
+//! assert_eq!(*span, Some(request as u64 + 1));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+/// The name of the span of a connection.
+pub const CONNECTION_SPAN: &str = "connection";
+
+/// The name of the span of a request.
+pub const REQUEST_SPAN: &str = "request";
@@ -0,0 +1,492 @@
+use std::{fmt, sync::Arc};
+
+use serde_json::Value;
+
+use crate::{
+    http::{FieldError, HTTPRequest, HTTPResponse, Problem, StatusCode},
+    router::Route,
+};
+
+/// A check of the JSON body of the requests of a route, set with [`Route::validate`].
+///
+/// Implemented by [`Schema`], and by closures and functions
+/// `Fn(&serde_json::Value) -> Vec<FieldError>` for checks a schema can't express.
+pub trait Validate: Send + Sync {
+    /// Checks `value`, the parsed body of a request.
+    ///
+    /// # Parameters
+    /// - `value`: The body.
+    ///
+    /// # Returns
+    /// The errors found, empty if the body is valid.
+    fn validate(&self, value: &Value) -> Vec<FieldError>;
+}
+
+impl<F: Fn(&Value) -> Vec<FieldError> + Send + Sync> Validate for F {
+    fn validate(&self, value: &Value) -> Vec<FieldError> {
+        self(value)
+    }
+}
+
+/// The body of a request checked by the [validation](Route::validate) of its route,
+/// found in its [extensions](crate::http::HTTPRequest::extensions) so that the
+/// handler doesn't parse it again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidBody(pub Value);
+
+/// The shape JSON values must have, e.g. the body of the requests of a route.
+///
+/// A schema accepts a type of value, built with [`Schema::object`],
+/// [`Schema::string`] and the like, narrowed with constraints such as
+/// [`Schema::min_len`] or [`Schema::one_of`]. Object schemas list their fields, the
+/// fields they don't list are accepted as they are. Errors name the field at fault
+/// with a path like `address.zip` or `tags[1]`, the empty path being the value
+/// itself.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{
+///     atomic::{AtomicUsize, Ordering},
+///     Arc, RwLock,
+/// };
+/// use fobserver::{
+///     args::Args,
+///     http::{self, FieldError, HTTPRequest, HTTPResponse, StatusCode},
+///     router::Router,
+///     testing::TestServer,
+///     validation::{Schema, ValidBody},
+/// };
+/// use serde_json::json;
+///
+/// static CALLS: AtomicUsize = AtomicUsize::new(0);
+///
+/// fn create(request: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+///     CALLS.fetch_add(1, Ordering::SeqCst);
+///     let ValidBody(user) = request.extensions.get::<ValidBody>().unwrap();
+///
+///     let mut response = HTTPResponse::ok();
+///     response.body = Some(user["address"]["city"].as_str().unwrap().to_string().into());
+///
+///     Ok(response)
+/// }
+///
+/// let user = Schema::object()
+///     .field("name", Schema::string().min_len(1).max_len(32))
+///     .field("age", Schema::integer().min(0.0).max(150.0))
+///     .field("role", Schema::string().one_of(["admin", "member"]))
+///     .field(
+///         "address",
+///         Schema::object()
+///             .field("city", Schema::string())
+///             .optional("zip", Schema::string().min_len(5).max_len(5)),
+///     )
+///     .optional("tags", Schema::array(Schema::string()).max_len(3));
+///
+/// let mut router = Router::new();
+/// router
+///     .add_route(http::Method::POST, "/users", http::Version::V11, create)
+///     .validate(user);
+///
+/// // Any check can be a function of the body
+/// router
+///     .add_route(http::Method::POST, "/ranges", http::Version::V11, create)
+///     .validate(|body: &serde_json::Value| match body["from"].as_i64() < body["to"].as_i64() {
+///         true => Vec::new(),
+///         false => vec![FieldError::new("to", "must be greater than from")],
+///     });
+///
+/// let server = TestServer::new(router, Args::new());
+/// let post = |path: &str, body: serde_json::Value| {
+///     server.post(path, &body.to_string())
+/// };
+/// let errors = |response: HTTPResponse| -> anyhow::Result<serde_json::Value> {
+///     assert_eq!(response.status_code, StatusCode::CODE422);
+///     assert_eq!(response.headers.get("Content-Type"), Some("application/problem+json"));
+///
+///     let body: serde_json::Value = serde_json::from_slice(response.body.unwrap().as_bytes().unwrap())?;
+///     Ok(body["errors"].clone())
+/// };
+///
+/// let valid = json!({
+///     "name": "fob",
+///     "age": 30,
+///     "role": "admin",
+///     "address": { "city": "Bologna", "zip": "40100" },
+///     "tags": ["a", "b"],
+/// });
+/// let response = post("/users", valid)?;
+/// assert_eq!(response.status_code, StatusCode::CODE200);
+/// assert_eq!(response.body.unwrap().as_bytes(), Some(&b"Bologna"[..]));
+///
+/// // A missing field, in a nested object
+/// let response = post("/users", json!({
+///     "name": "fob",
+///     "age": 30,
+///     "role": "admin",
+///     "address": { "zip": "40100" },
+/// }))?;
+/// assert_eq!(errors(response)?, json!([{ "field": "address.city", "message": "is required" }]));
+///
+/// // Values of the wrong type, or out of bounds
+/// let response = post("/users", json!({
+///     "name": "",
+///     "age": "thirty",
+///     "role": "owner",
+///     "address": { "city": "Bologna" },
+///     "tags": ["a", 2],
+/// }))?;
+/// assert_eq!(
+///     errors(response)?,
+///     json!([
+///         { "field": "name", "message": "must be at least 1 characters long" },
+///         { "field": "age", "message": "must be an integer" },
+///         { "field": "role", "message": "must be one of \"admin\", \"member\"" },
+///         { "field": "tags[1]", "message": "must be a string" },
+///     ])
+/// );
+///
+/// let response = post("/users", json!([]))?;
+/// assert_eq!(errors(response)?, json!([{ "field": "", "message": "must be an object" }]));
+///
+/// let response = post("/ranges", json!({ "from": 5, "to": 1 }))?;
+/// assert_eq!(errors(response)?[0]["field"], "to");
+///
+/// // Bodies that aren't JSON at all are bad requests
+/// let response = server.post("/users", "{")?;
+/// assert_eq!(response.status_code, StatusCode::CODE400);
+///
+/// // The handler only ran for the valid body
+/// assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    kind: Kind,
+    min_len: Option<usize>,
+    max_len: Option<usize>,
+    min: Option<f64>,
+    max: Option<f64>,
+    one_of: Vec<Value>,
+}
+
+/// The type of value a [`Schema`] accepts.
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Any,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array(Box<Schema>),
+    /// The fields of the object, and whether they are required.
+    Object(Vec<(String, Schema, bool)>),
+}
+
+impl Schema {
+    /// Creates a schema of the given type, without constraints.
+    fn new(kind: Kind) -> Self {
+        Schema {
+            kind,
+            min_len: None,
+            max_len: None,
+            min: None,
+            max: None,
+            one_of: Vec::new(),
+        }
+    }
+
+    /// Creates a schema accepting any value.
+    pub fn any() -> Self {
+        Schema::new(Kind::Any)
+    }
+
+    /// Creates a schema accepting `true` and `false`.
+    pub fn boolean() -> Self {
+        Schema::new(Kind::Boolean)
+    }
+
+    /// Creates a schema accepting numbers without a fractional part.
+    pub fn integer() -> Self {
+        Schema::new(Kind::Integer)
+    }
+
+    /// Creates a schema accepting any number.
+    pub fn number() -> Self {
+        Schema::new(Kind::Number)
+    }
+
+    /// Creates a schema accepting strings.
+    pub fn string() -> Self {
+        Schema::new(Kind::String)
+    }
+
+    /// Creates a schema accepting arrays whose items match `items`.
+    pub fn array(items: Schema) -> Self {
+        Schema::new(Kind::Array(Box::new(items)))
+    }
+
+    /// Creates a schema accepting objects, without fields so far.
+    pub fn object() -> Self {
+        Schema::new(Kind::Object(Vec::new()))
+    }
+
+    /// Adds the required field `name` to an object schema, replacing the one of the
+    /// same name if there was one. Ignored by other schemas.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the field.
+    /// - `schema`: The schema of its value.
+    pub fn field(self, name: &str, schema: Schema) -> Self {
+        self.with_field(name, schema, true)
+    }
+
+    /// Adds the optional field `name` to an object schema, like [`Schema::field`]:
+    /// the field may be missing, but must match `schema` if present.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the field.
+    /// - `schema`: The schema of its value.
+    pub fn optional(self, name: &str, schema: Schema) -> Self {
+        self.with_field(name, schema, false)
+    }
+
+    /// Sets the least number of characters of a string, or of items of an array.
+    pub fn min_len(mut self, min: usize) -> Self {
+        self.min_len = Some(min);
+
+        self
+    }
+
+    /// Sets the largest number of characters of a string, or of items of an array.
+    pub fn max_len(mut self, max: usize) -> Self {
+        self.max_len = Some(max);
+
+        self
+    }
+
+    /// Sets the least value of a number.
+    pub fn min(mut self, min: f64) -> Self {
+        self.min = Some(min);
+
+        self
+    }
+
+    /// Sets the largest value of a number.
+    pub fn max(mut self, max: f64) -> Self {
+        self.max = Some(max);
+
+        self
+    }
+
+    /// Only accepts the values `values`.
+    ///
+    /// # Parameters
+    /// - `values`: The values allowed, e.g. `["admin", "member"]`.
+    pub fn one_of<V: Into<Value>>(mut self, values: impl IntoIterator<Item = V>) -> Self {
+        self.one_of = values.into_iter().map(Into::into).collect();
+
+        self
+    }
+
+    /// Adds the field `name` to an object schema.
+    fn with_field(mut self, name: &str, schema: Schema, required: bool) -> Self {
+        if let Kind::Object(fields) = &mut self.kind {
+            fields.retain(|(field, _, _)| field != name);
+            fields.push((name.to_string(), schema, required));
+        }
+
+        self
+    }
+
+    /// Checks `value`, found at `path`, adding what is wrong with it to `errors`.
+    fn check(&self, value: &Value, path: &str, errors: &mut Vec<FieldError>) {
+        let error = |errors: &mut Vec<FieldError>, message: String| {
+            errors.push(FieldError::new(path, &message));
+        };
+
+        let len = match (&self.kind, value) {
+            (Kind::Any, _) => None,
+            (Kind::Boolean, Value::Bool(_)) => None,
+            (Kind::Integer, Value::Number(number)) if number.is_i64() || number.is_u64() => None,
+            (Kind::Number, Value::Number(_)) => None,
+            (Kind::String, Value::String(string)) => Some((string.chars().count(), "characters")),
+            (Kind::Array(items), Value::Array(values)) => {
+                for (i, value) in values.iter().enumerate() {
+                    items.check(value, &format!("{}[{}]", path, i), errors);
+                }
+
+                Some((values.len(), "items"))
+            }
+            (Kind::Object(fields), Value::Object(object)) => {
+                for (name, schema, required) in fields {
+                    let path = match path.is_empty() {
+                        true => name.clone(),
+                        false => format!("{}.{}", path, name),
+                    };
+
+                    match object.get(name) {
+                        Some(value) => schema.check(value, &path, errors),
+                        None if *required => errors.push(FieldError::new(&path, "is required")),
+                        None => {}
+                    }
+                }
+
+                None
+            }
+            (kind, _) => {
+                let expected = match kind {
+                    Kind::Boolean => "a boolean",
+                    Kind::Integer => "an integer",
+                    Kind::Number => "a number",
+                    Kind::String => "a string",
+                    Kind::Array(_) => "an array",
+                    _ => "an object",
+                };
+
+                return error(errors, format!("must be {}", expected));
+            }
+        };
+
+        if let Some((len, unit)) = len {
+            if let Some(min) = self.min_len.filter(|min| len < *min) {
+                return error(errors, format!("must be at least {} {} long", min, unit));
+            }
+            if let Some(max) = self.max_len.filter(|max| len > *max) {
+                return error(errors, format!("must be at most {} {} long", max, unit));
+            }
+        }
+
+        if let Some(number) = value.as_f64() {
+            if let Some(min) = self.min.filter(|min| number < *min) {
+                return error(errors, format!("must be at least {}", min));
+            }
+            if let Some(max) = self.max.filter(|max| number > *max) {
+                return error(errors, format!("must be at most {}", max));
+            }
+        }
+
+        if !self.one_of.is_empty() && !self.one_of.contains(value) {
+            let values: Vec<String> = self.one_of.iter().map(Value::to_string).collect();
+
+            error(errors, format!("must be one of {}", values.join(", ")));
+        }
+    }
+}
+
+impl Validate for Schema {
+    fn validate(&self, value: &Value) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        self.check(value, "", &mut errors);
+
+        errors
+    }
+}
+
+/// The validation of a route, see [`Route::validate`].
+#[derive(Clone)]
+pub(crate) struct Validator(Arc<dyn Validate>);
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Validator")
+    }
+}
+
+impl Validator {
+    /// Checks the body of `request`.
+    ///
+    /// # Parameters
+    /// - `request`: The request to check.
+    /// - `error`: Renders the errors of the server, used for bodies too large.
+    ///
+    /// # Returns
+    /// The request, with its parsed body in its extensions, or the response refusing
+    /// it: `422 Unprocessable Content` listing the errors, `400 Bad Request` if the
+    /// body isn't JSON, `413 Content Too Large` if it was spooled to disk.
+    pub(crate) fn check(
+        &self,
+        mut request: HTTPRequest,
+        error: &dyn Fn(StatusCode, &HTTPRequest) -> HTTPResponse,
+    ) -> Result<HTTPRequest, HTTPResponse> {
+        if request.body.is_none() && request.body_source.is_some() {
+            return Err(error(StatusCode::CODE413, &request));
+        }
+
+        let body = request.body.as_deref().unwrap_or_default();
+        let value: Value = match serde_json::from_str(body) {
+            Ok(value) => value,
+            Err(err) => {
+                let detail = format!("The body isn't valid JSON: {}", err);
+
+                return Err(Problem::bad_request().detail(&detail).into_response());
+            }
+        };
+
+        let errors = self.0.validate(&value);
+        if !errors.is_empty() {
+            return Err(Problem::validation(errors).into_response());
+        }
+
+        request.extensions.insert(ValidBody(value));
+
+        Ok(request)
+    }
+}
+
+impl Route {
+    /// Checks the JSON body of the requests of the route before the handler runs.
+    ///
+    /// Requests whose body doesn't pass `validator` are answered
+    /// `422 Unprocessable Content` with a [problem](crate::http::Problem) listing the
+    /// errors in its `errors` member, and bodies that aren't JSON
+    /// `400 Bad Request`, without calling the handler. Valid bodies are stored in the
+    /// extensions of the request as a [`ValidBody`]. Bodies too large to be held in
+    /// memory are answered `413 Content Too Large`, in the format of the
+    /// [error pages](crate::Server::set_error_pages) of the server.
+    ///
+    /// # Parameters
+    /// - `validator`: A [`Schema`], or a function returning the errors of a body.
+    ///
+    /// # Returns
+    /// A mutable reference to `self` to allow for method chaining.
+    ///
+    /// See [`Schema`] for a complete example.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, RwLock};
+    /// use fobserver::{
+    ///     args::Args,
+    ///     http::{self, BodySource, HTTPRequest, HTTPResponse, StatusCode},
+    ///     router::Router,
+    ///     testing::TestServer,
+    ///     validation::Schema,
+    /// };
+    ///
+    /// fn create(_: HTTPRequest, _: Arc<RwLock<Args>>) -> anyhow::Result<HTTPResponse> {
+    ///     Ok(HTTPResponse::ok())
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router
+    ///     .add_route(http::Method::POST, "/users", http::Version::V11, create)
+    ///     .validate(Schema::object());
+    /// let server = TestServer::new(router, Args::new());
+    ///
+    /// // A body spooled to disk isn't parsed, the client asked for JSON errors
+    /// let mut request: HTTPRequest = "POST /users HTTP/1.1\r\nAccept: application/json\r\n\r\n".parse()?;
+    /// request.body_source = Some(BodySource::File(std::env::temp_dir().join("fobserver-upload"), 1 << 30));
+    ///
+    /// let response = server.request(request)?;
+    /// assert_eq!(response.status_code, StatusCode::CODE413);
+    /// assert_eq!(response.headers.get("Content-Type"), Some("application/json"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn validate(&mut self, validator: impl Validate + 'static) -> &mut Self {
+        self.validator = Some(Validator(Arc::new(validator)));
+
+        self
+    }
+}
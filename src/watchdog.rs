@@ -0,0 +1,208 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Shutdown},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use socket2::{SockRef, Socket};
+
+use crate::{http::StatusCode, ServerConfig};
+
+/// A thread answering and closing the connections whose handler runs for too long.
+///
+/// Threads can't be stopped, so a handler running past its deadline keeps running:
+/// the watchdog answers `504 Gateway Timeout` in its place and closes the
+/// connection, and the response of the handler is thrown away once it returns.
+#[derive(Clone)]
+pub(crate) struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+/// The state shared by the watchdog thread and the workers.
+struct Shared {
+    state: Mutex<State>,
+    /// Notified when a deadline is added or the watchdog stops.
+    changed: Condvar,
+    config: Arc<ServerConfig>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The requests being handled, by deadline.
+    deadlines: BTreeMap<(Instant, u64), Watched>,
+    next_id: u64,
+    stopped: bool,
+}
+
+/// A connection waiting for the response of its handler.
+struct Watched {
+    socket: Socket,
+    addr: Option<IpAddr>,
+    /// Whether a response can be written on the socket as it is, i.e. without TLS.
+    respond: bool,
+}
+
+impl Watchdog {
+    /// Spawns the watchdog thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The settings of the server, used to answer timed out requests.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the watchdog, or an error if the thread can't be
+    /// spawned.
+    pub(crate) fn spawn(config: Arc<ServerConfig>) -> anyhow::Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::default(),
+            changed: Condvar::new(),
+            config,
+        });
+
+        let watchdog = Watchdog {
+            shared: shared.clone(),
+        };
+        let thread = shared.config.thread("watchdog");
+        thread.spawn(move || shared.run())?;
+
+        Ok(watchdog)
+    }
+
+    /// Watches the request being handled on `socket` until the returned deadline is
+    /// finished or dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `socket` - The socket of the connection.
+    /// * `timeout` - How long the handler may run.
+    /// * `addr` - The IP address of the client, for the access log.
+    /// * `respond` - Whether the watchdog can answer on the socket itself.
+    ///
+    /// # Returns
+    ///
+    /// Returns the deadline of the request, or `None` if the socket can't be watched.
+    pub(crate) fn watch(
+        &self,
+        socket: SockRef<'_>,
+        timeout: Duration,
+        addr: Option<IpAddr>,
+        respond: bool,
+    ) -> Option<Deadline> {
+        let socket = match socket.try_clone() {
+            Ok(socket) => socket,
+            Err(err) => {
+                log::warn!("Failed to watch handler timeout: {}", err);
+
+                return None;
+            }
+        };
+
+        let mut state = self.shared.lock();
+        state.next_id += 1;
+
+        let key = (Instant::now() + timeout, state.next_id);
+        let watched = Watched {
+            socket,
+            addr,
+            respond,
+        };
+        state.deadlines.insert(key, watched);
+        drop(state);
+
+        self.shared.changed.notify_one();
+
+        Some(Deadline {
+            shared: self.shared.clone(),
+            key,
+        })
+    }
+
+    /// Stops the watchdog thread, leaving the requests still watched alone.
+    pub(crate) fn stop(&self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_one();
+    }
+}
+
+impl Shared {
+    /// Locks the state, even if a thread panicked while holding it.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Expires the deadlines as they pass, until the watchdog is stopped.
+    fn run(&self) {
+        let mut state = self.lock();
+
+        while !state.stopped {
+            let Some(&(deadline, _)) = state.deadlines.keys().next() else {
+                state = self
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(|err| err.into_inner());
+
+                continue;
+            };
+
+            let now = Instant::now();
+            if deadline > now {
+                (state, _) = self
+                    .changed
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(|err| err.into_inner());
+
+                continue;
+            }
+
+            if let Some((_, watched)) = state.deadlines.pop_first() {
+                // Writing may block, don't hold up the workers meanwhile
+                drop(state);
+                self.expire(watched);
+                state = self.lock();
+            }
+        }
+    }
+
+    /// Answers a request whose handler ran out of time, and closes its connection.
+    fn expire(&self, watched: Watched) {
+        log::warn!("Handler timed out, closing connection");
+
+        if watched.respond {
+            if let Err(err) =
+                self.config
+                    .refuse(&watched.socket, StatusCode::CODE504, None, watched.addr)
+            {
+                log::debug!("Failed to answer timed out request: {}", err);
+            }
+        }
+
+        // Whatever the handler sends later must not reach the client
+        let _ = watched.socket.shutdown(Shutdown::Both);
+    }
+}
+
+/// The deadline of a request watched by a [`Watchdog`], no longer watched once dropped.
+pub(crate) struct Deadline {
+    shared: Arc<Shared>,
+    key: (Instant, u64),
+}
+
+impl Deadline {
+    /// Stops watching the request, once its handler returned.
+    ///
+    /// # Returns
+    ///
+    /// Returns `false` if the deadline passed already: the watchdog answered the
+    /// request and closed the connection, the response must be dropped.
+    pub(crate) fn finish(self) -> bool {
+        self.shared.lock().deadlines.remove(&self.key).is_some()
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        self.shared.lock().deadlines.remove(&self.key);
+    }
+}